@@ -0,0 +1,105 @@
+use channels_rs::combining::{CombiningSender, mpsc_combining};
+use channels_rs::prelude::*;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+const ITEMS_PER_PRODUCER: u64 = 1024;
+
+#[derive(Copy, Clone)]
+struct Event {}
+
+/// Run `producers` threads that send `ITEMS_PER_PRODUCER` items each through
+/// `send` while the current thread drains `rx`, returning the elapsed time.
+fn run<S, F>(senders: Vec<S>, rx: &Receiver<Event>, send: F) -> Duration
+where
+    S: Send + 'static,
+    F: Fn(&S) + Copy + Send + 'static,
+{
+    let total = senders.len() as u64 * ITEMS_PER_PRODUCER;
+    let barrier = Arc::new(Barrier::new(senders.len() + 1));
+    let handles: Vec<_> = senders
+        .into_iter()
+        .map(|sender| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..ITEMS_PER_PRODUCER {
+                    send(&sender);
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let started = Instant::now();
+    let mut received = 0;
+    while received < total {
+        if let RecvResult::Processed(n) = rx.try_recv_batch(1024, &|e| {
+            std::hint::black_box(e);
+        }) {
+            received += n as u64;
+        }
+    }
+    let elapsed = started.elapsed();
+    handles
+        .into_iter()
+        .for_each(|handle| handle.join().unwrap());
+    elapsed
+}
+
+fn bench_contended_producers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mpsc/contended");
+    for producers in [2, 8, 32, 64] {
+        group.throughput(Throughput::Elements(producers as u64 * ITEMS_PER_PRODUCER));
+
+        group.bench_with_input(
+            BenchmarkId::new("shared_cursor", producers),
+            &producers,
+            |b, &producers| {
+                let (tx, rx) = mpsc::<Event>(
+                    8192,
+                    ProducerWaitStrategyKind::Yielding,
+                    ConsumerWaitStrategyKind::Spinning,
+                );
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            let senders = (0..producers).map(|_| tx.clone()).collect();
+                            run(senders, &rx, |tx: &Sender<Event>| {
+                                tx.send(Event {}).unwrap()
+                            })
+                        })
+                        .sum()
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("combining", producers),
+            &producers,
+            |b, &producers| {
+                let (tx, rx) = mpsc_combining::<Event>(
+                    8192,
+                    producers + 1,
+                    ProducerWaitStrategyKind::Yielding,
+                    ConsumerWaitStrategyKind::Spinning,
+                );
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            let senders = (0..producers).map(|_| tx.clone()).collect();
+                            run(senders, &rx, |tx: &CombiningSender<Event>| {
+                                tx.send(Event {}).unwrap()
+                            })
+                        })
+                        .sum()
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_contended_producers);
+criterion_main!(benches);
@@ -24,7 +24,7 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
             };
 
             while is_running_clone.load(Ordering::Acquire) {
-                rx_clone.blocking_recv(1024, &handler)
+                rx_clone.blocking_recv(1024, &handler);
             }
         });
     }
@@ -35,7 +35,7 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
     group.throughput(Throughput::Elements(1));
     group.bench_function("push", |b| {
         b.iter(|| {
-            tx.send(event);
+            tx.send(event).unwrap();
         });
     });
 
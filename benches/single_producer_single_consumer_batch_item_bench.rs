@@ -23,7 +23,7 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
         };
 
         while is_running_clone.load(Ordering::Acquire) {
-            rx_clone.blocking_recv(1024, &handler)
+            rx_clone.blocking_recv(1024, &handler);
         }
     });
 
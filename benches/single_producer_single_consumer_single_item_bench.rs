@@ -23,7 +23,7 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
         };
 
         while is_running_clone.load(Ordering::Acquire) {
-            rx_clone.blocking_recv(1024, &handler)
+            rx_clone.blocking_recv(1024, &handler);
         }
     });
 
@@ -33,7 +33,7 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
     group.throughput(Throughput::Elements(1));
     group.bench_function("push", |b| {
         b.iter(|| {
-            tx.send(event);
+            tx.send(event).unwrap();
         });
     });
 
@@ -41,5 +41,44 @@ fn bench_ring_buffer_offer_poll(c: &mut Criterion) {
     is_running.store(false, Ordering::Release);
 }
 
-criterion_group!(benches, bench_ring_buffer_offer_poll);
+fn bench_static_ring_buffer_offer_poll(c: &mut Criterion) {
+    let (tx, rx) = spsc_static::<Event>(
+        8192,
+        ProducerWaitStrategyKind::Spinning,
+        ConsumerWaitStrategyKind::Spinning,
+    );
+    let is_running = Arc::new(AtomicBool::new(true));
+
+    let is_running_clone = is_running.clone();
+
+    let consumer = std::thread::spawn(move || {
+        let handler: fn(Event) = |e| {
+            std::hint::black_box(e);
+        };
+
+        while is_running_clone.load(Ordering::Acquire) {
+            rx.recv(1024, &handler);
+        }
+    });
+
+    let event: Event = Event {};
+
+    let mut group = c.benchmark_group("spsc_static/single");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("push", |b| {
+        b.iter(|| {
+            tx.send(event).unwrap();
+        });
+    });
+
+    group.finish();
+    is_running.store(false, Ordering::Release);
+    consumer.join().unwrap();
+}
+
+criterion_group!(
+    benches,
+    bench_ring_buffer_offer_poll,
+    bench_static_ring_buffer_offer_poll
+);
 criterion_main!(benches);
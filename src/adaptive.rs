@@ -0,0 +1,318 @@
+//! Consumers that size their batches and pick their wait from the load.
+//!
+//! An [`AdaptiveReceiver`] samples the occupancy of its ring buffer before
+//! every poll and sorts the channel into a [`Load`]. Under a low load it
+//! takes small batches and spins while the channel is empty, so every item is
+//! handled as soon as it arrives. Under a high load it doubles its batches,
+//! and once it catches up with producers it parks right away instead of
+//! spinning, letting the next batch build up with fewer wakeups. In between
+//! it keeps its batch size, and waits escalate from spinning to yielding to
+//! parking as the streak of empty polls grows.
+//!
+//! An observer set with [`AdaptiveReceiver::with_observer`] sees a
+//! [`PollReport`] of every poll, to export the decisions as metrics or to
+//! tune the [`AdaptiveConfig`].
+
+use crate::channels::{Receiver, RecvResult, RecvState};
+use crate::sync::spin_loop;
+use std::time::Duration;
+
+/// The weight of the latest occupancy sample in the smoothed occupancy.
+const SMOOTHING: f64 = 0.5;
+
+/// When an [`AdaptiveReceiver`] resizes its batches and how it waits.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AdaptiveConfig {
+    /// The smallest batch, taken under a low load.
+    pub min_batch: usize,
+    /// The largest batch, taken under a high load; capped at the capacity of
+    /// the channel.
+    pub max_batch: usize,
+    /// The smoothed share of occupied slots at or below which the load is low.
+    pub low_occupancy: f64,
+    /// The smoothed share of occupied slots at or above which the load is high.
+    pub high_occupancy: f64,
+    /// Number of consecutive empty polls that spin, unless the load is high.
+    pub spin_limit: usize,
+    /// Number of consecutive empty polls that yield after spinning.
+    pub yield_limit: usize,
+    /// How long every later empty poll parks the thread.
+    pub park_duration: Duration,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            min_batch: 1,
+            max_batch: 1024,
+            low_occupancy: 0.1,
+            high_occupancy: 0.5,
+            spin_limit: 128,
+            yield_limit: 16,
+            park_duration: Duration::from_micros(50),
+        }
+    }
+}
+
+/// The load of a channel, judged from its smoothed occupancy.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Load {
+    /// At most [`AdaptiveConfig::low_occupancy`] of the slots are occupied.
+    Low,
+    /// Between the low and the high occupancy.
+    Moderate,
+    /// At least [`AdaptiveConfig::high_occupancy`] of the slots are occupied.
+    High,
+}
+
+/// How an [`AdaptiveReceiver`] waits after a poll that found no item.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum IdleState {
+    /// Issue a pause hint.
+    Spinning,
+    /// Yield the thread to the scheduler.
+    Yielding,
+    /// Park the thread for [`AdaptiveConfig::park_duration`].
+    Parking,
+}
+
+/// What an [`AdaptiveReceiver`] observed and decided in a single poll.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PollReport {
+    /// The outcome of the poll.
+    pub result: RecvResult,
+    /// The smoothed share of occupied slots the poll started from.
+    pub occupancy: f64,
+    /// The load judged from the occupancy.
+    pub load: Load,
+    /// The batch size of the next poll.
+    pub batch_size: usize,
+    /// How the receiver waits before returning, if the poll found no item.
+    pub idle: Option<IdleState>,
+}
+
+/// A hook called with the report of every poll.
+type Observer = Box<dyn FnMut(&PollReport) + Send>;
+
+/// A receiver that adapts its batch size and wait to the load of the channel.
+///
+/// See the [module documentation](self).
+pub struct AdaptiveReceiver<T> {
+    receiver: Receiver<T>,
+    config: AdaptiveConfig,
+    batch_size: usize,
+    occupancy: f64,
+    empty_polls: usize,
+    observer: Option<Observer>,
+}
+
+impl<T> AdaptiveReceiver<T> {
+    /// Wrap `receiver`, starting with batches of [`AdaptiveConfig::min_batch`] items.
+    ///
+    /// # Panics
+    /// Panics if `min_batch` is zero or exceeds `max_batch`, and if
+    /// `low_occupancy` exceeds `high_occupancy`.
+    pub fn new(receiver: Receiver<T>, config: AdaptiveConfig) -> Self {
+        assert!(config.min_batch > 0, "min_batch must be greater than 0");
+        assert!(
+            config.min_batch <= config.max_batch,
+            "min_batch must not exceed max_batch"
+        );
+        assert!(
+            config.low_occupancy <= config.high_occupancy,
+            "low_occupancy must not exceed high_occupancy"
+        );
+        Self {
+            batch_size: config.min_batch.min(receiver.capacity()),
+            receiver,
+            config,
+            occupancy: 0.0,
+            empty_polls: 0,
+            observer: None,
+        }
+    }
+
+    /// Call `observer` with the [`PollReport`] of every poll.
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: FnMut(&PollReport) + Send + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Receive a batch of items, sized after the load of the channel.
+    ///
+    /// Invokes `handler` for each item. If no item is available, waits once as
+    /// the streak of empty polls and the load dictate, unless the channel is
+    /// closed or every sender is gone, in which case
+    /// [`RecvState::Disconnected`] is returned.
+    pub fn recv<H>(&mut self, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        let sampled = self.receiver.len() as f64 / self.receiver.capacity() as f64;
+        self.occupancy += (sampled - self.occupancy) * SMOOTHING;
+        let load = self.load();
+
+        let result = self.receiver.try_recv_batch(self.batch_size, handler);
+        let idle = match result {
+            RecvResult::Processed(received) => {
+                self.empty_polls = 0;
+                self.resize(received, load);
+                None
+            }
+            RecvResult::Empty => Some(self.idle_state(load)),
+            RecvResult::Disconnected => None,
+        };
+
+        if let Some(observer) = &mut self.observer {
+            observer(&PollReport {
+                result,
+                occupancy: self.occupancy,
+                load,
+                batch_size: self.batch_size,
+                idle,
+            });
+        }
+        match idle {
+            Some(IdleState::Spinning) => spin_loop(),
+            Some(IdleState::Yielding) => std::thread::yield_now(),
+            Some(IdleState::Parking) => std::thread::park_timeout(self.config.park_duration),
+            None => {}
+        }
+
+        match result {
+            RecvResult::Processed(_) => RecvState::Received,
+            RecvResult::Empty => RecvState::Empty,
+            RecvResult::Disconnected => RecvState::Disconnected,
+        }
+    }
+
+    /// Double the batch size after a full batch under a high load, and halve
+    /// it under a low load.
+    fn resize(&mut self, received: usize, load: Load) {
+        let max_batch = self.config.max_batch.min(self.receiver.capacity());
+        self.batch_size = match load {
+            Load::High if received == self.batch_size => (self.batch_size * 2).min(max_batch),
+            Load::Low => (self.batch_size / 2).max(self.config.min_batch),
+            _ => self.batch_size,
+        };
+    }
+
+    /// Returns how to wait after the next empty poll of the streak.
+    fn idle_state(&mut self, load: Load) -> IdleState {
+        let step = self.empty_polls;
+        self.empty_polls = self.empty_polls.saturating_add(1);
+        if load == Load::High {
+            return IdleState::Parking;
+        }
+        match step.checked_sub(self.config.spin_limit) {
+            None => IdleState::Spinning,
+            Some(step) if step < self.config.yield_limit => IdleState::Yielding,
+            Some(_) => IdleState::Parking,
+        }
+    }
+
+    /// Returns the load judged from the smoothed occupancy.
+    pub fn load(&self) -> Load {
+        match self.occupancy {
+            occupancy if occupancy <= self.config.low_occupancy => Load::Low,
+            occupancy if occupancy >= self.config.high_occupancy => Load::High,
+            _ => Load::Moderate,
+        }
+    }
+
+    /// Returns the smoothed share of occupied slots, between `0.0` and `1.0`.
+    pub fn occupancy(&self) -> f64 {
+        self.occupancy
+    }
+
+    /// Returns the batch size of the next poll.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Returns the wrapped receiver.
+    pub fn receiver(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+
+    /// Unwrap the receiver.
+    pub fn into_inner(self) -> Receiver<T> {
+        self.receiver
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::adaptive::{AdaptiveConfig, AdaptiveReceiver, IdleState, Load, PollReport};
+    use crate::channels::RecvState;
+    use crate::testing::yielding_spsc;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_batches_grow_under_load_and_waits_escalate_when_idle() {
+        let (tx, rx) = yielding_spsc::<u32>(64);
+        let config = AdaptiveConfig {
+            min_batch: 2,
+            max_batch: 16,
+            spin_limit: 2,
+            yield_limit: 1,
+            park_duration: Duration::from_micros(10),
+            ..AdaptiveConfig::default()
+        };
+        let reports = Arc::new(Mutex::new(Vec::<PollReport>::new()));
+        let observed = reports.clone();
+        let mut rx = AdaptiveReceiver::new(rx, config)
+            .with_observer(move |report| observed.lock().unwrap().push(*report));
+
+        for value in 0..64 {
+            tx.send(value).unwrap();
+        }
+        let received = RefCell::new(Vec::new());
+        let handler = |value| received.borrow_mut().push(value);
+        assert_eq!(rx.recv(&handler), RecvState::Received);
+        assert_eq!(rx.load(), Load::High);
+        assert_eq!(rx.batch_size(), 4);
+        while rx.batch_size() < 16 {
+            rx.recv(&handler);
+        }
+        while rx.recv(&handler) == RecvState::Received {}
+        assert!(received.take().into_iter().eq(0..64));
+
+        // Drained: the load falls and the batches shrink back.
+        while rx.load() != Load::Low {
+            rx.recv(&handler);
+        }
+        for value in 64..67 {
+            tx.send(value).unwrap();
+            assert_eq!(rx.recv(&handler), RecvState::Received);
+        }
+        assert_eq!(rx.batch_size(), 2);
+
+        // Empty polls spin, then yield, then park.
+        reports.lock().unwrap().clear();
+        for _ in 0..4 {
+            assert_eq!(rx.recv(&handler), RecvState::Empty);
+        }
+        let idle = reports
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|report| report.idle)
+            .collect::<Vec<_>>();
+        let expected = [
+            IdleState::Spinning,
+            IdleState::Spinning,
+            IdleState::Yielding,
+            IdleState::Parking,
+        ];
+        assert!(idle.into_iter().eq(expected.map(Some)));
+
+        drop(tx);
+        assert_eq!(rx.recv(&handler), RecvState::Disconnected);
+    }
+}
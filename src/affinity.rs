@@ -0,0 +1,97 @@
+//! Pinning threads to CPU cores.
+//!
+//! A latency-sensitive consumer that stays on one core is never migrated by
+//! the scheduler and keeps its caches warm. [`pin_current_thread`] restricts
+//! the calling thread to a single core, for producer threads and for anything
+//! else the application runs itself; consumers spawned with
+//! [`Receiver::spawn_consumer_with`](crate::channels::Receiver::spawn_consumer_with)
+//! are pinned through [`ConsumerOptions::pin_to_core`](crate::channels::ConsumerOptions::pin_to_core).
+//!
+//! Pinning uses `sched_setaffinity` on Linux and fails with
+//! [`io::ErrorKind::Unsupported`] on other systems.
+
+use std::io;
+
+/// Restrict the calling thread to the CPU core `core`.
+///
+/// # Errors
+/// Returns the error of the operating system, for example if the core does
+/// not exist or is outside the cpuset of the process, and
+/// [`io::ErrorKind::Unsupported`] on systems without affinity support.
+pub fn pin_current_thread(core: usize) -> io::Result<()> {
+    imp::pin_current_thread(core)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::io;
+
+    /// Number of cores a `cpu_set_t` of glibc and musl describes.
+    const CPU_SETSIZE: usize = 1024;
+
+    unsafe extern "C" {
+        fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+    }
+
+    pub(super) fn pin_current_thread(core: usize) -> io::Result<()> {
+        if core >= CPU_SETSIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "core is out of the range of a cpu set",
+            ));
+        }
+        let mut set = [0u64; CPU_SETSIZE / 64];
+        set[core / 64] |= 1 << (core % 64);
+        // SAFETY: the mask outlives the call and its size is passed along; a
+        // pid of zero is the calling thread.
+        match unsafe { sched_setaffinity(0, size_of_val(&set), set.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+
+    pub(super) fn pin_current_thread(_core: usize) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "thread affinity is not supported on this system",
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux", not(feature = "loom")))]
+mod tests {
+    use crate::affinity::pin_current_thread;
+    use crate::channels::{ConsumerOptions, spsc};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::io;
+
+    #[test]
+    fn test_consumers_are_pinned_to_existing_cores_only() {
+        let error = pin_current_thread(4096).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let (tx, rx) = spsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let options = ConsumerOptions::new("pinned", 4).pin_to_core(0);
+        let consumer = rx.spawn_consumer_with(options, drop).unwrap();
+        tx.send(1).unwrap();
+        drop(tx);
+        consumer.join().unwrap();
+
+        let (_tx, rx) = spsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let options = ConsumerOptions::new("unpinnable", 4).pin_to_core(1023);
+        assert!(rx.spawn_consumer_with(options, drop).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-event audit trail for consumers.
+//!
+//! An [`AuditTrail`] is a small ring, separate from the channel's own buffer,
+//! that remembers when each of the last `N` events was consumed, how long its
+//! handler ran and which consumer ran it. Attach one to receivers with
+//! [`Receiver::with_audit_trail`](crate::channels::Receiver::with_audit_trail)
+//! and [`dump`](AuditTrail::dump) it when a latency spike is observed to see
+//! exactly which recent events were slow to process.
+
+use crate::primitives::{PaddedCounter, SeqLock};
+use std::time::{Duration, Instant};
+
+/// What the audit trail remembers about a single consumed event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    sequence: i64,
+    consumer: usize,
+    consumed_at: Instant,
+    handler_duration: Duration,
+}
+
+impl AuditRecord {
+    /// Returns the sequence of the consumed event.
+    pub fn sequence(&self) -> i64 {
+        self.sequence
+    }
+
+    /// Returns the id of the consumer that processed the event.
+    pub fn consumer(&self) -> usize {
+        self.consumer
+    }
+
+    /// Returns when the handler started processing the event.
+    pub fn consumed_at(&self) -> Instant {
+        self.consumed_at
+    }
+
+    /// Returns how long the handler took to process the event.
+    pub fn handler_duration(&self) -> Duration {
+        self.handler_duration
+    }
+}
+
+/// A slot of the trail, tagged with the position it was recorded at.
+#[derive(Copy, Clone)]
+struct Entry {
+    position: u64,
+    record: AuditRecord,
+}
+
+/// A fixed-size ring of the most recently consumed events.
+///
+/// Recording claims a slot with a single atomic increment and writes it through
+/// a [`SeqLock`], so consumers never wait on each other or on a concurrent
+/// [`dump`](Self::dump).
+pub struct AuditTrail {
+    entries: Box<[SeqLock<Option<Entry>>]>,
+    next: PaddedCounter,
+}
+
+impl AuditTrail {
+    /// Create a trail that keeps the last `capacity` events.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        Self {
+            entries: (0..capacity).map(|_| SeqLock::new(None)).collect(),
+            next: PaddedCounter::default(),
+        }
+    }
+
+    /// Returns the number of events the trail keeps.
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Record that `consumer` processed `sequence`, starting at `consumed_at`.
+    pub(crate) fn record(
+        &self,
+        sequence: i64,
+        consumer: usize,
+        consumed_at: Instant,
+        handler_duration: Duration,
+    ) {
+        let position = self.next.fetch_add(1);
+        let record = AuditRecord {
+            sequence,
+            consumer,
+            consumed_at,
+            handler_duration,
+        };
+        self.entries[(position % self.entries.len() as u64) as usize]
+            .write(Some(Entry { position, record }));
+    }
+
+    /// Copy the recorded events out of the trail, oldest first.
+    pub fn dump(&self) -> Vec<AuditRecord> {
+        let mut entries: Vec<Entry> = self.entries.iter().filter_map(SeqLock::read).collect();
+        entries.sort_unstable_by_key(|entry| entry.position);
+        entries.into_iter().map(|entry| entry.record).collect()
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::audit::AuditTrail;
+    use crate::channels::RecvResult;
+    use crate::testing::yielding_spsc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_audit_trail_keeps_the_last_events_of_every_batch() {
+        let trail = Arc::new(AuditTrail::new(4));
+        let (tx, rx) = yielding_spsc::<u32>(8);
+        let rx = rx.with_audit_trail(trail.clone(), 7);
+        tx.send_n(0..6).unwrap();
+        let slow = Duration::from_millis(20);
+        let handler = |value| {
+            if value == 4 {
+                thread::sleep(slow);
+            }
+        };
+        assert_eq!(rx.try_recv_batch(3, &handler), RecvResult::Processed(3));
+        assert_eq!(rx.try_recv_batch(3, &handler), RecvResult::Processed(3));
+
+        let records = trail.dump();
+        let sequences: Vec<_> = records.iter().map(|record| record.sequence()).collect();
+        assert_eq!(sequences, [2, 3, 4, 5]);
+        assert!(records.iter().all(|record| record.consumer() == 7));
+        assert!(records[2].handler_duration() >= slow);
+        assert!(records[3].consumed_at() >= records[2].consumed_at() + slow);
+    }
+}
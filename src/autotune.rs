@@ -0,0 +1,368 @@
+//! Automatic wait strategy escalation under CPU pressure.
+//!
+//! Spinning consumers keep the latency of a channel low as long as every
+//! spinning thread has a core to itself. On an overcommitted host, or in a
+//! container whose CPU quota is smaller than its thread count, they instead
+//! burn the quota that producers and other threads need. [`AutoTune`] runs a
+//! watchdog thread that samples the CPU usage of the process and the share of
+//! idle polls of every registered channel, switches spinning consumers that
+//! are mostly idle to the [`Escalation`] of its [`TunePolicy`] while the
+//! process is overcommitted, and switches them back once the load drops.
+//!
+//! The CPU usage is read from `/proc/self/stat`, so the watchdog only acts on
+//! Linux. It is measured against [`std::thread::available_parallelism`],
+//! which accounts for the CPU quota of the container.
+
+use crate::channels::Receiver;
+use crate::coordinator::Coordinator;
+use crate::sync::{AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How an escalated consumer waits instead of spinning.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Escalation {
+    /// Yield the thread on every wait.
+    Yield,
+    /// Park the thread for the given duration on every wait.
+    ///
+    /// Producers do not wake a parked consumer, so every item published
+    /// while it parks waits for at most this long.
+    Park(Duration),
+}
+
+/// When [`AutoTune`] escalates and relaxes consumers.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TunePolicy {
+    /// How often the watchdog samples CPU usage and idle polls.
+    pub interval: Duration,
+    /// The CPU usage of the process, as a share of the available cores,
+    /// above which the host counts as overcommitted.
+    pub overcommitted_above: f64,
+    /// The CPU usage below which escalated consumers go back to spinning.
+    pub relaxed_below: f64,
+    /// The share of polls that found no item above which an overcommitted
+    /// consumer is escalated.
+    pub idle_ratio_above: f64,
+    /// How escalated consumers wait.
+    pub escalation: Escalation,
+}
+
+impl Default for TunePolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            overcommitted_above: 0.9,
+            relaxed_below: 0.6,
+            idle_ratio_above: 0.5,
+            escalation: Escalation::Park(Duration::from_micros(100)),
+        }
+    }
+}
+
+/// No escalation: the consumer waits with its own strategy.
+const SPIN: u8 = 0;
+/// [`Escalation::Yield`].
+const YIELD: u8 = 1;
+/// [`Escalation::Park`].
+const PARK: u8 = 2;
+
+/// The escalation state and idle poll counters of a channel, kept by its coordinator.
+pub(crate) struct Tuning {
+    spinning: bool,
+    mode: AtomicU8,
+    park_nanos: AtomicU64,
+    idle_polls: AtomicUsize,
+    batches: AtomicUsize,
+}
+
+impl Tuning {
+    /// Create the tuning state of a channel; only `spinning` consumers are escalated.
+    pub fn new(spinning: bool) -> Self {
+        Self {
+            spinning,
+            mode: AtomicU8::new(SPIN),
+            park_nanos: AtomicU64::new(0),
+            idle_polls: AtomicUsize::new(0),
+            batches: AtomicUsize::new(0),
+        }
+    }
+
+    /// Count a poll that found no item.
+    #[inline(always)]
+    pub fn idle(&self) {
+        if self.spinning {
+            self.idle_polls.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a batch taken by a consumer.
+    #[inline(always)]
+    pub fn progress(&self) {
+        if self.spinning {
+            self.batches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Wait as escalated, returning no later than `deadline` if there is one.
+    ///
+    /// Returns `false` without waiting if the consumer is not escalated.
+    #[inline(always)]
+    pub fn wait(&self, deadline: Option<Instant>) -> bool {
+        match self.mode.load(Ordering::Relaxed) {
+            SPIN => false,
+            YIELD => {
+                std::thread::yield_now();
+                true
+            }
+            _ => {
+                let park = Duration::from_nanos(self.park_nanos.load(Ordering::Relaxed));
+                let remaining = deadline.map_or(park, |deadline| {
+                    deadline.saturating_duration_since(Instant::now())
+                });
+                std::thread::park_timeout(park.min(remaining));
+                true
+            }
+        }
+    }
+
+    /// Returns how the consumer currently waits instead of spinning.
+    pub fn escalation(&self) -> Option<Escalation> {
+        match self.mode.load(Ordering::Relaxed) {
+            SPIN => None,
+            YIELD => Some(Escalation::Yield),
+            _ => Some(Escalation::Park(Duration::from_nanos(
+                self.park_nanos.load(Ordering::Relaxed),
+            ))),
+        }
+    }
+
+    /// Switch the consumer to `escalation`, or back to spinning.
+    fn escalate(&self, escalation: Option<Escalation>) {
+        let mode = match escalation {
+            None => SPIN,
+            Some(Escalation::Yield) => YIELD,
+            Some(Escalation::Park(duration)) => {
+                let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+                self.park_nanos.store(nanos, Ordering::Relaxed);
+                PARK
+            }
+        };
+        self.mode.store(mode, Ordering::Relaxed);
+    }
+
+    /// Returns the share of polls since the last call that found no item, or
+    /// `None` if there were none.
+    fn take_idle_ratio(&self) -> Option<f64> {
+        let idle = self.idle_polls.swap(0, Ordering::Relaxed);
+        let batches = self.batches.swap(0, Ordering::Relaxed);
+        match idle + batches {
+            0 => None,
+            polls => Some(idle as f64 / polls as f64),
+        }
+    }
+}
+
+/// Escalate or relax the consumers of `channels` for the CPU usage `pressure`.
+fn tune(policy: &TunePolicy, channels: &[Arc<Coordinator>], pressure: f64) {
+    for channel in channels {
+        let tuning = channel.tuning();
+        let idle_ratio = tuning.take_idle_ratio();
+        match tuning.escalation() {
+            None if pressure > policy.overcommitted_above
+                && idle_ratio.is_some_and(|ratio| ratio > policy.idle_ratio_above) =>
+            {
+                tuning.escalate(Some(policy.escalation));
+            }
+            Some(_) if pressure < policy.relaxed_below => tuning.escalate(None),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the CPU time the process has used, from `/proc/self/stat`.
+fn process_cpu_time() -> Option<Duration> {
+    /// Clock ticks per second of the `utime` and `stime` fields.
+    const USER_HZ: u64 = 100;
+
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // Fields after the parenthesized command name start at the third one;
+    // `utime` and `stime` are the fourteenth and fifteenth.
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(Duration::from_millis((utime + stime) * 1000 / USER_HZ))
+}
+
+/// Samples the CPU usage of the process as a share of the available cores.
+struct CpuSampler {
+    cores: f64,
+    last: Option<(Duration, Instant)>,
+}
+
+impl CpuSampler {
+    fn new() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        Self {
+            cores: cores as f64,
+            last: None,
+        }
+    }
+
+    /// Returns the CPU usage since the previous sample, or `None` on the first
+    /// sample or if the CPU time cannot be read.
+    fn sample(&mut self) -> Option<f64> {
+        let now = (process_cpu_time()?, Instant::now());
+        let (cpu, wall) = self.last.replace(now)?;
+        let elapsed = now.1.duration_since(wall).as_secs_f64();
+        (elapsed > 0.0).then(|| (now.0 - cpu).as_secs_f64() / elapsed / self.cores)
+    }
+}
+
+/// The state shared by an [`AutoTune`] handle and its watchdog thread.
+struct Shared {
+    policy: TunePolicy,
+    channels: Mutex<Vec<Weak<Coordinator>>>,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl Shared {
+    /// Returns the registered channels that are still alive, forgetting the others.
+    fn channels(&self) -> Vec<Arc<Coordinator>> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        channels.retain(|channel| channel.strong_count() > 0);
+        channels.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Sample and tune every `interval` until stopped.
+    fn watch(&self) {
+        let mut sampler = CpuSampler::new();
+        let mut stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        while !*stopped {
+            stopped = self
+                .stop
+                .wait_timeout(stopped, self.policy.interval)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+            if let Some(pressure) = sampler.sample() {
+                tune(&self.policy, &self.channels(), pressure);
+            }
+        }
+    }
+}
+
+/// A watchdog that escalates spinning consumers under CPU pressure.
+///
+/// See the [module documentation](self). Dropping the handle stops the
+/// watchdog and switches every escalated consumer back to spinning.
+pub struct AutoTune {
+    shared: Arc<Shared>,
+    watchdog: Option<JoinHandle<()>>,
+}
+
+impl AutoTune {
+    /// Start a watchdog tuning the channels later [`register`](Self::register)ed
+    /// with it according to `policy`.
+    pub fn enable(policy: TunePolicy) -> Self {
+        let shared = Arc::new(Shared {
+            policy,
+            channels: Mutex::new(Vec::new()),
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+        let watched = shared.clone();
+        let watchdog = std::thread::Builder::new()
+            .name("channels-autotune".to_owned())
+            .spawn(move || watched.watch())
+            .expect("failed to spawn the autotune watchdog");
+        Self {
+            shared,
+            watchdog: Some(watchdog),
+        }
+    }
+
+    /// Tune the consumers of the channel of `receiver`.
+    ///
+    /// Only channels created with the
+    /// [`Spinning`](crate::coordinator::ConsumerWaitStrategyKind::Spinning)
+    /// consumer strategy are ever escalated. The channel is forgotten once
+    /// all of its senders and receivers are dropped.
+    pub fn register<T>(&self, receiver: &Receiver<T>) {
+        let channel = Arc::downgrade(receiver.coordinator());
+        let mut channels = self
+            .shared
+            .channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        channels.push(channel);
+    }
+
+    /// Returns how the consumers of the channel of `receiver` currently wait
+    /// instead of spinning, or `None` if they spin.
+    pub fn escalation<T>(receiver: &Receiver<T>) -> Option<Escalation> {
+        receiver.coordinator().tuning().escalation()
+    }
+}
+
+impl Drop for AutoTune {
+    fn drop(&mut self) {
+        *self
+            .shared
+            .stopped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = true;
+        self.shared.stop.notify_all();
+        if let Some(watchdog) = self.watchdog.take() {
+            let _ = watchdog.join();
+        }
+        for channel in self.shared.channels() {
+            channel.tuning().escalate(None);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::autotune::{AutoTune, Escalation, TunePolicy, tune};
+    use crate::channels::{RecvState, spsc};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::testing::spinning_spsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_idle_spinning_consumers_escalate_under_pressure_and_relax() {
+        let policy = TunePolicy {
+            escalation: Escalation::Yield,
+            ..TunePolicy::default()
+        };
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let (_parking_tx, parking) = spsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Parking(Duration::from_micros(1)),
+        );
+        let channels = [rx.coordinator().clone(), parking.coordinator().clone()];
+
+        assert_eq!(rx.recv(1, &drop), RecvState::Empty);
+        assert_eq!(parking.recv(1, &drop), RecvState::Empty);
+        tune(&policy, &channels, 0.5);
+        assert_eq!(rx.recv(1, &drop), RecvState::Empty);
+        tune(&policy, &channels, 2.0);
+        assert_eq!(AutoTune::escalation(&rx), Some(Escalation::Yield));
+        assert_eq!(AutoTune::escalation(&parking), None);
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv(1, &drop), RecvState::Received);
+        tune(&policy, &channels, 0.7);
+        assert_eq!(AutoTune::escalation(&rx), Some(Escalation::Yield));
+        tune(&policy, &channels, 0.1);
+        assert_eq!(AutoTune::escalation(&rx), None);
+
+        let autotune = AutoTune::enable(policy);
+        autotune.register(&rx);
+        drop(autotune);
+        assert_eq!(AutoTune::escalation(&rx), None);
+    }
+}
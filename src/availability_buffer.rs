@@ -1,6 +1,10 @@
-use crate::{constants, utils};
-use std::mem::MaybeUninit;
-use std::sync::atomic::{AtomicI32, Ordering};
+use crate::constants;
+use crate::ordering::ordered;
+use crate::sync::{AtomicI32, Ordering};
+use crate::utils::Indexing;
+
+/// Number of flags padding each side of the buffer, a cache line's worth.
+const PADDING: usize = constants::CACHE_LINE_SIZE / size_of::<AtomicI32>();
 
 /// a buffer is used to track the availability of slots in a ring buffer.
 ///
@@ -21,17 +25,15 @@ use std::sync::atomic::{AtomicI32, Ordering};
 /// - the `get_available` method checks availability up to a given range.
 ///
 /// # memory layout
-/// the buffer is over-allocated with extra padding (see `constants::array_padding`)
-/// to reduce false sharing between cache lines.
+/// the buffer is over-allocated with a cache line of padding on each side
+/// (see `constants::cache_line_size`) to reduce false sharing between cache lines.
 ///
 /// # safety
 /// this struct implements `send` and `sync` manually, as it contains
 /// atomics and padded memory regions that are safe to share across threads.
 pub struct AvailabilityBuffer {
-    /// Bitmask for wrapping sequence indices into the buffer length.
-    mask: i64,
-    /// Number of bits to shift when calculating availability flags.
-    flag_shift: usize,
+    /// Maps sequences to slots and to the laps used as availability flags.
+    indexing: Indexing,
     /// Underlying buffer storing availability flags for each slot.
     /// Includes left and right padding to avoid false sharing.
     buffer: Box<[AtomicI32]>,
@@ -41,15 +43,13 @@ impl AvailabilityBuffer {
     /// Creates a new `AvailabilityBuffer` with the given size.
     ///
     /// # Arguments
-    /// * `buffer_size` - Must be a power of two for wrapping to work correctly.
+    /// * `buffer_size` - Power-of-two sizes use the fast mask path, other sizes a modulo.
     ///
     /// # Panics
-    /// May panic if `buffer_size` is not a power of two,
-    /// depending on usage of `ilog2`.
+    /// Panics if `buffer_size` is zero.
     pub fn new(buffer_size: usize) -> Self {
         Self {
-            mask: (buffer_size - 1) as i64,
-            flag_shift: buffer_size.ilog2() as usize,
+            indexing: Indexing::new(buffer_size),
             buffer: Self::init_buffer(buffer_size),
         }
     }
@@ -57,23 +57,26 @@ impl AvailabilityBuffer {
     /// Initializes the underlying availability buffer with `-1` values,
     /// meaning "not yet available".
     ///
-    /// Adds padding on both sides to avoid false sharing.
+    /// Adds padding on both sides to avoid false sharing. The padding is
+    /// initialized as well, since loom atomics are live objects that must not
+    /// be left uninitialized.
     fn init_buffer(size: usize) -> Box<[AtomicI32]> {
-        let mut buffer: Box<[MaybeUninit<AtomicI32>]> =
-            Box::new_uninit_slice(size + (constants::ARRAY_PADDING << 1));
-        for i in 0..size {
-            buffer[i + constants::ARRAY_PADDING].write(AtomicI32::new(-1));
-        }
-        unsafe { buffer.assume_init() }
+        (0..size + (PADDING << 1))
+            .map(|_| AtomicI32::new(-1))
+            .collect()
     }
 
     /// Computes the availability flag for a given sequence.
     ///
-    /// The flag is derived by shifting the sequence number.
+    /// The flag is the lap of the buffer the sequence belongs to.
     /// This allows detecting wrap-around reuse of slots.
+    ///
+    /// Truncating the lap to `i32` is safe for any number of laps: a slot is
+    /// only ever compared against the lap it is published in and the lap
+    /// before it, which always differ after truncation.
     #[inline(always)]
     fn calculate_flag(&self, sequence: i64) -> i32 {
-        (sequence >> self.flag_shift) as i32
+        self.indexing.lap(sequence) as i32
     }
 
     /// Returns the highest available sequence in the given range `[low, high]`.
@@ -86,10 +89,10 @@ impl AvailabilityBuffer {
     /// producers are visible before reading availability flags.
     pub fn get_available(&self, low: i64, high: i64) -> i64 {
         for sequence in low..=high {
-            let index = utils::wrap_index(sequence, self.mask, constants::ARRAY_PADDING);
+            let index = self.indexing.wrap(sequence, PADDING);
             let flag = self.calculate_flag(sequence);
             let atomic = &self.buffer[index];
-            if atomic.load(Ordering::Acquire) != flag {
+            if ordered!(Publish, Acquire, Acquire, atomic.load(Ordering::Acquire)) != flag {
                 return sequence - 1;
             }
         }
@@ -102,23 +105,45 @@ impl AvailabilityBuffer {
     /// Uses `Release` to ensure visibility of the write
     /// before consumers check availability.
     pub fn set(&self, sequence: i64) {
-        let index = utils::wrap_index(sequence, self.mask, constants::ARRAY_PADDING);
+        let index = self.indexing.wrap(sequence, PADDING);
         let flag = self.calculate_flag(sequence);
         let atomic = &self.buffer[index];
-        atomic.store(flag, Ordering::Release);
+        ordered!(
+            Publish,
+            Release,
+            Release,
+            atomic.store(flag, Ordering::Release)
+        );
     }
 
     /// Marks a range of sequences as available.
     ///
+    /// The flags are stored from `high` down to `low`, so a consumer scanning
+    /// from `low` sees either the whole range or none of it.
+    ///
     /// # Memory ordering
     /// Stores each flag with `Release`
     /// to publish all updates together.
     pub fn set_range(&self, low: i64, high: i64) {
-        for sequence in low..=high {
-            let index = utils::wrap_index(sequence, self.mask, constants::ARRAY_PADDING);
+        for sequence in (low..=high).rev() {
+            let index = self.indexing.wrap(sequence, PADDING);
             let flag = self.calculate_flag(sequence);
             let atomic = &self.buffer[index];
-            atomic.store(flag, Ordering::Release);
+            ordered!(
+                Publish,
+                Release,
+                Release,
+                atomic.store(flag, Ordering::Release)
+            );
+        }
+    }
+
+    /// Marks every slot as not yet available, as after creation.
+    ///
+    /// Only valid while no producer or consumer is active.
+    pub fn reset(&self) {
+        for atomic in &self.buffer[PADDING..self.buffer.len() - PADDING] {
+            atomic.store(-1, Ordering::Release);
         }
     }
 }
@@ -0,0 +1,199 @@
+//! Publish/subscribe over named topics.
+//!
+//! A [`TopicBus`] routes messages by topic name. Every topic is backed by a
+//! [`broadcast`] ring that is created the first time someone subscribes to
+//! it, so every subscriber of a topic receives every message published to it
+//! while it is subscribed, and the slowest subscriber of a topic gates its
+//! publishers. Messages published to a topic without subscribers are dropped.
+//!
+//! Subscribers are plain [`Receiver`]s: dropping one unsubscribes it, and
+//! once its topic is [`remove`](TopicBus::remove)d or the bus is dropped it
+//! drains the messages already published and then reports
+//! [`RecvState::Disconnected`](crate::channels::RecvState::Disconnected).
+
+use crate::channels::{Receiver, Sender, broadcast};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::SendError;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The ring configuration of a topic.
+#[derive(Copy, Clone, Debug)]
+pub struct TopicConfig {
+    capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+}
+
+impl TopicConfig {
+    /// Create a configuration for rings of `capacity` slots with the given
+    /// publisher and subscriber wait strategies.
+    pub fn new(
+        capacity: usize,
+        pw: ProducerWaitStrategyKind,
+        cw: ConsumerWaitStrategyKind,
+    ) -> Self {
+        Self { capacity, pw, cw }
+    }
+}
+
+/// The ring of a topic.
+struct Topic<T> {
+    sender: Sender<T>,
+    /// A detached receiver that keeps the ring open between subscribers and
+    /// creates new ones.
+    anchor: Receiver<T>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Topic<T> {
+    fn new(config: TopicConfig) -> Self {
+        let (sender, anchor) = broadcast(config.capacity, config.pw, config.cw);
+        anchor.detach();
+        Self { sender, anchor }
+    }
+
+    /// Returns the number of live subscribers, not counting the anchor.
+    fn subscribers(&self) -> usize {
+        self.anchor.receiver_count() - 1
+    }
+}
+
+/// An in-process message router with named topics.
+///
+/// See the [module documentation](self).
+pub struct TopicBus<T> {
+    topics: RwLock<HashMap<String, Arc<Topic<T>>>>,
+    configs: RwLock<HashMap<String, TopicConfig>>,
+    default_config: TopicConfig,
+}
+
+impl<T: Clone + Send + Sync + 'static> TopicBus<T> {
+    /// Create a bus whose topics use `default_config` unless
+    /// [`configure`](Self::configure)d otherwise.
+    pub fn new(default_config: TopicConfig) -> Self {
+        Self {
+            topics: RwLock::new(HashMap::new()),
+            configs: RwLock::new(HashMap::new()),
+            default_config,
+        }
+    }
+
+    /// Use `config` for the ring of `topic` when it is created.
+    ///
+    /// Takes effect the next time the ring is created: a topic that already
+    /// has a ring keeps it until it is [`remove`](Self::remove)d.
+    pub fn configure(&self, topic: &str, config: TopicConfig) {
+        let mut configs = self.configs.write().unwrap_or_else(|e| e.into_inner());
+        configs.insert(topic.to_owned(), config);
+    }
+
+    /// Publish `message` to every current subscriber of `topic`.
+    ///
+    /// Waits according to the producer wait strategy of the topic while its
+    /// slowest subscriber lags a full ring behind. Returns the number of
+    /// subscribers the message was published to, which is `0` if the message
+    /// was dropped because the topic has none.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the message if a subscriber closed
+    /// the topic, including while this call waits for free space.
+    pub fn publish(&self, topic: &str, message: T) -> Result<usize, SendError<T>> {
+        let topics = self.topics.read().unwrap_or_else(|e| e.into_inner());
+        let Some(topic) = topics.get(topic).cloned() else {
+            return Ok(0);
+        };
+        drop(topics);
+
+        match topic.subscribers() {
+            0 => Ok(0),
+            subscribers => topic.sender.send(message).map(|()| subscribers),
+        }
+    }
+
+    /// Subscribe to `topic`, creating its ring if it has none.
+    ///
+    /// The returned receiver gets every message published to the topic from
+    /// now on. Cloning it subscribes again, starting where the clone's
+    /// original is.
+    pub fn subscribe(&self, topic: &str) -> Receiver<T> {
+        let mut topics = self.topics.write().unwrap_or_else(|e| e.into_inner());
+        let topic = topics
+            .entry(topic.to_owned())
+            .or_insert_with_key(|name| Arc::new(Topic::new(self.config_of(name))));
+        topic.anchor.subscribe_latest()
+    }
+
+    /// Returns the number of live subscribers of `topic`.
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        let topics = self.topics.read().unwrap_or_else(|e| e.into_inner());
+        topics.get(topic).map_or(0, |topic| topic.subscribers())
+    }
+
+    /// Returns the names of the topics that have a ring.
+    pub fn topics(&self) -> Vec<String> {
+        let topics = self.topics.read().unwrap_or_else(|e| e.into_inner());
+        topics.keys().cloned().collect()
+    }
+
+    /// Drop the ring of `topic`, disconnecting its subscribers once they have
+    /// received the messages already published.
+    ///
+    /// The next subscription creates a new ring. Returns `false` if the topic
+    /// has no ring.
+    pub fn remove(&self, topic: &str) -> bool {
+        let mut topics = self.topics.write().unwrap_or_else(|e| e.into_inner());
+        topics.remove(topic).is_some()
+    }
+
+    /// Returns the configuration of the ring of `topic`.
+    fn config_of(&self, topic: &str) -> TopicConfig {
+        let configs = self.configs.read().unwrap_or_else(|e| e.into_inner());
+        configs.get(topic).copied().unwrap_or(self.default_config)
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::bus::{TopicBus, TopicConfig};
+    use crate::channels::RecvState;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_topics_route_messages_to_current_subscribers() {
+        let config = TopicConfig::new(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let bus = TopicBus::<u32>::new(config);
+        assert!(matches!(bus.publish("prices", 0), Ok(0)));
+
+        let first = bus.subscribe("prices");
+        let orders = bus.subscribe("orders");
+        assert!(matches!(bus.publish("prices", 1), Ok(1)));
+        let second = bus.subscribe("prices");
+        assert!(matches!(bus.publish("prices", 2), Ok(2)));
+        assert!(matches!(bus.publish("orders", 3), Ok(1)));
+        assert_eq!(bus.subscriber_count("prices"), 2);
+
+        let received = RefCell::new(Vec::new());
+        let handler = |item| received.borrow_mut().push(item);
+        assert_eq!(first.recv(8, &handler), RecvState::Received);
+        assert_eq!(second.recv(8, &handler), RecvState::Received);
+        assert_eq!(orders.recv(8, &handler), RecvState::Received);
+        assert_eq!(*received.borrow(), vec![1, 2, 2, 3]);
+
+        drop((first, second));
+        assert_eq!(bus.subscriber_count("prices"), 0);
+        for message in 0..16 {
+            assert!(matches!(bus.publish("prices", message), Ok(0)));
+        }
+
+        assert!(bus.remove("orders"));
+        assert_eq!(orders.recv(8, &handler), RecvState::Disconnected);
+        let mut topics = bus.topics();
+        topics.sort();
+        assert_eq!(topics, vec!["prices".to_owned()]);
+    }
+}
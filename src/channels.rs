@@ -2,29 +2,69 @@
 //!
 //! This module provides [`Sender`] and [`Receiver`] types built on top of
 //! a ringBuffer with pluggable wait strategies. It supports different
-//! concurrency configurations such as SPSC, MPSC, SPMC, and MPMC.
+//! concurrency configurations such as SPSC, MPSC, SPMC, MPMC and broadcast.
 //!
 //! The design is inspired by the Disruptor pattern, but with Rust’s ownership
 //! and type safety. It allows batching, lock-free sending, and configurable
 //! waiting strategies for both producers and consumers.
 
-use crate::coordinator::Coordinator;
-use crate::poller::State::Idle;
-use crate::poller::{MultiConsumerPoller, SingleConsumerPoller};
+use crate::audit::AuditTrail;
+use crate::coordinator::{ConsumerWaitStrategy, Coordinator, NotifyPolicy, ProducerWaitStrategy};
+use crate::errors::{
+    ChannelPoisoned, CloseReason, RebaseError, SendError, SequencesAbandoned, SequencesExhausted,
+    TrySendError,
+};
+use crate::flow::{FlowController, FlowHandle, RateLimit};
+#[cfg(feature = "inspect")]
+use crate::inspect::DebugState;
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSnapshot;
+#[cfg(all(feature = "mp", feature = "mc"))]
+use crate::poller::BroadcastPoller;
+use crate::poller::State::{self, Idle};
+use crate::poller::{AckPoller, Poller, SingleConsumerPoller};
+#[cfg(feature = "mc")]
+use crate::poller::{MultiConsumerPoller, TeePoller};
 use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
-use crate::ring_buffer::RingBuffer;
-use crate::sequencer::{MultiProducerSequencer, SingleProducerSequencer};
+use crate::primitives::PaddedFlag;
+use crate::producers::ProducerStatus;
+#[cfg(feature = "registry")]
+use crate::registry::{self, Registration};
+use crate::ring_buffer::{ActiveClaims, Claimed, RingBuffer};
+use crate::sequence::{INITIAL_VALUE, Sequence};
+#[cfg(feature = "mp")]
+use crate::sequencer::MultiProducerSequencer;
+use crate::sequencer::{ClaimError, GatingSequences, Sequencer, SingleProducerSequencer};
+use crate::sync::{Ordering, fence};
+use crate::topology::Topology;
 use crate::utils;
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub use crate::failover::{FailoverReceiver, Standby};
+pub use crate::in_place::ErrorPolicy;
+pub use crate::scoped::{ScopedChannel, scoped};
+pub use crate::windows::{BatchReason, Window};
 
 /// A sending half of the channel.
 ///
 /// `Sender<T>` pushes values into a ringBuffer and notifies the consumer
 /// through the coordinator. It supports both single-item and batched sends.
-#[derive(Clone)]
+///
+/// Once every `Sender` is dropped, receivers drain what is left in the buffer
+/// and then report [`RecvState::Disconnected`].
 pub struct Sender<T> {
     buffer: Arc<RingBuffer<T>>,
     coordinator: Arc<Coordinator>,
+    producer: Option<usize>,
+    rate_limit: Option<Arc<RateLimit>>,
 }
 
 /// A receiving half of the channel.
@@ -32,198 +72,4191 @@ pub struct Sender<T> {
 /// `Receiver<T>` pulls values from a ringBuffer using a poller and can either
 /// spin/yield/park/block depending on the chosen wait strategy. It supports both
 /// non-blocking and blocking receive loops.
-#[derive(Clone)]
+///
+/// Once every `Receiver` is dropped, the channel is closed and sends fail with
+/// [`SendError::Closed`].
 pub struct Receiver<T> {
     buffer: Arc<RingBuffer<T>>,
+    poller: Arc<dyn Poller<T>>,
     coordinator: Arc<Coordinator>,
+    flow: Option<Arc<dyn FlowController>>,
+    audit: Option<(Arc<AuditTrail>, usize)>,
+    budget: Option<TimeBudget>,
+    panics: PanicPolicy,
+    /// The claims of the receiver not handed back yet, recorded if it shares
+    /// its poller with other receivers.
+    claims: Option<ActiveClaims>,
 }
 
-impl<T> Sender<T> {
-    /// Send a single value into the buffer.
-    ///
-    /// If the buffer is full, the configured producer wait strategy determines
-    /// how the call behaves (e.g. spin, yield, or park).
-    pub fn send(&self, value: T) {
-        self.buffer.push(value, &self.coordinator);
-        self.coordinator.wakeup_consumer()
+/// The time a receiver may spend handling a single batch.
+#[derive(Copy, Clone, Debug)]
+struct TimeBudget {
+    limit: Duration,
+    check_every: usize,
+}
+
+/// A reference to a published event that can be handed to other threads.
+///
+/// An `EventRef` pairs the sequence of an event with the [epoch](Receiver::epoch)
+/// it was published in. Slots are reused every lap, and sequences after a
+/// [`rebase`], so a plain index into the ring is ambiguous; resolving an
+/// `EventRef` through [`Receiver::resolve`] instead fails safely once the slot
+/// has been overwritten or the channel rebased.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EventRef {
+    sequence: i64,
+    epoch: i64,
+}
+
+impl EventRef {
+    /// The sequence the event was published at.
+    pub fn sequence(&self) -> i64 {
+        self.sequence
     }
 
-    /// Send multiple values into the buffer in a batch.
-    ///
-    /// This is more efficient than calling [`send`](Self::send) repeatedly,
-    /// as it reduces synchronization overhead.
+    /// The epoch of the channel the event was published in, see [`Receiver::epoch`].
+    pub fn epoch(&self) -> i64 {
+        self.epoch
+    }
+
+    /// The logical position the event was published at, see [`sequence_to_position`].
+    pub fn position(&self) -> u64 {
+        self.sequence as u64
+    }
+}
+
+/// Where an item handed to [`Receiver::recv_with_context`] sits in the ring
+/// and in its batch.
+///
+/// Knowing which item ends the batch lets a consumer flush buffered I/O or
+/// commit work once per batch instead of once per item.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Context {
+    pub(crate) sequence: i64,
+    pub(crate) end_of_batch: bool,
+}
+
+impl Context {
+    /// The sequence the item was published at.
+    pub fn sequence(&self) -> i64 {
+        self.sequence
+    }
+
+    /// The logical position the item was published at, see [`sequence_to_position`].
+    pub fn position(&self) -> u64 {
+        self.sequence as u64
+    }
+
+    /// Returns `true` if the item is the last one of the batch being handled,
+    /// so no more items follow before the receiver polls again.
+    pub fn is_end_of_batch(&self) -> bool {
+        self.end_of_batch
+    }
+}
+
+/// A range of ring positions reserved by [`Sender::reserve_sequence_range`].
+///
+/// The range is inclusive on both ends. It must eventually be handed to
+/// [`Sender::publish_into`]: consumers cannot progress past a reserved range
+/// until it is published.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "consumers stall until a reserved range is published"]
+pub struct SequenceRange {
+    low: i64,
+    high: i64,
+}
+
+impl SequenceRange {
+    /// The first reserved sequence.
+    pub fn low(&self) -> i64 {
+        self.low
+    }
+
+    /// The last reserved sequence.
+    pub fn high(&self) -> i64 {
+        self.high
+    }
+
+    /// The number of reserved sequences.
+    pub fn len(&self) -> usize {
+        (self.high - self.low + 1) as usize
+    }
+
+    /// Always `false`: an empty range cannot be reserved.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// A gating sequence registered by [`Receiver::add_gating_sequence`].
+///
+/// Producers do not claim a slot until every registered gating sequence has
+/// moved past the sequence published in it one lap earlier, which lets a
+/// consumer attached to a live channel read published items through
+/// [`Receiver::peek`] without them being overwritten. Dropping the gating
+/// sequence stops gating producers on it.
+pub struct GatingSequence {
+    sequences: Arc<GatingSequences>,
+    slot: usize,
+}
+
+impl GatingSequence {
+    /// The last sequence the gated consumer has handled.
     ///
-    /// # Type Parameters
-    /// - `I`: an `IntoIterator` where the iterator implements `ExactSizeIterator`.
-    pub fn send_n<I>(&self, items: I)
-    where
-        I: IntoIterator<Item = T>,
-        I::IntoIter: ExactSizeIterator,
-    {
-        self.buffer.push_n(items, &self.coordinator);
-        self.coordinator.wakeup_consumer()
+    /// Starts at the highest sequence claimed when the gating sequence was registered.
+    pub fn get(&self) -> i64 {
+        self.sequences.get(self.slot).get_acquire()
+    }
+
+    /// Note that the gated consumer has handled every sequence up to `sequence`,
+    /// releasing their slots to producers.
+    pub fn set(&self, sequence: i64) {
+        self.sequences.get(self.slot).set_release(sequence);
     }
 }
 
-impl<T> Receiver<T> {
-    /// Attempt to receive up to `batch_size` items.
+impl Drop for GatingSequence {
+    fn drop(&mut self) {
+        self.sequences.remove(self.slot);
+    }
+}
+
+/// The sequence every channel starts at, preceding the sequence of its first item.
+///
+/// The sequences returned by [`Context::sequence`], [`SlotGuard::sequence`]
+/// and the like count the items published since the channel was created,
+/// starting at `INITIAL_SEQUENCE + 1`.
+pub const INITIAL_SEQUENCE: i64 = INITIAL_VALUE;
+
+/// Returns the logical position of the item published at `sequence`, or
+/// `None` for [`INITIAL_SEQUENCE`], which no item is published at.
+///
+/// The first item of a channel is at position `0` and every item is one
+/// position after the previous one. Positions never wrap around the ring
+/// buffer, so they identify the items of a channel for its whole lifetime,
+/// unless the channel is [`rebase`]d.
+pub fn sequence_to_position(sequence: i64) -> Option<u64> {
+    u64::try_from(sequence).ok()
+}
+
+/// Returns the sequence of the item at logical `position`, or
+/// [`INITIAL_SEQUENCE`] for `None`; the inverse of [`sequence_to_position`].
+///
+/// # Panics
+/// Panics if `position` is beyond the sequences of a channel.
+pub fn position_to_sequence(position: Option<u64>) -> i64 {
+    position.map_or(INITIAL_SEQUENCE, |position| {
+        i64::try_from(position).expect("position beyond the sequences of a channel")
+    })
+}
+
+impl<T> Clone for Sender<T> {
+    /// Clone the sender, registering a new producer.
     ///
-    /// Invokes the provided `handler` closure for each item.
-    pub fn recv<H>(&self, batch_size: usize, handler: &H)
-    where
-        H: Fn(T),
-    {
-        if self.buffer.poll(batch_size, handler) == Idle {
-            self.coordinator.consumer_wait();
+    /// # Panics
+    /// Panics on channels with a bounded number of producers if the maximum
+    /// is already registered; use [`try_clone`](Sender::try_clone) to handle that.
+    fn clone(&self) -> Self {
+        self.try_clone()
+            .expect("maximum number of producers already registered")
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let (Some(id), Some(producers)) = (self.producer, self.coordinator.producers()) {
+            producers.release(id);
         }
+        self.coordinator.remove_sender();
     }
+}
 
-    /// Continuously attempt to receive items until at least one batch is processed.
-    ///
-    /// This method blocks according to the configured consumer wait strategy.
-    /// It is typically used in consumer loops.
-    pub fn blocking_recv<H>(&self, batch_size: usize, handler: &H)
-    where
-        H: Fn(T),
-    {
-        while self.buffer.poll(batch_size, handler) == Idle {
-            self.coordinator.consumer_wait();
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.subscribed(self.poller.subscribe())
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    /// Release the ranges of claims that were forgotten instead of dropped, so
+    /// that the receivers sharing the poller and the producers carry on.
+    fn drop(&mut self) {
+        let claims = self.claims.as_ref();
+        let reclaimed = claims.map_or(0, |claims| self.buffer.reclaim(&*self.poller, claims));
+        if reclaimed > 0 {
+            self.coordinator.wakeup_producers();
         }
+        self.buffer.unsubscribe(&*self.poller);
+        self.coordinator.remove_receiver();
     }
 }
 
-/// Create a **single-producer single-consumer (SPSC)** channel.
-///
-/// - One producer thread
-/// - One consumer thread
+/// A blocking iterator over the items of a channel, created by [`Receiver::iter`].
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+    claimed: Option<Claimed<'a, T>>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.next_claimed(&mut self.claimed, true)
+    }
+}
+
+/// An iterator over the items currently available in a channel, created by
+/// [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T>,
+    claimed: Option<Claimed<'a, T>>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.next_claimed(&mut self.claimed, false)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// The outcome of a receive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecvState {
+    /// At least one item was handed to the handler.
+    Received,
+    /// No item was available.
+    Empty,
+    /// The channel is closed or every sender is gone, and the buffer has been drained.
+    Disconnected,
+    /// The handler panicked under [`PanicPolicy::Poison`] and the channel is
+    /// now closed as poisoned.
+    Poisoned,
+}
+
+/// The outcome of a [`Receiver::recv`], with the number of items it handed
+/// to the handler.
 ///
-/// # Parameters
-/// - `buffer_size`: capacity of the underlying ring buffer.
-/// - `pw`: producer wait strategy.
-/// - `cw`: consumer wait strategy.
-pub fn spsc<T>(
-    buffer_size: usize,
-    pw: ProducerWaitStrategyKind,
-    cw: ConsumerWaitStrategyKind,
-) -> (Sender<T>, Receiver<T>) {
-    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
-    utils::assert_buffer_size_pow_of_2(buffer_size);
+/// Compares equal to the [`RecvState`] it reports, so callers that only care
+/// whether anything was received can keep comparing against one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PollOutcome {
+    pub(crate) state: RecvState,
+    pub(crate) processed: usize,
+    pub(crate) remaining_hint: usize,
+}
 
-    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
-    let poller = Box::new(SingleConsumerPoller::new());
-    let coordinator = Arc::new(Coordinator::new(pw, cw));
+impl PollOutcome {
+    /// Returns whether items were received, none were available, or none can
+    /// arrive any more.
+    pub fn state(&self) -> RecvState {
+        self.state
+    }
 
-    let buffer: Arc<RingBuffer<T>> = Arc::new(RingBuffer::new(buffer_size, sequencer, poller));
-    let sender = Sender {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
-    let receiver = Receiver {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
+    /// Returns the number of items handed to the handler, zero unless
+    /// [`RecvState::Received`] is reported.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
 
-    (sender, receiver)
+    /// Returns the number of items left in the buffer after the poll, zero
+    /// unless [`RecvState::Received`] is reported.
+    ///
+    /// Read after the poll without synchronizing with producers or other
+    /// receivers, so it is only a hint for sizing the next batch: producers
+    /// may publish more in the meantime, and the items other receivers of the
+    /// channel have not released yet are counted too.
+    pub fn remaining_hint(&self) -> usize {
+        self.remaining_hint
+    }
 }
 
-/// Create a **multi-producer single-consumer (MPSC)** channel.
+impl PartialEq<RecvState> for PollOutcome {
+    fn eq(&self, other: &RecvState) -> bool {
+        self.state == *other
+    }
+}
+
+impl From<PollOutcome> for RecvState {
+    fn from(outcome: PollOutcome) -> Self {
+        outcome.state
+    }
+}
+
+/// The outcome of a [`Receiver::try_recv_batch`], which never waits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecvResult {
+    /// The given number of items, at least one, were handed to the handler.
+    Processed(usize),
+    /// No item was available.
+    Empty,
+    /// The channel is closed or every sender is gone, and the buffer has been drained.
+    Disconnected,
+}
+
+/// What a receiver does when its handler panics, set with
+/// [`Receiver::with_panic_policy`].
 ///
-/// - Multiple producers
-/// - One consumer
+/// The item the handler panicked on is lost under every policy.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Let the panic unwind out of the receive, handing the rest of the batch
+    /// back to the channel to be received again, by this receiver or by
+    /// another one sharing its items.
+    #[default]
+    Republish,
+    /// Close the channel as poisoned, with [`ChannelPoisoned`] as the reason,
+    /// drop the rest of the batch and report [`RecvState::Poisoned`].
+    Poison,
+    /// Carry on with the rest of the batch.
+    Skip,
+}
+
+/// A claimed slot of the buffer, created by [`Sender::claim`] or [`Sender::claim_existing`].
 ///
-/// # Parameters
-/// - `buffer_size`: capacity of the underlying ring buffer.
-/// - `pw`: producer wait strategy.
-/// - `cw`: consumer wait strategy.
-pub fn mpsc<T>(
-    buffer_size: usize,
-    pw: ProducerWaitStrategyKind,
-    cw: ConsumerWaitStrategyKind,
-) -> (Sender<T>, Receiver<T>) {
-    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
-    utils::assert_buffer_size_pow_of_2(buffer_size);
+/// Dereferences to the item under construction, which is published to
+/// consumers once the guard is [`commit`](Self::commit)ted or dropped.
+pub struct SlotGuard<'a, T> {
+    permit: SendPermits<'a, T>,
+}
 
-    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
-    let poller = Box::new(SingleConsumerPoller::new());
-    let coordinator = Arc::new(Coordinator::new(pw, cw));
+impl<T> SlotGuard<'_, T> {
+    /// Returns the sequence of the claimed slot.
+    pub fn sequence(&self) -> i64 {
+        self.permit.next
+    }
 
-    let buffer: Arc<RingBuffer<T>> = Arc::new(RingBuffer::new(buffer_size, sequencer, poller));
-    let sender = Sender {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
-    let receiver = Receiver {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
+    /// Publish the item to consumers.
+    pub fn commit(self) {}
+}
 
-    (sender, receiver)
+impl<T> Deref for SlotGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the slot was initialized when it was claimed, and consumers
+        // cannot read it before it is published.
+        unsafe { &*self.permit.sender.buffer.slot(self.permit.next) }
+    }
 }
 
-/// Create a **single-producer multi-consumer (SPMC)** channel.
+impl<T> DerefMut for SlotGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: see `deref`; the guard is the only handle to the slot.
+        unsafe { &mut *self.permit.sender.buffer.slot(self.permit.next) }
+    }
+}
+
+impl<T> Drop for SlotGuard<'_, T> {
+    fn drop(&mut self) {
+        self.permit.publish_in_place();
+    }
+}
+
+/// A window of sequences claimed ahead of time for one thread, created by
+/// [`Sender::producer_handle`].
 ///
-/// - One producer
-/// - Multiple consumers
+/// Sends through the handle fill the window one item at a time and claim the
+/// next window once it is used up, so producers touch the shared claim cursor
+/// once per window instead of once per item. Consumers cannot get past the
+/// part of a window that is not filled yet, so a handle suits threads that
+/// send steadily.
 ///
-/// # Parameters
-/// - `buffer_size`: capacity of the underlying ring buffer.
-/// - `pw`: producer wait strategy.
-/// - `cw`: consumer wait strategy.
-pub fn spmc<T>(
-    buffer_size: usize,
-    pw: ProducerWaitStrategyKind,
-    cw: ConsumerWaitStrategyKind,
-) -> (Sender<T>, Receiver<T>) {
-    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
-    utils::assert_buffer_size_pow_of_2(buffer_size);
-
-    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
-    let poller = Box::new(MultiConsumerPoller::new());
-    let coordinator = Arc::new(Coordinator::new(pw, cw));
+/// Dropping the handle hands the rest of its window back to producers, as
+/// dropping [`SendPermits`] does.
+#[cfg(feature = "mp")]
+pub struct ProducerHandle<'a, T> {
+    window: usize,
+    permits: SendPermits<'a, T>,
+}
 
-    let buffer: Arc<RingBuffer<T>> = Arc::new(RingBuffer::new(buffer_size, sequencer, poller));
-    let sender = Sender {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
-    let receiver = Receiver {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
+#[cfg(feature = "mp")]
+impl<T> ProducerHandle<'_, T> {
+    /// Send a single value into the next sequence of the window.
+    ///
+    /// Claims a new window first if the current one is used up, waiting
+    /// according to the producer wait strategy until all of it is free.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed,
+    /// including while this call waits for free space.
+    pub fn send(&mut self, value: T) -> Result<(), SendError<T>> {
+        let sender = self.permits.sender;
+        if !sender.admitted(1) {
+            return Err(sender.closed(value));
+        }
+        if self.permits.remaining() == 0 {
+            match sender.permits(self.window) {
+                Ok(permits) => self.permits = permits,
+                Err(_) => return Err(sender.closed(value)),
+            }
+        }
+        self.permits.send(value)
+    }
 
-    (sender, receiver)
+    /// Returns the number of sequences left in the current window.
+    pub fn remaining(&self) -> usize {
+        self.permits.remaining()
+    }
 }
 
-/// Create a **multi-producer multi-consumer (MPMC)** channel.
+/// A slot reserved by [`Sender::reserve`] or [`Sender::try_reserve`], to be
+/// filled once the value exists.
 ///
-/// - Multiple producers
-/// - Multiple consumers
+/// Capacity is taken when the permit is created, so [`send`](Self::send) never
+/// waits for free space. Consumers cannot get past the slot until the permit
+/// is used, so they receive the items sent after it once it is.
 ///
-/// # Parameters
-/// - `buffer_size`: capacity of the underlying ring buffer.
-/// - `pw`: producer wait strategy.
-/// - `cw`: consumer wait strategy.
-pub fn mpmc<T>(
-    buffer_size: usize,
-    pw: ProducerWaitStrategyKind,
-    cw: ConsumerWaitStrategyKind,
-) -> (Sender<T>, Receiver<T>) {
-    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
-    utils::assert_buffer_size_pow_of_2(buffer_size);
+/// Dropping the permit without sending hands the slot back to producers. If
+/// another producer claimed sequences after it in the meantime, the slot
+/// cannot be handed back, and the channel is closed with
+/// [`SequencesAbandoned`] instead of stalling its consumers for good.
+#[must_use = "consumers stall until a reserved slot is sent"]
+pub struct SendPermit<'a, T> {
+    permits: SendPermits<'a, T>,
+}
 
-    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
-    let poller = Box::new(MultiConsumerPoller::new());
-    let coordinator = Arc::new(Coordinator::new(pw, cw));
+impl<T> SendPermit<'_, T> {
+    /// Returns the sequence of the reserved slot.
+    pub fn sequence(&self) -> i64 {
+        self.permits.next
+    }
 
-    let buffer: Arc<RingBuffer<T>> = Arc::new(RingBuffer::new(buffer_size, sequencer, poller));
-    let sender = Sender {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
-    let receiver = Receiver {
-        buffer: buffer.clone(),
-        coordinator: coordinator.clone(),
-    };
+    /// Write `value` into the reserved slot and publish it to consumers.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel was closed
+    /// since the slot was reserved.
+    pub fn send(mut self, value: T) -> Result<(), SendError<T>> {
+        self.permits.send(value)
+    }
+}
 
-    (sender, receiver)
+/// Consecutive slots reserved by [`Sender::reserve_n`], filled in order.
+///
+/// See [`SendPermit`]; dropping the permits hands the slots that were not
+/// sent back to producers. Slot guards, producer handles and sequence ranges
+/// reserve their slots as permits too.
+#[must_use = "consumers stall until the reserved slots are sent"]
+pub struct SendPermits<'a, T> {
+    sender: &'a Sender<T>,
+    next: i64,
+    high: i64,
+}
+
+impl<'a, T> SendPermits<'a, T> {
+    /// Permits for the reserved range `[low, high]`.
+    fn new(sender: &'a Sender<T>, (low, high): (i64, i64)) -> Self {
+        Self {
+            sender,
+            next: low,
+            high,
+        }
+    }
+
+    /// Write `value` into the next reserved slot and publish it to consumers.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel was closed
+    /// since the slots were reserved.
+    ///
+    /// # Panics
+    /// Panics if every reserved slot was sent already.
+    pub fn send(&mut self, value: T) -> Result<(), SendError<T>> {
+        self.publish(1, [value])
+            .map_err(|SendError::Closed([value], reason)| SendError::Closed(value, reason))
+    }
+
+    /// Returns the number of reserved slots not sent yet.
+    pub fn remaining(&self) -> usize {
+        (self.high - self.next + 1) as usize
+    }
+
+    /// Write `items` into the next `n` reserved slots and publish them.
+    fn publish<I>(&mut self, n: usize, items: I) -> Result<(), SendError<I>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        assert!(n <= self.remaining(), "every reserved slot was sent");
+        let sender = self.sender;
+        sender.producing(n, || {
+            if sender.coordinator.is_closed() {
+                return Err(sender.closed(items));
+            }
+            let low = self.next;
+            self.next += n as i64;
+            sender
+                .buffer
+                .publish_reserved(low, self.next - 1, items, sender.producer);
+            sender.notify(n);
+            Ok(())
+        })
+    }
+
+    /// Publish the next reserved slot, which was initialized in place.
+    fn publish_in_place(&mut self) {
+        let sender = self.sender;
+        let _ = sender.producing(1, || {
+            sender.buffer.publish(self.next);
+            self.next += 1;
+            sender.notify(1);
+            Ok::<_, ()>(())
+        });
+    }
+
+    /// Hand the reserved slots over as a detached range, see [`Sender::publish_into`].
+    fn into_range(mut self) -> SequenceRange {
+        let range = SequenceRange {
+            low: self.next,
+            high: self.high,
+        };
+        self.next = self.high + 1;
+        range
+    }
+}
+
+impl<T> Drop for SendPermits<'_, T> {
+    fn drop(&mut self) {
+        if self.remaining() == 0 || self.sender.coordinator.is_closed() {
+            return;
+        }
+        if !self.sender.buffer.unreserve(self.next, self.high) {
+            let reason = Arc::new(SequencesAbandoned);
+            self.sender.coordinator.close(Some(reason));
+        }
+    }
+}
+
+/// Sends that wake the consumer once for all of them, created by [`Sender::batch`].
+///
+/// Every item is published as soon as it is sent, so consumers that are
+/// running pick it up at once, but parked consumers are only woken when the
+/// batch is [`flush`](Self::flush)ed or dropped, or when a send finds the
+/// buffer full and has to wait for them. A producer sending a burst of items
+/// one at a time then signals once per burst instead of once per item.
+pub struct SenderBatch<'a, T> {
+    sender: &'a Sender<T>,
+    pending: usize,
+}
+
+impl<T> SenderBatch<'_, T> {
+    /// Send a single value into the buffer without waking the consumer.
+    ///
+    /// If the buffer is full, the consumer is woken for the items sent so
+    /// far, and the call waits according to the producer wait strategy.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed,
+    /// including while this call waits for free space.
+    pub fn send(&mut self, value: T) -> Result<(), SendError<T>> {
+        let sender = self.sender;
+        if sender.coordinator.is_rendezvous() {
+            self.flush();
+            return sender.send(value);
+        }
+        sender.producing(1, || {
+            if !sender.admitted(1) {
+                return Err(sender.closed(value));
+            }
+            if let Err((_, value)) = sender.buffer.try_push(value, sender.producer) {
+                self.flush();
+                sender
+                    .buffer
+                    .push(value, &sender.coordinator, sender.producer)
+                    .map_err(|value| sender.closed(value))?;
+            }
+            self.pending += 1;
+            Ok(())
+        })
+    }
+
+    /// Wake the consumer for the items sent since the last wakeup, if any.
+    pub fn flush(&mut self) {
+        if self.pending > 0 {
+            self.sender.notify(self.pending);
+            self.pending = 0;
+        }
+    }
+
+    /// Returns the number of items sent since the consumer was last woken.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+impl<T> Drop for SenderBatch<'_, T> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// A receiver whose items stay in the channel until it acknowledges them.
+///
+/// Receiving clones an item out of its slot, and producers cannot reuse the
+/// slot before [`ack`](Self::ack) hands it back. Until then, the receiver can
+/// [`rewind`](Self::rewind) to an item it received and receive it, and every
+/// item after it, again, for instance to replay them after a failure. Items
+/// that are received and not acknowledged take up slots, so producers stall
+/// once the buffer holds nothing else.
+///
+/// Created by [`spsc_acked`] and [`mpsc_acked`].
+pub struct AckReceiver<T> {
+    receiver: Receiver<T>,
+    /// The last sequence received, the acknowledged one being the gating
+    /// sequence of the channel.
+    consumed: Arc<Sequence>,
+}
+
+impl<T> AckReceiver<T> {
+    /// Receive up to `batch_size` items, passing the [`Context`] of each, whose
+    /// sequence is what [`ack`](Self::ack) and [`rewind`](Self::rewind) take.
+    ///
+    /// Waits according to the consumer wait strategy if no item is
+    /// available, see [`Receiver::recv_with_context`].
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(Context, T),
+    {
+        self.receiver.recv_with_context(batch_size, handler)
+    }
+
+    /// Acknowledge every item up to `sequence`, handing their slots back to
+    /// producers.
+    ///
+    /// Items acknowledged already are ignored. Returns `false` and
+    /// acknowledges nothing if the item at `sequence` was not received, or
+    /// was rewound past.
+    pub fn ack(&mut self, sequence: i64) -> bool {
+        if sequence > self.consumed.get_relaxed() {
+            return false;
+        }
+        let (buffer, coordinator) = self.receiver.parts();
+        if sequence > buffer.gating_sequence() {
+            buffer.release(sequence);
+            coordinator.wakeup_producers();
+        }
+        true
+    }
+
+    /// Move the receiver back so that the next receive starts at `sequence`.
+    ///
+    /// Returns `false` and moves nothing if the item at `sequence` is
+    /// acknowledged already, or comes after the next item to receive.
+    pub fn rewind(&mut self, sequence: i64) -> bool {
+        let (buffer, _) = self.receiver.parts();
+        if sequence <= buffer.gating_sequence() || sequence > self.consumed.get_relaxed() + 1 {
+            return false;
+        }
+        self.consumed.set_release(sequence - 1);
+        true
+    }
+
+    /// Rewind to the first item not acknowledged, so that every item received
+    /// and not acknowledged is received again.
+    pub fn replay(&mut self) {
+        let (buffer, _) = self.receiver.parts();
+        self.consumed.set_release(buffer.gating_sequence());
+    }
+
+    /// Returns the number of items received and not acknowledged yet.
+    pub fn unacked(&self) -> usize {
+        let (buffer, _) = self.receiver.parts();
+        (self.consumed.get_acquire() - buffer.gating_sequence()).max(0) as usize
+    }
+
+    /// Returns the logical position of the last item received, or `None` if
+    /// none was, see [`Receiver::last_consumed`].
+    pub fn last_consumed(&self) -> Option<u64> {
+        self.receiver.last_consumed()
+    }
+
+    /// Returns the logical position of the last item acknowledged, or `None`
+    /// if none was, see [`sequence_to_position`].
+    pub fn last_acked(&self) -> Option<u64> {
+        let (buffer, _) = self.receiver.parts();
+        sequence_to_position(buffer.gating_sequence())
+    }
+
+    /// Close the channel, see [`Receiver::close`].
+    pub fn close(&self) -> bool {
+        self.receiver.close()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.receiver.capacity()
+    }
+}
+
+/// How [`Receiver::spawn_consumer_with`] runs a consumer thread.
+#[derive(Clone, Debug)]
+pub struct ConsumerOptions {
+    name: String,
+    batch_size: usize,
+    core: Option<usize>,
+}
+
+impl ConsumerOptions {
+    /// Run a thread named `name` that receives batches of up to `batch_size` items.
+    pub fn new(name: impl Into<String>, batch_size: usize) -> Self {
+        Self {
+            name: name.into(),
+            batch_size,
+            core: None,
+        }
+    }
+
+    /// Pin the thread to the CPU core `core` before it starts consuming,
+    /// see [`affinity`](crate::affinity).
+    #[cfg(feature = "affinity")]
+    pub fn pin_to_core(mut self, core: usize) -> Self {
+        self.core = Some(core);
+        self
+    }
+}
+
+/// A consumer running on a thread of its own, created by [`Receiver::spawn_consumer`]
+/// or [`Receiver::spawn_consumer_with`].
+///
+/// Dropping the handle detaches the thread, which keeps consuming until the
+/// channel disconnects.
+pub struct ConsumerHandle {
+    stopped: Arc<PaddedFlag>,
+    coordinator: Arc<Coordinator>,
+    thread: JoinHandle<()>,
+}
+
+impl ConsumerHandle {
+    /// Ask the consumer to stop once it has handled its current batch.
+    ///
+    /// Items it has not taken yet stay in the buffer for other receivers; to
+    /// have every item handled instead, close the channel and [`join`](Self::join).
+    pub fn stop(&self) {
+        self.stopped.set();
+        self.coordinator.wake_consumers();
+    }
+
+    /// Returns `true` once the consumer thread has exited.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait for the consumer thread to exit.
+    ///
+    /// The consumer exits once it is [`stop`](Self::stop)ped, or once the
+    /// channel is closed or every sender is gone and the buffer is drained.
+    ///
+    /// # Errors
+    /// Returns the panic payload if the handler panicked.
+    pub fn join(self) -> thread::Result<()> {
+        // A blocked consumer may miss a single wakeup to another receiver of
+        // the channel, so keep waking it until it saw the request.
+        while self.stopped.is_set() && !self.thread.is_finished() {
+            self.coordinator.wake_consumers();
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.thread.join()
+    }
+}
+
+/// Generate the `send_withN` methods of [`Sender`], which pass their
+/// arguments through [`Sender::send_with`] to the translator.
+macro_rules! send_with_args {
+    ($($(#[$doc:meta])* fn $name:ident($($arg:ident: $ty:ident),+);)+) => {
+        $(
+            $(#[$doc])*
+            ///
+            /// # Errors
+            /// Returns [`SendError::Closed`] with the arguments if the channel is closed.
+            pub fn $name<$($ty,)+ F>(
+                &self,
+                translator: F,
+                $($arg: $ty),+
+            ) -> Result<(), SendError<($($ty,)+)>>
+            where
+                T: Default,
+                F: FnOnce(&mut T, i64, $($ty),+),
+            {
+                self.translate(($($arg,)+), |slot, sequence, ($($arg,)+)| {
+                    translator(slot, sequence, $($arg),+)
+                })
+            }
+        )+
+    };
+}
+
+impl<T> Sender<T> {
+    /// Clone the sender, or return `None` if the channel has a bounded number
+    /// of producers and the maximum is already registered.
+    pub fn try_clone(&self) -> Option<Self> {
+        let producer = match self.coordinator.producers() {
+            Some(producers) => Some(producers.register()?),
+            None => None,
+        };
+        self.coordinator.add_sender();
+        Some(Self {
+            buffer: self.buffer.clone(),
+            coordinator: self.coordinator.clone(),
+            producer,
+            rate_limit: self.rate_limit.clone(),
+        })
+    }
+
+    /// Returns the id of this producer on channels with a bounded number of producers.
+    pub fn producer_id(&self) -> Option<usize> {
+        self.producer
+    }
+
+    /// Returns the maximum number of live senders, if the channel bounds it.
+    pub fn max_producers(&self) -> Option<usize> {
+        self.coordinator
+            .producers()
+            .map(|producers| producers.max_producers())
+    }
+
+    /// Returns a snapshot of every registered producer, ordered by id.
+    ///
+    /// Empty unless the channel was created with a bounded number of producers.
+    pub fn producer_statuses(&self) -> Vec<ProducerStatus> {
+        self.coordinator
+            .producers()
+            .map(|producers| producers.statuses())
+            .unwrap_or_default()
+    }
+
+    /// Limit how fast this sender, and the clones made from it from now on,
+    /// send: `rate` items per second on average, in bursts of up to `burst`
+    /// items.
+    ///
+    /// Sends over the limit wait according to the producer wait strategy
+    /// before they claim slots, which smooths a bursty producer before its
+    /// items reach the ring buffer, while non-blocking sends fail with
+    /// [`TrySendError::WouldBlock`]. The clones share the limit. Items sent
+    /// through reserved slots are counted when they are reserved.
+    ///
+    /// # Panics
+    /// Panics if `rate` is not a positive finite number or `burst` is zero.
+    pub fn with_rate_limit(mut self, rate: f64, burst: usize) -> Self {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "rate must be a positive finite number"
+        );
+        assert!(burst > 0, "burst must not be zero");
+        self.rate_limit = Some(Arc::new(RateLimit::new(rate, burst)));
+        self
+    }
+
+    /// Wait until the rate limit, if any, admits `n` more items.
+    ///
+    /// Returns `false` if the channel is closed, before or while waiting.
+    #[inline(always)]
+    fn admitted(&self, n: usize) -> bool {
+        let Some(limit) = &self.rate_limit else {
+            return !self.coordinator.is_closed();
+        };
+        let mut waited = false;
+        loop {
+            if self.coordinator.is_closed() {
+                return false;
+            }
+            match limit.try_take(n) {
+                Ok(()) => break,
+                Err(wait) => {
+                    self.coordinator.producer_wait_until(Instant::now() + wait);
+                    waited = true;
+                }
+            }
+        }
+        if waited {
+            self.coordinator.producer_progress();
+        }
+        true
+    }
+
+    /// Take `n` more items off the rate limit, if any, without waiting.
+    #[inline(always)]
+    fn try_admitted(&self, n: usize) -> bool {
+        self.rate_limit
+            .as_ref()
+            .is_none_or(|limit| limit.try_take(n).is_ok())
+    }
+
+    /// Give `n` items admitted by [`try_admitted`](Self::try_admitted) back
+    /// to the rate limit, after they could not be sent.
+    fn unadmit(&self, n: usize) {
+        if let Some(limit) = &self.rate_limit {
+            limit.give_back(n);
+        }
+    }
+
+    /// Run a send in this producer's slot, counting the `n` items it delivers.
+    #[inline(always)]
+    fn producing<V, E>(&self, n: usize, send: impl FnOnce() -> Result<V, E>) -> Result<V, E> {
+        let (Some(id), Some(producers)) = (self.producer, self.coordinator.producers()) else {
+            return send();
+        };
+        producers.begin(id);
+        let started = Instant::now();
+        let result = send();
+        let sent = if result.is_ok() { n } else { 0 };
+        producers.end(id, sent, started.elapsed());
+        result
+    }
+
+    /// Send a single value into the buffer.
+    ///
+    /// If the buffer is full, the configured producer wait strategy determines
+    /// how the call behaves (e.g. spin, yield, or park). On a rendezvous
+    /// channel, see [`spsc_rendezvous`], it waits the same way until a receiver
+    /// has taken the value.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value and the consumer's reason if
+    /// the channel is closed, including while this call waits for free space.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.producing(1, || {
+            if !self.admitted(1) {
+                return Err(self.closed(value));
+            }
+            if self.coordinator.is_rendezvous() {
+                return self.hand_off(value);
+            }
+            self.buffer
+                .push(value, &self.coordinator, self.producer)
+                .map_err(|value| self.closed(value))?;
+            self.notify(1);
+            Ok(())
+        })
+    }
+
+    /// Hand `value` to a receiver of a rendezvous channel, waiting according
+    /// to the producer wait strategy until one waits for it, and then until
+    /// it took it.
+    fn hand_off(&self, value: T) -> Result<(), SendError<T>> {
+        while !self.coordinator.has_taker() {
+            if self.coordinator.is_closed() {
+                return Err(self.closed(value));
+            }
+            self.coordinator.producer_wait();
+        }
+        self.buffer
+            .push(value, &self.coordinator, self.producer)
+            .map_err(|value| self.closed(value))?;
+        self.notify(1);
+        // Once the last receiver is gone nobody takes the item, but it has
+        // left the sender's hands already.
+        while self.buffer.backlog() > 0 && self.coordinator.receiver_count() > 0 {
+            self.coordinator.producer_wait();
+        }
+        self.coordinator.producer_progress();
+        Ok(())
+    }
+
+    /// Try to send a single value without waiting for free space.
+    ///
+    /// Unlike [`send`](Self::send), this never waits for consumers, so producers
+    /// can back off or shed load instead of stalling on a full buffer. On a
+    /// rendezvous channel it only hands the value off if a receiver waits for
+    /// one, and does not wait for the receiver to take it.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the buffer has no free slot, or if no
+    ///   receiver of a rendezvous channel waits for a value.
+    /// - [`TrySendError::WouldBlock`] if the rate limit of the sender admits
+    ///   no more items for now, see [`with_rate_limit`](Self::with_rate_limit).
+    /// - [`TrySendError::Closed`] if the channel is closed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.try_send_bounded(value, usize::MAX)
+    }
+
+    /// Try to send a single value, bounding the time spent competing for a slot.
+    ///
+    /// Works like [`try_send`](Self::try_send), except that on multi-producer
+    /// channels a claim that loses the race against other producers is retried
+    /// at most `max_retries` times, so the worst-case time spent in this call
+    /// is bounded. Single-producer channels never contend for the cursor.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the buffer has no free slot, or if no
+    ///   receiver of a rendezvous channel waits for a value.
+    /// - [`TrySendError::WouldBlock`] if every claim attempt lost to another
+    ///   producer, or if the rate limit of the sender admits no more items.
+    /// - [`TrySendError::Closed`] if the channel is closed.
+    pub fn try_send_bounded(&self, value: T, max_retries: usize) -> Result<(), TrySendError<T>> {
+        self.producing(1, || {
+            if self.coordinator.is_closed() {
+                return Err(TrySendError::Closed(value, self.coordinator.close_reason()));
+            }
+            if self.coordinator.is_rendezvous() && !self.coordinator.has_taker() {
+                return Err(TrySendError::Full(value));
+            }
+            if !self.try_admitted(1) {
+                return Err(TrySendError::WouldBlock(value));
+            }
+            let result = self
+                .buffer
+                .try_push_bounded(value, max_retries, self.producer);
+            if result.is_err() {
+                self.unadmit(1);
+            }
+            self.try_sent(result)
+        })
+    }
+
+    /// Reserve `n` consecutive ring positions before their payloads exist.
+    ///
+    /// This lets an external coordinator (a deterministic replayer, a consensus
+    /// layer, ...) decide the global order of messages up front and produce the
+    /// payloads later with [`publish_into`](Self::publish_into). Waits according
+    /// to the producer wait strategy if the buffer has no room for the range.
+    ///
+    /// Consumers receive the range in the position it was reserved at: items
+    /// sent after the range, and ranges reserved after it and published first,
+    /// are only received once the range is published.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed.
+    ///
+    /// # Panics
+    /// If `n` is zero or greater than the buffer size it will panic
+    pub fn reserve_sequence_range(&self, n: usize) -> Result<SequenceRange, SendError<()>> {
+        self.reserve_n(n).map(SendPermits::into_range)
+    }
+
+    /// Reserve the next slot of the buffer before the value to send exists.
+    ///
+    /// The returned [`SendPermit`] sends the value without waiting, so work
+    /// that may fail, or that should not start unless its result can be sent,
+    /// only runs once capacity is guaranteed. Waits according to the producer
+    /// wait strategy if the buffer is full.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed, including while
+    /// this call waits for free space.
+    pub fn reserve(&self) -> Result<SendPermit<'_, T>, SendError<()>> {
+        let permits = self.reserve_n(1)?;
+        Ok(SendPermit { permits })
+    }
+
+    /// Reserve the next `n` slots of the buffer, see [`reserve`](Self::reserve).
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed, including while
+    /// this call waits for free space.
+    ///
+    /// # Panics
+    /// If `n` is zero or greater than the buffer size it will panic
+    pub fn reserve_n(&self, n: usize) -> Result<SendPermits<'_, T>, SendError<()>> {
+        if !self.admitted(n) {
+            return Err(self.closed(()));
+        }
+        self.permits(n)
+    }
+
+    /// Reserve the next `n` slots without going through the rate limit,
+    /// waiting according to the producer wait strategy if the buffer is full.
+    fn permits(&self, n: usize) -> Result<SendPermits<'_, T>, SendError<()>> {
+        self.producing(0, || match self.buffer.reserve(n, &self.coordinator) {
+            Ok(range) => Ok(SendPermits::new(self, range)),
+            Err(_) => Err(self.closed(())),
+        })
+    }
+
+    /// Try to reserve the next slot of the buffer without waiting for free
+    /// space, see [`reserve`](Self::reserve).
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the buffer has no free slot.
+    /// - [`TrySendError::WouldBlock`] if the rate limit of the sender admits
+    ///   no more items for now.
+    /// - [`TrySendError::Closed`] if the channel is closed.
+    pub fn try_reserve(&self) -> Result<SendPermit<'_, T>, TrySendError<()>> {
+        if self.coordinator.is_closed() {
+            return Err(TrySendError::Closed((), self.coordinator.close_reason()));
+        }
+        if !self.try_admitted(1) {
+            return Err(TrySendError::WouldBlock(()));
+        }
+        match self.buffer.try_reserve(1) {
+            Ok(range) => Ok(SendPermit {
+                permits: SendPermits::new(self, range),
+            }),
+            Err(error) => {
+                self.unadmit(1);
+                Err(self.try_error(error, ()))
+            }
+        }
+    }
+
+    /// Claim the next slot of the buffer to construct an item in place.
+    ///
+    /// The slot starts out as `T::default()` and is modified through the
+    /// returned [`SlotGuard`], which publishes it to consumers when it is
+    /// committed or dropped. This spares events with large inline buffers from
+    /// being built on the stack and moved into the buffer. Waits according to
+    /// the producer wait strategy if the buffer is full.
+    ///
+    /// Consumers cannot get past the slot until it is committed, so they
+    /// receive the items sent after it once it is.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed.
+    pub fn claim(&self) -> Result<SlotGuard<'_, T>, SendError<()>>
+    where
+        T: Default,
+    {
+        let permit = self.reserve_n(1)?;
+        self.buffer
+            .init_reserved(permit.next, T::default(), self.producer);
+        Ok(SlotGuard { permit })
+    }
+
+    /// Claim the next slot of a channel created with a factory, such as
+    /// [`spsc_with_factory`], to update the item left in it by the previous lap.
+    ///
+    /// Unlike [`claim`](Self::claim), the slot is not reset, so the returned
+    /// [`SlotGuard`] hands out the existing item, and the allocations it owns
+    /// can be reused. The guard publishes the item when it is committed or
+    /// dropped. Waits according to the producer wait strategy if the buffer is full.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed.
+    ///
+    /// # Panics
+    /// Panics if the channel was not created with a factory.
+    pub fn claim_existing(&self) -> Result<SlotGuard<'_, T>, SendError<()>> {
+        assert!(
+            self.buffer.is_prefilled(),
+            "only channels created with a factory hand out the items of their slots"
+        );
+        let permit = self.reserve_n(1)?;
+        self.buffer.reuse_reserved(permit.next, self.producer);
+        Ok(SlotGuard { permit })
+    }
+
+    /// Publish an item built in place by `translator`, Disruptor style.
+    ///
+    /// The translator gets the slot and its sequence. The slot holds
+    /// `T::default()`, or the item of the previous lap on a channel created
+    /// with a factory, such as [`spsc_with_factory`], so the allocations it
+    /// owns can be reused. The item is published once the translator returns,
+    /// or panics. Waits according to the producer wait strategy if the buffer
+    /// is full.
+    ///
+    /// The `send_with1` to `send_with5` variants pass extra arguments through
+    /// to the translator, and hand them back if the channel is closed.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if the channel is closed.
+    pub fn send_with<F>(&self, translator: F) -> Result<(), SendError<()>>
+    where
+        T: Default,
+        F: FnOnce(&mut T, i64),
+    {
+        self.translate((), |slot, sequence, ()| translator(slot, sequence))
+    }
+
+    send_with_args! {
+        /// Same as [`send_with`](Self::send_with), passing `a` to the translator.
+        fn send_with1(a: A);
+        /// Same as [`send_with`](Self::send_with), passing `a` and `b` to the translator.
+        fn send_with2(a: A, b: B);
+        /// Same as [`send_with`](Self::send_with), passing `a` to `c` to the translator.
+        fn send_with3(a: A, b: B, c: C);
+        /// Same as [`send_with`](Self::send_with), passing `a` to `d` to the translator.
+        fn send_with4(a: A, b: B, c: C, d: D);
+        /// Same as [`send_with`](Self::send_with), passing `a` to `e` to the translator.
+        fn send_with5(a: A, b: B, c: C, d: D, e: E);
+    }
+
+    /// Publish a batch of items built in place by `translator`, one per item
+    /// of `args`.
+    ///
+    /// See [`send_with`](Self::send_with). The whole batch is claimed at once
+    /// and published once every item is built, so translators taking several
+    /// arguments receive them as a tuple.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the untouched iterator if the channel
+    /// is closed, including while this call waits for free space.
+    ///
+    /// # Panics
+    /// If `args` holds more items than the buffer size it will panic
+    pub fn send_n_with<I, F>(&self, args: I, translator: F) -> Result<(), SendError<I::IntoIter>>
+    where
+        T: Default,
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        F: FnMut(&mut T, i64, I::Item),
+    {
+        let args = args.into_iter();
+        let len = args.len();
+        if len == 0 {
+            return Ok(());
+        }
+        self.producing(len, || {
+            if !self.admitted(len) {
+                return Err(self.closed(args));
+            }
+            let Ok((low, high)) = self.buffer.reserve(len, &self.coordinator) else {
+                return Err(self.closed(args));
+            };
+            self.buffer
+                .translate_reserved(low, high, args, translator, self.producer);
+            self.notify(len);
+            Ok(())
+        })
+    }
+
+    /// Claim a slot, see [`send_with`](Self::send_with), and hand it to
+    /// `translator` together with `args`.
+    fn translate<A, F>(&self, args: A, translator: F) -> Result<(), SendError<A>>
+    where
+        T: Default,
+        F: FnOnce(&mut T, i64, A),
+    {
+        let claimed = match self.buffer.is_prefilled() {
+            true => self.claim_existing(),
+            false => self.claim(),
+        };
+        let Ok(mut slot) = claimed else {
+            return Err(self.closed(args));
+        };
+        let sequence = slot.sequence();
+        translator(&mut slot, sequence, args);
+        Ok(())
+    }
+
+    /// Write `items` into a reserved range and publish it to consumers.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the items if the channel is closed.
+    ///
+    /// # Panics
+    /// If the number of items differs from the length of `range` it will panic
+    pub fn publish_into<I>(&self, range: SequenceRange, items: I) -> Result<(), SendError<I>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let n = range.len();
+        SendPermits::new(self, (range.low, range.high)).publish(n, items)
+    }
+
+    /// Send multiple values into the buffer in a batch.
+    ///
+    /// This is more efficient than calling [`send`](Self::send) repeatedly,
+    /// as it reduces synchronization overhead. The items take consecutive
+    /// sequences, published as the [`BatchAtomicity`] of the channel says.
+    ///
+    /// # Type Parameters
+    /// - `I`: an `IntoIterator` where the iterator implements `ExactSizeIterator`.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the untouched iterator if the channel
+    /// is closed, including while this call waits for free space.
+    pub fn send_n<I>(&self, items: I) -> Result<(), SendError<I::IntoIter>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let items = items.into_iter();
+        let len = items.len();
+        self.producing(len, || {
+            if !self.admitted(len) {
+                return Err(self.closed(items));
+            }
+            self.buffer
+                .push_n(items, &self.coordinator, self.producer)
+                .map_err(|items| self.closed(items))?;
+            self.notify(len);
+            Ok(())
+        })
+    }
+
+    /// Create a handle that claims `window` sequences at a time for the
+    /// calling thread, see [`ProducerHandle`].
+    ///
+    /// Returns `None` on single-producer channels, whose producer never
+    /// contends for the claim cursor.
+    ///
+    /// # Panics
+    /// If `window` is zero or greater than the buffer size it will panic
+    #[cfg(feature = "mp")]
+    pub fn producer_handle(&self, window: usize) -> Option<ProducerHandle<'_, T>> {
+        assert!(window > 0, "cannot claim an empty window");
+        assert!(
+            window <= self.buffer.buffer_size(),
+            "window must not exceed the buffer size"
+        );
+        self.buffer.is_multi_producer().then(|| ProducerHandle {
+            window,
+            permits: SendPermits::new(self, (0, -1)),
+        })
+    }
+
+    /// Start a batch of sends that wakes the consumer once, when it is
+    /// dropped, see [`SenderBatch`].
+    pub fn batch(&self) -> SenderBatch<'_, T> {
+        SenderBatch {
+            sender: self,
+            pending: 0,
+        }
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+
+    /// Returns the reason the consumer closed the channel with, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.coordinator.close_reason()
+    }
+
+    /// Close the channel, starting a shutdown.
+    ///
+    /// Every subsequent send fails with [`SendError::Closed`], and producers
+    /// waiting for free slots give up, but receivers still get every item
+    /// published before and report [`RecvState::Disconnected`] once they have
+    /// drained the buffer. Returns `false` if the channel was already closed.
+    pub fn close(&self) -> bool {
+        self.coordinator.close(None)
+    }
+
+    /// Wait until the receivers have consumed every item claimed on the
+    /// channel before this call, waiting according to the producer wait strategy.
+    ///
+    /// Items claimed by other senders count as well, so a flush waits for
+    /// their sends to complete. Together with [`close`](Self::close) this
+    /// shuts a channel down without losing queued items: close it so that no
+    /// new items arrive, then flush to know that the receivers handled the
+    /// rest.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] if every receiver is gone before the items
+    /// were consumed.
+    pub fn flush(&self) -> Result<(), SendError<()>> {
+        let target = self.buffer.cursor();
+        let mut waited = false;
+        while self.buffer.gating_sequence() < target {
+            if self.coordinator.receiver_count() == 0 {
+                return Err(self.closed(()));
+            }
+            self.coordinator.producer_wait();
+            waited = true;
+        }
+        if waited {
+            self.coordinator.producer_progress();
+        }
+        // Pairs with the release of the consumed items, so that their effects
+        // are visible once the flush returns.
+        fence(Ordering::Acquire);
+        Ok(())
+    }
+
+    /// Set the policy deciding when producers wake a blocked consumer.
+    ///
+    /// The policy is shared by every sender of the channel, and defaults to
+    /// [`NotifyPolicy::Always`].
+    pub fn set_notify_policy(&self, policy: NotifyPolicy) {
+        self.coordinator.set_notify_policy(policy);
+    }
+
+    /// Returns the policy deciding when producers wake a blocked consumer.
+    pub fn notify_policy(&self) -> NotifyPolicy {
+        self.coordinator.notify_policy()
+    }
+
+    /// Register a second producer on a single-producer channel and return its sender.
+    ///
+    /// The channel switches to the multi-producer sequencer, so channels that
+    /// usually have a single producer only pay for multi-producer claims once
+    /// another one shows up. The switch needs a quiescent point: taking the
+    /// only sender and the only receiver mutably guarantees that no producer or
+    /// consumer is active, and the channel must be drained. The sequences start
+    /// over as after [`rebase`]. On channels that already support several
+    /// producers this is a plain [`clone`](Clone::clone).
+    ///
+    /// # Errors
+    /// - [`RebaseError::ForeignChannel`] if `self` and `receiver` belong to different channels.
+    /// - [`RebaseError::Shared`] if other senders or receivers of the channel are alive.
+    /// - [`RebaseError::NotDrained`] if published items have not been received yet.
+    #[cfg(feature = "mp")]
+    pub fn register_additional_producer(
+        &mut self,
+        receiver: &mut Receiver<T>,
+    ) -> Result<Sender<T>, RebaseError> {
+        if self.buffer.is_multi_producer() {
+            return Ok(self.clone());
+        }
+        if !Arc::ptr_eq(&self.buffer, &receiver.buffer) {
+            return Err(RebaseError::ForeignChannel);
+        }
+        if self.coordinator.sender_count() != 1 || self.coordinator.receiver_count() != 1 {
+            return Err(RebaseError::Shared);
+        }
+
+        // Park the receiver on an empty buffer so the sender holds the only reference.
+        let placeholder: RingBuffer<T> =
+            RingBuffer::new(1, 0, Box::new(SingleProducerSequencer::new(1)));
+        drop(std::mem::replace(
+            &mut receiver.buffer,
+            Arc::new(placeholder),
+        ));
+        let upgraded = match Arc::get_mut(&mut self.buffer) {
+            Some(buffer) => match buffer.upgrade(&*receiver.poller) {
+                true => {
+                    #[cfg(feature = "registry")]
+                    registration(&self.coordinator).rebind(buffer.downgrade_sequencer());
+                    Ok(())
+                }
+                false => Err(RebaseError::NotDrained),
+            },
+            None => Err(RebaseError::Shared),
+        };
+        receiver.buffer = self.buffer.clone();
+        upgraded.map(|()| self.clone())
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.buffer_size()
+    }
+
+    /// Returns the number of items sent that consumers have not released yet.
+    ///
+    /// The count is a snapshot of the producer cursor and the gating sequence,
+    /// and may be stale by the time it is used. On multi-producer channels it
+    /// includes slots that are claimed but not published yet, and on broadcast
+    /// channels an item is counted until the slowest receiver has received it.
+    pub fn len(&self) -> usize {
+        self.buffer.backlog().min(self.capacity())
+    }
+
+    /// Returns the number of items that can be sent before a send waits for a free slot.
+    ///
+    /// Credits on credit-paced channels are not taken into account.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if no item is waiting in the ring buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if every slot of the ring buffer is taken.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Returns the logical position of the last item published on the
+    /// channel, by any sender, or `None` if none was published yet.
+    ///
+    /// Every item up to that position is published, so that receivers can
+    /// take it. See [`sequence_to_position`] for what positions are.
+    pub fn last_published(&self) -> Option<u64> {
+        sequence_to_position(self.buffer.published())
+    }
+
+    /// Returns a snapshot of the counters of the channel, shared by every
+    /// sender and receiver.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.coordinator.metrics()
+    }
+
+    /// Returns the id the channel is listed under in the
+    /// [`registry`], unique for the lifetime of the process.
+    #[cfg(feature = "registry")]
+    pub fn channel_id(&self) -> u64 {
+        registration(&self.coordinator).id()
+    }
+
+    /// Report the sequences of the channel without taking any item, to
+    /// diagnose a stall, see [`DebugState`].
+    #[cfg(feature = "inspect")]
+    pub fn debug_state(&self) -> DebugState {
+        self.buffer.snapshot(None)
+    }
+
+    /// Returns the coordinator of the channel.
+    #[cfg(feature = "futures")]
+    pub(crate) fn coordinator(&self) -> &Coordinator {
+        &self.coordinator
+    }
+
+    /// Wake the consumer after `published` items were published, as the notify policy allows.
+    #[inline(always)]
+    fn notify(&self, published: usize) {
+        self.coordinator
+            .notify_consumer(published, || self.buffer.backlog());
+    }
+
+    /// Wake the consumer after a successful non-blocking push, or map the claim failure.
+    fn try_sent(&self, result: Result<(), (ClaimError, T)>) -> Result<(), TrySendError<T>> {
+        match result {
+            Ok(()) => {
+                self.notify(1);
+                Ok(())
+            }
+            Err((error, value)) => Err(self.try_error(error, value)),
+        }
+    }
+
+    /// Build the error returned for a claim that failed with `error`.
+    fn try_error<V>(&self, error: ClaimError, value: V) -> TrySendError<V> {
+        match error {
+            ClaimError::Full => TrySendError::Full(value),
+            ClaimError::Contended => TrySendError::WouldBlock(value),
+            ClaimError::Closed => TrySendError::Closed(value, self.coordinator.close_reason()),
+            ClaimError::Exhausted => {
+                self.coordinator.close(Some(Arc::new(SequencesExhausted)));
+                TrySendError::Closed(value, self.coordinator.close_reason())
+            }
+        }
+    }
+
+    /// Build the error returned for a send on a closed channel.
+    fn closed<V>(&self, value: V) -> SendError<V> {
+        SendError::Closed(value, self.coordinator.close_reason())
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Register a new receiver of the channel polling through `poller`, or
+    /// sharing the poller of this one if there is none.
+    pub(crate) fn subscribed(&self, poller: Option<Box<dyn Poller<T>>>) -> Self {
+        self.coordinator.add_receiver();
+        let poller = match poller {
+            Some(poller) => Arc::from(poller),
+            None => self.poller.clone(),
+        };
+        let claims = poller.shares_claims().then(ActiveClaims::default);
+        Self {
+            buffer: self.buffer.clone(),
+            poller,
+            coordinator: self.coordinator.clone(),
+            flow: self.flow.clone(),
+            audit: self.audit.clone(),
+            budget: self.budget,
+            panics: self.panics,
+            claims,
+        }
+    }
+
+    /// Like [`clone`](Clone::clone), but a receiver that consumes independently
+    /// of this one only sees the items published from now on.
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    pub(crate) fn subscribe_latest(&self) -> Self {
+        self.subscribed(self.buffer.subscribe_latest(&*self.poller))
+    }
+
+    /// Stop gating producers on a receiver that consumes independently of the
+    /// others, which must not receive through it any more.
+    ///
+    /// The receiver still counts as alive, so the channel stays open while it
+    /// is kept, and it can still create receivers with
+    /// [`subscribe_latest`](Self::subscribe_latest).
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    pub(crate) fn detach(&self) {
+        self.buffer.unsubscribe(&*self.poller);
+    }
+
+    /// Returns the number of live receivers of the channel, this one included.
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    pub(crate) fn receiver_count(&self) -> usize {
+        self.coordinator.receiver_count()
+    }
+
+    /// Attach a [`FlowController`] that is consulted before every batch.
+    ///
+    /// The controller caps the size of each batch this receiver polls, which
+    /// lets a downstream sink push backpressure through the ring to producers.
+    /// Clones made afterwards share the same controller.
+    pub fn with_flow_controller(mut self, controller: Arc<dyn FlowController>) -> Self {
+        self.flow = Some(controller);
+        self
+    }
+
+    /// Returns a handle that wakes the consumers of the channel, for a
+    /// [`FlowController`] to resume them after pausing them with a `0` permit.
+    pub fn flow_handle(&self) -> FlowHandle {
+        FlowHandle::new(self.coordinator.clone())
+    }
+
+    /// Record every event this receiver hands to a handler in `trail`.
+    ///
+    /// Each record notes when the handler started, how long it ran and the
+    /// `consumer` id given here, which tells clones sharing a trail apart.
+    /// Clones made afterwards record into the same trail under the same id.
+    pub fn with_audit_trail(mut self, trail: Arc<AuditTrail>, consumer: usize) -> Self {
+        self.audit = Some((trail, consumer));
+        self
+    }
+
+    /// Bound the time a single receive spends handing items to the handler.
+    ///
+    /// The batch is polled in chunks of at most `check_every` items, and the
+    /// receive stops after the chunk during which `budget` ran out, releasing
+    /// what it processed and leaving the rest of the batch in the buffer. This
+    /// returns control in time to a consumer thread that also services other
+    /// work, at the cost of a claim per chunk. Applies to [`recv`](Self::recv),
+    /// [`blocking_recv`](Self::blocking_recv), [`try_recv_batch`](Self::try_recv_batch)
+    /// and [`recv_with_ref`](Self::recv_with_ref). Clones made afterwards keep
+    /// the same budget.
+    ///
+    /// # Panics
+    /// Panics if `check_every` is zero.
+    pub fn with_time_budget(mut self, budget: Duration, check_every: usize) -> Self {
+        assert!(
+            check_every > 0,
+            "time budget must be checked every few items"
+        );
+        self.budget = Some(TimeBudget {
+            limit: budget,
+            check_every,
+        });
+        self
+    }
+
+    /// Handle panics of the handler as `policy` asks.
+    ///
+    /// Applies to [`recv`](Self::recv), [`blocking_recv`](Self::blocking_recv),
+    /// [`try_recv_batch`](Self::try_recv_batch) and [`recv_with_ref`](Self::recv_with_ref).
+    /// Under any policy but [`PanicPolicy::Republish`], which is the default,
+    /// every item is handed to the handler within [`catch_unwind`](std::panic::catch_unwind).
+    /// Clones made afterwards keep the same policy.
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panics = policy;
+        self
+    }
+
+    /// Poll up to `batch_size` items, noting progress to the consumer wait strategy.
+    #[inline(always)]
+    fn poll<H>(&self, batch_size: usize, handler: &H) -> State
+    where
+        H: Fn(Context, T),
+    {
+        let batch_size = self.permitted(batch_size);
+        let state = match self.panics {
+            PanicPolicy::Republish => self.poll_budgeted(batch_size, handler),
+            policy => self.poll_caught(batch_size, handler, policy),
+        };
+        self.progressed(state);
+        state
+    }
+
+    /// Poll up to `batch_size` items, catching the panics of the handler.
+    ///
+    /// Under [`PanicPolicy::Poison`] the items after the first panic are dropped.
+    fn poll_caught<H>(&self, batch_size: usize, handler: &H, policy: PanicPolicy) -> State
+    where
+        H: Fn(Context, T),
+    {
+        let poisoned = Cell::new(false);
+        let caught = |context: Context, item: T| {
+            if poisoned.get() {
+                return;
+            }
+            let handled = panic::catch_unwind(AssertUnwindSafe(|| handler(context, item)));
+            if handled.is_err() && policy == PanicPolicy::Poison {
+                poisoned.set(true);
+                let reason = Arc::new(ChannelPoisoned {
+                    sequence: context.sequence,
+                });
+                self.coordinator.close(Some(reason));
+            }
+        };
+        self.poll_budgeted(batch_size, &caught)
+    }
+
+    /// Note the progress of a poll that returned `state`.
+    #[inline(always)]
+    fn progressed(&self, state: State) {
+        if let State::Processing(consumed) = state {
+            self.coordinator.consumer_progress(consumed);
+        }
+    }
+
+    /// Poll up to `batch_size` items, in chunks when a time budget is set.
+    #[inline(always)]
+    fn poll_budgeted<H>(&self, batch_size: usize, handler: &H) -> State
+    where
+        H: Fn(Context, T),
+    {
+        let Some(budget) = self.budget else {
+            return self.poll_chunk(batch_size, handler);
+        };
+
+        let start = Instant::now();
+        let mut remaining = batch_size;
+        let mut consumed = 0;
+        while remaining > 0 {
+            let chunk = remaining.min(budget.check_every);
+            let State::Processing(polled) = self.poll_chunk(chunk, handler) else {
+                break;
+            };
+            consumed += polled;
+            remaining -= chunk;
+            if start.elapsed() >= budget.limit {
+                break;
+            }
+        }
+        match consumed {
+            0 => Idle,
+            consumed => State::Processing(consumed),
+        }
+    }
+
+    /// Poll up to `batch_size` items, timing the handler when an audit trail is attached.
+    #[inline(always)]
+    fn poll_chunk<H>(&self, batch_size: usize, handler: &H) -> State
+    where
+        H: Fn(Context, T),
+    {
+        match &self.audit {
+            None => self
+                .buffer
+                .poll_sequenced(&*self.poller, batch_size, handler),
+            Some((trail, consumer)) => {
+                let handler = |context: Context, item: T| {
+                    let consumed_at = Instant::now();
+                    handler(context, item);
+                    let sequence = context.sequence;
+                    trail.record(sequence, *consumer, consumed_at, consumed_at.elapsed());
+                };
+                self.buffer
+                    .poll_sequenced(&*self.poller, batch_size, &handler)
+            }
+        }
+    }
+
+    /// Returns how many of `batch_size` items the flow controller admits.
+    #[inline(always)]
+    fn permitted(&self, batch_size: usize) -> usize {
+        match &self.flow {
+            Some(flow) => flow.permit(batch_size).min(batch_size),
+            None => batch_size,
+        }
+    }
+
+    /// Poll up to as many of `batch_size` items as the flow controller admits,
+    /// noting progress to the consumer wait strategy.
+    pub(crate) fn poll_permitted<H>(&self, batch_size: usize, handler: &H) -> State
+    where
+        H: Fn(T),
+    {
+        let state = self
+            .buffer
+            .poll(&*self.poller, self.permitted(batch_size), handler);
+        self.progressed(state);
+        state
+    }
+
+    /// Claim up to as many of `batch_size` items as the flow controller admits
+    /// in place, noting progress to the consumer wait strategy.
+    ///
+    /// If no item is available, waits once according to the consumer wait
+    /// strategy and returns [`RecvState::Empty`], or returns
+    /// [`RecvState::Disconnected`] once the channel is closed or every sender
+    /// is gone, and the buffer is drained.
+    pub(crate) fn claim_or_wait(&self, batch_size: usize) -> Result<Claimed<'_, T>, RecvState> {
+        let finished = self.coordinator.is_finished();
+        let batch_size = self.permitted(batch_size);
+        let claimed = match batch_size {
+            0 => None,
+            _ => self.buffer.claim(
+                &*self.poller,
+                batch_size,
+                &self.coordinator,
+                self.claims.as_ref(),
+            ),
+        };
+        let Some(claimed) = claimed else {
+            if finished {
+                return Err(RecvState::Disconnected);
+            }
+            self.coordinator.consumer_wait();
+            return Err(RecvState::Empty);
+        };
+        self.coordinator.consumer_progress(claimed.len());
+        Ok(claimed)
+    }
+
+    /// Attempt to receive up to `batch_size` items.
+    ///
+    /// Invokes the provided `handler` closure for each item. If no item is
+    /// available, waits once according to the consumer wait strategy, unless
+    /// the channel is closed or every sender is gone, in which case
+    /// [`RecvState::Disconnected`] is returned. A poisoned channel reports a
+    /// disconnect right away; a panic of the handler is handled as the
+    /// [`PanicPolicy`] of the receiver asks.
+    ///
+    /// The returned [`PollOutcome`] also tells how many items were received
+    /// and roughly how many are left for the next call.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        self.recv_sequenced(batch_size, &|_, item| handler(item))
+    }
+
+    /// Receive up to `batch_size` items without ever waiting.
+    ///
+    /// Invokes `handler` for each item and reports how many were consumed.
+    /// Unlike [`recv`](Self::recv), an empty buffer returns
+    /// [`RecvResult::Empty`] right away instead of applying the consumer wait strategy.
+    pub fn try_recv_batch<H>(&self, batch_size: usize, handler: &H) -> RecvResult
+    where
+        H: Fn(T),
+    {
+        let finished = self.coordinator.is_finished();
+        let processed = Cell::new(0);
+        self.poll(batch_size, &|_, item| {
+            processed.set(processed.get() + 1);
+            handler(item);
+        });
+
+        match processed.get() {
+            0 if finished => RecvResult::Disconnected,
+            0 => RecvResult::Empty,
+            n => RecvResult::Processed(n),
+        }
+    }
+
+    /// Move up to `max` of the items currently in the buffer into `items`
+    /// without ever waiting.
+    ///
+    /// The items are taken with a single claim, so the buffer's sequences are
+    /// updated once however many items are moved, which suits flushing a
+    /// channel on shutdown and consumers that sort or merge items before
+    /// processing them. At most a buffer's worth of items is moved, which is
+    /// everything published that the claim can see.
+    pub fn drain_into(&self, items: &mut Vec<T>, max: usize) -> RecvResult {
+        let finished = self.coordinator.is_finished();
+        let max = self.permitted(max.min(self.buffer.buffer_size()));
+        let claimed = match max {
+            0 => None,
+            _ => self
+                .buffer
+                .claim(&*self.poller, max, &self.coordinator, self.claims.as_ref()),
+        };
+        let Some(claimed) = claimed else {
+            return match finished {
+                true => RecvResult::Disconnected,
+                false => RecvResult::Empty,
+            };
+        };
+        let drained = claimed.len();
+        self.coordinator.consumer_progress(drained);
+        items.extend(claimed);
+        RecvResult::Processed(drained)
+    }
+
+    /// Move the items currently in the buffer into the caller-owned `out`,
+    /// with a single poll and without ever waiting.
+    ///
+    /// Returns the number of items written to the front of `out`, which the
+    /// caller then owns and must drop, e.g. with
+    /// [`MaybeUninit::assume_init_drop`], if they need it. Items are written
+    /// as a plain array, so consumers can process them with index arithmetic
+    /// or SIMD instead of a handler call per item. At most a buffer's worth of
+    /// items is moved. Returns `0` both if the buffer is empty and once it is
+    /// disconnected; [`try_recv_batch`](Self::try_recv_batch) tells them apart.
+    pub fn recv_into(&self, out: &mut [MaybeUninit<T>]) -> usize {
+        let max = self.permitted(out.len());
+        let received = self.buffer.poll_into(&*self.poller, &mut out[..max]);
+        self.progressed(match received {
+            0 => Idle,
+            n => State::Processing(n),
+        });
+        received
+    }
+
+    /// Move every item currently in the buffer into a new `Vec`, see
+    /// [`drain_into`](Self::drain_into).
+    pub fn drain_all(&self) -> Vec<T> {
+        let mut items = Vec::new();
+        self.drain_into(&mut items, usize::MAX);
+        items
+    }
+
+    /// Poll once and wait if nothing was available, reporting a disconnect
+    /// instead of waiting once the buffer is drained and no more items can
+    /// arrive, or right away once the channel is poisoned.
+    fn recv_sequenced<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(Context, T),
+    {
+        // Read before polling, so that everything the last sender published
+        // is seen by the poll if it reports a disconnect.
+        let finished = self.coordinator.is_finished();
+        let polled = match finished && self.is_poisoned() {
+            true => Idle,
+            false => self.poll(batch_size, handler),
+        };
+        let state = match polled {
+            State::Processing(processed)
+                if self.panics == PanicPolicy::Poison && self.is_poisoned() =>
+            {
+                return PollOutcome {
+                    state: RecvState::Poisoned,
+                    processed,
+                    remaining_hint: 0,
+                };
+            }
+            State::Processing(processed) => {
+                return PollOutcome {
+                    state: RecvState::Received,
+                    processed,
+                    remaining_hint: self.len(),
+                };
+            }
+            Idle if finished => RecvState::Disconnected,
+            Idle => {
+                self.coordinator.consumer_wait();
+                RecvState::Empty
+            }
+        };
+        PollOutcome {
+            state,
+            processed: 0,
+            remaining_hint: 0,
+        }
+    }
+
+    /// Take up to `limit` items without waiting, for consumers that serve
+    /// several receivers in turn.
+    ///
+    /// Returns how many items were handed to `handler`, together with
+    /// [`RecvState::Disconnected`] if none were and none can arrive any more.
+    pub(crate) fn recv_up_to<H>(&self, limit: usize, handler: &H) -> (usize, RecvState)
+    where
+        H: Fn(T),
+    {
+        let finished = self.coordinator.is_finished();
+        let taken = Cell::new(0);
+        let counted = |_, item: T| {
+            taken.set(taken.get() + 1);
+            handler(item);
+        };
+
+        while taken.get() < limit {
+            let batch_size = (limit - taken.get()).min(self.buffer.buffer_size());
+            if self.poll(batch_size, &counted) == Idle {
+                break;
+            }
+        }
+
+        let state = match taken.get() {
+            0 if finished => RecvState::Disconnected,
+            0 => RecvState::Empty,
+            _ => RecvState::Received,
+        };
+        (taken.get(), state)
+    }
+
+    /// Attempt to receive up to `batch_size` items, passing an [`EventRef`] for each.
+    ///
+    /// The reference can be handed to other threads and later resolved with
+    /// [`resolve`](Self::resolve) for as long as the slot has not been reused.
+    pub fn recv_with_ref<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(EventRef, T),
+    {
+        let handler = |Context { sequence, .. }, item: T| {
+            let epoch = self.buffer.epoch_of(sequence);
+            handler(EventRef { sequence, epoch }, item)
+        };
+
+        self.recv_sequenced(batch_size, &handler)
+    }
+
+    /// Attempt to receive up to `batch_size` items, passing each one to the
+    /// handler together with the id of the producer that sent it.
+    ///
+    /// Ids are only known on channels with a bounded number of producers, see
+    /// [`Sender::producer_id`]; on other channels the handler gets `None`.
+    /// Waits like [`recv`](Self::recv) if no item is available.
+    pub fn recv_with_producer<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(Option<usize>, T),
+    {
+        let buffer = &self.buffer;
+        let handler = |context: Context, item: T| handler(buffer.stamp_of(context.sequence), item);
+        self.recv_sequenced(batch_size, &handler)
+    }
+
+    /// Attempt to receive up to `batch_size` items, passing each one to the
+    /// handler together with its [`Context`].
+    ///
+    /// The last item of every batch is flagged with
+    /// [`is_end_of_batch`](Context::is_end_of_batch), like `endOfBatch` in the
+    /// event handlers of the LMAX Disruptor. Waits like [`recv`](Self::recv)
+    /// if no item is available.
+    pub fn recv_with_context<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(Context, T),
+    {
+        self.recv_sequenced(batch_size, handler)
+    }
+
+    /// Returns `true` if a handler failed under [`ErrorPolicy::Halt`] or
+    /// panicked under [`PanicPolicy::Poison`].
+    pub fn is_poisoned(&self) -> bool {
+        self.coordinator.is_closed()
+            && self
+                .coordinator
+                .close_reason()
+                .is_some_and(|reason| reason.is::<ChannelPoisoned>())
+    }
+
+    /// Returns an iterator that receives items, waiting for more according to
+    /// the consumer wait strategy.
+    ///
+    /// The iterator ends once the channel is closed or every sender is gone,
+    /// and the buffer is drained. Items are claimed from the buffer in batches;
+    /// on multi-consumer channels the unyielded rest of the current batch is
+    /// dropped with the iterator, on single-consumer channels it stays in the buffer.
+    /// The batch of an iterator that is forgotten rather than dropped is handed
+    /// back to producers once the receiver is dropped, leaking its rest.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            receiver: self,
+            claimed: None,
+        }
+    }
+
+    /// Returns an iterator over the items currently available, which never waits.
+    ///
+    /// Dropping it mid-batch behaves like dropping an [`Iter`].
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter {
+            receiver: self,
+            claimed: None,
+        }
+    }
+
+    /// Move the next item out of `claimed`, claiming a new batch once it is used up.
+    ///
+    /// Returns `None` when nothing is available, or, if `block` is set, waits
+    /// until an item arrives or the channel is closed or disconnected and drained.
+    fn next_claimed<'a>(&'a self, claimed: &mut Option<Claimed<'a, T>>, block: bool) -> Option<T> {
+        if let Some(item) = claimed.as_mut().and_then(Iterator::next) {
+            return Some(item);
+        }
+        // Hand the used-up batch back before claiming the next one.
+        *claimed = None;
+
+        loop {
+            let finished = self.coordinator.is_finished();
+            let batch_size = self.permitted(self.buffer.buffer_size());
+            if batch_size > 0 {
+                if let Some(mut batch) = self.buffer.claim(
+                    &*self.poller,
+                    batch_size,
+                    &self.coordinator,
+                    self.claims.as_ref(),
+                ) {
+                    self.coordinator.consumer_progress(batch.len());
+                    let item = batch.next();
+                    *claimed = Some(batch);
+                    return item;
+                }
+                if finished {
+                    return None;
+                }
+            }
+            if !block {
+                return None;
+            }
+            self.coordinator.consumer_wait();
+        }
+    }
+
+    /// Consume on a new thread named `name`, handing batches of up to
+    /// `batch_size` items to `handler`.
+    ///
+    /// The thread runs the receive loop every consumer would write by hand,
+    /// waiting according to the consumer wait strategy while the buffer is
+    /// empty, until it is stopped through the returned handle or the channel
+    /// disconnects.
+    ///
+    /// # Panics
+    /// Panics if the thread cannot be spawned.
+    pub fn spawn_consumer<H>(self, name: &str, batch_size: usize, handler: H) -> ConsumerHandle
+    where
+        T: Send + 'static,
+        H: FnMut(T) + Send + 'static,
+    {
+        self.spawn_consumer_with(ConsumerOptions::new(name, batch_size), handler)
+            .expect("failed to spawn the consumer thread")
+    }
+
+    /// Like [`spawn_consumer`](Self::spawn_consumer), with the thread
+    /// configured by `options`.
+    ///
+    /// # Errors
+    /// Returns the error of spawning the thread, or of pinning it to its core,
+    /// in which case the thread exits and drops the receiver.
+    pub fn spawn_consumer_with<H>(
+        self,
+        options: ConsumerOptions,
+        handler: H,
+    ) -> io::Result<ConsumerHandle>
+    where
+        T: Send + 'static,
+        H: FnMut(T) + Send + 'static,
+    {
+        let stopped = Arc::new(PaddedFlag::default());
+        let coordinator = self.coordinator.clone();
+        let stop = stopped.clone();
+        let (started, start) = std::sync::mpsc::sync_channel(1);
+        let ConsumerOptions {
+            name,
+            batch_size,
+            core,
+        } = options;
+        let thread = thread::Builder::new().name(name).spawn(move || {
+            let pinned = match core {
+                #[cfg(feature = "affinity")]
+                Some(core) => crate::affinity::pin_current_thread(core),
+                _ => Ok(()),
+            };
+            let failed = pinned.is_err();
+            let _ = started.send(pinned);
+            if failed {
+                return;
+            }
+
+            let handler = RefCell::new(handler);
+            let handle = |item| (handler.borrow_mut())(item);
+            while !stop.is_set() {
+                if self.recv(batch_size, &handle) == RecvState::Disconnected {
+                    break;
+                }
+            }
+        })?;
+        match start.recv() {
+            Ok(Ok(())) => Ok(ConsumerHandle {
+                stopped,
+                coordinator,
+                thread,
+            }),
+            Ok(Err(error)) => {
+                let _ = thread.join();
+                Err(error)
+            }
+            Err(_) => Err(io::Error::other(
+                "the consumer thread exited before starting",
+            )),
+        }
+    }
+
+    /// Close the channel, starting a shutdown, see [`Sender::close`].
+    ///
+    /// Returns `false` if the channel was already closed.
+    pub fn close(&self) -> bool {
+        self.coordinator.close(None)
+    }
+
+    /// Close the channel because the consumer cannot continue, recording why.
+    ///
+    /// Every subsequent send fails with [`SendError::Closed`] carrying `reason`,
+    /// and producers currently waiting for free slots give up with the same
+    /// error. Returns `false` if the channel was already closed, in which case
+    /// the first reason is kept.
+    pub fn close_with_error<E>(&self, reason: E) -> bool
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.coordinator.close(Some(Arc::new(reason)))
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.buffer_size()
+    }
+
+    /// Returns the number of items waiting in the ring buffer.
+    ///
+    /// See [`Sender::len`].
+    pub fn len(&self) -> usize {
+        self.buffer.backlog().min(self.capacity())
+    }
+
+    /// Returns `true` if no item is waiting in the ring buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if every slot of the ring buffer is taken.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Returns the logical position of the last item this receiver took, or
+    /// `None` if it took none yet.
+    ///
+    /// Receivers that split the items between them share a position: the
+    /// last one any of them took. See [`sequence_to_position`] for what positions are.
+    pub fn last_consumed(&self) -> Option<u64> {
+        sequence_to_position(self.buffer.position(&*self.poller))
+    }
+
+    /// Returns a snapshot of the counters of the channel.
+    ///
+    /// See [`Sender::metrics`].
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.coordinator.metrics()
+    }
+
+    /// Returns the id the channel is listed under in the registry.
+    ///
+    /// See [`Sender::channel_id`].
+    #[cfg(feature = "registry")]
+    pub fn channel_id(&self) -> u64 {
+        registration(&self.coordinator).id()
+    }
+
+    /// Report the sequences of the channel and the position of this receiver
+    /// without taking any item, to diagnose a stall, see [`DebugState`].
+    #[cfg(feature = "inspect")]
+    pub fn debug_state(&self) -> DebugState {
+        self.buffer.snapshot(Some(&*self.poller))
+    }
+
+    /// Like [`debug_state`](Self::debug_state), and clone the items this
+    /// receiver has yet to take, leaving them in the channel.
+    ///
+    /// The items are only cloned, and [`DebugState::items`] only returns
+    /// `Some`, if no other receiver can take them meanwhile: on broadcast
+    /// channels, and on channels without other receivers. Borrowing this
+    /// receiver mutably keeps it from taking them.
+    #[cfg(feature = "inspect")]
+    pub fn debug_state_with_items(&mut self) -> DebugState<T>
+    where
+        T: Clone,
+    {
+        let state = self.buffer.snapshot(Some(&*self.poller));
+        let exclusive = self.poller.retains() || self.coordinator.receiver_count() == 1;
+        let items = exclusive.then(|| {
+            let position = state.position().unwrap_or(state.gating());
+            // SAFETY: the items up to the published sequence are published,
+            // this receiver is the only one that could move them out and is
+            // borrowed mutably, and producers are gated on its position.
+            unsafe { self.buffer.clone_range(position + 1, state.published()) }
+        });
+        state.with_items(items)
+    }
+
+    /// Returns the ring buffer and coordinator, for consumers that process the
+    /// buffer without a poller.
+    pub(crate) fn parts(&self) -> (&RingBuffer<T>, &Coordinator) {
+        (&self.buffer, &self.coordinator)
+    }
+
+    /// Returns the coordinator of the channel.
+    pub(crate) fn coordinator(&self) -> &Arc<Coordinator> {
+        &self.coordinator
+    }
+
+    /// Returns `true` if items are cloned out of the buffer, as on broadcast channels.
+    pub(crate) fn retains(&self) -> bool {
+        self.poller.retains()
+    }
+
+    /// Admit `n` more items from producers on a credit-paced channel.
+    ///
+    /// Producers on channels created with one of the `*_with_credits` constructors
+    /// can only claim sequences up to the credit watermark, regardless of free
+    /// slots, which lets the consumer enforce pacing policies at the source.
+    /// On other channels this has no effect. Producers blocked on exhausted
+    /// credits are woken up.
+    pub fn grant(&self, n: usize) {
+        self.buffer.grant(n);
+        self.coordinator.wakeup_producers();
+    }
+
+    /// Returns the current epoch of the channel.
+    ///
+    /// The epoch starts at zero and increments each time producers complete a
+    /// lap of the ring buffer. A [`rebase`] moves the sequences back to the
+    /// start, but not the epoch, which goes on from the lap after the last one.
+    pub fn epoch(&self) -> i64 {
+        self.buffer.epoch()
+    }
+
+    /// Copy the event referenced by `event` out of the ring buffer.
+    ///
+    /// Returns `None` if the slot has been reused by a later lap since the
+    /// reference was taken, or if the channel was [`rebase`]d since. Resolving
+    /// never consumes the event.
+    ///
+    /// The event is copied rather than borrowed: producers are not gated on
+    /// references, so a producer could overwrite the slot while a `&T` into it
+    /// is alive. A copy is only handed out once it is known not to overlap
+    /// such a write, see [`peek`](Self::peek), hence the `T: Copy` bound.
+    pub fn resolve(&self, event: EventRef) -> Option<T>
+    where
+        T: Copy,
+    {
+        if self.buffer.epoch_of(event.sequence) != event.epoch {
+            return None;
+        }
+        self.buffer.peek(event.sequence)
+    }
+
+    /// Copy the item published at `sequence` without consuming it.
+    ///
+    /// Returns `None` if the sequence has not been published yet or if its slot
+    /// has already been reused by a later lap, which cannot happen to the
+    /// items ahead of a registered [`GatingSequence`].
+    ///
+    /// Unless a gating sequence holds producers back, the item is copied
+    /// while a producer may be overwriting its slot. Such a copy is detected
+    /// and discarded, but the read itself is a data race as far as the Rust
+    /// memory model is concerned, the same one every seqlock relies on.
+    pub fn peek(&self, sequence: i64) -> Option<T>
+    where
+        T: Copy,
+    {
+        self.buffer.peek(sequence)
+    }
+
+    /// Register a gating sequence that producers wait for, so that a consumer
+    /// can be attached to the channel while it is live.
+    ///
+    /// The gating sequence starts at the highest sequence claimed by producers,
+    /// so the attached consumer sees every item published from now on. Returns
+    /// `None` if the channel already has the maximum of 16 registered gating
+    /// sequences.
+    pub fn add_gating_sequence(&self) -> Option<GatingSequence> {
+        let slot = self.buffer.add_gating_sequence()?;
+        Some(GatingSequence {
+            sequences: self.buffer.gating_sequences().clone(),
+            slot,
+        })
+    }
+
+    /// Stop gating producers on `sequence`.
+    ///
+    /// Same as dropping it. Returns `false` if `sequence` was registered on
+    /// another channel, which stops gating that channel's producers instead.
+    pub fn remove_gating_sequence(&self, sequence: GatingSequence) -> bool {
+        Arc::ptr_eq(&sequence.sequences, self.buffer.gating_sequences())
+    }
+
+    /// Continuously attempt to receive items until at least one batch is processed.
+    ///
+    /// This method blocks according to the configured consumer wait strategy.
+    /// It is typically used in consumer loops, and returns
+    /// [`RecvState::Disconnected`] instead of blocking forever once the channel
+    /// is closed or every sender is gone, and the buffer is drained.
+    pub fn blocking_recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        loop {
+            let outcome = self.recv(batch_size, handler);
+            if outcome != RecvState::Empty {
+                return outcome;
+            }
+        }
+    }
+}
+
+/// Wire a ring buffer, sequencer, poller and coordinator into a channel pair.
+///
+/// Padding and spin budgets are taken from the current [`Topology`].
+fn channel<T>(
+    buffer_size: usize,
+    sequencer: Box<dyn Sequencer>,
+    poller: Box<dyn Poller<T>>,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+    max_producers: Option<usize>,
+) -> (Sender<T>, Receiver<T>) {
+    let coordinator = coordinator(pw, cw, max_producers);
+    channel_with(buffer_size, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// Create the coordinator of a channel with built-in wait strategies.
+pub(crate) fn coordinator(
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+    max_producers: Option<usize>,
+) -> Coordinator {
+    let spin_budget = Topology::current().spin_budget();
+    Coordinator::new(pw, cw, spin_budget, max_producers)
+}
+
+/// Like [`channel`], with an existing `coordinator` and with `prepare`
+/// applied to the ring buffer before it is shared.
+fn channel_with<T, F>(
+    buffer_size: usize,
+    sequencer: Box<dyn Sequencer>,
+    poller: Box<dyn Poller<T>>,
+    coordinator: Coordinator,
+    prepare: F,
+) -> (Sender<T>, Receiver<T>)
+where
+    F: FnOnce(RingBuffer<T>) -> RingBuffer<T>,
+{
+    let padding = Topology::current().array_padding();
+    let producer = coordinator.producers().map(|_| 0);
+
+    let buffer = prepare(RingBuffer::new(buffer_size, padding, sequencer));
+    let buffer = match producer {
+        Some(_) => buffer.stamped(),
+        None => buffer,
+    };
+    let buffer: Arc<RingBuffer<T>> = Arc::new(match poller.retains() {
+        true => buffer.retaining(),
+        false => buffer,
+    });
+
+    #[cfg(feature = "registry")]
+    let coordinator = coordinator.with_registration(Registration::new(
+        buffer_size,
+        poller.consumers(),
+        poller.retains(),
+        buffer.downgrade_sequencer(),
+    ));
+    let coordinator = Arc::new(coordinator);
+    #[cfg(feature = "registry")]
+    registry::register(&coordinator);
+    let sender = Sender {
+        buffer: buffer.clone(),
+        coordinator: coordinator.clone(),
+        producer,
+        rate_limit: None,
+    };
+    let poller: Arc<dyn Poller<T>> = Arc::from(poller);
+    let claims = poller.shares_claims().then(ActiveClaims::default);
+    let receiver = Receiver {
+        buffer: buffer.clone(),
+        poller,
+        coordinator: coordinator.clone(),
+        flow: None,
+        audit: None,
+        budget: None,
+        panics: PanicPolicy::default(),
+        claims,
+    };
+
+    (sender, receiver)
+}
+
+/// Returns the entry in the registry of a channel created by [`channel_with`].
+#[cfg(feature = "registry")]
+fn registration(coordinator: &Coordinator) -> &Registration {
+    coordinator
+        .registration()
+        .expect("channels are registered when they are created")
+}
+
+/// Validate a requested buffer size.
+pub(crate) fn assert_buffer_size(buffer_size: usize) {
+    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
+    utils::assert_buffer_size_is_not_zero(buffer_size);
+}
+
+/// Rebase the sequences of a quiesced channel to their initial values.
+///
+/// Long-running channels accumulate ever larger sequences. Rebasing moves them
+/// back to the start, clearing the multi-producer availability flags, so that
+/// nothing relies on behavior at huge sequence values and persisted offsets
+/// stay compact. Credits granted but not used yet are kept, and every
+/// [`EventRef`] taken before the rebase becomes meaningless.
+///
+/// Taking the only sender and the only receiver mutably guarantees that no
+/// producer or consumer is active while the sequences move.
+///
+/// # Errors
+/// - [`RebaseError::ForeignChannel`] if `sender` and `receiver` belong to different channels.
+/// - [`RebaseError::Shared`] if other senders or receivers of the channel are alive.
+/// - [`RebaseError::NotDrained`] if published items have not been received yet.
+pub fn rebase<T>(sender: &mut Sender<T>, receiver: &mut Receiver<T>) -> Result<(), RebaseError> {
+    if !Arc::ptr_eq(&sender.buffer, &receiver.buffer) {
+        return Err(RebaseError::ForeignChannel);
+    }
+    let coordinator = &receiver.coordinator;
+    if coordinator.sender_count() != 1 || coordinator.receiver_count() != 1 {
+        return Err(RebaseError::Shared);
+    }
+    if !receiver.buffer.rebase(&*receiver.poller) {
+        return Err(RebaseError::NotDrained);
+    }
+    Ok(())
+}
+
+/// Create a **single-producer single-consumer (SPSC)** channel.
+///
+/// - One producer thread
+/// - One consumer thread
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+pub fn spsc<T>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **single-producer single-consumer (SPSC)** channel whose slots
+/// are filled up front with items created by `factory`.
+///
+/// The slots stay filled for the lifetime of the channel: the producer updates
+/// the item left in a slot with [`Sender::claim_existing`], and the consumer
+/// handles items in their slots with [`Receiver::recv_in_place`], so items
+/// that own allocations are reused instead of being created for every send.
+/// An item sent by value replaces the item of its slot, and a slot whose
+/// item is received by value is refilled from `factory`.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+/// - `factory`: creates the item of every slot.
+pub fn spsc_with_factory<T, F>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+    factory: F,
+) -> (Sender<T>, Receiver<T>)
+where
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    let coordinator = coordinator(pw, cw, None);
+    let prepare = |buffer: RingBuffer<T>| buffer.prefilled(factory);
+    channel_with(buffer_size, sequencer, poller, coordinator, prepare)
+}
+
+/// Create a **rendezvous** channel with a single producer and a single consumer.
+///
+/// The channel holds no items of its own: [`Sender::send`] waits until the
+/// receiver runs out of items, hands the value over, and returns once the
+/// receiver took it, so both sides meet at every item. [`Sender::try_send`]
+/// only succeeds while the receiver waits. A receiver waits for values in
+/// [`Receiver::recv`] and the other receive calls that wait according to the
+/// consumer wait strategy; receive calls that never wait, like
+/// [`Receiver::try_recv_batch`], only take a value handed to an earlier wait.
+///
+/// Sends other than `send` and `try_send` behave as on a channel with a
+/// single slot.
+///
+/// # Parameters
+/// - `pw`: producer wait strategy, applied while waiting for the receiver.
+/// - `cw`: consumer wait strategy.
+pub fn spsc_rendezvous<T>(
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    let sequencer = Box::new(SingleProducerSequencer::new(1));
+    let poller = Box::new(SingleConsumerPoller::new());
+    let coordinator = coordinator(pw, cw, None).with_rendezvous();
+    channel_with(1, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel.
+///
+/// - Multiple producers
+/// - One consumer
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(feature = "mp")]
+pub fn mpsc<T>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **single-producer multi-consumer (SPMC)** channel.
+///
+/// - One producer
+/// - Multiple consumers
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(feature = "mc")]
+pub fn spmc<T>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **multi-producer multi-consumer (MPMC)** channel.
+///
+/// - Multiple producers
+/// - Multiple consumers
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn mpmc<T>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **single-producer multi-consumer (SPMC)** channel whose consumers
+/// share the items as `fairness` asks.
+///
+/// See [`spmc`] for the parameters.
+#[cfg(feature = "mc")]
+pub fn spmc_with_fairness<T>(
+    buffer_size: usize,
+    fairness: ConsumerFairness,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::with_fairness(buffer_size, fairness));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **multi-producer multi-consumer (MPMC)** channel whose consumers
+/// share the items as `fairness` asks.
+///
+/// See [`mpmc`] for the parameters.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn mpmc_with_fairness<T>(
+    buffer_size: usize,
+    fairness: ConsumerFairness,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::with_fairness(buffer_size, fairness));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **broadcast** channel.
+///
+/// - Multiple producers
+/// - Multiple consumers, each of which receives every item
+///
+/// Every clone of the receiver has its own consumer sequence and gets a clone
+/// of every item published after the receiver it was cloned from last
+/// received. Producers are gated by the slowest receiver; dropping a receiver
+/// stops it from gating producers. Receivers clone the items out of the shared
+/// slots concurrently, so `T` must be `Sync` as well as `Send`.
+///
+/// A wakeup of the [`Blocking`](ConsumerWaitStrategyKind::Blocking) consumer
+/// strategy is taken by a single receiver, so the others may only wake on the
+/// next send. Prefer the other strategies for receivers that must not lag.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn broadcast<T: Clone + Send + Sync + 'static>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(BroadcastPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **tee** channel, mirroring every item to `groups` consumer groups.
+///
+/// - Single producer
+/// - A fixed number of consumer groups, each of which receives every item
+///
+/// Returns a receiver per group. Every group has its own consumer sequence
+/// and gets a clone of every item, taken when the group receives it, and
+/// producers are gated by the slowest group. The groups are fixed when the
+/// channel is created: clones of a receiver join its group and share its
+/// sequence like clones of a single-consumer receiver, and a group whose
+/// receivers are all dropped stops gating producers. Groups clone the items
+/// out of the shared slots concurrently, so `T` must be `Sync` as well as `Send`.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `groups`: number of consumer groups.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+///
+/// # Panics
+/// Panics if `groups` is zero.
+#[cfg(feature = "mc")]
+pub fn tee<T: Clone + Send + Sync + 'static>(
+    buffer_size: usize,
+    groups: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Vec<Receiver<T>>) {
+    assert_buffer_size(buffer_size);
+    assert!(groups > 0, "a tee channel needs at least one group");
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let mut pollers = TeePoller::groups(groups).into_iter();
+    let first = Box::new(pollers.next().expect("at least one group"));
+    let (sender, receiver) = channel(buffer_size, sequencer, first, pw, cw, None);
+    let others: Vec<_> = pollers
+        .map(|poller| receiver.subscribed(Some(Box::new(poller))))
+        .collect();
+    (sender, std::iter::once(receiver).chain(others).collect())
+}
+
+/// Create a **single-producer single-consumer (SPSC)** channel whose
+/// receiver acknowledges the items it received, see [`AckReceiver`].
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+pub fn spsc_acked<T: Clone + Send + Sync + 'static>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, AckReceiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    acked(buffer_size, sequencer, pw, cw)
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel whose receiver
+/// acknowledges the items it received, see [`AckReceiver`].
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(feature = "mp")]
+pub fn mpsc_acked<T: Clone + Send + Sync + 'static>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, AckReceiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    acked(buffer_size, sequencer, pw, cw)
+}
+
+/// Like [`channel`], with a receiver that acknowledges items.
+fn acked<T: Clone + Send + Sync + 'static>(
+    buffer_size: usize,
+    sequencer: Box<dyn Sequencer>,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, AckReceiver<T>) {
+    let consumed = Arc::new(Sequence::default());
+    let poller = Box::new(AckPoller::new(consumed.clone()));
+    let (sender, receiver) = channel(buffer_size, sequencer, poller, pw, cw, None);
+    (sender, AckReceiver { receiver, consumed })
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel with at most
+/// `max_producers` senders alive at a time.
+///
+/// Every sender is registered in a producer slot, see [`Sender::producer_id`]
+/// and [`Sender::producer_statuses`]. Cloning a sender beyond the maximum
+/// panics, and [`Sender::try_clone`] returns `None` instead. Sequences are
+/// claimed the same way as on an [`mpsc`] channel.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `max_producers`: maximum number of live senders.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+#[cfg(feature = "mp")]
+pub fn mpsc_with_producers<T>(
+    buffer_size: usize,
+    max_producers: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, Some(max_producers))
+}
+
+/// Create a **multi-producer multi-consumer (MPMC)** channel with at most
+/// `max_producers` senders alive at a time.
+///
+/// See [`mpsc_with_producers`] for the producer slot semantics.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn mpmc_with_producers<T>(
+    buffer_size: usize,
+    max_producers: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    channel(buffer_size, sequencer, poller, pw, cw, Some(max_producers))
+}
+
+/// Create a credit-paced **single-producer single-consumer (SPSC)** channel.
+///
+/// The producer may only claim `initial_credits` items until the consumer
+/// admits more with [`Receiver::grant`], independently of free slots.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `initial_credits`: number of items producers may send before the first grant.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+pub fn spsc_with_credits<T>(
+    buffer_size: usize,
+    initial_credits: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::with_credits(
+        buffer_size,
+        initial_credits,
+    ));
+    let poller = Box::new(SingleConsumerPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a credit-paced **multi-producer single-consumer (MPSC)** channel.
+///
+/// See [`spsc_with_credits`] for the credit semantics.
+#[cfg(feature = "mp")]
+pub fn mpsc_with_credits<T>(
+    buffer_size: usize,
+    initial_credits: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::with_credits(
+        buffer_size,
+        initial_credits,
+    ));
+    let poller = Box::new(SingleConsumerPoller::new());
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a credit-paced **single-producer multi-consumer (SPMC)** channel.
+///
+/// See [`spsc_with_credits`] for the credit semantics.
+#[cfg(feature = "mc")]
+pub fn spmc_with_credits<T>(
+    buffer_size: usize,
+    initial_credits: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::with_credits(
+        buffer_size,
+        initial_credits,
+    ));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a credit-paced **multi-producer multi-consumer (MPMC)** channel.
+///
+/// See [`spsc_with_credits`] for the credit semantics.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn mpmc_with_credits<T>(
+    buffer_size: usize,
+    initial_credits: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::with_credits(
+        buffer_size,
+        initial_credits,
+    ));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    channel(buffer_size, sequencer, poller, pw, cw, None)
+}
+
+/// Create a **single-producer single-consumer (SPSC)** channel with custom wait strategies.
+///
+/// The strategies decide how producers wait for free slots and how the
+/// consumer waits for items, and how producers wake it, see
+/// [`ConsumerWaitStrategy`].
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+pub fn spsc_with_strategies<T>(
+    buffer_size: usize,
+    pw: Box<dyn ProducerWaitStrategy>,
+    cw: Box<dyn ConsumerWaitStrategy>,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    let coordinator = Coordinator::with_custom(pw, cw, None);
+    channel_with(buffer_size, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel with custom wait strategies.
+///
+/// See [`spsc_with_strategies`] for the strategy semantics.
+#[cfg(feature = "mp")]
+pub fn mpsc_with_strategies<T>(
+    buffer_size: usize,
+    pw: Box<dyn ProducerWaitStrategy>,
+    cw: Box<dyn ConsumerWaitStrategy>,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(SingleConsumerPoller::new());
+    let coordinator = Coordinator::with_custom(pw, cw, None);
+    channel_with(buffer_size, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// Create a **single-producer multi-consumer (SPMC)** channel with custom wait strategies.
+///
+/// See [`spsc_with_strategies`] for the strategy semantics.
+#[cfg(feature = "mc")]
+pub fn spmc_with_strategies<T>(
+    buffer_size: usize,
+    pw: Box<dyn ProducerWaitStrategy>,
+    cw: Box<dyn ConsumerWaitStrategy>,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    let coordinator = Coordinator::with_custom(pw, cw, None);
+    channel_with(buffer_size, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// Create a **multi-producer multi-consumer (MPMC)** channel with custom wait strategies.
+///
+/// See [`spsc_with_strategies`] for the strategy semantics.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub fn mpmc_with_strategies<T>(
+    buffer_size: usize,
+    pw: Box<dyn ProducerWaitStrategy>,
+    cw: Box<dyn ConsumerWaitStrategy>,
+) -> (Sender<T>, Receiver<T>) {
+    assert_buffer_size(buffer_size);
+    let sequencer = Box::new(MultiProducerSequencer::new(buffer_size));
+    let poller = Box::new(MultiConsumerPoller::new(buffer_size));
+    let coordinator = Coordinator::with_custom(pw, cw, None);
+    channel_with(buffer_size, sequencer, poller, coordinator, |buffer| buffer)
+}
+
+/// How many producers a channel built by [`ChannelBuilder`] supports.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Producers {
+    /// A single producer at a time, which claims without contention.
+    #[default]
+    Single,
+    /// Any number of concurrent producers.
+    #[cfg(feature = "mp")]
+    Multi,
+}
+
+/// How many consumers a channel built by [`ChannelBuilder`] supports.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Consumers {
+    /// A single consumer at a time.
+    #[default]
+    Single,
+    /// Any number of concurrent consumers, each receiving a share of the items.
+    #[cfg(feature = "mc")]
+    Multi,
+}
+
+/// How the consumers of a multi-consumer channel share the items.
+#[cfg(feature = "mc")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ConsumerFairness {
+    /// Consumers race for batches, so the consumer that comes back first may
+    /// take every batch while the others stay idle. The fastest option.
+    #[default]
+    Throughput,
+    /// Consumers take turns to claim, and each claim takes at most an even
+    /// share of the waiting items among the receivers, so every consumer
+    /// gets work under contention at the cost of a handoff per batch.
+    Fair,
+}
+
+/// How the items of a batch sent with [`Sender::send_n`] and the like become
+/// visible to consumers.
+///
+/// Every batch occupies a contiguous range of sequences, so the batches of
+/// different producers never interleave in the channel. The option decides
+/// whether consumers may start on a batch before it is fully published.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum BatchAtomicity {
+    /// Large batches are published in chunks, so consumers start on the head
+    /// of a batch while its producer is still writing the tail.
+    #[default]
+    Chunked,
+    /// Every batch is published at once: consumers see either all of it or
+    /// none of it, at the cost of waiting for the whole batch to be written.
+    Contiguous,
+}
+
+/// Fluent configuration of a channel, as an alternative to the positional
+/// constructors such as [`spsc`] and [`spsc_with_credits`].
+///
+/// Every option has a default, so only the ones that matter need to be set:
+///
+/// ```
+/// use channels_rs::prelude::*;
+///
+/// let (tx, rx) = ChannelBuilder::<u32>::new()
+///     .capacity(8192)
+///     .producer_wait(ProducerWaitStrategyKind::Yielding)
+///     .consumer_wait(ConsumerWaitStrategyKind::Blocking)
+///     .build();
+/// tx.send(1).unwrap();
+/// assert_eq!(rx.drain_all(), vec![1]);
+/// ```
+#[derive(Debug)]
+pub struct ChannelBuilder<T> {
+    capacity: usize,
+    producers: Producers,
+    consumers: Consumers,
+    producer_wait: ProducerWaitStrategyKind,
+    consumer_wait: ConsumerWaitStrategyKind,
+    max_producers: Option<usize>,
+    credits: Option<usize>,
+    notify_policy: NotifyPolicy,
+    batch_atomicity: BatchAtomicity,
+    #[cfg(feature = "mc")]
+    fairness: ConsumerFairness,
+    #[cfg(feature = "registry")]
+    name: Option<String>,
+    items: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for ChannelBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ChannelBuilder<T> {
+    /// Start from the defaults: 1024 slots, a single producer and a single
+    /// consumer, the default wait strategies, no bound on producers, no
+    /// credits, [`NotifyPolicy::Always`], [`BatchAtomicity::Chunked`] and,
+    /// with multiple consumers, [`ConsumerFairness::Throughput`].
+    pub fn new() -> Self {
+        Self {
+            capacity: 1024,
+            producers: Producers::default(),
+            consumers: Consumers::default(),
+            producer_wait: ProducerWaitStrategyKind::default(),
+            consumer_wait: ConsumerWaitStrategyKind::default(),
+            max_producers: None,
+            credits: None,
+            notify_policy: NotifyPolicy::default(),
+            batch_atomicity: BatchAtomicity::default(),
+            #[cfg(feature = "mc")]
+            fairness: ConsumerFairness::default(),
+            #[cfg(feature = "registry")]
+            name: None,
+            items: PhantomData,
+        }
+    }
+
+    /// Set the capacity of the underlying ring buffer.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set how many producers the channel supports.
+    pub fn producers(mut self, producers: Producers) -> Self {
+        self.producers = producers;
+        self
+    }
+
+    /// Set how many consumers the channel supports.
+    pub fn consumers(mut self, consumers: Consumers) -> Self {
+        self.consumers = consumers;
+        self
+    }
+
+    /// Set how producers wait for free slots.
+    pub fn producer_wait(mut self, strategy: ProducerWaitStrategyKind) -> Self {
+        self.producer_wait = strategy;
+        self
+    }
+
+    /// Set how consumers wait for items.
+    pub fn consumer_wait(mut self, strategy: ConsumerWaitStrategyKind) -> Self {
+        self.consumer_wait = strategy;
+        self
+    }
+
+    /// Allow at most `max_producers` senders alive at a time, each registered
+    /// in a producer slot, see [`Sender::producer_id`].
+    pub fn max_producers(mut self, max_producers: usize) -> Self {
+        self.max_producers = Some(max_producers);
+        self
+    }
+
+    /// Pace producers with credits, letting them send `initial_credits` items
+    /// before the first grant, see [`spsc_with_credits`].
+    pub fn credits(mut self, initial_credits: usize) -> Self {
+        self.credits = Some(initial_credits);
+        self
+    }
+
+    /// Set when producers wake a blocking consumer, see [`Sender::set_notify_policy`].
+    pub fn notify_policy(mut self, policy: NotifyPolicy) -> Self {
+        self.notify_policy = policy;
+        self
+    }
+
+    /// Set how the items of a batch become visible to consumers, see [`BatchAtomicity`].
+    pub fn batch_atomicity(mut self, atomicity: BatchAtomicity) -> Self {
+        self.batch_atomicity = atomicity;
+        self
+    }
+
+    /// Set how multiple consumers share the items, see [`ConsumerFairness`].
+    #[cfg(feature = "mc")]
+    pub fn fairness(mut self, fairness: ConsumerFairness) -> Self {
+        self.fairness = fairness;
+        self
+    }
+
+    /// Name the channel in the [`registry`].
+    #[cfg(feature = "registry")]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Create the channel.
+    ///
+    /// # Panics
+    /// Panics if the capacity is zero or exceeds `i64::MAX`.
+    pub fn build(self) -> (Sender<T>, Receiver<T>) {
+        let buffer_size = self.capacity;
+        assert_buffer_size(buffer_size);
+        let sequencer: Box<dyn Sequencer> = match (self.producers, self.credits) {
+            (Producers::Single, None) => Box::new(SingleProducerSequencer::new(buffer_size)),
+            (Producers::Single, Some(credits)) => {
+                Box::new(SingleProducerSequencer::with_credits(buffer_size, credits))
+            }
+            #[cfg(feature = "mp")]
+            (Producers::Multi, None) => Box::new(MultiProducerSequencer::new(buffer_size)),
+            #[cfg(feature = "mp")]
+            (Producers::Multi, Some(credits)) => {
+                Box::new(MultiProducerSequencer::with_credits(buffer_size, credits))
+            }
+        };
+        let poller: Box<dyn Poller<T>> = match self.consumers {
+            Consumers::Single => Box::new(SingleConsumerPoller::new()),
+            #[cfg(feature = "mc")]
+            Consumers::Multi => Box::new(MultiConsumerPoller::with_fairness(
+                buffer_size,
+                self.fairness,
+            )),
+        };
+        let coordinator = coordinator(self.producer_wait, self.consumer_wait, self.max_producers);
+        let atomicity = self.batch_atomicity;
+        let channel =
+            channel_with(
+                buffer_size,
+                sequencer,
+                poller,
+                coordinator,
+                |buffer| match atomicity {
+                    BatchAtomicity::Chunked => buffer,
+                    BatchAtomicity::Contiguous => buffer.contiguous(),
+                },
+            );
+        channel.0.set_notify_policy(self.notify_policy);
+        #[cfg(feature = "registry")]
+        if let Some(name) = self.name {
+            registration(&channel.0.coordinator).set_name(name);
+        }
+        channel
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{
+        ChannelBuilder, Context, INITIAL_SEQUENCE, RecvResult, RecvState, position_to_sequence,
+        sequence_to_position, spsc, spsc_acked, spsc_rendezvous, spsc_with_factory,
+    };
+    #[cfg(feature = "mc")]
+    use crate::channels::{ConsumerFairness, PanicPolicy, spmc_with_fairness};
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    use crate::channels::{broadcast, mpmc, rebase};
+    #[cfg(feature = "mp")]
+    use crate::channels::{mpsc, mpsc_with_producers};
+    use crate::coordinator::{ConsumerWaitStrategyKind, NotifyPolicy, ProducerWaitStrategyKind};
+    #[cfg(feature = "mc")]
+    use crate::errors::ChannelPoisoned;
+    #[cfg(feature = "mp")]
+    use crate::errors::{RebaseError, SequencesAbandoned};
+    use crate::errors::{SendError, TrySendError};
+    use crate::testing::{filled_spsc, recv_all, spinning_spsc, yielding_spsc};
+    use std::cell::{Cell, RefCell};
+    use std::mem::MaybeUninit;
+    #[cfg(feature = "mc")]
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_recv_drains_before_reporting_disconnect() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+
+        let received = Cell::new(0);
+        let handler = |value: u32| received.set(received.get() + value);
+        assert_eq!(rx.recv(1, &handler), RecvState::Received);
+        assert_eq!(rx.blocking_recv(4, &handler), RecvState::Received);
+        assert_eq!(rx.blocking_recv(4, &handler), RecvState::Disconnected);
+        assert_eq!(received.get(), 3);
+    }
+
+    #[test]
+    fn test_sender_clone_keeps_channel_connected() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let clone = tx.clone();
+        drop(tx);
+        assert_eq!(rx.recv(4, &|_| {}), RecvState::Empty);
+        drop(clone);
+        assert_eq!(rx.recv(4, &|_| {}), RecvState::Disconnected);
+    }
+
+    #[test]
+    fn test_send_fails_once_receivers_are_gone() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let clone = rx.clone();
+        drop(rx);
+        assert!(tx.send(1).is_ok());
+        drop(clone);
+        assert!(matches!(tx.send(2), Err(SendError::Closed(2, None))));
+        assert!(tx.is_closed());
+    }
+
+    #[test]
+    fn test_iter_ends_once_senders_are_gone() {
+        let (tx, rx) = spinning_spsc::<u32>(8);
+        let producer = std::thread::spawn(move || {
+            for value in 0..100 {
+                tx.send(value).unwrap();
+            }
+        });
+        let received: Vec<u32> = rx.iter().collect();
+        producer.join().unwrap();
+        assert_eq!(received, (0..100).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_try_iter_keeps_unyielded_items_on_single_consumer() {
+        let (tx, rx) = spinning_spsc::<u32>(8);
+        tx.send_n([1, 2, 3, 4]).unwrap();
+        assert_eq!(rx.try_iter().take(2).collect::<Vec<u32>>(), [1, 2]);
+        assert_eq!(rx.try_iter().collect::<Vec<u32>>(), [3, 4]);
+        assert_eq!(rx.try_iter().next(), None);
+    }
+
+    #[test]
+    fn test_try_recv_batch_reports_count_without_waiting() {
+        let (tx, rx) = spsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Empty);
+
+        tx.send_n(0..6).unwrap();
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Processed(4));
+        drop(tx);
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Processed(2));
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Disconnected);
+    }
+
+    #[test]
+    fn test_recv_reports_processed_and_remaining_items() {
+        let (tx, rx) = spinning_spsc::<u32>(8);
+        tx.send_n(0..6).unwrap();
+        let outcome = rx.recv(4, &|_| {});
+        assert_eq!(outcome.state(), RecvState::Received);
+        assert_eq!((outcome.processed(), outcome.remaining_hint()), (4, 2));
+        let outcome = rx.recv_with_context(4, &|_, _| {});
+        assert_eq!((outcome.processed(), outcome.remaining_hint()), (2, 0));
+
+        let outcome = rx.recv(4, &|_| {});
+        assert_eq!(outcome, RecvState::Empty);
+        assert_eq!(outcome.processed(), 0);
+        drop(tx);
+        assert_eq!(
+            RecvState::from(rx.recv(4, &|_| {})),
+            RecvState::Disconnected
+        );
+    }
+
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    #[test]
+    fn test_rebase_restarts_sequences_of_a_drained_channel() {
+        let (mut tx, mut rx) = mpmc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        tx.send_n(0..3).unwrap();
+        assert_eq!(rebase(&mut tx, &mut rx), Err(RebaseError::NotDrained));
+        while rx.try_recv_batch(4, &|_| {}) != RecvResult::Empty {}
+
+        let other = rx.clone();
+        assert_eq!(rebase(&mut tx, &mut rx), Err(RebaseError::Shared));
+        drop(other);
+        assert_eq!(rebase(&mut tx, &mut rx), Ok(()));
+
+        tx.send(7).unwrap();
+        let sequences = RefCell::new(Vec::new());
+        rx.recv_with_ref(4, &|event, value| {
+            sequences.borrow_mut().push((event.sequence(), value))
+        });
+        assert_eq!(sequences.into_inner(), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn test_event_refs_stop_resolving_once_their_slot_is_overwritten() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        tx.send(1).unwrap();
+        let event = Cell::new(None);
+        rx.recv_with_ref(1, &|reference, _| event.set(Some(reference)));
+        let event = event.get().unwrap();
+        assert_eq!(rx.resolve(event), Some(1));
+
+        tx.send_n(2..5).unwrap();
+        assert_eq!(rx.resolve(event), Some(1));
+        while rx.try_recv_batch(4, &|_| {}) != RecvResult::Empty {}
+        tx.send(5).unwrap();
+        assert_eq!(rx.resolve(event), None);
+    }
+
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    #[test]
+    fn test_event_refs_taken_before_a_rebase_stop_resolving() {
+        let (mut tx, mut rx) = spinning_spsc::<u32>(4);
+        let event = Cell::new(None);
+        let handler = |reference, _| event.set(Some(reference));
+
+        tx.send(1).unwrap();
+        rx.recv_with_ref(1, &handler);
+        let before = event.get().unwrap();
+        assert_eq!(rx.resolve(before), Some(1));
+        assert_eq!(rebase(&mut tx, &mut rx), Ok(()));
+
+        tx.send(2).unwrap();
+        rx.recv_with_ref(1, &handler);
+        let after = event.get().unwrap();
+        assert_eq!(after.sequence(), before.sequence());
+        assert_eq!(after.epoch(), before.epoch() + 1);
+        assert_eq!(rx.resolve(before), None);
+        assert_eq!(rx.resolve(after), Some(2));
+    }
+
+    #[test]
+    fn test_context_flags_the_end_of_each_batch() {
+        let (tx, rx) = spinning_spsc::<u32>(8);
+        tx.send_n(10..15).unwrap();
+
+        let received = RefCell::new(Vec::new());
+        let handler = |context: Context, value| {
+            let entry = (context.sequence(), value, context.is_end_of_batch());
+            received.borrow_mut().push(entry);
+        };
+        assert_eq!(rx.recv_with_context(3, &handler), RecvState::Received);
+        assert_eq!(rx.recv_with_context(3, &handler), RecvState::Received);
+        assert_eq!(
+            received.into_inner(),
+            [
+                (0, 10, false),
+                (1, 11, false),
+                (2, 12, true),
+                (3, 13, false),
+                (4, 14, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_positions_count_items_across_laps() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        assert_eq!((tx.last_published(), rx.last_consumed()), (None, None));
+        assert_eq!(position_to_sequence(None), INITIAL_SEQUENCE);
+
+        let positions = RefCell::new(Vec::new());
+        let handler = |context: Context, _| positions.borrow_mut().push(context.position());
+        for round in 0..3 {
+            tx.send_n(0..3).unwrap();
+            assert_eq!(tx.last_published(), Some(round * 3 + 2));
+            assert_eq!(rx.recv_with_context(2, &handler), RecvState::Received);
+            assert_eq!(rx.last_consumed(), Some(round * 3 + 1));
+            assert_eq!(rx.recv_with_context(2, &handler), RecvState::Received);
+        }
+        assert_eq!(rx.last_consumed(), tx.last_published());
+        assert!(positions.into_inner().into_iter().eq(0..9));
+        let last = tx.last_published();
+        assert_eq!(sequence_to_position(position_to_sequence(last)), last);
+    }
+
+    #[test]
+    fn test_acked_receivers_replay_unacknowledged_items() {
+        let (tx, mut rx) = spsc_acked::<String>(
+            4,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let received = RefCell::new(Vec::new());
+        let handler = |context: Context, value: String| {
+            received.borrow_mut().push((context.sequence(), value));
+        };
+        tx.send_n((0..4).map(|i| i.to_string())).unwrap();
+        assert_eq!(rx.recv(4, &handler), RecvState::Received);
+        assert_eq!(rx.unacked(), 4);
+        // Received items keep their slots until they are acknowledged.
+        assert!(matches!(
+            tx.try_send("4".into()),
+            Err(TrySendError::Full(_))
+        ));
+        assert!(!rx.ack(4));
+        assert!(rx.ack(1));
+        assert_eq!((rx.last_acked(), rx.last_consumed()), (Some(1), Some(3)));
+        tx.send_n(["4".into(), "5".into()]).unwrap();
+
+        // Replay from an unacknowledged item, but not from an acknowledged one.
+        assert!(!rx.rewind(1));
+        assert!(rx.rewind(3));
+        received.borrow_mut().clear();
+        assert_eq!(rx.recv(4, &handler), RecvState::Received);
+        assert_eq!(
+            received.take(),
+            [(3, "3".into()), (4, "4".into()), (5, "5".into())]
+        );
+        rx.replay();
+        assert_eq!(rx.recv(4, &handler), RecvState::Received);
+        assert_eq!(received.take().first(), Some(&(2, "2".to_string())));
+        assert!(rx.ack(5));
+        assert_eq!(rx.unacked(), 0);
+
+        drop(tx);
+        assert_eq!(rx.recv(4, &handler), RecvState::Disconnected);
+    }
+
+    #[test]
+    fn test_claimed_slot_is_published_on_commit() {
+        let (tx, rx) = spinning_spsc::<[u8; 32]>(4);
+        let mut slot = tx.claim().unwrap();
+        slot[0] = 7;
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Empty);
+        slot.commit();
+
+        let first = Cell::new(0);
+        assert_eq!(
+            rx.try_recv_batch(4, &|event: [u8; 32]| first.set(event[0])),
+            RecvResult::Processed(1)
+        );
+        assert_eq!(first.get(), 7);
+    }
+
+    #[test]
+    fn test_sends_wait_behind_a_claimed_slot() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let mut first = tx.claim().unwrap();
+        *first = 1;
+        tx.send(3).unwrap();
+        let mut second = tx.claim().unwrap();
+        tx.try_send(4).unwrap();
+        *second = 2;
+        second.commit();
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Empty);
+
+        // The guard still owns its slot, so consumers cannot read it yet.
+        *first += 9;
+        first.commit();
+        assert_eq!(rx.drain_all(), [10, 3, 2, 4]);
+        tx.send(5).unwrap();
+        assert_eq!(rx.drain_all(), [5]);
+    }
+
+    #[test]
+    fn test_permits_send_into_reserved_slots() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let first = tx.reserve().unwrap();
+        let mut rest = tx.reserve_n(2).unwrap();
+        let last = tx.try_reserve().unwrap();
+        assert!(matches!(tx.try_reserve(), Err(TrySendError::Full(()))));
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Empty);
+
+        first.send(1).unwrap();
+        rest.send(2).unwrap();
+        rest.send(3).unwrap();
+        assert_eq!(rest.remaining(), 0);
+        last.send(4).unwrap();
+        assert_eq!(rx.drain_all(), [1, 2, 3, 4]);
+
+        // An unused permit hands its slot back.
+        drop(tx.reserve().unwrap());
+        tx.send(5).unwrap();
+        assert_eq!(rx.drain_all(), [5]);
+        assert!(!tx.is_closed());
+
+        let permit = tx.reserve().unwrap();
+        rx.close();
+        assert!(matches!(permit.send(6), Err(SendError::Closed(6, _))));
+    }
+
+    #[test]
+    fn test_sends_wait_behind_an_unused_permit() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        let first = tx.reserve().unwrap();
+        let mut rest = tx.reserve_n(2).unwrap();
+        tx.send(3).unwrap();
+        rest.send(1).unwrap();
+        assert_eq!(rx.try_recv_batch(4, &|_| {}), RecvResult::Empty);
+
+        first.send(0).unwrap();
+        assert_eq!(rx.drain_all(), [0, 1]);
+        rest.send(2).unwrap();
+        assert_eq!(rx.drain_all(), [2, 3]);
+        tx.send(4).unwrap();
+        assert_eq!(rx.drain_all(), [4]);
+    }
+
+    #[test]
+    fn test_batched_sends_wake_blocked_consumers_once_per_batch() {
+        let (tx, rx) = spsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let mut batch = tx.batch();
+        batch.send(0).unwrap();
+        batch.send(1).unwrap();
+        assert_eq!(batch.pending(), 2);
+        // Published at once, even before the consumer is woken.
+        assert_eq!(rx.len(), 2);
+        batch.flush();
+        assert_eq!(batch.pending(), 0);
+        drop(batch);
+
+        // A full buffer wakes the consumer before the batch waits for it.
+        let consumer = std::thread::spawn(move || rx.iter().collect::<Vec<_>>());
+        let mut batch = tx.batch();
+        for value in 2..50 {
+            batch.send(value).unwrap();
+        }
+        drop(batch);
+        drop(tx);
+        assert_eq!(consumer.join().unwrap(), (0..50).collect::<Vec<_>>());
+    }
+
+    /// Counts how many of its instances were dropped.
+    #[derive(Clone)]
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_dropping_the_channel_drops_unconsumed_items() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = spinning_spsc::<Tracked>(4);
+        tx.send_n((0..3).map(|_| Tracked(drops.clone()))).unwrap();
+        rx.recv(2, &drop);
+        tx.send_n((0..3).map(|_| Tracked(drops.clone()))).unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        drop(tx);
+        drop(rx);
+        assert_eq!(drops.load(Ordering::Relaxed), 6);
+    }
+
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    #[test]
+    fn test_dropping_a_broadcast_channel_drops_retained_items() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = broadcast::<Tracked>(
+            4,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        tx.send_n((0..3).map(|_| Tracked(drops.clone()))).unwrap();
+        rx.recv(2, &drop);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        drop(tx);
+        drop(rx);
+        assert_eq!(drops.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn test_time_budget_stops_a_batch_early() {
+        let (tx, rx) = spinning_spsc::<u32>(16);
+        let rx = rx.with_time_budget(Duration::from_millis(5), 2);
+        tx.send_n(0..10).unwrap();
+
+        let slow = |_| std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(rx.try_recv_batch(16, &slow), RecvResult::Processed(2));
+        assert_eq!(rx.try_recv_batch(16, &|_| {}), RecvResult::Processed(8));
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn test_items_are_stamped_with_their_producer() {
+        let (first, rx) = mpsc_with_producers::<u32>(
+            8,
+            2,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let second = first.clone();
+        first.send(1).unwrap();
+        second.send_n([2, 3]).unwrap();
+
+        let received = RefCell::new(Vec::new());
+        rx.recv_with_producer(8, &|producer, value| {
+            received.borrow_mut().push((producer, value))
+        });
+        assert_eq!(
+            received.into_inner(),
+            vec![(Some(0), 1), (Some(1), 2), (Some(1), 3)]
+        );
+
+        let statuses = first.producer_statuses();
+        assert_eq!(statuses[0].sent(), 1);
+        assert_eq!(statuses[1].sent(), 2);
+        assert!(statuses[1].send_time() >= statuses[1].longest_send());
+    }
+
+    #[test]
+    fn test_occupancy_follows_sends_and_receives() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        assert!(tx.is_empty() && rx.is_empty());
+        assert_eq!(tx.remaining_capacity(), 4);
+
+        tx.send_n(0..4).unwrap();
+        assert!(tx.is_full() && rx.is_full());
+        assert_eq!(tx.remaining_capacity(), 0);
+
+        rx.try_recv_batch(3, &drop);
+        assert_eq!((tx.len(), rx.len()), (1, 1));
+        assert_eq!(tx.remaining_capacity(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "mp")]
+    fn test_additional_producer_upgrades_a_drained_spsc_channel() {
+        let (mut tx, mut rx) = yielding_spsc::<u32>(4);
+        tx.send_n(0..3).unwrap();
+        assert!(matches!(
+            tx.register_additional_producer(&mut rx),
+            Err(RebaseError::NotDrained)
+        ));
+        assert_eq!(rx.try_recv_batch(4, &drop), RecvResult::Processed(3));
+
+        let other = tx.register_additional_producer(&mut rx).unwrap();
+        let producers: Vec<_> = [tx, other]
+            .into_iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                std::thread::spawn(move || {
+                    for value in 0..500 {
+                        tx.send(index as u32 * 1000 + value).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let mut received = recv_all(&rx, 4);
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+
+        received.sort_unstable();
+        let expected: Vec<u32> = (0..500).chain(1000..1500).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "mp")]
+    fn test_producer_handles_claim_windows_ahead() {
+        let (tx, rx) = mpsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let producers: Vec<_> = (0..2)
+            .map(|index| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    let mut handle = tx.producer_handle(4).unwrap();
+                    for value in 0..100 {
+                        handle.send(index * 1000 + value).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let received = RefCell::new(Vec::new());
+        while received.borrow().len() < 200 {
+            rx.recv(8, &|value| received.borrow_mut().push(value));
+        }
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+        let mut received = received.into_inner();
+        received.sort_unstable();
+        let expected: Vec<u32> = (0..100).chain(1000..1100).collect();
+        assert_eq!(received, expected);
+
+        let mut handle = tx.producer_handle(4).unwrap();
+        handle.send(1).unwrap();
+        assert_eq!(handle.remaining(), 3);
+        drop(handle);
+        tx.send(2).unwrap();
+        let received = RefCell::new(Vec::new());
+        assert_eq!(
+            rx.try_recv_batch(8, &|value| received.borrow_mut().push(value)),
+            RecvResult::Processed(2)
+        );
+        assert_eq!(*received.borrow(), [1, 2]);
+
+        let mut handle = tx.producer_handle(4).unwrap();
+        handle.send(3).unwrap();
+        tx.send(4).unwrap();
+        drop(handle);
+        assert!(tx.is_closed());
+        let reason = tx.close_reason().unwrap();
+        assert!(reason.downcast_ref::<SequencesAbandoned>().is_some());
+
+        let (tx, _rx) = yielding_spsc::<u32>(8);
+        assert!(tx.producer_handle(4).is_none());
+    }
+
+    #[test]
+    fn test_rendezvous_sends_wait_for_the_receiver() {
+        let (tx, rx) = spsc_rendezvous::<u32>(
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        assert!(matches!(tx.try_send(0), Err(TrySendError::Full(0))));
+
+        let handed_off = Arc::new(AtomicUsize::new(0));
+        let counter = handed_off.clone();
+        let sender = std::thread::spawn(move || {
+            for value in 1..=3 {
+                tx.send(value).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(handed_off.load(Ordering::SeqCst), 0);
+
+        let received = recv_all(&rx, 1);
+        sender.join().unwrap();
+        assert_eq!(handed_off.load(Ordering::SeqCst), 3);
+        assert_eq!(received, [1, 2, 3]);
+    }
+
+    #[cfg(feature = "mc")]
+    #[test]
+    fn test_panicking_handlers_follow_the_panic_policy() {
+        let (tx, rx) = spmc_with_fairness::<u32>(
+            8,
+            ConsumerFairness::Throughput,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let other = rx.clone();
+        let received = RefCell::new(Vec::new());
+        let handler = |value: u32| {
+            assert_ne!(value, 1, "unlucky");
+            received.borrow_mut().push(value);
+        };
+
+        // The rest of the batch goes to the next claim, of any receiver.
+        tx.send_n(0..6).unwrap();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| rx.recv(4, &handler)));
+        assert!(panicked.is_err());
+        assert_eq!(other.recv(8, &handler).processed(), 2);
+        assert_eq!(other.recv(8, &handler).processed(), 2);
+        assert_eq!(*received.borrow(), [0, 2, 3, 4, 5]);
+        assert!(rx.is_empty());
+
+        let skipping = rx.clone().with_panic_policy(PanicPolicy::Skip);
+        tx.send_n([1, 6]).unwrap();
+        assert_eq!(skipping.recv(8, &handler).processed(), 2);
+        assert_eq!(received.borrow().last(), Some(&6));
+
+        let poisoning = rx.clone().with_panic_policy(PanicPolicy::Poison);
+        tx.send_n([7, 1, 8]).unwrap();
+        let outcome = poisoning.recv(8, &handler);
+        assert_eq!(
+            (outcome.state(), outcome.processed()),
+            (RecvState::Poisoned, 3)
+        );
+        assert_eq!(received.borrow().last(), Some(&7));
+        let reason = tx.send(9).unwrap_err().reason().cloned().unwrap();
+        assert_eq!(
+            reason.downcast_ref::<ChannelPoisoned>(),
+            Some(&ChannelPoisoned { sequence: 9 })
+        );
+        assert_eq!(other.recv(8, &handler), RecvState::Disconnected);
+    }
+
+    #[test]
+    fn test_drain_moves_available_items_at_once() {
+        let (tx, rx) = filled_spsc::<u32>(8, 0..6);
+        let mut items = vec![100];
+        assert_eq!(rx.drain_into(&mut items, 4), RecvResult::Processed(4));
+        assert_eq!(items, [100, 0, 1, 2, 3]);
+        assert_eq!(rx.drain_into(&mut items, 0), RecvResult::Empty);
+
+        tx.send_n(6..10).unwrap();
+        assert_eq!(rx.drain_all(), [4, 5, 6, 7, 8, 9]);
+        assert_eq!(rx.drain_into(&mut items, 4), RecvResult::Empty);
+        drop(tx);
+        assert_eq!(rx.drain_into(&mut items, 4), RecvResult::Disconnected);
+        assert!(rx.drain_all().is_empty());
+    }
+
+    #[test]
+    fn test_recv_into_fills_the_front_of_the_slice() {
+        let (_tx, rx) = filled_spsc::<u32>(8, 0..6);
+        let mut out = [MaybeUninit::uninit(); 16];
+        assert_eq!(rx.recv_into(&mut out[..4]), 4);
+        // SAFETY: the first four items were written.
+        let items = out[..4].iter().map(|item| unsafe { item.assume_init() });
+        assert!(items.eq(0..4));
+        assert_eq!(rx.recv_into(&mut out), 2);
+        // SAFETY: the first two items were written.
+        let items = out[..2].iter().map(|item| unsafe { item.assume_init() });
+        assert!(items.eq(4..6));
+        assert_eq!(rx.recv_into(&mut out), 0);
+        assert_eq!(rx.recv_into(&mut []), 0);
+    }
+
+    #[test]
+    fn test_closed_channels_deliver_pending_items_and_flush() {
+        let (tx, rx) = filled_spsc::<u32>(8, 0..3);
+        assert!(tx.close());
+        assert!(!rx.close());
+        assert!(matches!(tx.send(3), Err(SendError::Closed(3, None))));
+
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let consumer = std::thread::spawn(move || {
+            while rx.recv(2, &|_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }) != RecvState::Disconnected
+            {}
+        });
+        tx.flush().unwrap();
+        assert_eq!(received.load(Ordering::Relaxed), 3);
+        consumer.join().unwrap();
+        assert!(matches!(tx.flush(), Ok(())));
+
+        let (tx, rx) = yielding_spsc::<u32>(8);
+        tx.send(1).unwrap();
+        drop(rx);
+        assert!(matches!(tx.flush(), Err(SendError::Closed((), _))));
+    }
+
+    #[test]
+    fn test_try_send_reports_full_and_closed() {
+        let (tx, rx) = yielding_spsc::<u32>(2);
+        tx.try_send(0).unwrap();
+        tx.try_send_bounded(1, 0).unwrap();
+        assert!(matches!(tx.try_send(2), Err(TrySendError::Full(2))));
+        assert!(matches!(
+            tx.try_send_bounded(2, 0),
+            Err(TrySendError::Full(2))
+        ));
+        assert_eq!(rx.drain_all(), [0, 1]);
+
+        rx.close_with_error(std::io::Error::other("shutdown"));
+        let Err(TrySendError::Closed(2, Some(reason))) = tx.try_send(2) else {
+            panic!("the channel is closed");
+        };
+        assert_eq!(reason.to_string(), "shutdown");
+        assert!(matches!(
+            tx.try_send_bounded(3, 0),
+            Err(TrySendError::Closed(3, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn test_reserved_ranges_publish_in_reserve_order() {
+        let (tx, rx) = yielding_spsc::<u32>(8);
+        let first = tx.reserve_sequence_range(2).unwrap();
+        let second = tx.reserve_sequence_range(3).unwrap();
+        tx.publish_into(first, [0, 1]).unwrap();
+        assert_eq!(rx.drain_all(), [0, 1]);
+        tx.publish_into(second, 2..5).unwrap();
+        assert_eq!(rx.drain_all(), [2, 3, 4]);
+
+        // A range published first is held back until the earlier one is.
+        let third = tx.reserve_sequence_range(1).unwrap();
+        let fourth = tx.reserve_sequence_range(1).unwrap();
+        tx.publish_into(fourth, [6]).unwrap();
+        assert_eq!(rx.try_recv_batch(8, &|_| {}), RecvResult::Empty);
+        tx.publish_into(third, [5]).unwrap();
+        assert_eq!(rx.drain_all(), [5, 6]);
+    }
+
+    #[test]
+    fn test_sends_wait_behind_an_outstanding_range() {
+        let (tx, rx) = yielding_spsc::<u32>(8);
+        tx.send(0).unwrap();
+        let range = tx.reserve_sequence_range(2).unwrap();
+        tx.send(3).unwrap();
+        tx.try_send(4).unwrap();
+        tx.send_n([5, 6]).unwrap();
+        assert_eq!(rx.drain_all(), [0]);
+
+        tx.publish_into(range, [1, 2]).unwrap();
+        assert_eq!(rx.drain_all(), [1, 2, 3, 4, 5, 6]);
+        tx.send(7).unwrap();
+        assert_eq!(rx.drain_all(), [7]);
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn test_try_send_bounded_gives_up_on_contention() {
+        use crate::channels::Sender;
+        use crate::sched::{self, SchedHook};
+
+        /// Claims a slot from another producer before the first claim attempt.
+        struct Race {
+            rival: Sender<u32>,
+            raced: Cell<bool>,
+        }
+
+        impl SchedHook for Race {
+            fn before_try_claim(&self, _: i64) {
+                if !self.raced.replace(true) {
+                    let rival = &self.rival;
+                    thread::scope(|scope| {
+                        scope.spawn(|| rival.try_send(0).unwrap());
+                    });
+                }
+            }
+        }
+
+        let (tx, rx) = mpsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let race = || Race {
+            rival: tx.clone(),
+            raced: Cell::new(false),
+        };
+
+        let guard = sched::install(race());
+        assert!(matches!(
+            tx.try_send_bounded(1, 0),
+            Err(TrySendError::WouldBlock(1))
+        ));
+        drop(guard);
+        let _guard = sched::install(race());
+        tx.try_send_bounded(1, 1).unwrap();
+        assert_eq!(rx.drain_all(), [0, 0, 1]);
+    }
+
+    #[test]
+    fn test_close_with_error_hands_the_reason_to_senders() {
+        let (tx, rx) = spsc::<u32>(
+            2,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send_n(0..2).unwrap();
+        let blocked = thread::scope(|scope| {
+            let blocked = scope.spawn(|| tx.send(2));
+            thread::sleep(Duration::from_millis(50));
+            assert!(rx.close_with_error(std::io::Error::other("disk full")));
+            blocked.join().unwrap()
+        });
+        assert!(!rx.close_with_error(std::io::Error::other("ignored")));
+
+        // The sender waiting for a free slot gives up with the reason, and
+        // so does every later send.
+        let Err(SendError::Closed(2, Some(reason))) = blocked else {
+            panic!("the waiting send fails with the reason");
+        };
+        assert_eq!(reason.to_string(), "disk full");
+        let Err(SendError::Closed(3, Some(reason))) = tx.send(3) else {
+            panic!("the send fails with the reason");
+        };
+        assert_eq!(reason.to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_builder_configures_the_channel() {
+        let (tx, rx) = ChannelBuilder::<u32>::new()
+            .capacity(8)
+            .credits(2)
+            .max_producers(2)
+            .producer_wait(ProducerWaitStrategyKind::Yielding)
+            .consumer_wait(ConsumerWaitStrategyKind::Yielding)
+            .notify_policy(NotifyPolicy::EveryN(4))
+            .build();
+        assert_eq!(tx.max_producers(), Some(2));
+        assert_eq!(tx.notify_policy(), NotifyPolicy::EveryN(4));
+        tx.send_n(0..2).unwrap();
+        assert!(matches!(tx.try_send(2), Err(TrySendError::Full(2))));
+        rx.grant(1);
+        tx.try_send(2).unwrap();
+        assert_eq!(rx.drain_all(), [0, 1, 2]);
+
+        #[cfg(all(feature = "mp", feature = "mc"))]
+        {
+            use crate::channels::{Consumers, Producers};
+
+            let (tx, rx) = ChannelBuilder::<u32>::new()
+                .capacity(4)
+                .producers(Producers::Multi)
+                .consumers(Consumers::Multi)
+                .build();
+            let (other_tx, other_rx) = (tx.clone(), rx.clone());
+            tx.send(1).unwrap();
+            other_tx.send(2).unwrap();
+            assert_eq!(rx.try_recv_batch(1, &drop), RecvResult::Processed(1));
+            assert_eq!(other_rx.try_recv_batch(1, &drop), RecvResult::Processed(1));
+            assert_eq!(rx.try_recv_batch(1, &drop), RecvResult::Empty);
+        }
+    }
+
+    #[test]
+    fn test_spawned_consumers_stop_and_drain() {
+        let (tx, rx) = spsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let consumer = rx.spawn_consumer("consumer", 4, move |value| {
+            counter.fetch_add(value as usize, Ordering::Relaxed);
+        });
+        for value in 1..=10 {
+            tx.send(value).unwrap();
+        }
+        tx.close();
+        consumer.join().unwrap();
+        assert_eq!(received.load(Ordering::Relaxed), 55);
+
+        let (tx, rx) = spsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let consumer = rx.spawn_consumer("idle", 4, drop);
+        consumer.stop();
+        consumer.join().unwrap();
+        assert!(tx.send(1).is_err());
+    }
+
+    #[test]
+    fn test_translators_publish_in_place() {
+        let (tx, rx) = yielding_spsc::<(u32, u32)>(8);
+        tx.send_with(|slot, sequence| slot.0 = sequence as u32)
+            .unwrap();
+        tx.send_with2(|slot, _, a, b| *slot = (a, b), 7, 8).unwrap();
+        tx.send_n_with([(1, 2), (3, 4)], |slot, sequence, (a, b)| {
+            *slot = (a * 10, b + sequence as u32)
+        })
+        .unwrap();
+        assert_eq!(rx.drain_all(), [(0, 0), (7, 8), (10, 4), (30, 7)]);
+
+        let (tx, rx) = spsc_with_factory(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+            || Vec::<u8>::with_capacity(16),
+        );
+        tx.send_with3(|slot, _, a, b, c| slot.extend([a, b, c]), 1, 2, 3)
+            .unwrap();
+        let items = RefCell::new(Vec::new());
+        rx.recv(4, &|item: Vec<u8>| items.borrow_mut().push(item));
+        assert_eq!(items.into_inner(), [vec![1, 2, 3]]);
+
+        rx.close();
+        assert!(matches!(
+            tx.send_with1(|slot, _, a| slot.push(a), 9),
+            Err(SendError::Closed((9,), _))
+        ));
+    }
 }
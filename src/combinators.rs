@@ -0,0 +1,178 @@
+//! Receiver combinators.
+//!
+//! [`Receive`] is implemented by [`Receiver`] and by the wrappers returned by
+//! its combinators, so trivial transformation stages can be stacked onto a
+//! receiver (`rx.map(f).filter(p)`) and run inside its poll path, instead of
+//! needing an extra ring and thread of their own.
+
+use crate::channels::{Receiver, RecvState};
+
+/// A receiver-like handle that hands received items to a handler.
+pub trait Receive {
+    /// The type of the items handed to handlers.
+    type Item;
+
+    /// Attempt to receive up to `batch_size` items.
+    ///
+    /// See [`Receiver::recv`].
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(Self::Item);
+
+    /// Continuously attempt to receive items until at least one batch is processed.
+    ///
+    /// See [`Receiver::blocking_recv`].
+    fn blocking_recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(Self::Item),
+    {
+        loop {
+            match self.recv(batch_size, handler) {
+                RecvState::Empty => continue,
+                state => return state,
+            }
+        }
+    }
+
+    /// Hand handlers `f(item)` instead of every item.
+    fn map<U, F>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Item) -> U,
+    {
+        Map { inner: self, f }
+    }
+
+    /// Only hand handlers the items `predicate` accepts; the others are dropped.
+    ///
+    /// A receive that took items from the channel reports
+    /// [`RecvState::Received`] even if the predicate rejected all of them.
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        Self: Sized,
+        P: Fn(&Self::Item) -> bool,
+    {
+        Filter {
+            inner: self,
+            predicate,
+        }
+    }
+
+    /// Call `f` with a reference to every item before it is handed on.
+    fn inspect<F>(self, f: F) -> Inspect<Self, F>
+    where
+        Self: Sized,
+        F: Fn(&Self::Item),
+    {
+        Inspect { inner: self, f }
+    }
+}
+
+impl<T> Receive for Receiver<T> {
+    type Item = T;
+
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        Receiver::recv(self, batch_size, handler).state()
+    }
+}
+
+/// A receiver that maps its items, created by [`Receive::map`].
+pub struct Map<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F, U> Receive for Map<R, F>
+where
+    R: Receive,
+    F: Fn(R::Item) -> U,
+{
+    type Item = U;
+
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(U),
+    {
+        self.inner.recv(batch_size, &|item| handler((self.f)(item)))
+    }
+}
+
+/// A receiver that drops the items a predicate rejects, created by [`Receive::filter`].
+pub struct Filter<R, P> {
+    inner: R,
+    predicate: P,
+}
+
+impl<R, P> Receive for Filter<R, P>
+where
+    R: Receive,
+    P: Fn(&R::Item) -> bool,
+{
+    type Item = R::Item;
+
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(R::Item),
+    {
+        self.inner.recv(batch_size, &|item| {
+            if (self.predicate)(&item) {
+                handler(item);
+            }
+        })
+    }
+}
+
+/// A receiver that observes its items, created by [`Receive::inspect`].
+pub struct Inspect<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> Receive for Inspect<R, F>
+where
+    R: Receive,
+    F: Fn(&R::Item),
+{
+    type Item = R::Item;
+
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(R::Item),
+    {
+        self.inner.recv(batch_size, &|item| {
+            (self.f)(&item);
+            handler(item);
+        })
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::combinators::Receive;
+    use crate::testing::spinning_spsc;
+    use std::cell::{Cell, RefCell};
+
+    #[test]
+    fn test_combinators_run_in_the_poll_path() {
+        let (tx, rx) = spinning_spsc::<u32>(16);
+        let inspected = Cell::new(0);
+        let rx = rx
+            .inspect(|_| inspected.set(inspected.get() + 1))
+            .filter(|value| value % 2 == 0)
+            .map(|value| value * 10);
+
+        tx.send_n(0..6).unwrap();
+        drop(tx);
+        let received = RefCell::new(Vec::new());
+        let handler = |value| received.borrow_mut().push(value);
+        assert_eq!(rx.blocking_recv(16, &handler), RecvState::Received);
+        assert_eq!(rx.blocking_recv(16, &handler), RecvState::Disconnected);
+
+        assert_eq!(inspected.get(), 6);
+        assert_eq!(received.into_inner(), vec![0, 20, 40]);
+    }
+}
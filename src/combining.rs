@@ -0,0 +1,287 @@
+//! Flat-combining front-end for heavily contended multi-producer channels.
+//!
+//! Every claim of a multi-producer channel increments the shared claim
+//! cursor, and with dozens of producers the cache line of that cursor becomes
+//! the bottleneck. The senders of a [`mpsc_combining`] channel instead stage
+//! their item in a cell of their own. Whichever producer takes the combiner
+//! lock collects the staged items of every producer and publishes them with a
+//! single claim, while the others wait for their cell to be served. Only one
+//! producer ever claims at a time, so the ring underneath uses the
+//! single-producer sequencer and no cursor is contended at all.
+//!
+//! Combining costs a handoff per item, which only pays off once producers
+//! would otherwise queue up on the cursor; see the
+//! `multi_producer_combining_bench` bench for the crossover on a given machine.
+
+use crate::channels::{Receiver, Sender, spsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::SendError;
+use crate::producers::ProducerRegistry;
+use crate::sync::{AtomicBool, AtomicU8, Ordering, spin_loop};
+use crate::utils::CachePadded;
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::thread;
+
+/// Number of polls of its cell a waiting producer spins for before yielding.
+const SPIN_POLLS: usize = 64;
+
+/// The cell holds nothing.
+const EMPTY: u8 = 0;
+/// The cell holds an item waiting for a combiner.
+const STAGED: u8 = 1;
+/// A combiner published the item of the cell.
+const SENT: u8 = 2;
+/// A combiner found the channel closed and left the item in the cell.
+const CLOSED: u8 = 3;
+
+/// The staging cell of a single producer.
+struct StagingCell<T> {
+    state: AtomicU8,
+    item: UnsafeCell<Option<T>>,
+}
+
+/// The state shared by the senders of a combining channel.
+struct Shared<T> {
+    sender: Sender<T>,
+    cells: Box<[CachePadded<StagingCell<T>>]>,
+    producers: ProducerRegistry,
+    combining: AtomicBool,
+    /// The items and cells of the batch being combined, only touched by the
+    /// holder of the combiner lock.
+    batch: UnsafeCell<(Vec<T>, Vec<usize>)>,
+}
+
+// SAFETY: items move between threads through the staging cells, whose
+// accesses are ordered by the cell state, and the batch is only used by the
+// holder of the combiner lock.
+unsafe impl<T: Send> Send for Shared<T> {}
+
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    /// Publish the items staged in every cell with a single claim.
+    ///
+    /// Must be called with the combiner lock held.
+    fn combine(&self) {
+        // SAFETY: the combiner lock is held.
+        let (items, cells) = unsafe { &mut *self.batch.get() };
+        for (index, cell) in self.cells.iter().enumerate() {
+            if cell.state.load(Ordering::Acquire) == STAGED {
+                // SAFETY: a staged item belongs to the combiner until it
+                // changes the state of the cell.
+                items.extend(unsafe { (*cell.item.get()).take() });
+                cells.push(index);
+            }
+        }
+        if cells.is_empty() {
+            return;
+        }
+
+        let state = match self.sender.send_n(items.drain(..)) {
+            Ok(()) => SENT,
+            Err(SendError::Closed(unsent, _)) => {
+                for (&index, item) in cells.iter().zip(unsent) {
+                    // SAFETY: see above, the cell is still staged.
+                    unsafe { *self.cells[index].item.get() = Some(item) };
+                }
+                CLOSED
+            }
+        };
+        for index in cells.drain(..) {
+            self.cells[index].state.store(state, Ordering::Release);
+        }
+    }
+}
+
+/// The sending half of a flat-combining channel, created by [`mpsc_combining`].
+///
+/// Each clone owns a staging cell, so a channel has at most as many senders
+/// as it has cells.
+pub struct CombiningSender<T> {
+    shared: Arc<Shared<T>>,
+    id: usize,
+}
+
+impl<T> CombiningSender<T> {
+    /// Send a single value into the buffer.
+    ///
+    /// Stages the value and either publishes it together with the values
+    /// staged by other producers, or waits until another producer has done
+    /// so. The producer that publishes waits according to the producer wait
+    /// strategy while the buffer is full, and the others wait with it.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed,
+    /// including while this call waits for free space.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let shared = &*self.shared;
+        if shared.sender.is_closed() {
+            return Err(SendError::Closed(value, shared.sender.close_reason()));
+        }
+        let cell = &shared.cells[self.id];
+        // SAFETY: the cell is empty, so it belongs to this producer.
+        unsafe { *cell.item.get() = Some(value) };
+        cell.state.store(STAGED, Ordering::Release);
+
+        let mut polls = 0;
+        loop {
+            if !shared.combining.swap(true, Ordering::Acquire) {
+                shared.combine();
+                shared.combining.store(false, Ordering::Release);
+            }
+            match cell.state.load(Ordering::Acquire) {
+                SENT => {
+                    cell.state.store(EMPTY, Ordering::Relaxed);
+                    return Ok(());
+                }
+                CLOSED => {
+                    cell.state.store(EMPTY, Ordering::Relaxed);
+                    // SAFETY: the combiner handed the cell back with the item.
+                    let value = unsafe { (*cell.item.get()).take() };
+                    let reason = shared.sender.close_reason();
+                    return Err(SendError::Closed(value.expect("staged item"), reason));
+                }
+                _ if polls < SPIN_POLLS => {
+                    polls += 1;
+                    spin_loop();
+                }
+                _ => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Clone the sender, or return `None` if every staging cell is taken.
+    pub fn try_clone(&self) -> Option<Self> {
+        let id = self.shared.producers.register()?;
+        Some(Self {
+            shared: self.shared.clone(),
+            id,
+        })
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.shared.sender.is_closed()
+    }
+}
+
+impl<T> Clone for CombiningSender<T> {
+    /// Clone the sender, registering a new staging cell.
+    ///
+    /// # Panics
+    /// Panics if every staging cell is already taken; use
+    /// [`try_clone`](CombiningSender::try_clone) to handle that.
+    fn clone(&self) -> Self {
+        self.try_clone()
+            .expect("maximum number of producers already registered")
+    }
+}
+
+impl<T> Drop for CombiningSender<T> {
+    fn drop(&mut self) {
+        self.shared.producers.release(self.id);
+    }
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel whose senders
+/// publish through a flat-combining front-end.
+///
+/// Suited to deployments with so many producers that the claim cursor of
+/// [`mpsc`](crate::channels::mpsc) becomes the bottleneck, see the
+/// [module documentation](self). The receiver disconnects once every sender
+/// is dropped.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `max_producers`: maximum number of live senders.
+/// - `pw`: producer wait strategy of the combining producer.
+/// - `cw`: consumer wait strategy.
+///
+/// # Panics
+/// Panics if `max_producers` is zero or greater than `buffer_size`, since a
+/// combined batch must fit into the buffer.
+pub fn mpsc_combining<T>(
+    buffer_size: usize,
+    max_producers: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (CombiningSender<T>, Receiver<T>) {
+    assert!(
+        max_producers <= buffer_size,
+        "max_producers must not exceed buffer_size"
+    );
+    let (sender, receiver) = spsc(buffer_size, pw, cw);
+    let producers = ProducerRegistry::new(max_producers);
+    let id = producers
+        .register()
+        .expect("a fresh registry has free slots");
+    let cells = (0..max_producers)
+        .map(|_| {
+            CachePadded(StagingCell {
+                state: AtomicU8::new(EMPTY),
+                item: UnsafeCell::new(None),
+            })
+        })
+        .collect();
+    let shared = Arc::new(Shared {
+        sender,
+        cells,
+        producers,
+        combining: AtomicBool::new(false),
+        batch: UnsafeCell::new((
+            Vec::with_capacity(max_producers),
+            Vec::with_capacity(max_producers),
+        )),
+    });
+    (CombiningSender { shared, id }, receiver)
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::combining::mpsc_combining;
+    use crate::errors::SendError;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::testing::recv_all;
+
+    #[test]
+    fn test_combined_sends_deliver_every_item() {
+        let (tx, rx) = mpsc_combining::<u32>(
+            8,
+            5,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let producers: Vec<_> = (0..4)
+            .map(|index| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for value in 0..200 {
+                        tx.send(index * 1000 + value).unwrap();
+                    }
+                })
+            })
+            .collect();
+        assert!(tx.try_clone().is_none());
+        drop(tx);
+
+        let mut received = recv_all(&rx, 8);
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+        received.sort_unstable();
+        let expected: Vec<u32> = (0..4)
+            .flat_map(|index| index * 1000..index * 1000 + 200)
+            .collect();
+        assert_eq!(received, expected);
+
+        let (tx, rx) = mpsc_combining::<u32>(
+            8,
+            2,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        rx.close_with_error(std::fmt::Error);
+        assert!(matches!(tx.send(1), Err(SendError::Closed(1, Some(_)))));
+    }
+}
@@ -1,6 +1,26 @@
-/// Typical CPU cache line size in bytes.
+/// CPU cache line size of the target architecture in bytes.
 ///
-/// Most modern CPUs have a cache line of 64 bytes.
+/// Most CPUs have a cache line of 64 bytes, but Apple silicon and other
+/// recent aarch64 cores as well as POWER use 128 bytes, and s390x 256 bytes.
+/// Padding to a line that is too short lets neighbours share a line again, so
+/// the size is picked per architecture at compile time, like crossbeam-utils
+/// does. It aligns [`CachePadded`](crate::utils::CachePadded) and thereby every
+/// sequence and padded primitive, and sizes the padding of the availability
+/// buffer; ring buffers pad by the line size of the [`Topology`](crate::topology::Topology)
+/// they are created with, which can be overridden at runtime.
+#[cfg(any(target_arch = "aarch64", target_arch = "powerpc64"))]
+pub const CACHE_LINE_SIZE: usize = 128;
+
+/// CPU cache line size of the target architecture in bytes.
+#[cfg(target_arch = "s390x")]
+pub const CACHE_LINE_SIZE: usize = 256;
+
+/// CPU cache line size of the target architecture in bytes.
+#[cfg(not(any(
+    target_arch = "aarch64",
+    target_arch = "powerpc64",
+    target_arch = "s390x"
+)))]
 pub const CACHE_LINE_SIZE: usize = 64;
 
 /// Size of a raw pointer on the target architecture in bytes.
@@ -9,9 +29,25 @@ pub const CACHE_LINE_SIZE: usize = 64;
 /// This is used for calculating padding or memory layout alignment.
 pub const POINTER_SIZE: usize = size_of::<*const u8>();
 
-/// Number of pointer-sized elements that fit into a single cache line.
+/// Highest sequence producers may claim.
+///
+/// Sequences are `i64` and never wrap around. Capping them at half the range
+/// leaves headroom for every `sequence + n` the protocol computes, and still
+/// lasts over a thousand years at a billion items per second. A claim past
+/// the cap closes the channel with [`SequencesExhausted`](crate::errors::SequencesExhausted)
+/// instead of overflowing; [`rebase`](crate::channels::rebase) moves a drained
+/// channel back to the start of the sequence space long before that.
+pub const MAX_SEQUENCE: i64 = i64::MAX >> 1;
+
+/// Number of slots a batch send writes before publishing them to consumers.
+///
+/// Publishing a large batch in chunks lets consumers start on its head while
+/// the producer is still writing the tail.
+pub const PUBLISH_CHUNK_SIZE: usize = 256;
+
+/// Longest time a [`Selector`](crate::select::Selector) waits for a signal
+/// before it checks its receivers again.
 ///
-/// This is computed as `CACHE_LINE_SIZE / POINTER_SIZE` and is commonly used
-/// to pad arrays or structs to align to cache lines, reducing false sharing
-/// between threads in concurrent data structures.
-pub const ARRAY_PADDING: usize = CACHE_LINE_SIZE / POINTER_SIZE;
+/// A producer may publish just as a receiver is registered and miss the new
+/// signal, which the next check picks up.
+pub const SELECT_RECHECK: std::time::Duration = std::time::Duration::from_millis(10);
@@ -1,5 +1,18 @@
-use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use crate::autotune::Tuning;
+use crate::errors::CloseReason;
+#[cfg(feature = "futures")]
+use crate::futures::Wakers;
+#[cfg(feature = "metrics")]
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::producers::ProducerRegistry;
+#[cfg(feature = "registry")]
+use crate::registry::Registration;
+use crate::select::Signal;
+use crate::sync::{self, AtomicBool, AtomicU8, AtomicUsize, Ordering, spin_loop};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "futures")]
+use std::task::Waker;
+use std::time::{Duration, Instant};
 
 /// Describes the wait strategy for a consumer in a concurrent data structure.
 ///
@@ -12,8 +25,27 @@ pub enum ConsumerWaitStrategyKind {
     Parking(Duration),
     /// Yield the thread to the scheduler.
     Yielding,
-    /// Block using a condition variable until signaled.
+    /// Park the thread until a producer signals.
     Blocking,
+    /// Spin for `spin_limit` consecutive waits, then yield for `yield_limit`
+    /// more, then park for `park_duration` per wait, see [`Backoff`].
+    Backoff {
+        spin_limit: usize,
+        yield_limit: usize,
+        park_duration: Duration,
+    },
+}
+
+impl Default for ConsumerWaitStrategyKind {
+    /// A [`Backoff`](Self::Backoff) with the [`Backoff::default`] limits.
+    fn default() -> Self {
+        let backoff = Backoff::default();
+        Self::Backoff {
+            spin_limit: backoff.spin_limit,
+            yield_limit: backoff.yield_limit,
+            park_duration: backoff.park_duration,
+        }
+    }
 }
 
 /// Describes the wait strategy for a producer in a concurrent data structure.
@@ -27,31 +59,269 @@ pub enum ProducerWaitStrategyKind {
     Parking(Duration),
     /// Yield the thread to the scheduler.
     Yielding,
+    /// Park the thread until a consumer frees slots.
+    Blocking,
+    /// Spin for `spin_limit` consecutive waits, then yield for `yield_limit`
+    /// more, then park for `park_duration` per wait, see [`Backoff`].
+    Backoff {
+        spin_limit: usize,
+        yield_limit: usize,
+        park_duration: Duration,
+    },
+}
+
+impl Default for ProducerWaitStrategyKind {
+    /// A [`Backoff`](Self::Backoff) with the [`Backoff::default`] limits.
+    fn default() -> Self {
+        let backoff = Backoff::default();
+        Self::Backoff {
+            spin_limit: backoff.spin_limit,
+            yield_limit: backoff.yield_limit,
+            park_duration: backoff.park_duration,
+        }
+    }
+}
+
+/// A wait strategy that escalates from spinning to yielding to parking.
+///
+/// The first waits of a streak only issue a pause hint, which keeps the
+/// latency of short waits low, later ones yield the thread, and once a wait
+/// drags on the thread parks instead of burning its core. The streak is kept
+/// per thread and restarts once the thread makes progress on the channel.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Backoff {
+    /// Number of consecutive waits that spin.
+    pub spin_limit: usize,
+    /// Number of consecutive waits that yield after spinning.
+    pub yield_limit: usize,
+    /// How long every later wait parks the thread.
+    pub park_duration: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            spin_limit: 128,
+            yield_limit: 16,
+            park_duration: Duration::from_micros(50),
+        }
+    }
+}
+
+thread_local! {
+    /// The backoff strategy the current thread waits on, by address, and the
+    /// number of consecutive waits in its streak.
+    static BACKOFF_STREAK: std::cell::Cell<(usize, usize)> = const { std::cell::Cell::new((0, 0)) };
+}
+
+impl Backoff {
+    /// Returns the position of this wait in the streak of the current thread.
+    ///
+    /// Waiting on another backoff strategy restarts the streak.
+    fn next_step(&self) -> usize {
+        let owner = self as *const Self as usize;
+        BACKOFF_STREAK.with(|streak| {
+            let step = match streak.get() {
+                (current, step) if current == owner => step,
+                _ => 0,
+            };
+            streak.set((owner, step.saturating_add(1)));
+            step
+        })
+    }
+
+    /// Restart the streak of the current thread.
+    fn reset(&self) {
+        BACKOFF_STREAK.with(|streak| streak.set((0, 0)));
+    }
+
+    /// Wait for the next step of the streak, parking no later than `deadline`.
+    fn wait_until(&self, deadline: Option<Instant>) {
+        let step = self.next_step();
+        if step < self.spin_limit {
+            spin_loop();
+        } else if step - self.spin_limit < self.yield_limit {
+            std::thread::yield_now();
+        } else {
+            let duration = match deadline {
+                Some(deadline) => self
+                    .park_duration
+                    .min(deadline.saturating_duration_since(Instant::now())),
+                None => self.park_duration,
+            };
+            std::thread::park_timeout(duration);
+        }
+    }
+}
+
+impl ConsumerWaitStrategy for Backoff {
+    fn wait(&self) {
+        self.wait_until(None);
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        Backoff::wait_until(self, Some(deadline));
+    }
+
+    #[warn(unused)]
+    fn signal(&self) {
+        //no-op
+    }
+
+    fn reset(&self) {
+        Backoff::reset(self);
+    }
+}
+
+impl ProducerWaitStrategy for Backoff {
+    fn wait(&self) {
+        self.wait_until(None);
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        Backoff::wait_until(self, Some(deadline));
+    }
+
+    fn reset(&self) {
+        Backoff::reset(self);
+    }
+}
+
+/// Decides when producers wake a blocked consumer after publishing items.
+///
+/// Waking a parked [`Blocking`](ConsumerWaitStrategyKind::Blocking) consumer
+/// costs a syscall, which is wasted on a consumer that is woken for every
+/// tiny message when the application would rather have it handle larger
+/// batches. Items that do not trigger a wakeup stay in the buffer until a
+/// later publish does, the channel is closed, or the last sender is dropped,
+/// so a threshold trades latency for fewer wakeups. Consumers that do not
+/// block only see the policy as skipped signals.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum NotifyPolicy {
+    /// Wake the consumer after every publish.
+    #[default]
+    Always,
+    /// Wake the consumer once every `n` published items.
+    EveryN(usize),
+    /// Wake the consumer once at least `n` items wait in the buffer.
+    BacklogAtLeast(usize),
+}
+
+/// The [`NotifyPolicy`] of a channel, shared by its producers.
+struct Notifier {
+    kind: AtomicU8,
+    threshold: AtomicUsize,
+    unnotified: AtomicUsize,
+}
+
+/// [`NotifyPolicy::Always`].
+const NOTIFY_ALWAYS: u8 = 0;
+/// [`NotifyPolicy::EveryN`].
+const NOTIFY_EVERY_N: u8 = 1;
+/// [`NotifyPolicy::BacklogAtLeast`].
+const NOTIFY_BACKLOG_AT_LEAST: u8 = 2;
+
+impl Notifier {
+    fn new() -> Self {
+        Self {
+            kind: AtomicU8::new(NOTIFY_ALWAYS),
+            threshold: AtomicUsize::new(0),
+            unnotified: AtomicUsize::new(0),
+        }
+    }
+
+    fn set(&self, policy: NotifyPolicy) {
+        let (kind, threshold) = match policy {
+            NotifyPolicy::Always => (NOTIFY_ALWAYS, 0),
+            NotifyPolicy::EveryN(n) => (NOTIFY_EVERY_N, n),
+            NotifyPolicy::BacklogAtLeast(n) => (NOTIFY_BACKLOG_AT_LEAST, n),
+        };
+        self.threshold.store(threshold, Ordering::Relaxed);
+        self.unnotified.store(0, Ordering::Relaxed);
+        self.kind.store(kind, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> NotifyPolicy {
+        let threshold = self.threshold.load(Ordering::Relaxed);
+        match self.kind.load(Ordering::Relaxed) {
+            NOTIFY_EVERY_N => NotifyPolicy::EveryN(threshold),
+            NOTIFY_BACKLOG_AT_LEAST => NotifyPolicy::BacklogAtLeast(threshold),
+            _ => NotifyPolicy::Always,
+        }
+    }
+
+    /// Returns `true` if publishing `published` items should wake the consumer.
+    #[inline(always)]
+    fn should_notify(&self, published: usize, backlog: impl FnOnce() -> usize) -> bool {
+        match self.kind.load(Ordering::Relaxed) {
+            NOTIFY_ALWAYS => true,
+            NOTIFY_EVERY_N => {
+                let threshold = self.threshold.load(Ordering::Relaxed);
+                let unnotified =
+                    self.unnotified.fetch_add(published, Ordering::Relaxed) + published;
+                if unnotified < threshold {
+                    return false;
+                }
+                self.unnotified.fetch_sub(unnotified, Ordering::Relaxed);
+                true
+            }
+            _ => backlog() >= self.threshold.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Trait representing a consumer wait strategy.
-pub(crate) trait ConsumerWaitStrategy: Send + Sync {
+///
+/// Implement it to plug custom waiting logic, such as an eventfd or an
+/// instrumented wait, into channels created with one of the
+/// `*_with_strategies` constructors, like
+/// [`spsc_with_strategies`](crate::channels::spsc_with_strategies).
+pub trait ConsumerWaitStrategy: Send + Sync {
     /// Wait according to the strategy.
+    ///
+    /// Called by a consumer that found no items. Returning early is always
+    /// allowed; the consumer polls again.
     fn wait(&self);
 
+    /// Wait according to the strategy, but return no later than `deadline`.
+    ///
+    /// Strategies whose `wait` returns promptly can rely on the default, which
+    /// simply calls [`wait`](Self::wait).
+    fn wait_until(&self, _deadline: Instant) {
+        self.wait();
+    }
+
     /// Optionally wake up the consumer if it is blocked.
+    ///
+    /// Called by producers after publishing items, as the channel's
+    /// [`NotifyPolicy`] allows, and when the channel is closed or its last
+    /// sender is dropped.
     fn signal(&self);
+
+    /// Note that the consumer made progress after waiting.
+    ///
+    /// Strategies that escalate over consecutive waits start over.
+    fn reset(&self) {}
 }
 
 /// Spin-loop wait strategy for consumers.
 #[derive(Clone)]
-pub(crate) struct ConsumerSpinningStrategy {}
+pub(crate) struct ConsumerSpinningStrategy {
+    spins: usize,
+}
 
 impl ConsumerSpinningStrategy {
-    /// Create a new spinning strategy.
-    pub fn new() -> Self {
-        Self {}
+    /// Create a new spinning strategy issuing `spins` pause hints per wait.
+    pub fn new(spins: usize) -> Self {
+        Self { spins }
     }
 }
 
 impl ConsumerWaitStrategy for ConsumerSpinningStrategy {
     fn wait(&self) {
-        std::hint::spin_loop();
+        for _ in 0..self.spins {
+            spin_loop();
+        }
     }
 
     #[warn(unused)]
@@ -78,6 +348,11 @@ impl ConsumerWaitStrategy for ConsumerParkingStrategy {
         std::thread::park_timeout(self.duration);
     }
 
+    fn wait_until(&self, deadline: Instant) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        std::thread::park_timeout(self.duration.min(remaining));
+    }
+
     #[warn(unused)]
     fn signal(&self) {
         //no-op
@@ -106,58 +381,169 @@ impl ConsumerWaitStrategy for ConsumerYieldingStrategy {
     }
 }
 
-/// Blocking wait strategy for consumers using a condition variable.
+sync::thread_local! {
+    /// The wakeup the current thread last waited on, by address, and the
+    /// generation of that wakeup it saw when the wait returned. Not `const`,
+    /// which the thread locals of loom do not take.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static WAKEUP_SEEN: std::cell::Cell<(usize, usize)> = std::cell::Cell::new((0, 0));
+}
+
+/// Parks waiting threads until another thread signals, without taking a
+/// lock unless a thread is actually parked.
+///
+/// Every signal moves the generation on, and every thread remembers the
+/// generation it saw when its last wait returned, after which it checked its
+/// condition again. A thread that waits while the generation is still the one
+/// it saw parks until a signal moves it on, and one that waits after a signal
+/// returns at once, however many threads wait: no thread can take a signal
+/// meant for another. A signal that finds threads parked takes the lock of the
+/// parked threads and wakes all of them, so each one checks its condition again.
+///
+/// The first wait of a thread on a wakeup, or after it waited on another one,
+/// returns at once, which waits are always allowed to.
+#[derive(Default)]
+pub(crate) struct Wakeup {
+    /// The number of parked threads.
+    sleepers: AtomicUsize,
+    /// Counts the signals.
+    generation: AtomicUsize,
+    /// The parked threads, only touched when parking or waking one.
+    parked: sync::Mutex<Vec<sync::Thread>>,
+}
+
+impl Wakeup {
+    /// Park until a signal arrives or `deadline` passes, unless one arrived
+    /// since the last wait of the current thread returned.
+    pub(crate) fn wait(&self, deadline: Option<Instant>) {
+        let wakeup = self as *const Self as usize;
+        let generation = self.generation.load(Ordering::SeqCst);
+        if WAKEUP_SEEN.with(std::cell::Cell::get) == (wakeup, generation) {
+            let current = sync::current();
+            self.parked.lock().unwrap().push(current.clone());
+            // Announced before the generation is checked again, so a signal
+            // that moves it after the check sees the sleeper and wakes it.
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            while self.generation.load(Ordering::SeqCst) == generation {
+                match deadline {
+                    None => sync::park(),
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero() {
+                            break;
+                        }
+                        sync::park_timeout(remaining);
+                    }
+                }
+            }
+            self.sleepers.fetch_sub(1, Ordering::SeqCst);
+            let mut parked = self.parked.lock().unwrap();
+            if let Some(index) = parked.iter().position(|t| t.id() == current.id()) {
+                parked.swap_remove(index);
+            }
+        }
+        // Synchronizes with the signals counted, so the condition checked
+        // after the wait sees everything done before them.
+        let seen = self.generation.load(Ordering::SeqCst);
+        WAKEUP_SEEN.with(|cell| cell.set((wakeup, seen)));
+    }
+
+    /// Wake the parked threads, and the next wait of every other thread.
+    pub(crate) fn signal(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        // Read-modify-write rather than a load: either it sees a sleeper
+        // announced before it, or that sleeper's announcement reads from it
+        // and sees the generation moved on.
+        if self.sleepers.fetch_add(0, Ordering::SeqCst) > 0 {
+            for thread in self.parked.lock().unwrap().iter() {
+                thread.unpark();
+            }
+        }
+    }
+}
+
+/// Blocking wait strategy for consumers that park until a producer signals.
+///
+/// Producers signal after every publish the notify policy allows, which
+/// stays off locks while no consumer is parked, see [`Wakeup`].
 #[derive(Clone)]
 pub(crate) struct ConsumerBlockingStrategy {
-    state: Arc<(Condvar, Mutex<bool>)>,
+    wakeup: Arc<Wakeup>,
 }
 
 impl ConsumerBlockingStrategy {
     /// Create a new blocking strategy.
     pub fn new() -> Self {
         Self {
-            state: Arc::new((Condvar::new(), Mutex::new(false))),
+            wakeup: Arc::default(),
         }
     }
 }
 
 impl ConsumerWaitStrategy for ConsumerBlockingStrategy {
     fn wait(&self) {
-        let (condvar, mutex) = &*self.state;
-        let mut guard = mutex.lock().unwrap();
-        while !*guard {
-            guard = condvar.wait(guard).unwrap();
-        }
-        *guard = false;
+        self.wakeup.wait(None);
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        self.wakeup.wait(Some(deadline));
     }
 
     fn signal(&self) {
-        let (condvar, mutex) = &*self.state;
-        let mut guard = mutex.lock().unwrap();
-        *guard = true;
-        condvar.notify_all();
+        self.wakeup.signal();
     }
 }
 
 /// Trait representing a producer wait strategy.
-pub(crate) trait ProducerWaitStrategy: Send + Sync {
+///
+/// See [`ConsumerWaitStrategy`] for how to plug in a custom strategy.
+pub trait ProducerWaitStrategy: Send + Sync {
+    /// Wait according to the strategy.
+    ///
+    /// Called by a producer that found no free slots. Returning early is
+    /// always allowed; the producer checks again.
     fn wait(&self);
+
+    /// Wait according to the strategy, but return no later than `deadline`.
+    ///
+    /// Called by a producer held back by the rate limit of its sender.
+    /// Strategies whose `wait` returns promptly can rely on the default,
+    /// which simply calls [`wait`](Self::wait).
+    fn wait_until(&self, _deadline: Instant) {
+        self.wait();
+    }
+
+    /// Optionally wake up producers waiting for free slots.
+    ///
+    /// Called by consumers after they release slots, when a consumer of a
+    /// rendezvous channel starts waiting for an item, and when the channel is
+    /// closed or a receiver is dropped.
+    fn signal(&self) {}
+
+    /// Note that the producer made progress after waiting.
+    ///
+    /// Strategies that escalate over consecutive waits start over.
+    fn reset(&self) {}
 }
 
 /// Spin-loop wait strategy for producers.
 #[derive(Clone)]
-pub(crate) struct ProducerSpinningStrategy {}
+pub(crate) struct ProducerSpinningStrategy {
+    spins: usize,
+}
 
 impl ProducerSpinningStrategy {
-    /// Create a new spinning strategy.
-    pub fn new() -> Self {
-        Self {}
+    /// Create a new spinning strategy issuing `spins` pause hints per wait.
+    pub fn new(spins: usize) -> Self {
+        Self { spins }
     }
 }
 
 impl ProducerWaitStrategy for ProducerSpinningStrategy {
     fn wait(&self) {
-        std::hint::spin_loop();
+        for _ in 0..self.spins {
+            spin_loop();
+        }
     }
 }
 
@@ -178,6 +564,11 @@ impl ProducerWaitStrategy for ProducerParkingStrategy {
     fn wait(&self) {
         std::thread::park_timeout(self.duration);
     }
+
+    fn wait_until(&self, deadline: Instant) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        std::thread::park_timeout(self.duration.min(remaining));
+    }
 }
 
 /// Yielding wait strategy for producers.
@@ -197,47 +588,680 @@ impl ProducerWaitStrategy for ProducerYieldingStrategy {
     }
 }
 
+/// Blocking wait strategy for producers that park until a consumer frees slots.
+///
+/// Consumers signal after every batch they release, which stays off locks
+/// while no producer is parked, see [`Wakeup`].
+#[derive(Clone)]
+pub(crate) struct ProducerBlockingStrategy {
+    wakeup: Arc<Wakeup>,
+}
+
+impl ProducerBlockingStrategy {
+    /// Create a new blocking strategy.
+    pub fn new() -> Self {
+        Self {
+            wakeup: Arc::default(),
+        }
+    }
+}
+
+impl ProducerWaitStrategy for ProducerBlockingStrategy {
+    fn wait(&self) {
+        self.wakeup.wait(None);
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        self.wakeup.wait(Some(deadline));
+    }
+
+    fn signal(&self) {
+        self.wakeup.signal();
+    }
+}
+
+/// The channel accepts sends.
+const OPEN: u8 = 0;
+/// The channel was closed; sends fail and waiting producers give up.
+const CLOSED: u8 = 1;
+
 /// Coordinates producer and consumer wait strategies.
+///
+/// Also holds the lifecycle state shared by both halves of a channel: a small
+/// status word that the hot path reads, the optional reason the channel was
+/// closed with, which is only touched when closing or reporting, the number
+/// of live senders and receivers, the producer slots of channels with a
+/// bounded number of producers, the signals of the selectors watching the
+/// channel, whether a consumer of a rendezvous channel waits for an item, and
+/// the wakers of the tasks waiting on either side.
 pub(crate) struct Coordinator {
     cw: Box<dyn ConsumerWaitStrategy>,
     pw: Box<dyn ProducerWaitStrategy>,
+    state: AtomicU8,
+    reason: Mutex<Option<CloseReason>>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    producers: Option<ProducerRegistry>,
+    notifier: Notifier,
+    watchers: Mutex<Vec<Arc<Signal>>>,
+    watching: AtomicUsize,
+    tuning: Tuning,
+    /// Set while a receiver of a rendezvous channel waits for an item, or
+    /// `None` on other channels.
+    taker: Option<AtomicBool>,
+    #[cfg(feature = "futures")]
+    consumer_tasks: Wakers,
+    #[cfg(feature = "futures")]
+    producer_tasks: Wakers,
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
+    /// The wait strategies the coordinator was created with, or `None` for
+    /// custom strategies.
+    #[cfg(feature = "registry")]
+    waits: Option<(ProducerWaitStrategyKind, ConsumerWaitStrategyKind)>,
+    #[cfg(feature = "registry")]
+    registration: Option<Registration>,
 }
 
 impl Coordinator {
     /// Create a new coordinator with the specified producer and consumer wait strategies.
-    pub fn new(pw: ProducerWaitStrategyKind, cw: ConsumerWaitStrategyKind) -> Self {
+    ///
+    /// Spinning strategies issue `spin_budget` pause hints per wait. With
+    /// `max_producers`, the first producer is registered in slot `0`.
+    pub fn new(
+        pw: ProducerWaitStrategyKind,
+        cw: ConsumerWaitStrategyKind,
+        spin_budget: usize,
+        max_producers: Option<usize>,
+    ) -> Self {
+        #[cfg(feature = "registry")]
+        let waits = Some((pw, cw));
+        let spinning = matches!(cw, ConsumerWaitStrategyKind::Spinning);
         let cw: Box<dyn ConsumerWaitStrategy> = match cw {
-            ConsumerWaitStrategyKind::Spinning => Box::new(ConsumerSpinningStrategy::new()),
+            ConsumerWaitStrategyKind::Spinning => {
+                Box::new(ConsumerSpinningStrategy::new(spin_budget))
+            }
             ConsumerWaitStrategyKind::Parking(duration) => {
                 Box::new(ConsumerParkingStrategy::new(duration))
             }
             ConsumerWaitStrategyKind::Yielding => Box::new(ConsumerYieldingStrategy::new()),
             ConsumerWaitStrategyKind::Blocking => Box::new(ConsumerBlockingStrategy::new()),
+            ConsumerWaitStrategyKind::Backoff {
+                spin_limit,
+                yield_limit,
+                park_duration,
+            } => Box::new(Backoff {
+                spin_limit,
+                yield_limit,
+                park_duration,
+            }),
         };
 
         let pw: Box<dyn ProducerWaitStrategy> = match pw {
-            ProducerWaitStrategyKind::Spinning => Box::new(ProducerSpinningStrategy::new()),
+            ProducerWaitStrategyKind::Spinning => {
+                Box::new(ProducerSpinningStrategy::new(spin_budget))
+            }
             ProducerWaitStrategyKind::Parking(duration) => {
                 Box::new(ProducerParkingStrategy::new(duration))
             }
             ProducerWaitStrategyKind::Yielding => Box::new(ProducerYieldingStrategy::new()),
+            ProducerWaitStrategyKind::Blocking => Box::new(ProducerBlockingStrategy::new()),
+            ProducerWaitStrategyKind::Backoff {
+                spin_limit,
+                yield_limit,
+                park_duration,
+            } => Box::new(Backoff {
+                spin_limit,
+                yield_limit,
+                park_duration,
+            }),
         };
 
-        Self { cw, pw }
+        Self {
+            tuning: Tuning::new(spinning),
+            #[cfg(feature = "registry")]
+            waits,
+            ..Self::with_custom(pw, cw, max_producers)
+        }
+    }
+
+    /// Create a new coordinator with custom producer and consumer wait strategies.
+    ///
+    /// With `max_producers`, the first producer is registered in slot `0`.
+    pub fn with_custom(
+        pw: Box<dyn ProducerWaitStrategy>,
+        cw: Box<dyn ConsumerWaitStrategy>,
+        max_producers: Option<usize>,
+    ) -> Self {
+        Self {
+            cw,
+            pw,
+            state: AtomicU8::new(OPEN),
+            reason: Mutex::new(None),
+            senders: AtomicUsize::new(1),
+            receivers: AtomicUsize::new(1),
+            producers: max_producers.map(|max_producers| {
+                let registry = ProducerRegistry::new(max_producers);
+                registry.register();
+                registry
+            }),
+            notifier: Notifier::new(),
+            watchers: Mutex::new(Vec::new()),
+            watching: AtomicUsize::new(0),
+            tuning: Tuning::new(false),
+            taker: None,
+            #[cfg(feature = "futures")]
+            consumer_tasks: Wakers::default(),
+            #[cfg(feature = "futures")]
+            producer_tasks: Wakers::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Metrics::default(),
+            #[cfg(feature = "registry")]
+            waits: None,
+            #[cfg(feature = "registry")]
+            registration: None,
+        }
+    }
+
+    /// Pair producers with waiting consumers, see [`has_taker`](Self::has_taker).
+    pub fn with_rendezvous(mut self) -> Self {
+        self.taker = Some(AtomicBool::new(false));
+        self
+    }
+
+    /// Attach the entry of the channel in the registry.
+    #[cfg(feature = "registry")]
+    pub fn with_registration(mut self, registration: Registration) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    /// Returns the entry of the channel in the registry, if it was registered.
+    #[cfg(feature = "registry")]
+    pub fn registration(&self) -> Option<&Registration> {
+        self.registration.as_ref()
+    }
+
+    /// Returns the wait strategies the coordinator was created with, or
+    /// `None` for custom strategies.
+    #[cfg(feature = "registry")]
+    pub fn waits(&self) -> Option<(ProducerWaitStrategyKind, ConsumerWaitStrategyKind)> {
+        self.waits
+    }
+
+    /// Returns `true` if producers hand items off to waiting consumers.
+    #[inline(always)]
+    pub fn is_rendezvous(&self) -> bool {
+        self.taker.is_some()
+    }
+
+    /// Returns `true` if a consumer of a rendezvous channel ran out of items
+    /// and has not taken one since.
+    pub fn has_taker(&self) -> bool {
+        self.taker
+            .as_ref()
+            .is_some_and(|taker| taker.load(Ordering::Acquire))
+    }
+
+    /// Note that a consumer is waiting for an item, on rendezvous channels.
+    #[inline(always)]
+    fn taking(&self) {
+        if let Some(taker) = &self.taker {
+            taker.store(true, Ordering::Release);
+            self.signal_producers();
+        }
     }
 
     /// Wait according to the producer strategy.
     pub fn producer_wait(&self) {
+        #[cfg(feature = "metrics")]
+        self.metrics.producer_wait();
         self.pw.wait();
     }
 
+    /// Wait according to the producer strategy, returning no later than `deadline`.
+    pub fn producer_wait_until(&self, deadline: Instant) {
+        #[cfg(feature = "metrics")]
+        self.metrics.producer_wait();
+        self.pw.wait_until(deadline);
+    }
+
+    /// Note that a producer made progress after waiting.
+    pub fn producer_progress(&self) {
+        self.pw.reset();
+    }
+
+    /// Wake up producers that may be blocked waiting for free slots.
+    #[inline(always)]
+    pub fn wakeup_producers(&self) {
+        self.signal_producers();
+    }
+
+    /// Wake up a producer that may be blocked, and every task waiting for free slots.
+    #[inline(always)]
+    fn signal_producers(&self) {
+        self.pw.signal();
+        #[cfg(feature = "futures")]
+        self.producer_tasks.wake();
+    }
+
     /// Wait according to the consumer strategy.
     pub fn consumer_wait(&self) {
-        self.cw.wait();
+        #[cfg(feature = "metrics")]
+        self.metrics.consumer_idle();
+        self.taking();
+        self.tuning.idle();
+        if !self.tuning.wait(None) {
+            self.cw.wait();
+        }
+    }
+
+    /// Note that a consumer took a batch of `consumed` items, waking producers
+    /// waiting for the slots it released.
+    #[inline(always)]
+    pub fn consumer_progress(&self, _consumed: usize) {
+        #[cfg(feature = "metrics")]
+        self.metrics.consumed(_consumed);
+        if let Some(taker) = &self.taker {
+            taker.store(false, Ordering::Relaxed);
+        }
+        self.tuning.progress();
+        self.cw.reset();
+        self.signal_producers();
+    }
+
+    /// Wait according to the consumer strategy, returning no later than `deadline`.
+    pub fn consumer_wait_until(&self, deadline: Instant) {
+        #[cfg(feature = "metrics")]
+        self.metrics.consumer_idle();
+        self.taking();
+        self.tuning.idle();
+        if !self.tuning.wait(Some(deadline)) {
+            self.cw.wait_until(deadline);
+        }
+    }
+
+    /// Wake up a consumer that may be blocked after `published` items were
+    /// published, if the [`NotifyPolicy`] asks for it.
+    ///
+    /// `backlog` returns the number of items waiting in the buffer, and is only
+    /// called by [`NotifyPolicy::BacklogAtLeast`].
+    #[inline(always)]
+    pub fn notify_consumer(&self, published: usize, backlog: impl FnOnce() -> usize) {
+        #[cfg(feature = "metrics")]
+        self.metrics.published(published);
+        if self.notifier.should_notify(published, backlog) {
+            self.signal_consumers();
+        }
+    }
+
+    /// Returns the escalation state of the consumers, see [`crate::autotune`].
+    pub fn tuning(&self) -> &Tuning {
+        &self.tuning
+    }
+
+    /// Returns a snapshot of the channel's counters.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Wake up blocked consumers without publishing, so that they notice a
+    /// request to stop, a flow controller admitting items again or a lease
+    /// moved to a standby.
+    pub fn wake_consumers(&self) {
+        self.signal_consumers();
     }
 
-    /// Wake up a consumer that may be blocked.
-    pub fn wakeup_consumer(&self) {
+    /// Wake up a consumer that may be blocked, every selector watching the
+    /// channel, and every task waiting for items.
+    #[inline(always)]
+    fn signal_consumers(&self) {
         self.cw.signal();
+        if self.watching.load(Ordering::Relaxed) > 0 {
+            for signal in self.watchers.lock().unwrap().iter() {
+                signal.notify();
+            }
+        }
+        #[cfg(feature = "futures")]
+        self.consumer_tasks.wake();
+    }
+
+    /// Wake the task of `waker` once items may have arrived or the channel
+    /// finished, noting that a consumer waits for an item.
+    #[cfg(feature = "futures")]
+    pub fn register_consumer(&self, waker: &Waker) {
+        self.consumer_tasks.register(waker);
+        self.taking();
+    }
+
+    /// Wake the task of `waker` once slots may have been freed or the channel closed.
+    #[cfg(feature = "futures")]
+    pub fn register_producer(&self, waker: &Waker) {
+        self.producer_tasks.register(waker);
+    }
+
+    /// Signal `signal` whenever the consumer is woken up.
+    pub fn watch(&self, signal: Arc<Signal>) {
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.push(signal);
+        self.watching.store(watchers.len(), Ordering::Relaxed);
+    }
+
+    /// Stop signaling `signal`, registered with [`watch`](Self::watch).
+    pub fn unwatch(&self, signal: &Arc<Signal>) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(index) = watchers.iter().position(|w| Arc::ptr_eq(w, signal)) {
+            watchers.swap_remove(index);
+        }
+        self.watching.store(watchers.len(), Ordering::Relaxed);
+    }
+
+    /// Set the policy deciding when producers wake the consumer.
+    pub fn set_notify_policy(&self, policy: NotifyPolicy) {
+        self.notifier.set(policy);
+    }
+
+    /// Returns the policy deciding when producers wake the consumer.
+    pub fn notify_policy(&self) -> NotifyPolicy {
+        self.notifier.get()
+    }
+
+    /// Close the channel, recording an optional reason.
+    ///
+    /// Returns `false` if the channel was already closed, in which case the
+    /// first reason is kept.
+    pub fn close(&self, reason: Option<CloseReason>) -> bool {
+        let mut guard = self.reason.lock().unwrap();
+        if self.state.load(Ordering::Relaxed) == CLOSED {
+            return false;
+        }
+        *guard = reason;
+        self.state.store(CLOSED, Ordering::Release);
+        drop(guard);
+        self.signal_consumers();
+        self.signal_producers();
+        true
+    }
+
+    /// Returns `true` once the channel has been closed.
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == CLOSED
+    }
+
+    /// Returns the reason the channel was closed with, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.reason.lock().unwrap().clone()
+    }
+
+    /// Register a new sender.
+    pub fn add_sender(&self) {
+        self.senders.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Unregister a sender, waking the consumer once the last one is gone.
+    pub fn remove_sender(&self) {
+        if self.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.signal_consumers();
+        }
+    }
+
+    /// Returns `true` once every sender has been dropped.
+    ///
+    /// Everything published before the last sender was dropped is visible to a
+    /// consumer that observed this returning `true`.
+    #[inline(always)]
+    pub fn is_disconnected(&self) -> bool {
+        self.senders.load(Ordering::Acquire) == 0
+    }
+
+    /// Returns the number of live senders.
+    pub fn sender_count(&self) -> usize {
+        self.senders.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of live receivers.
+    pub fn receiver_count(&self) -> usize {
+        self.receivers.load(Ordering::Acquire)
+    }
+
+    /// Returns the producer slots, if the number of producers is bounded.
+    #[inline(always)]
+    pub fn producers(&self) -> Option<&ProducerRegistry> {
+        self.producers.as_ref()
+    }
+
+    /// Returns `true` once no more items can arrive: the channel is closed or
+    /// every sender has been dropped.
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        self.is_disconnected() || self.is_closed()
+    }
+
+    /// Register a new receiver.
+    pub fn add_receiver(&self) {
+        self.receivers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Unregister a receiver, closing the channel once the last one is gone,
+    /// and waking producers that the receiver may have gated otherwise.
+    pub fn remove_receiver(&self) {
+        if self.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.close(None);
+        } else {
+            self.signal_producers();
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvState, spsc_with_strategies};
+    #[cfg(feature = "mp")]
+    use crate::channels::{mpsc, spsc};
+    use crate::coordinator::{
+        Backoff, ConsumerBlockingStrategy, ConsumerWaitStrategy, ConsumerWaitStrategyKind,
+        Coordinator, NotifyPolicy, ProducerWaitStrategy, ProducerWaitStrategyKind,
+    };
+    use crate::sync::{AtomicUsize, Ordering};
+    #[cfg(feature = "mp")]
+    use std::cell::RefCell;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Returns `true` if the blocked consumer was signaled, waiting at most `timeout`.
+    fn signaled(coordinator: &Coordinator, timeout: Duration) -> bool {
+        let start = Instant::now();
+        coordinator.consumer_wait_until(start + timeout);
+        start.elapsed() < timeout
+    }
+
+    #[test]
+    fn test_every_n_policy_wakes_the_consumer_once_per_n_items() {
+        let coordinator = Coordinator::new(
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Blocking,
+            0,
+            None,
+        );
+        coordinator.set_notify_policy(NotifyPolicy::EveryN(3));
+        assert_eq!(coordinator.notify_policy(), NotifyPolicy::EveryN(3));
+        // The first wait of a thread returns at once.
+        coordinator.consumer_wait_until(Instant::now());
+
+        coordinator.notify_consumer(1, || 0);
+        coordinator.notify_consumer(1, || 0);
+        assert!(!signaled(&coordinator, Duration::from_millis(20)));
+        coordinator.notify_consumer(1, || 0);
+        assert!(signaled(&coordinator, Duration::from_secs(10)));
+
+        coordinator.set_notify_policy(NotifyPolicy::BacklogAtLeast(8));
+        coordinator.notify_consumer(4, || 4);
+        assert!(!signaled(&coordinator, Duration::from_millis(20)));
+        coordinator.notify_consumer(4, || 8);
+        assert!(signaled(&coordinator, Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_backoff_parks_only_after_spinning_and_yielding() {
+        let backoff = Backoff {
+            spin_limit: 2,
+            yield_limit: 1,
+            park_duration: Duration::from_millis(20),
+        };
+        let timed = |waits: usize| {
+            let start = Instant::now();
+            (0..waits).for_each(|_| ConsumerWaitStrategy::wait(&backoff));
+            start.elapsed()
+        };
+
+        assert!(timed(3) < Duration::from_millis(20));
+        assert!(timed(1) >= Duration::from_millis(10));
+        ConsumerWaitStrategy::reset(&backoff);
+        assert!(timed(3) < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_blocking_consumers_park_until_signaled() {
+        let strategy = ConsumerBlockingStrategy::new();
+        strategy.signal();
+        strategy.signal();
+        strategy.wait();
+        let start = Instant::now();
+        strategy.wait_until(start + Duration::from_millis(20));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let sleepers = |n| {
+            while strategy.wakeup.sleepers.load(Ordering::SeqCst) != n {
+                std::thread::yield_now();
+            }
+        };
+        let consumers: Vec<_> = (0..2)
+            .map(|_| {
+                let strategy = strategy.clone();
+                // The first wait of a thread returns at once, the second parks.
+                std::thread::spawn(move || {
+                    strategy.wait();
+                    strategy.wait();
+                })
+            })
+            .collect();
+        sleepers(2);
+        strategy.signal();
+        sleepers(0);
+        consumers
+            .into_iter()
+            .for_each(|consumer| consumer.join().unwrap());
+        assert!(strategy.wakeup.parked.lock().unwrap().is_empty());
+    }
+
+    /// Counts the waits and signals of a channel.
+    #[derive(Clone, Default)]
+    struct CountingStrategy {
+        waits: Arc<AtomicUsize>,
+        signals: Arc<AtomicUsize>,
+    }
+
+    impl ConsumerWaitStrategy for CountingStrategy {
+        fn wait(&self) {
+            self.waits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn signal(&self) {
+            self.signals.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    impl ProducerWaitStrategy for CountingStrategy {
+        fn wait(&self) {
+            self.waits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_custom_wait_strategies_are_used() {
+        let producer = CountingStrategy::default();
+        let consumer = CountingStrategy::default();
+        let (tx, rx) =
+            spsc_with_strategies::<u32>(2, Box::new(producer.clone()), Box::new(consumer.clone()));
+
+        tx.send_n([1, 2]).unwrap();
+        assert_eq!(consumer.signals.load(Ordering::Relaxed), 1);
+        let sender = std::thread::spawn(move || tx.send(3).unwrap());
+        while producer.waits.load(Ordering::Relaxed) == 0 {
+            std::thread::yield_now();
+        }
+
+        assert_eq!(rx.recv(2, &drop), RecvState::Received);
+        sender.join().unwrap();
+        // Both sends signal, and so does the sender disconnecting.
+        assert_eq!(consumer.signals.load(Ordering::Relaxed), 3);
+        assert_eq!(rx.recv(2, &drop), RecvState::Received);
+        assert_eq!(rx.recv(2, &drop), RecvState::Disconnected);
+        assert_eq!(consumer.waits.load(Ordering::Relaxed), 0);
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn test_blocking_producers_are_woken_by_consumers() {
+        let (tx, rx) = mpsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let producers: Vec<_> = (0..3)
+            .map(|index| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for value in 0..300 {
+                        tx.send(index * 1000 + value).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let received = RefCell::new(Vec::new());
+        let mut rounds = 0;
+        loop {
+            rounds += 1;
+            let state = match rounds % 3 {
+                0 => rx
+                    .recv(2, &|value| received.borrow_mut().push(value))
+                    .state(),
+                1 => {
+                    received.borrow_mut().extend(rx.try_iter().take(3));
+                    RecvState::Received
+                }
+                _ => {
+                    received.borrow_mut().extend(rx.drain_all());
+                    RecvState::Received
+                }
+            };
+            if state == RecvState::Disconnected {
+                break;
+            }
+        }
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+        let mut received = received.into_inner();
+        received.sort_unstable();
+        let expected: Vec<u32> = (0..3)
+            .flat_map(|index| index * 1000..index * 1000 + 300)
+            .collect();
+        assert_eq!(received, expected);
+
+        let (tx, rx) = spsc::<u32>(
+            2,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send_n(0..2).unwrap();
+        let blocked = std::thread::spawn(move || tx.send(2));
+        std::thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert!(blocked.join().unwrap().is_err());
     }
 }
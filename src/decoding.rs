@@ -0,0 +1,220 @@
+//! Typed receiving over untrusted bytes.
+//!
+//! Producers send raw frames into an ordinary byte channel. A
+//! [`DecodingReceiver`] turns each frame into a typed value with a pluggable
+//! [`Decoder`] directly in the poll loop, so ingestion from a network source
+//! does not need a second queue between decoding and processing. Frames that
+//! fail to decode or validate, or that make the decoder panic, are handed to a
+//! dead-letter callback together with the reason instead of reaching the handler.
+
+use crate::channels::{PollOutcome, Receiver};
+use crate::errors::DecodeError;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Turns a frame of bytes into a value and checks it before it is delivered.
+pub trait Decoder<T>: Send + Sync {
+    /// Deserialize a value from `frame`.
+    ///
+    /// # Errors
+    /// Returns [`DecodeError::Malformed`] if `frame` is not a valid encoding.
+    fn decode(&self, frame: &[u8]) -> Result<T, DecodeError>;
+
+    /// Check a decoded value before it is handed to the handler.
+    ///
+    /// Accepts every value by default.
+    ///
+    /// # Errors
+    /// Returns [`DecodeError::Invalid`] if `value` must not be processed.
+    fn validate(&self, _value: &T) -> Result<(), DecodeError> {
+        Ok(())
+    }
+}
+
+impl<T, F> Decoder<T> for F
+where
+    F: Fn(&[u8]) -> Result<T, DecodeError> + Send + Sync,
+{
+    fn decode(&self, frame: &[u8]) -> Result<T, DecodeError> {
+        self(frame)
+    }
+}
+
+/// Deserializes frames encoded with bincode, such as those sent by
+/// [`ByteSender::send`](crate::framed::ByteSender::send).
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned> Decoder<T> for Bincode {
+    fn decode(&self, frame: &[u8]) -> Result<T, DecodeError> {
+        bincode::deserialize(frame).map_err(|error| DecodeError::Malformed(error.into()))
+    }
+}
+
+/// A frame that was rejected, with the reason.
+#[derive(Debug)]
+pub struct DeadLetter {
+    frame: Vec<u8>,
+    error: DecodeError,
+}
+
+impl DeadLetter {
+    /// Returns the rejected frame.
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    /// Returns why the frame was rejected.
+    pub fn error(&self) -> &DecodeError {
+        &self.error
+    }
+
+    /// Take back the rejected frame and the reason.
+    pub fn into_parts(self) -> (Vec<u8>, DecodeError) {
+        (self.frame, self.error)
+    }
+}
+
+/// A receiver of raw frames that delivers decoded, validated values.
+pub struct DecodingReceiver<T, D: Decoder<T>> {
+    receiver: Receiver<Vec<u8>>,
+    decoder: D,
+    dead_letter: Box<dyn Fn(DeadLetter) + Send + Sync>,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, D: Decoder<T>> DecodingReceiver<T, D> {
+    /// Decode the frames arriving on `receiver` with `decoder`, passing rejected
+    /// frames to `dead_letter`.
+    pub fn new<L>(receiver: Receiver<Vec<u8>>, decoder: D, dead_letter: L) -> Self
+    where
+        L: Fn(DeadLetter) + Send + Sync + 'static,
+    {
+        Self {
+            receiver,
+            decoder,
+            dead_letter: Box::new(dead_letter),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Decode and validate a single frame, catching decoder panics.
+    fn decode(&self, frame: &[u8]) -> Result<T, DecodeError> {
+        let decoder = &self.decoder;
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let value = decoder.decode(frame)?;
+            decoder.validate(&value)?;
+            Ok(value)
+        }))
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(DecodeError::Panicked(message))
+        })
+    }
+
+    /// Attempt to receive up to `batch_size` frames.
+    ///
+    /// Invokes `handler` for every frame that decodes and validates, and the
+    /// dead-letter callback for every other frame. Waits like
+    /// [`Receiver::recv`] if no frame is available.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        self.receiver
+            .recv(batch_size, &|frame: Vec<u8>| self.deliver(frame, handler))
+    }
+
+    /// Continuously attempt to receive frames until at least one batch is processed.
+    ///
+    /// See [`Receiver::blocking_recv`].
+    pub fn blocking_recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        self.receiver
+            .blocking_recv(batch_size, &|frame: Vec<u8>| self.deliver(frame, handler))
+    }
+
+    /// Hand a decoded frame to `handler`, or the frame to the dead-letter callback.
+    fn deliver<H>(&self, frame: Vec<u8>, handler: &H)
+    where
+        H: Fn(T),
+    {
+        match self.decode(&frame) {
+            Ok(value) => handler(value),
+            Err(error) => (self.dead_letter)(DeadLetter { frame, error }),
+        }
+    }
+
+    /// Returns the underlying receiver of raw frames.
+    pub fn get_ref(&self) -> &Receiver<Vec<u8>> {
+        &self.receiver
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::decoding::{DeadLetter, Decoder, DecodingReceiver};
+    use crate::errors::DecodeError;
+    use crate::testing::yielding_spsc;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    /// Decodes big-endian `u16`s, rejecting zero and panicking on `u16::MAX`.
+    struct NonZero;
+
+    impl Decoder<u16> for NonZero {
+        fn decode(&self, frame: &[u8]) -> Result<u16, DecodeError> {
+            let bytes = frame
+                .try_into()
+                .map_err(|error| DecodeError::Malformed(Box::new(error)))?;
+            match u16::from_be_bytes(bytes) {
+                u16::MAX => panic!("reserved value"),
+                value => Ok(value),
+            }
+        }
+
+        fn validate(&self, value: &u16) -> Result<(), DecodeError> {
+            match value {
+                0 => Err(DecodeError::Invalid("zero".into())),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejected_frames_reach_the_dead_letter_callback() {
+        let (tx, rx) = yielding_spsc::<Vec<u8>>(8);
+        let letters = Arc::new(Mutex::new(Vec::new()));
+        let dead_letters = letters.clone();
+        let rx = DecodingReceiver::new(rx, NonZero, move |letter: DeadLetter| {
+            dead_letters.lock().unwrap().push(letter.into_parts());
+        });
+        let frames = [
+            vec![0, 1],
+            vec![1],
+            vec![0, 0],
+            vec![0xff, 0xff],
+            vec![0, 2],
+        ];
+        tx.send_n(frames).unwrap();
+
+        let values = RefCell::new(Vec::new());
+        rx.recv(8, &|value| values.borrow_mut().push(value));
+        assert_eq!(values.into_inner(), [1, 2]);
+
+        let letters = letters.lock().unwrap();
+        assert_eq!(letters.len(), 3);
+        assert!(matches!(&letters[0], (frame, DecodeError::Malformed(_)) if *frame == [1]));
+        assert!(matches!(&letters[1], (frame, DecodeError::Invalid(_)) if *frame == [0, 0]));
+        let (frame, error) = &letters[2];
+        assert_eq!(*frame, [0xff, 0xff]);
+        assert!(matches!(error, DecodeError::Panicked(message) if message == "reserved value"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Error types returned by the channel API.
+//!
+//! Errors that fail to deliver a value always hand that value back to the
+//! caller, so nothing is lost when a send does not go through.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// The reason a consumer gave when closing a channel.
+///
+/// Shared between every producer that observes the closed channel.
+pub type CloseReason = Arc<dyn Error + Send + Sync>;
+
+/// An error returned from a blocking send on a [`Sender`](crate::channels::Sender).
+///
+/// The value that could not be sent is carried inside the error and can be
+/// recovered with [`into_inner`](Self::into_inner).
+pub enum SendError<T> {
+    /// The channel was closed; carries the value and the consumer's reason, if any.
+    Closed(T, Option<CloseReason>),
+}
+
+impl<T> SendError<T> {
+    /// Take back the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Closed(value, _) => value,
+        }
+    }
+
+    /// Returns the reason the channel was closed with, if one was given.
+    pub fn reason(&self) -> Option<&CloseReason> {
+        match self {
+            SendError::Closed(_, reason) => reason.as_ref(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Closed(_, reason) => f.debug_tuple("Closed").field(reason).finish(),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason() {
+            Some(reason) => write!(f, "sending on a closed channel: {reason}"),
+            None => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.reason()
+            .map(|reason| &**reason as &(dyn Error + 'static))
+    }
+}
+
+/// An error returned from a non-blocking send on a [`Sender`](crate::channels::Sender).
+///
+/// The value that could not be sent is carried inside the error and can be
+/// recovered with [`into_inner`](Self::into_inner).
+pub enum TrySendError<T> {
+    /// The ring buffer has no free slot for the value.
+    Full(T),
+    /// The claim lost the race against other producers more times than
+    /// allowed, or the rate limit of the sender admits no more items for now.
+    WouldBlock(T),
+    /// The channel was closed; carries the value and the consumer's reason, if any.
+    Closed(T, Option<CloseReason>),
+}
+
+impl<T> TrySendError<T> {
+    /// Take back the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value)
+            | TrySendError::WouldBlock(value)
+            | TrySendError::Closed(value, _) => value,
+        }
+    }
+
+    /// Returns `true` if the send failed because the buffer was full.
+    pub fn is_full(&self) -> bool {
+        matches!(self, TrySendError::Full(_))
+    }
+
+    /// Returns `true` if the send failed because of contention between
+    /// producers or because of the rate limit of the sender.
+    pub fn is_would_block(&self) -> bool {
+        matches!(self, TrySendError::WouldBlock(_))
+    }
+
+    /// Returns `true` if the send failed because the channel was closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TrySendError::Closed(..))
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::WouldBlock(_) => f.write_str("WouldBlock(..)"),
+            TrySendError::Closed(_, reason) => f.debug_tuple("Closed").field(reason).finish(),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("sending on a full channel"),
+            TrySendError::WouldBlock(_) => {
+                f.write_str("sending on a contended channel exceeded its retry budget")
+            }
+            TrySendError::Closed(_, Some(reason)) => {
+                write!(f, "sending on a closed channel: {reason}")
+            }
+            TrySendError::Closed(_, None) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+/// A boxed error produced by a [`Decoder`](crate::decoding::Decoder).
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
+/// Why a frame received on a decoding channel was rejected.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The frame could not be deserialized.
+    Malformed(BoxedError),
+    /// The frame was deserialized but the value failed validation.
+    Invalid(BoxedError),
+    /// The decoder panicked on the frame; carries the panic message, if any.
+    Panicked(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed(error) => write!(f, "malformed frame: {error}"),
+            DecodeError::Invalid(error) => write!(f, "invalid frame: {error}"),
+            DecodeError::Panicked(message) => write!(f, "decoder panicked: {message}"),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Malformed(error) | DecodeError::Invalid(error) => {
+                Some(&**error as &(dyn Error + 'static))
+            }
+            DecodeError::Panicked(_) => None,
+        }
+    }
+}
+
+/// Why a frame could not be sent on a [`ByteSender`](crate::framed::ByteSender).
+#[derive(Debug)]
+pub enum FrameError {
+    /// The frame is longer than the longest frame the channel can hold.
+    TooLarge {
+        /// The length of the frame in bytes.
+        len: usize,
+        /// The length of the longest frame the channel can hold.
+        max: usize,
+    },
+    /// The channel has no room for the frame right now.
+    Full,
+    /// The value could not be serialized into a frame.
+    Encode(BoxedError),
+    /// The channel was closed; carries the consumer's reason, if any.
+    Closed(Option<CloseReason>),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooLarge { len, max } => {
+                write!(f, "frame of {len} bytes exceeds the maximum of {max} bytes")
+            }
+            FrameError::Full => f.write_str("no room for the frame in the channel"),
+            FrameError::Encode(error) => write!(f, "failed to encode the frame: {error}"),
+            FrameError::Closed(Some(reason)) => write!(f, "sending on a closed channel: {reason}"),
+            FrameError::Closed(None) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl Error for FrameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FrameError::Encode(error) => Some(&**error as &(dyn Error + 'static)),
+            FrameError::Closed(reason) => reason
+                .as_ref()
+                .map(|reason| &**reason as &(dyn Error + 'static)),
+            FrameError::TooLarge { .. } | FrameError::Full => None,
+        }
+    }
+}
+
+/// An error returned when a transform asks for more scratch memory than is available.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScratchExhausted {
+    /// The number of bytes requested.
+    pub requested: usize,
+    /// The capacity of the scratch memory.
+    pub capacity: usize,
+}
+
+impl fmt::Display for ScratchExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "requested {} bytes of scratch memory, but only {} are available",
+            self.requested, self.capacity
+        )
+    }
+}
+
+impl Error for ScratchExhausted {}
+
+/// The reason a channel is closed with once producers used up its sequences.
+///
+/// Sequences are never reused, and a channel that published `i64::MAX / 2`
+/// items, which takes over a thousand years at a billion items per second,
+/// closes instead of letting them overflow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SequencesExhausted;
+
+impl fmt::Display for SequencesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the channel used up its sequence space")
+    }
+}
+
+impl Error for SequencesExhausted {}
+
+/// The reason a channel is closed with once a
+/// [`ProducerHandle`](crate::channels::ProducerHandle) was dropped with
+/// claimed sequences it could not hand back.
+///
+/// Consumers can never get past sequences that are claimed but not
+/// published, so the channel is closed instead of stalling for good.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SequencesAbandoned;
+
+impl fmt::Display for SequencesAbandoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a producer abandoned sequences it claimed")
+    }
+}
+
+impl Error for SequencesAbandoned {}
+
+/// The reason a channel is closed with once a handler failed under
+/// [`ErrorPolicy::Halt`](crate::channels::ErrorPolicy::Halt), or panicked
+/// under [`PanicPolicy::Poison`](crate::channels::PanicPolicy::Poison).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChannelPoisoned {
+    /// The sequence of the item the handler failed on.
+    pub sequence: i64,
+}
+
+impl fmt::Display for ChannelPoisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a handler failed on sequence {}", self.sequence)
+    }
+}
+
+impl Error for ChannelPoisoned {}
+
+/// An error returned from [`Receiver::recv_fallible`](crate::channels::Receiver::recv_fallible)
+/// when the handler failed on an item.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HandlerFailed<E> {
+    /// The sequence of the item the handler failed on.
+    pub sequence: i64,
+    /// The error the handler returned.
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for HandlerFailed<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "handler failed on sequence {}: {}",
+            self.sequence, self.error
+        )
+    }
+}
+
+impl<E: Error + 'static> Error for HandlerFailed<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error returned from [`rebase`](crate::channels::rebase).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RebaseError {
+    /// The sender and the receiver belong to different channels.
+    ForeignChannel,
+    /// Other senders or receivers of the channel are still alive.
+    Shared,
+    /// Published items have not been consumed yet.
+    NotDrained,
+}
+
+impl fmt::Display for RebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebaseError::ForeignChannel => {
+                write!(f, "sender and receiver belong to different channels")
+            }
+            RebaseError::Shared => write!(f, "other senders or receivers are still alive"),
+            RebaseError::NotDrained => write!(f, "published items have not been consumed yet"),
+        }
+    }
+}
+
+impl Error for RebaseError {}
@@ -0,0 +1,266 @@
+//! Receivers that standbys can take over from without losing items.
+
+use crate::channels::{PollOutcome, Receiver, RecvResult, RecvState};
+use crate::sync::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+impl<T> Receiver<T> {
+    /// Make this receiver the active one of a [`FailoverReceiver`] that
+    /// [`Standby`] receivers can take over from.
+    ///
+    /// The standbys share the poller of this receiver, including its gating
+    /// sequence, whatever the topology of the channel.
+    pub fn into_failover(self) -> FailoverReceiver<T> {
+        let lease = Lease {
+            holder: AtomicU64::new(0),
+            receiving: AtomicUsize::new(0),
+            handover: Mutex::new(()),
+            next_id: AtomicU64::new(1),
+        };
+        FailoverReceiver {
+            receiver: self,
+            lease: Arc::new(lease),
+            id: 0,
+        }
+    }
+}
+
+/// The id of the [`FailoverReceiver`] allowed to consume, shared with its standbys.
+struct Lease {
+    holder: AtomicU64,
+    /// The number of receives checking or holding the lease.
+    receiving: AtomicUsize,
+    /// Serializes activations.
+    handover: Mutex<()>,
+    next_id: AtomicU64,
+}
+
+impl Lease {
+    /// Start a receive of the receiver `id`, or return `None` if it does not
+    /// hold the lease.
+    fn enter(&self, id: u64) -> Option<Receiving<'_>> {
+        // Paired with the swap and load of `hand_over`: either the activation
+        // waits for this receive, or this receive sees the new holder.
+        self.receiving.fetch_add(1, Ordering::SeqCst);
+        let receiving = Receiving(self);
+        (self.holder.load(Ordering::SeqCst) == id).then_some(receiving)
+    }
+
+    /// Move the lease to the receiver `id` once the receive in progress is done.
+    fn hand_over(&self, id: u64) {
+        // A handler that panicked during an activation left the lease consistent.
+        let _handover = self.handover.lock().unwrap_or_else(PoisonError::into_inner);
+        self.holder.swap(id, Ordering::SeqCst);
+        while self.receiving.load(Ordering::SeqCst) > 0 {
+            thread::yield_now();
+        }
+    }
+
+    /// Returns `true` if the receiver `id` holds the lease.
+    fn is_held_by(&self, id: u64) -> bool {
+        self.holder.load(Ordering::Acquire) == id
+    }
+}
+
+/// A receive counted by [`Lease::enter`] until it is dropped, even by a
+/// panicking handler.
+struct Receiving<'a>(&'a Lease);
+
+impl Drop for Receiving<'_> {
+    fn drop(&mut self) {
+        self.0.receiving.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// A receiver that [`Standby`] receivers can take over from.
+///
+/// The receiver and its standbys share the poller, and so the gating sequence,
+/// of the receiver it was made from, and only the one holding the lease
+/// consumes. [`Standby::activate`] moves the lease once the receive in
+/// progress, handler included, is done, so the standby carries on with the
+/// first item the previous holder did not finish, and no item is lost or
+/// received twice.
+///
+/// Created by [`Receiver::into_failover`].
+pub struct FailoverReceiver<T> {
+    receiver: Receiver<T>,
+    lease: Arc<Lease>,
+    id: u64,
+}
+
+impl<T> FailoverReceiver<T> {
+    /// Attempt to receive up to `batch_size` items, see [`Receiver::recv`].
+    ///
+    /// Waits according to the consumer wait strategy without holding the
+    /// lease if no item is available, so an idle receiver never holds up an
+    /// activation. Returns [`RecvState::Disconnected`] without receiving once
+    /// a standby has taken over.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        let (state, processed) = match self.try_recv_batch(batch_size, handler) {
+            RecvResult::Processed(processed) => (RecvState::Received, processed),
+            RecvResult::Disconnected => (RecvState::Disconnected, 0),
+            RecvResult::Empty => {
+                self.receiver.coordinator().consumer_wait();
+                (RecvState::Empty, 0)
+            }
+        };
+        let remaining_hint = match state {
+            RecvState::Received => self.receiver.len(),
+            _ => 0,
+        };
+        PollOutcome {
+            state,
+            processed,
+            remaining_hint,
+        }
+    }
+
+    /// Receive up to `batch_size` items without ever waiting, see
+    /// [`Receiver::try_recv_batch`].
+    ///
+    /// Returns [`RecvResult::Disconnected`] once a standby has taken over.
+    pub fn try_recv_batch<H>(&self, batch_size: usize, handler: &H) -> RecvResult
+    where
+        H: Fn(T),
+    {
+        match self.lease.enter(self.id) {
+            Some(_receiving) => self.receiver.try_recv_batch(batch_size, handler),
+            None => RecvResult::Disconnected,
+        }
+    }
+
+    /// Register a standby that can take over from this receiver, or from
+    /// whichever receiver of the lease holds it when the standby is activated.
+    pub fn standby(&self) -> Standby<T> {
+        let id = self.lease.next_id.fetch_add(1, Ordering::Relaxed);
+        Standby {
+            receiver: self.receiver.subscribed(None),
+            lease: self.lease.clone(),
+            id,
+        }
+    }
+
+    /// Returns `true` if no standby has taken over from this receiver.
+    pub fn is_active(&self) -> bool {
+        self.lease.is_held_by(self.id)
+    }
+
+    /// Returns the logical position of the last item received through the
+    /// shared gating sequence, see [`Receiver::last_consumed`].
+    pub fn last_consumed(&self) -> Option<u64> {
+        self.receiver.last_consumed()
+    }
+
+    /// Close the channel, see [`Receiver::close`].
+    pub fn close(&self) -> bool {
+        self.receiver.close()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+}
+
+/// A receiver that does not consume until it is activated, created by
+/// [`FailoverReceiver::standby`].
+///
+/// Counts as a receiver of the channel while it waits, so the channel stays
+/// open when the active receiver is dropped.
+pub struct Standby<T> {
+    receiver: Receiver<T>,
+    lease: Arc<Lease>,
+    id: u64,
+}
+
+impl<T> Standby<T> {
+    /// Take over consuming from the receiver that holds the lease.
+    ///
+    /// Waits for a receive of that receiver in progress to finish, after which
+    /// every receive it attempts reports a disconnect, and wakes it if it is
+    /// blocked waiting for items. The returned receiver starts at the first
+    /// item not received yet.
+    pub fn activate(self) -> FailoverReceiver<T> {
+        self.lease.hand_over(self.id);
+        self.receiver.coordinator().wake_consumers();
+        FailoverReceiver {
+            receiver: self.receiver,
+            lease: self.lease,
+            id: self.id,
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvResult, RecvState, spsc};
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::testing::yielding_spsc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_standby_takes_over_without_losing_or_repeating_items() {
+        let (tx, rx) = yielding_spsc::<u32>(16);
+        let primary = rx.into_failover();
+        let standby = primary.standby();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handled = received.clone();
+        let consumer = thread::spawn(move || {
+            while primary.recv(4, &|value| handled.lock().unwrap().push(value))
+                != RecvState::Disconnected
+            {}
+            primary
+        });
+
+        for value in 0..500 {
+            tx.send(value).unwrap();
+        }
+        let active = standby.activate();
+        let primary = consumer.join().unwrap();
+        assert!(!primary.is_active() && active.is_active());
+        let producer = thread::spawn(move || {
+            for value in 500..1000 {
+                tx.send(value).unwrap();
+            }
+        });
+        while active.recv(16, &|value| received.lock().unwrap().push(value))
+            != RecvState::Disconnected
+        {}
+        producer.join().unwrap();
+        assert!(received.lock().unwrap().iter().copied().eq(0..1000));
+        assert_eq!(active.last_consumed(), Some(999));
+        assert_eq!(primary.recv(4, &|_| {}), RecvState::Disconnected);
+    }
+
+    #[test]
+    fn test_activating_a_standby_wakes_a_blocked_primary() {
+        let (tx, rx) = spsc::<u32>(
+            16,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let primary = rx.into_failover();
+        let standby = primary.standby();
+        let consumer = thread::spawn(move || {
+            while primary.recv(4, &|_| {}) != RecvState::Disconnected {}
+            primary
+        });
+
+        // Nothing is sent, so only the activation can wake the primary.
+        thread::sleep(Duration::from_millis(50));
+        let active = standby.activate();
+        let primary = consumer.join().unwrap();
+        assert!(!primary.is_active() && active.is_active());
+        tx.send(7).unwrap();
+        assert_eq!(
+            active.try_recv_batch(4, &|value| assert_eq!(value, 7)),
+            RecvResult::Processed(1)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Fair merging of several channels into a single consumer.
+//!
+//! A [`FanIn`] serves its source [`Receiver`]s in rounds. Within a round every
+//! source may deliver at most its quota of items, so a chatty upstream channel
+//! cannot monopolize the merged consumer while quieter sources wait. Each
+//! source counts the rounds in which it ran into its quota, which shows which
+//! tenants are being throttled by the fairness rules.
+
+use crate::channels::{Receiver, RecvState};
+use crate::primitives::PaddedCounter;
+
+/// A snapshot of the counters of a single source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceStats {
+    id: usize,
+    quota: usize,
+    delivered: u64,
+    starved: u64,
+}
+
+impl SourceStats {
+    /// Returns the source id, as returned by [`FanIn::add`].
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the maximum number of items the source delivers per round.
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Returns the number of items the source has delivered.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// Returns the number of rounds in which the source used up its quota, so
+    /// items it had waiting may have been deferred to a later round.
+    pub fn starved(&self) -> u64 {
+        self.starved
+    }
+}
+
+/// A receiver merged into a [`FanIn`].
+struct Source<T> {
+    receiver: Receiver<T>,
+    quota: usize,
+    delivered: PaddedCounter,
+    starved: PaddedCounter,
+}
+
+/// Merges several receivers into one consumer with per-source quotas.
+///
+/// Rounds start at a different source each time, so no source is always
+/// served first.
+pub struct FanIn<T> {
+    sources: Vec<Source<T>>,
+    rounds: PaddedCounter,
+}
+
+impl<T> Default for FanIn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FanIn<T> {
+    /// Create a fan-in without sources.
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            rounds: PaddedCounter::default(),
+        }
+    }
+
+    /// Merge `receiver` in, delivering at most `quota` of its items per round.
+    ///
+    /// Returns the id of the new source, which is passed to the handler along
+    /// with every item the source delivers.
+    ///
+    /// # Panics
+    /// Panics if `quota` is zero.
+    pub fn add(&mut self, receiver: Receiver<T>, quota: usize) -> usize {
+        assert!(quota > 0, "quota must be greater than zero");
+        self.sources.push(Source {
+            receiver,
+            quota,
+            delivered: PaddedCounter::default(),
+            starved: PaddedCounter::default(),
+        });
+        self.sources.len() - 1
+    }
+
+    /// Returns the number of sources.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns `true` if no source has been added.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Run a single round over every source, without waiting.
+    ///
+    /// Invokes `handler` with the source id and the item for every item
+    /// delivered. Returns [`RecvState::Disconnected`] once every source is closed
+    /// or has lost all its senders, and has been drained.
+    pub fn recv<H>(&self, handler: &H) -> RecvState
+    where
+        H: Fn(usize, T),
+    {
+        let count = self.sources.len();
+        let start = match count {
+            0 => return RecvState::Disconnected,
+            _ => (self.rounds.fetch_add(1) % count as u64) as usize,
+        };
+
+        let mut received = false;
+        let mut disconnected = true;
+        for id in (start..count).chain(0..start) {
+            let source = &self.sources[id];
+            let (taken, state) = source
+                .receiver
+                .recv_up_to(source.quota, &|item| handler(id, item));
+
+            source.delivered.add(taken as u64);
+            if taken == source.quota {
+                source.starved.increment();
+            }
+            received |= state == RecvState::Received;
+            disconnected &= state == RecvState::Disconnected;
+        }
+
+        match (received, disconnected) {
+            (true, _) => RecvState::Received,
+            (false, true) => RecvState::Disconnected,
+            (false, false) => RecvState::Empty,
+        }
+    }
+
+    /// Run rounds until at least one item is delivered or every source is disconnected.
+    ///
+    /// The sources have no common wait strategy, so the thread yields between
+    /// empty rounds.
+    pub fn blocking_recv<H>(&self, handler: &H) -> RecvState
+    where
+        H: Fn(usize, T),
+    {
+        loop {
+            match self.recv(handler) {
+                RecvState::Empty => std::thread::yield_now(),
+                state => return state,
+            }
+        }
+    }
+
+    /// Take a snapshot of the counters of every source.
+    pub fn stats(&self) -> Vec<SourceStats> {
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(id, source)| SourceStats {
+                id,
+                quota: source.quota,
+                delivered: source.delivered.get(),
+                starved: source.starved.get(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::fan_in::FanIn;
+    use crate::testing::spinning_spsc;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_quota_keeps_chatty_source_from_starving_others() {
+        let (chatty_tx, chatty_rx) = spinning_spsc::<u32>(64);
+        let (quiet_tx, quiet_rx) = spinning_spsc::<u32>(64);
+        let mut fan_in = FanIn::new();
+        let chatty = fan_in.add(chatty_rx, 4);
+        let quiet = fan_in.add(quiet_rx, 4);
+
+        chatty_tx.send_n(0..32).unwrap();
+        quiet_tx.send_n(100..102).unwrap();
+
+        let received = RefCell::new(Vec::new());
+        let handler = |id, item| received.borrow_mut().push((id, item));
+        assert_eq!(fan_in.recv(&handler), RecvState::Received);
+        assert_eq!(received.borrow().len(), 6);
+        assert!(received.borrow().contains(&(quiet, 101)));
+
+        let stats = fan_in.stats();
+        assert_eq!((stats[chatty].delivered(), stats[chatty].starved()), (4, 1));
+        assert_eq!((stats[quiet].delivered(), stats[quiet].starved()), (2, 0));
+
+        drop(chatty_tx);
+        drop(quiet_tx);
+        while fan_in.recv(&handler) == RecvState::Received {}
+        assert_eq!(received.borrow().len(), 34);
+        assert_eq!(fan_in.recv(&handler), RecvState::Disconnected);
+    }
+}
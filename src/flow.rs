@@ -0,0 +1,194 @@
+//! Downstream flow control for consumers.
+//!
+//! A [`FlowController`] lets a downstream sink (a bounded network writer, a
+//! slow disk, ...) throttle how fast a [`Receiver`](crate::channels::Receiver)
+//! drains its ring buffer. Items the controller does not admit stay in the
+//! ring, so once it fills up the backpressure reaches producers through the
+//! regular producer wait strategy instead of ad hoc sleeps in handler code.
+//!
+//! On the other side, [`Sender::with_rate_limit`] smooths a bursty producer
+//! before its items reach the ring buffer, so downstream consumers are
+//! protected without an external throttle.
+//!
+//! [`Sender::with_rate_limit`]: crate::channels::Sender::with_rate_limit
+
+use crate::coordinator::Coordinator;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A hook the receiver consults before every batch it polls.
+pub trait FlowController: Send + Sync {
+    /// Returns how many of the `want` items the consumer may take right now.
+    ///
+    /// Returning `0` pauses consumption: the receiver waits according to its
+    /// consumer wait strategy and asks again on the next receive. A blocking
+    /// consumer only asks again once it is woken, so a controller that pauses
+    /// one should [`resume`](FlowHandle::resume) it when it admits items again.
+    /// Values larger than `want` are treated as `want`.
+    fn permit(&self, want: usize) -> usize;
+}
+
+/// Wakes the consumers of a channel paused by its [`FlowController`],
+/// created by [`Receiver::flow_handle`](crate::channels::Receiver::flow_handle).
+#[derive(Clone)]
+pub struct FlowHandle {
+    coordinator: Arc<Coordinator>,
+}
+
+impl FlowHandle {
+    pub(crate) fn new(coordinator: Arc<Coordinator>) -> Self {
+        Self { coordinator }
+    }
+
+    /// Wake the waiting consumers of the channel so they ask their flow
+    /// controllers for a permit again.
+    pub fn resume(&self) {
+        self.coordinator.wake_consumers();
+    }
+}
+
+/// A token bucket limiting how fast a sender sends, see
+/// [`Sender::with_rate_limit`](crate::channels::Sender::with_rate_limit).
+///
+/// Rather than counting tokens, the bucket records when it is full again, as
+/// the generic cell rate algorithm does: taking `n` tokens pushes that time
+/// `n` refill intervals further, which is allowed as long as it stays within
+/// `burst` intervals from now.
+pub(crate) struct RateLimit {
+    start: Instant,
+    /// Nanoseconds it takes to refill a token.
+    interval: f64,
+    /// Nanoseconds it takes to refill the whole bucket.
+    capacity: u64,
+    /// When the bucket is full again, in nanoseconds since `start`.
+    full_at: AtomicU64,
+}
+
+impl RateLimit {
+    /// Admit `rate` items per second on average, in bursts of up to `burst` items.
+    pub fn new(rate: f64, burst: usize) -> Self {
+        let interval = 1e9 / rate;
+        Self {
+            start: Instant::now(),
+            interval,
+            capacity: (interval * burst as f64) as u64,
+            full_at: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the refill time of `n` tokens, in nanoseconds.
+    fn cost(&self, n: usize) -> u64 {
+        (self.interval * n as f64) as u64
+    }
+
+    /// Take `n` tokens, or return how long to wait for them to refill.
+    ///
+    /// A full bucket admits any `n`, so a batch larger than the burst is
+    /// admitted once the bucket is full and leaves it in debt.
+    pub fn try_take(&self, n: usize) -> Result<(), Duration> {
+        let now = self.start.elapsed().as_nanos() as u64;
+        let cost = self.cost(n);
+        let mut full_at = self.full_at.load(Ordering::Relaxed);
+        loop {
+            let next = full_at.max(now) + cost;
+            if full_at > now && next - now > self.capacity {
+                let wait = (next - now - self.capacity).min(full_at - now);
+                return Err(Duration::from_nanos(wait));
+            }
+            match self.full_at.compare_exchange_weak(
+                full_at,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => full_at = current,
+            }
+        }
+    }
+
+    /// Give back `n` tokens taken for items that could not be sent.
+    pub fn give_back(&self, n: usize) {
+        let cost = self.cost(n);
+        let _ = self
+            .full_at
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |full_at| {
+                Some(full_at.saturating_sub(cost))
+            });
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::spsc;
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::errors::{SendError, TrySendError};
+    use crate::flow::FlowController;
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_flow_handle_resumes_a_paused_blocking_consumer() {
+        struct Gate(AtomicBool);
+        impl FlowController for Gate {
+            fn permit(&self, want: usize) -> usize {
+                match self.0.load(Ordering::Acquire) {
+                    true => want,
+                    false => 0,
+                }
+            }
+        }
+
+        let gate = Arc::new(Gate(AtomicBool::new(false)));
+        let (tx, rx) = spsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let rx = rx.with_flow_controller(gate.clone());
+        let handle = rx.flow_handle();
+        tx.send(7).unwrap();
+        let consumer = thread::spawn(move || {
+            let received = Cell::new(None);
+            while received.get().is_none() {
+                rx.recv(1, &|value| received.set(Some(value)));
+            }
+            received.get()
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!consumer.is_finished());
+        gate.0.store(true, Ordering::Release);
+        handle.resume();
+        assert_eq!(consumer.join().unwrap(), Some(7));
+    }
+
+    #[test]
+    fn test_rate_limited_senders_wait_for_tokens() {
+        let (tx, rx) = spsc::<u32>(
+            64,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let tx = tx.with_rate_limit(1000.0, 5);
+        for value in 0..5 {
+            tx.try_send(value).unwrap();
+        }
+        assert!(matches!(tx.try_send(5), Err(TrySendError::WouldBlock(5))));
+
+        // A batch larger than the burst waits for a full bucket and leaves
+        // it in debt, which the next send waits out.
+        let started = Instant::now();
+        tx.send_n(5..25).unwrap();
+        tx.send(25).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert!(rx.drain_all().into_iter().eq(0..26));
+
+        rx.close();
+        assert!(matches!(tx.send(26), Err(SendError::Closed(26, _))));
+    }
+}
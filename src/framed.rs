@@ -0,0 +1,596 @@
+//! Byte channels carrying variable-length frames.
+//!
+//! A [`byte_channel`] is a single-producer single-consumer ring of bytes
+//! rather than of slots. Every frame is written as a four-byte little-endian
+//! length followed by its bytes, right after the previous frame, so small
+//! messages share the space of a slot-sized one and a frame may be as long as
+//! the whole ring. Frames wrap around the end of the ring; the receiver hands
+//! a frame that does so to the handler through a copy, and every other frame
+//! straight out of the ring.
+//!
+//! Frames are raw bytes, which the receiver can turn into values with any
+//! [`Decoder`]. With the `serde` feature, `ByteSender::send` serializes a
+//! value with bincode directly into the ring and `ByteReceiver::recv`
+//! deserializes it, so a single channel carries messages of different types
+//! and sizes without an allocation per message.
+
+use crate::channels::{RecvState, coordinator};
+use crate::coordinator::{ConsumerWaitStrategyKind, Coordinator, ProducerWaitStrategyKind};
+#[cfg(feature = "serde")]
+use crate::decoding::Bincode;
+use crate::decoding::Decoder;
+use crate::errors::{DecodeError, FrameError};
+use crate::sync::{AtomicU64, Ordering};
+use crate::utils::CachePadded;
+use std::cell::{Cell, RefCell, UnsafeCell};
+#[cfg(feature = "serde")]
+use std::io;
+use std::ptr;
+use std::sync::Arc;
+
+/// Length of the prefix of every frame.
+const PREFIX: usize = size_of::<u32>();
+
+/// A ring of bytes holding length-prefixed frames.
+struct ByteRingBuffer {
+    bytes: Box<[UnsafeCell<u8>]>,
+    /// Number of bytes ever published by the producer.
+    tail: CachePadded<AtomicU64>,
+    /// Number of bytes ever released by the consumer.
+    head: CachePadded<AtomicU64>,
+}
+
+// SAFETY: the producer only writes bytes between the head and the capacity
+// ahead of it, and the consumer only reads bytes before the tail, both
+// ordered by the release and acquire pairs on the tail and the head.
+unsafe impl Sync for ByteRingBuffer {}
+
+impl ByteRingBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            bytes: (0..capacity).map(|_| UnsafeCell::new(0)).collect(),
+            tail: CachePadded(AtomicU64::new(0)),
+            head: CachePadded(AtomicU64::new(0)),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns the index of `position` in the ring and the number of bytes
+    /// from there to the end of the ring.
+    fn locate(&self, position: u64) -> (usize, usize) {
+        let index = (position % self.capacity() as u64) as usize;
+        (index, self.capacity() - index)
+    }
+
+    fn ptr(&self, index: usize) -> *mut u8 {
+        UnsafeCell::raw_get(self.bytes[index..].as_ptr())
+    }
+
+    /// Copy `data` into the ring from `position` on, wrapping at the end.
+    ///
+    /// # Safety
+    /// The bytes must belong to the producer, i.e. lie before the head plus
+    /// the capacity and not be published yet.
+    unsafe fn write(&self, position: u64, data: &[u8]) {
+        let (index, until_end) = self.locate(position);
+        let (first, second) = data.split_at(data.len().min(until_end));
+        // SAFETY: both parts lie within the ring and belong to the producer.
+        unsafe {
+            ptr::copy_nonoverlapping(first.as_ptr(), self.ptr(index), first.len());
+            ptr::copy_nonoverlapping(second.as_ptr(), self.ptr(0), second.len());
+        }
+    }
+
+    /// Copy the bytes from `position` on into `out`, wrapping at the end.
+    ///
+    /// # Safety
+    /// The bytes must be published and not released yet.
+    unsafe fn read(&self, position: u64, out: &mut [u8]) {
+        let (index, until_end) = self.locate(position);
+        let (first, second) = out.split_at_mut(out.len().min(until_end));
+        // SAFETY: both parts lie within the ring and belong to the consumer.
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr(index), first.as_mut_ptr(), first.len());
+            ptr::copy_nonoverlapping(self.ptr(0), second.as_mut_ptr(), second.len());
+        }
+    }
+
+    /// Returns the `len` bytes from `position` on as the part up to the end
+    /// of the ring and the part that wraps around to its start.
+    ///
+    /// # Safety
+    /// The bytes must belong to the producer, and nothing else may access
+    /// them while the slices are alive.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn slices_mut(&self, position: u64, len: usize) -> (&mut [u8], &mut [u8]) {
+        let (index, until_end) = self.locate(position);
+        let first = len.min(until_end);
+        // SAFETY: both parts lie within the ring and are exclusive to the caller.
+        unsafe {
+            (
+                std::slice::from_raw_parts_mut(self.ptr(index), first),
+                std::slice::from_raw_parts_mut(self.ptr(0), len - first),
+            )
+        }
+    }
+
+    /// Returns the `len` bytes from `position` on, unless they wrap around
+    /// the end of the ring.
+    ///
+    /// # Safety
+    /// The bytes must be published and not released before the slice is dropped.
+    unsafe fn contiguous(&self, position: u64, len: usize) -> Option<&[u8]> {
+        let (index, until_end) = self.locate(position);
+        // SAFETY: the bytes lie within the ring and belong to the consumer.
+        (len <= until_end).then(|| unsafe { std::slice::from_raw_parts(self.ptr(index), len) })
+    }
+}
+
+/// The sending half of a byte channel, created by [`byte_channel`].
+///
+/// A channel has a single producer, so the sender can be moved to another
+/// thread but not shared.
+pub struct ByteSender {
+    ring: Arc<ByteRingBuffer>,
+    coordinator: Arc<Coordinator>,
+    tail: Cell<u64>,
+    cached_head: Cell<u64>,
+}
+
+impl ByteSender {
+    /// Send a single frame, waiting according to the producer wait strategy
+    /// until the channel has room for it.
+    ///
+    /// # Errors
+    /// - [`FrameError::TooLarge`] if the frame is longer than [`max_frame_len`](Self::max_frame_len).
+    /// - [`FrameError::Closed`] if the channel is closed, including while this
+    ///   call waits for room.
+    pub fn send_frame(&self, frame: &[u8]) -> Result<(), FrameError> {
+        self.framing(frame.len(), true, |position| {
+            // SAFETY: the reserved bytes belong to the producer.
+            unsafe { self.ring.write(position, frame) };
+            Ok(())
+        })
+    }
+
+    /// Try to send a single frame without waiting for room.
+    ///
+    /// # Errors
+    /// - [`FrameError::TooLarge`] if the frame is longer than [`max_frame_len`](Self::max_frame_len).
+    /// - [`FrameError::Full`] if the channel has no room for the frame.
+    /// - [`FrameError::Closed`] if the channel is closed.
+    pub fn try_send_frame(&self, frame: &[u8]) -> Result<(), FrameError> {
+        self.framing(frame.len(), false, |position| {
+            // SAFETY: the reserved bytes belong to the producer.
+            unsafe { self.ring.write(position, frame) };
+            Ok(())
+        })
+    }
+
+    /// Serialize `value` with bincode into a frame and send it, waiting
+    /// according to the producer wait strategy until the channel has room.
+    ///
+    /// # Errors
+    /// - [`FrameError::Encode`] if the value cannot be serialized.
+    /// - [`FrameError::TooLarge`] if the encoding is longer than [`max_frame_len`](Self::max_frame_len).
+    /// - [`FrameError::Closed`] if the channel is closed, including while this
+    ///   call waits for room.
+    #[cfg(feature = "serde")]
+    pub fn send<T: serde::Serialize + ?Sized>(&self, value: &T) -> Result<(), FrameError> {
+        let len =
+            bincode::serialized_size(value).map_err(|error| FrameError::Encode(error.into()))?;
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        self.framing(len, true, |position| {
+            let writer = RingWriter {
+                ring: &self.ring,
+                position,
+                remaining: len,
+            };
+            bincode::serialize_into(writer, value).map_err(|error| FrameError::Encode(error.into()))
+        })
+    }
+
+    /// Returns the length of the longest frame the channel can hold.
+    pub fn max_frame_len(&self) -> usize {
+        (self.ring.capacity() - PREFIX).min(u32::MAX as usize)
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+
+    /// Claim room for a frame of `len` bytes to be written in place, waiting
+    /// according to the producer wait strategy until the channel has room.
+    ///
+    /// The room may wrap around the end of the ring, so it is handed out as
+    /// two slices, see [`ByteClaim::as_mut_slices`]. A network receive path
+    /// can read straight into them, claiming the longest frame it expects and
+    /// committing only the bytes it got with [`ByteClaim::commit_len`].
+    ///
+    /// # Errors
+    /// - [`FrameError::TooLarge`] if `len` exceeds [`max_frame_len`](Self::max_frame_len).
+    /// - [`FrameError::Closed`] if the channel is closed, including while this
+    ///   call waits for room.
+    pub fn claim_bytes(&mut self, len: usize) -> Result<ByteClaim<'_>, FrameError> {
+        self.reserve(len, true)?;
+        Ok(ByteClaim { sender: self, len })
+    }
+
+    /// Try to claim room for a frame of `len` bytes without waiting for room.
+    ///
+    /// # Errors
+    /// - [`FrameError::TooLarge`] if `len` exceeds [`max_frame_len`](Self::max_frame_len).
+    /// - [`FrameError::Full`] if the channel has no room for the frame.
+    /// - [`FrameError::Closed`] if the channel is closed.
+    pub fn try_claim_bytes(&mut self, len: usize) -> Result<ByteClaim<'_>, FrameError> {
+        self.reserve(len, false)?;
+        Ok(ByteClaim { sender: self, len })
+    }
+
+    /// Reserve room for a frame of `len` bytes, let `fill` write it from the
+    /// position it is handed, and publish it.
+    fn framing<F>(&self, len: usize, wait: bool, fill: F) -> Result<(), FrameError>
+    where
+        F: FnOnce(u64) -> Result<(), FrameError>,
+    {
+        self.reserve(len, wait)?;
+        fill(self.tail.get() + PREFIX as u64)?;
+        self.publish(len);
+        Ok(())
+    }
+
+    /// Make sure the ring has room for a frame of `len` bytes after the tail.
+    fn reserve(&self, len: usize, wait: bool) -> Result<(), FrameError> {
+        let max = self.max_frame_len();
+        if len > max {
+            return Err(FrameError::TooLarge { len, max });
+        }
+        let tail = self.tail.get();
+        let needed = (PREFIX + len) as u64;
+        let mut waited = false;
+        while tail + needed - self.cached_head.get() > self.ring.capacity() as u64 {
+            if self.coordinator.is_closed() {
+                return Err(FrameError::Closed(self.coordinator.close_reason()));
+            }
+            self.cached_head.set(self.ring.head.load(Ordering::Acquire));
+            if tail + needed - self.cached_head.get() <= self.ring.capacity() as u64 {
+                break;
+            }
+            if !wait {
+                return Err(FrameError::Full);
+            }
+            self.coordinator.producer_wait();
+            waited = true;
+        }
+        if waited {
+            self.coordinator.producer_progress();
+        }
+        if self.coordinator.is_closed() {
+            return Err(FrameError::Closed(self.coordinator.close_reason()));
+        }
+        Ok(())
+    }
+
+    /// Publish the frame of `len` bytes written after the tail.
+    fn publish(&self, len: usize) {
+        let tail = self.tail.get();
+        // SAFETY: the bytes of a reserved frame belong to the producer.
+        unsafe { self.ring.write(tail, &(len as u32).to_le_bytes()) };
+        let tail = tail + (PREFIX + len) as u64;
+        self.ring.tail.store(tail, Ordering::Release);
+        self.tail.set(tail);
+        self.coordinator.notify_consumer(1, || {
+            (tail - self.ring.head.load(Ordering::Relaxed)) as usize
+        });
+    }
+}
+
+/// Room for a frame claimed by [`ByteSender::claim_bytes`], published when
+/// dropped.
+pub struct ByteClaim<'a> {
+    sender: &'a ByteSender,
+    len: usize,
+}
+
+impl ByteClaim<'_> {
+    /// Returns the claimed room as two slices, the second of which holds the
+    /// part that wraps around to the start of the ring and is empty otherwise.
+    ///
+    /// The bytes hold whatever an earlier frame left there.
+    pub fn as_mut_slices(&mut self) -> (&mut [u8], &mut [u8]) {
+        let position = self.sender.tail.get() + PREFIX as u64;
+        // SAFETY: the claimed bytes belong to the producer, which the claim
+        // borrows mutably.
+        unsafe { self.sender.ring.slices_mut(position, self.len) }
+    }
+
+    /// Returns the number of bytes claimed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no bytes were claimed.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Publish the claimed bytes as a frame.
+    pub fn commit(self) {}
+
+    /// Publish the first `len` claimed bytes as a frame, giving the rest back.
+    ///
+    /// # Panics
+    /// Panics if `len` exceeds the number of bytes claimed.
+    pub fn commit_len(mut self, len: usize) {
+        assert!(len <= self.len, "cannot commit more bytes than claimed");
+        self.len = len;
+    }
+}
+
+impl Drop for ByteClaim<'_> {
+    fn drop(&mut self) {
+        self.sender.publish(self.len);
+    }
+}
+
+impl Drop for ByteSender {
+    fn drop(&mut self) {
+        self.coordinator.remove_sender();
+    }
+}
+
+/// Writes a frame into the bytes a [`ByteSender`] reserved for it.
+#[cfg(feature = "serde")]
+struct RingWriter<'a> {
+    ring: &'a ByteRingBuffer,
+    position: u64,
+    remaining: usize,
+}
+
+#[cfg(feature = "serde")]
+impl io::Write for RingWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // A value that serializes differently than it was sized must not
+        // overwrite the frames after its own.
+        if data.len() > self.remaining {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        // SAFETY: the bytes lie within those reserved for the frame.
+        unsafe { self.ring.write(self.position, data) };
+        self.position += data.len() as u64;
+        self.remaining -= data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The receiving half of a byte channel, created by [`byte_channel`].
+///
+/// A channel has a single consumer, so the receiver can be moved to another
+/// thread but not shared.
+pub struct ByteReceiver {
+    ring: Arc<ByteRingBuffer>,
+    coordinator: Arc<Coordinator>,
+    head: Cell<u64>,
+    /// Holds the frames that wrap around the end of the ring.
+    scratch: RefCell<Vec<u8>>,
+}
+
+impl ByteReceiver {
+    /// Attempt to receive up to `max_frames` frames.
+    ///
+    /// Invokes `handler` for each frame. If no frame is available, waits once
+    /// according to the consumer wait strategy, unless the channel is closed
+    /// or the sender is gone, in which case [`RecvState::Disconnected`] is returned.
+    pub fn recv_frames<H>(&self, max_frames: usize, handler: &H) -> RecvState
+    where
+        H: Fn(&[u8]),
+    {
+        // Read before polling, so that everything the sender published is
+        // seen by the poll if it reports a disconnect.
+        let finished = self.coordinator.is_finished();
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        let mut position = self.head.get();
+        let mut received = 0;
+        while position < tail && received < max_frames {
+            let mut prefix = [0; PREFIX];
+            // SAFETY: the frame is published and released only below.
+            unsafe { self.ring.read(position, &mut prefix) };
+            let len = u32::from_le_bytes(prefix) as usize;
+            let start = position + PREFIX as u64;
+            // SAFETY: as above.
+            match unsafe { self.ring.contiguous(start, len) } {
+                Some(frame) => handler(frame),
+                None => {
+                    let mut scratch = self.scratch.borrow_mut();
+                    scratch.resize(len, 0);
+                    // SAFETY: as above.
+                    unsafe { self.ring.read(start, &mut scratch) };
+                    handler(&scratch);
+                }
+            }
+            position = start + len as u64;
+            received += 1;
+        }
+
+        if received == 0 {
+            if finished {
+                return RecvState::Disconnected;
+            }
+            self.coordinator.consumer_wait();
+            return RecvState::Empty;
+        }
+        self.ring.head.store(position, Ordering::Release);
+        self.head.set(position);
+        self.coordinator.consumer_progress(received);
+        RecvState::Received
+    }
+
+    /// Attempt to receive up to `max_frames` frames, decoding each one with `decoder`.
+    ///
+    /// Invokes `handler` with the value of every frame that decodes and
+    /// validates, or with the reason it does not. Waits like
+    /// [`recv_frames`](Self::recv_frames) if no frame is available.
+    pub fn recv_decoded<T, D, H>(&self, max_frames: usize, decoder: &D, handler: &H) -> RecvState
+    where
+        D: Decoder<T>,
+        H: Fn(Result<T, DecodeError>),
+    {
+        self.recv_frames(max_frames, &|frame| {
+            let value = decoder.decode(frame);
+            handler(value.and_then(|value| decoder.validate(&value).map(|()| value)))
+        })
+    }
+
+    /// Attempt to receive up to `max_frames` values sent with
+    /// [`ByteSender::send`], deserializing each one with bincode.
+    ///
+    /// See [`recv_decoded`](Self::recv_decoded).
+    #[cfg(feature = "serde")]
+    pub fn recv<T, H>(&self, max_frames: usize, handler: &H) -> RecvState
+    where
+        T: serde::de::DeserializeOwned,
+        H: Fn(Result<T, DecodeError>),
+    {
+        self.recv_decoded(max_frames, &Bincode, handler)
+    }
+
+    /// Close the channel, so that the sender fails from now on.
+    pub fn close(&self) {
+        self.coordinator.close(None);
+    }
+}
+
+impl Drop for ByteReceiver {
+    fn drop(&mut self) {
+        self.coordinator.remove_receiver();
+    }
+}
+
+/// Create a **single-producer single-consumer (SPSC)** channel of frames
+/// over a ring of `capacity` bytes.
+///
+/// The longest frame the channel holds is four bytes shorter than
+/// `capacity`, see the [module documentation](self).
+///
+/// # Panics
+/// Panics if `capacity` is not greater than four bytes.
+pub fn byte_channel(
+    capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (ByteSender, ByteReceiver) {
+    assert!(capacity > PREFIX, "capacity must exceed the frame prefix");
+    let ring = Arc::new(ByteRingBuffer::new(capacity));
+    let coordinator = Arc::new(coordinator(pw, cw, None));
+    let sender = ByteSender {
+        ring: ring.clone(),
+        coordinator: coordinator.clone(),
+        tail: Cell::new(0),
+        cached_head: Cell::new(0),
+    };
+    let receiver = ByteReceiver {
+        ring,
+        coordinator,
+        head: Cell::new(0),
+        scratch: RefCell::new(Vec::new()),
+    };
+    (sender, receiver)
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::errors::FrameError;
+    use crate::framed::byte_channel;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_frames_of_any_length_wrap_around_the_ring() {
+        let (tx, rx) = byte_channel(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        assert!(matches!(
+            tx.send_frame(&[0; 13]),
+            Err(FrameError::TooLarge { len: 13, max: 12 })
+        ));
+        tx.try_send_frame(b"abcdef").unwrap();
+        assert!(matches!(tx.try_send_frame(b"ghij"), Err(FrameError::Full)));
+
+        let consumer = std::thread::spawn(move || {
+            let frames = RefCell::new(Vec::new());
+            while rx.recv_frames(2, &|frame| frames.borrow_mut().push(frame.to_vec()))
+                != RecvState::Disconnected
+            {}
+            frames.into_inner()
+        });
+        let sent: Vec<Vec<u8>> = (0..100u8)
+            .map(|index| vec![index; index as usize % 13])
+            .collect();
+        for frame in &sent {
+            tx.send_frame(frame).unwrap();
+        }
+        drop(tx);
+        let mut received = consumer.join().unwrap();
+        assert_eq!(received.remove(0), b"abcdef");
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn test_claimed_bytes_wrap_into_a_second_slice() {
+        let (mut tx, rx) = byte_channel(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send_frame(b"abcdef").unwrap();
+        assert_eq!(rx.recv_frames(1, &|_| {}), RecvState::Received);
+
+        // The prefix takes bytes 10 to 13, so the frame wraps after two bytes.
+        let mut claim = tx.claim_bytes(10).unwrap();
+        let (first, second) = claim.as_mut_slices();
+        assert_eq!((first.len(), second.len()), (2, 8));
+        first.copy_from_slice(b"gh");
+        second[..3].copy_from_slice(b"ijk");
+        claim.commit_len(5);
+        assert!(matches!(tx.try_claim_bytes(8), Err(FrameError::Full)));
+
+        let frames = RefCell::new(Vec::new());
+        rx.recv_frames(4, &|frame| frames.borrow_mut().push(frame.to_vec()));
+        assert_eq!(frames.into_inner(), [b"ghijk"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialized_values_of_different_types_share_a_channel() {
+        use crate::errors::DecodeError;
+
+        let (tx, rx) = byte_channel(
+            64,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send(&(7u32, String::from("seven"))).unwrap();
+        tx.send(&vec![1u64, 2, 3]).unwrap();
+
+        let pairs = RefCell::new(Vec::new());
+        let state = rx.recv(1, &|pair: Result<(u32, String), DecodeError>| {
+            pairs.borrow_mut().push(pair.unwrap())
+        });
+        assert_eq!(state, RecvState::Received);
+        assert_eq!(pairs.into_inner(), [(7, String::from("seven"))]);
+        rx.recv(1, &|values: Result<Vec<u64>, DecodeError>| {
+            assert_eq!(values.unwrap(), [1, 2, 3])
+        });
+    }
+}
@@ -0,0 +1,175 @@
+//! Receivers as futures streams and senders as futures sinks.
+//!
+//! With the `futures` feature, [`Receiver`] implements
+//! [`Stream`] and [`Sender`] implements
+//! [`Sink`], so channels plug into async combinators and
+//! the libraries built on them. A task that finds the channel empty, or full,
+//! registers its waker with the channel and is woken wherever a blocked
+//! consumer, or producer, would be: when items are published, as the
+//! [`NotifyPolicy`](crate::coordinator::NotifyPolicy) allows, when slots are
+//! released, and when the channel is closed or disconnected.
+//!
+//! A stream takes one item per poll. A sink publishes every item as it is
+//! started, so flushing has nothing left to do, and closing it leaves the
+//! channel open for the other senders: the channel disconnects once every
+//! sender is dropped.
+
+use crate::channels::{Receiver, RecvResult, Sender};
+use crate::errors::{SendError, TrySendError};
+use crate::sync::{AtomicUsize, Ordering, fence};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::cell::Cell;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+/// The wakers of the tasks waiting on one side of a channel.
+#[derive(Default)]
+pub(crate) struct Wakers {
+    wakers: Mutex<Vec<Waker>>,
+    waiting: AtomicUsize,
+}
+
+impl Wakers {
+    /// Wake `waker` on the next [`wake`](Self::wake).
+    ///
+    /// A wake that happened before the registration is lost, so the caller
+    /// checks the channel again afterwards before it returns pending.
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        self.waiting.store(wakers.len(), Ordering::Relaxed);
+        drop(wakers);
+        // Pairs with the fence in `wake`: either the check that follows sees
+        // the progress, or the waking side sees the registration.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Wake every registered task.
+    #[inline(always)]
+    pub fn wake(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap();
+            self.waiting.store(0, Ordering::Relaxed);
+            std::mem::take(&mut *wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Take the next item without waiting, or `None` if nothing is available yet.
+fn poll_item<T>(receiver: &Receiver<T>) -> Option<Poll<Option<T>>> {
+    let item = Cell::new(None);
+    match receiver.try_recv_batch(1, &|value| item.set(Some(value))) {
+        RecvResult::Processed(_) => Some(Poll::Ready(item.take())),
+        RecvResult::Disconnected => Some(Poll::Ready(None)),
+        RecvResult::Empty => None,
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    /// Take the next item, or wait for one to be published.
+    ///
+    /// Ends once the channel is closed or every sender is gone, and every
+    /// item has been received.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(poll) = poll_item(&self) {
+            return poll;
+        }
+        self.coordinator().register_consumer(cx.waker());
+        poll_item(&self).unwrap_or(Poll::Pending)
+    }
+}
+
+/// Returns `true` if a send would not wait, or would fail at once.
+fn has_room<T>(sender: &Sender<T>) -> bool {
+    let coordinator = sender.coordinator();
+    coordinator.is_closed()
+        || sender.remaining_capacity() > 0
+            && (!coordinator.is_rendezvous() || coordinator.has_taker())
+}
+
+impl<T> Sink<T> for Sender<T> {
+    type Error = SendError<T>;
+
+    /// Wait until the ring buffer has a free slot, or the channel is closed,
+    /// in which case [`start_send`](Sink::start_send) returns the error.
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), SendError<T>>> {
+        if has_room(&self) {
+            return Poll::Ready(Ok(()));
+        }
+        self.coordinator().register_producer(cx.waker());
+        match has_room(&self) {
+            true => Poll::Ready(Ok(())),
+            false => Poll::Pending,
+        }
+    }
+
+    /// Publish `item`.
+    ///
+    /// On multi-producer channels another producer may take the slot found
+    /// by [`poll_ready`](Sink::poll_ready) first, in which case the send
+    /// waits according to the producer wait strategy.
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), SendError<T>> {
+        match self.try_send(item) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Closed(item, reason)) => Err(SendError::Closed(item, reason)),
+            Err(error) => self.send(error.into_inner()),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), SendError<T>>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), SendError<T>>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::mpsc;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use futures_executor::block_on;
+    use futures_util::{SinkExt, StreamExt, stream};
+    use std::thread;
+
+    #[test]
+    fn test_tasks_stream_and_sink_through_a_full_channel() {
+        let (tx, rx) = mpsc::<u32>(
+            4,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let producers: Vec<_> = (0..2)
+            .map(|index| {
+                let mut tx = tx.clone();
+                thread::spawn(move || {
+                    let mut values = stream::iter((0..200).map(|value| Ok(index * 1000 + value)));
+                    block_on(tx.send_all(&mut values)).unwrap();
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let received: Vec<u32> = block_on(rx.collect());
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+        assert_eq!(received.len(), 400);
+        for index in 0..2 {
+            let sent = received.iter().filter(|&&value| value / 1000 == index);
+            assert!(sent.map(|value| value % 1000).eq(0..200));
+        }
+    }
+}
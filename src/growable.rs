@@ -0,0 +1,361 @@
+//! Channels that grow their ring buffer instead of blocking full producers.
+//!
+//! A [`growable_mpsc`] channel starts with a small multi-producer ring. When a
+//! producer finds it full, the ring is replaced by one of twice the capacity,
+//! up to a maximum, and the producers carry on sending into the new ring. The
+//! old ring is sealed: the receiver drains what is left in it, including the
+//! sends that were in flight when it was replaced, and only then moves on to
+//! the new one, so the items of every producer are still received in the order
+//! they were sent.
+//!
+//! Growing allocates a new ring and costs the producer that triggers it a
+//! lock, which suits deployments that cannot pick a capacity up front and
+//! rather pay for a rare migration than block producers. Once the maximum
+//! capacity is reached, producers wait for free slots as on any other channel.
+
+use crate::channels::{Receiver, RecvResult, RecvState, Sender, assert_buffer_size, mpsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::{SendError, TrySendError};
+use crate::sync::{AtomicBool, AtomicUsize, Ordering};
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A ring of a growable channel.
+struct Generation<T> {
+    sender: Sender<T>,
+    capacity: usize,
+    /// The number of sends into this ring in progress.
+    writers: AtomicUsize,
+    /// Set once the ring was replaced, after which no send starts on it.
+    sealed: AtomicBool,
+    /// The ring replacing this one and its receiver, taken by the receiver
+    /// once this ring is drained.
+    next: Mutex<Option<Reading<T>>>,
+}
+
+impl<T> Generation<T> {
+    fn new(sender: Sender<T>, capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            sender,
+            capacity,
+            writers: AtomicUsize::new(0),
+            sealed: AtomicBool::new(false),
+            next: Mutex::new(None),
+        })
+    }
+
+    /// Returns `true` if the ring was replaced and every send into it finished.
+    fn is_retired(&self) -> bool {
+        self.sealed.load(Ordering::SeqCst) && self.writers.load(Ordering::Acquire) == 0
+    }
+}
+
+/// The state shared by both halves of a growable channel.
+struct Shared<T> {
+    current: RwLock<Arc<Generation<T>>>,
+    max_capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+    senders: AtomicUsize,
+    /// Set once either half is gone, so the channel must not grow any more.
+    finished: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    /// Returns the ring producers send into.
+    fn current(&self) -> Arc<Generation<T>> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the `full` ring by one of twice its capacity, unless another
+    /// producer already did or the channel is finished.
+    fn grow(&self, full: &Arc<Generation<T>>) {
+        let mut current = self.current.write().unwrap();
+        if !Arc::ptr_eq(&current, full) || self.finished.load(Ordering::Relaxed) {
+            return;
+        }
+        let capacity = (full.capacity * 2).min(self.max_capacity);
+        let (sender, receiver) = mpsc(capacity, self.pw, self.cw);
+        let next = Generation::new(sender, capacity);
+        *full.next.lock().unwrap() = Some(Reading {
+            generation: next.clone(),
+            receiver,
+        });
+        *current = next;
+
+        // Sends that find the ring sealed move on to the new one, and closing
+        // it wakes a consumer waiting for the items it will no longer get.
+        full.sealed.store(true, Ordering::SeqCst);
+        full.sender.close();
+    }
+
+    /// Close the current ring for good, once either half is gone.
+    fn finish(&self) {
+        let current = self.current.read().unwrap();
+        self.finished.store(true, Ordering::Relaxed);
+        current.sender.close();
+    }
+}
+
+/// The sending half of a growable channel, created by [`growable_mpsc`].
+pub struct GrowableSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> GrowableSender<T> {
+    /// Send a single value, growing the ring if it is full.
+    ///
+    /// Only waits according to the producer wait strategy once the ring has
+    /// reached the maximum capacity and is full.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the receiver is gone.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.sending(value, true) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Closed(value, reason)) => Err(SendError::Closed(value, reason)),
+            Err(_) => unreachable!("a waiting send is never full"),
+        }
+    }
+
+    /// Try to send a single value, growing the ring if it is full, without
+    /// ever waiting for free space.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the ring reached the maximum capacity and is full.
+    /// - [`TrySendError::Closed`] if the receiver is gone.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.sending(value, false)
+    }
+
+    fn sending(&self, mut value: T, wait: bool) -> Result<(), TrySendError<T>> {
+        loop {
+            let generation = self.shared.current();
+            generation.writers.fetch_add(1, Ordering::SeqCst);
+            if generation.sealed.load(Ordering::SeqCst) {
+                generation.writers.fetch_sub(1, Ordering::Release);
+                continue;
+            }
+
+            let full = generation.capacity >= self.shared.max_capacity;
+            let result = match generation.sender.try_send(value) {
+                Err(TrySendError::Full(rejected)) if full && wait => generation
+                    .sender
+                    .send(rejected)
+                    .map_err(|SendError::Closed(value, reason)| {
+                        TrySendError::Closed(value, reason)
+                    }),
+                result => result,
+            };
+            generation.writers.fetch_sub(1, Ordering::Release);
+
+            value = match result {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(rejected)) if !full => {
+                    self.shared.grow(&generation);
+                    rejected
+                }
+                Err(TrySendError::WouldBlock(rejected)) => rejected,
+                // The ring was sealed while this send was in progress.
+                Err(TrySendError::Closed(rejected, _))
+                    if generation.sealed.load(Ordering::SeqCst) =>
+                {
+                    rejected
+                }
+                Err(error) => return Err(error),
+            };
+        }
+    }
+
+    /// Returns the capacity of the ring producers currently send into.
+    pub fn capacity(&self) -> usize {
+        self.shared.current().capacity
+    }
+
+    /// Returns `true` if the receiver is gone.
+    pub fn is_closed(&self) -> bool {
+        self.shared.finished.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Clone for GrowableSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for GrowableSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.finish();
+        }
+    }
+}
+
+/// The ring a [`GrowableReceiver`] drains.
+struct Reading<T> {
+    generation: Arc<Generation<T>>,
+    receiver: Receiver<T>,
+}
+
+/// The receiving half of a growable channel, created by [`growable_mpsc`].
+///
+/// See the [module documentation](self).
+pub struct GrowableReceiver<T> {
+    shared: Arc<Shared<T>>,
+    reading: RefCell<Reading<T>>,
+}
+
+impl<T> GrowableReceiver<T> {
+    /// Attempt to receive up to `batch_size` items.
+    ///
+    /// Drains the rings that were replaced before the ones replacing them,
+    /// taking at most a ring's worth of items at a time, and otherwise
+    /// behaves like [`Receiver::recv`]: returns
+    /// [`RecvState::Disconnected`] once every sender is gone and every item
+    /// has been received.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        let mut reading = self.reading.borrow_mut();
+        loop {
+            let batch_size = batch_size.min(reading.generation.capacity);
+            let state = reading.receiver.recv(batch_size, handler).state();
+            if state != RecvState::Disconnected || !reading.generation.sealed.load(Ordering::SeqCst)
+            {
+                return state;
+            }
+            if !reading.generation.is_retired() {
+                std::thread::yield_now();
+                continue;
+            }
+
+            // Every send into the ring finished, so a last poll drains it.
+            let result = reading.receiver.try_recv_batch(batch_size, handler);
+            if let RecvResult::Processed(_) = result {
+                return RecvState::Received;
+            }
+            let next = reading.generation.next.lock().unwrap().take();
+            *reading = next.expect("a sealed ring has a successor");
+        }
+    }
+
+    /// Returns the capacity of the ring being drained.
+    pub fn capacity(&self) -> usize {
+        self.reading.borrow().generation.capacity
+    }
+}
+
+impl<T> Drop for GrowableReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.finish();
+    }
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel that starts
+/// with `initial_capacity` slots and doubles its capacity whenever it is
+/// full, up to `max_capacity`.
+///
+/// See the [module documentation](self).
+///
+/// # Panics
+/// Panics if `initial_capacity` exceeds `max_capacity`, and if either is not
+/// a valid buffer size.
+pub fn growable_mpsc<T>(
+    initial_capacity: usize,
+    max_capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (GrowableSender<T>, GrowableReceiver<T>) {
+    assert!(
+        initial_capacity <= max_capacity,
+        "initial_capacity must not exceed max_capacity"
+    );
+    assert_buffer_size(max_capacity);
+    let (sender, receiver) = mpsc(initial_capacity, pw, cw);
+    let generation = Generation::new(sender, initial_capacity);
+    let shared = Arc::new(Shared {
+        current: RwLock::new(generation.clone()),
+        max_capacity,
+        pw,
+        cw,
+        senders: AtomicUsize::new(1),
+        finished: AtomicBool::new(false),
+    });
+    let reading = Reading {
+        generation,
+        receiver,
+    };
+    (
+        GrowableSender {
+            shared: shared.clone(),
+        },
+        GrowableReceiver {
+            shared,
+            reading: RefCell::new(reading),
+        },
+    )
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::errors::TrySendError;
+    use crate::growable::growable_mpsc;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_full_rings_grow_and_keep_the_order_of_each_producer() {
+        let (tx, rx) = growable_mpsc::<u32>(
+            2,
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        // Rings of 2, 4 and 8 slots fill up before the largest one does.
+        for value in 0..30 {
+            tx.try_send(value).unwrap();
+        }
+        assert_eq!(tx.capacity(), 16);
+        assert!(matches!(tx.try_send(30), Err(TrySendError::Full(30))));
+
+        let received = RefCell::new(Vec::new());
+        while rx.recv(4, &|value| received.borrow_mut().push(value)) == RecvState::Received {}
+        assert!(received.take().into_iter().eq(0..30));
+        assert_eq!(rx.capacity(), 16);
+
+        let (tx, rx) = growable_mpsc::<u32>(
+            2,
+            64,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let producers: Vec<_> = (0..3)
+            .map(|index| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for value in 0..500 {
+                        tx.send(index * 1000 + value).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        while rx.recv(8, &|value| received.borrow_mut().push(value)) != RecvState::Disconnected {}
+        producers
+            .into_iter()
+            .for_each(|producer| producer.join().unwrap());
+        let received = received.into_inner();
+        assert_eq!(received.len(), 1500);
+        for index in 0..3 {
+            let sent = received.iter().filter(|&&value| value / 1000 == index);
+            assert!(sent.map(|value| value % 1000).eq(0..500));
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Receive paths that hand items to handlers in their slots.
+//!
+//! Instead of moving every item out of the ring, these receives claim a batch
+//! and let the handler transform, inspect or copy the items where they are,
+//! which spares large items or decoding stages a copy per item.
+
+use crate::channels::{Receiver, RecvState};
+use crate::errors::{ChannelPoisoned, HandlerFailed};
+use crate::transform::Scratch;
+use std::sync::Arc;
+
+/// What [`Receiver::recv_fallible`] does with an item its handler failed on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Drop the item.
+    Skip,
+    /// Hand the item to the handler up to `attempts` more times, and drop it
+    /// if the handler keeps failing.
+    Retry { attempts: usize },
+    /// Close the channel as poisoned, with [`ChannelPoisoned`] as the reason,
+    /// and treat the item like the rest of the batch.
+    Halt,
+}
+
+impl<T> Receiver<T> {
+    /// Attempt to receive up to `batch_size` items, transforming each one in
+    /// ring memory before it is handed to `handler`.
+    ///
+    /// `transform` runs on the item while it is still in its slot, with
+    /// `scratch` as temporary memory, which spares decompression or decryption
+    /// stages a copy into a second channel. Items the transform fails on are
+    /// handed to `on_error` together with the error instead of `handler`. Waits
+    /// like [`recv`](Self::recv) if no item is available.
+    ///
+    /// # Panics
+    /// Panics on broadcast channels, where items are shared between receivers.
+    pub fn map_in_place<F, E, H, R>(
+        &self,
+        batch_size: usize,
+        scratch: &mut Scratch,
+        mut transform: F,
+        handler: &H,
+        mut on_error: R,
+    ) -> RecvState
+    where
+        F: FnMut(&mut T, &mut Scratch) -> Result<(), E>,
+        H: Fn(T),
+        R: FnMut(T, E),
+    {
+        assert!(
+            !self.retains(),
+            "cannot transform items of a broadcast channel in place"
+        );
+        let mut claimed = match self.claim_or_wait(batch_size) {
+            Ok(claimed) => claimed,
+            Err(state) => return state,
+        };
+
+        while let Some(item) = claimed.peek_mut() {
+            let result = transform(item, scratch);
+            let item = claimed.next().expect("peeked item is claimed");
+            match result {
+                Ok(()) => handler(item),
+                Err(error) => on_error(item, error),
+            }
+        }
+        RecvState::Received
+    }
+
+    /// Attempt to receive up to `batch_size` items, handing each one to
+    /// `handler` in its slot.
+    ///
+    /// On channels created with a factory, such as [`spsc_with_factory`](crate::channels::spsc_with_factory), the
+    /// items stay in the ring for producers to reuse; otherwise they are
+    /// dropped once handled. Waits like [`recv`](Self::recv) if no item is available.
+    ///
+    /// # Panics
+    /// Panics on broadcast channels, where items are shared between receivers.
+    pub fn recv_in_place<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(&mut T),
+    {
+        assert!(
+            !self.retains(),
+            "cannot receive items of a broadcast channel in place"
+        );
+        let mut claimed = match self.claim_or_wait(batch_size) {
+            Ok(claimed) => claimed,
+            Err(state) => return state,
+        };
+
+        while let Some(item) = claimed.peek_mut() {
+            handler(item);
+            claimed.advance();
+        }
+        RecvState::Received
+    }
+
+    /// Attempt to receive up to `batch_size` items, handing each one to a
+    /// fallible `handler` in its slot.
+    ///
+    /// Items the handler succeeds on are consumed like in
+    /// [`recv_in_place`](Self::recv_in_place). The first failure stops the
+    /// batch: `policy` decides what happens to the failed item, and the error
+    /// is returned together with the item's sequence. The rest of the batch
+    /// stays in the buffer on single-consumer channels, and is dropped on
+    /// multi-consumer channels, whose other receivers have moved past it.
+    /// Waits like [`recv`](Self::recv) if no item is available, and reports
+    /// [`RecvState::Disconnected`] once the channel is poisoned.
+    ///
+    /// # Errors
+    /// Returns [`HandlerFailed`] with the sequence and the error of the item
+    /// the handler failed on.
+    ///
+    /// # Panics
+    /// Panics on broadcast channels, where items are shared between receivers.
+    pub fn recv_fallible<H, E>(
+        &self,
+        batch_size: usize,
+        mut handler: H,
+        policy: ErrorPolicy,
+    ) -> Result<RecvState, HandlerFailed<E>>
+    where
+        H: FnMut(&mut T) -> Result<(), E>,
+    {
+        assert!(
+            !self.retains(),
+            "cannot receive items of a broadcast channel in place"
+        );
+        if self.is_poisoned() {
+            return Ok(RecvState::Disconnected);
+        }
+        let mut claimed = match self.claim_or_wait(batch_size) {
+            Ok(claimed) => claimed,
+            Err(state) => return Ok(state),
+        };
+
+        loop {
+            let sequence = claimed.sequence();
+            let Some(item) = claimed.peek_mut() else {
+                break;
+            };
+            let mut result = handler(item);
+            if let ErrorPolicy::Retry { attempts } = policy {
+                for _ in 0..attempts {
+                    let Err(_) = result else { break };
+                    result = handler(claimed.peek_mut().expect("failed item is claimed"));
+                }
+            }
+            let Err(error) = result else {
+                claimed.advance();
+                continue;
+            };
+            match policy {
+                ErrorPolicy::Halt => {
+                    let reason = Arc::new(ChannelPoisoned { sequence });
+                    self.coordinator().close(Some(reason));
+                }
+                ErrorPolicy::Skip | ErrorPolicy::Retry { .. } => claimed.advance(),
+            }
+            return Err(HandlerFailed { sequence, error });
+        }
+        Ok(RecvState::Received)
+    }
+
+    /// Attempt to receive up to `batch_size` items as slices of the ring buffer.
+    ///
+    /// Hands `handler` the claimed items in place, as one contiguous slice, or
+    /// two if the batch wraps around the end of the ring, which spares
+    /// high-throughput decoders a copy per item. Waits like [`recv`](Self::recv)
+    /// if no item is available.
+    pub fn recv_slices<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        T: Copy,
+        H: Fn(&[T]),
+    {
+        let mut claimed = match self.claim_or_wait(batch_size) {
+            Ok(claimed) => claimed,
+            Err(state) => return state,
+        };
+
+        let (first, second) = claimed.as_slices();
+        handler(first);
+        if !second.is_empty() {
+            handler(second);
+        }
+        claimed.consume();
+        RecvState::Received
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{ErrorPolicy, RecvResult, RecvState, spsc_with_factory};
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::errors::{ChannelPoisoned, ScratchExhausted};
+    use crate::testing::{filled_spsc, spinning_spsc};
+    use crate::transform::Scratch;
+    use std::cell::{Cell, RefCell};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_map_in_place_routes_failed_items_to_on_error() {
+        let (tx, rx) = spinning_spsc::<Vec<u8>>(8);
+        tx.send(vec![1, 2, 3]).unwrap();
+        tx.send(vec![0; 16]).unwrap();
+
+        let mut scratch = Scratch::new(4);
+        let received = RefCell::new(Vec::new());
+        let mut failed = Vec::new();
+        let state = rx.map_in_place(
+            8,
+            &mut scratch,
+            |frame: &mut Vec<u8>, scratch: &mut Scratch| {
+                let reversed = scratch.take(frame.len())?;
+                reversed.copy_from_slice(frame);
+                reversed.reverse();
+                frame.copy_from_slice(reversed);
+                Ok::<(), ScratchExhausted>(())
+            },
+            &|frame| received.borrow_mut().push(frame),
+            |frame, error| failed.push((frame.len(), error.requested)),
+        );
+
+        assert_eq!(state, RecvState::Received);
+        assert_eq!(received.into_inner(), vec![vec![3, 2, 1]]);
+        assert_eq!(failed, vec![(16, 16)]);
+    }
+
+    #[test]
+    fn test_recv_slices_splits_a_batch_that_wraps() {
+        let (tx, rx) = spinning_spsc::<u32>(8);
+        tx.send_n(0..6).unwrap();
+        assert_eq!(rx.try_recv_batch(8, &|_| {}), RecvResult::Processed(6));
+        tx.send_n(6..11).unwrap();
+
+        let slices = RefCell::new(Vec::new());
+        let state = rx.recv_slices(8, &|slice: &[u32]| slices.borrow_mut().push(slice.to_vec()));
+        assert_eq!(state, RecvState::Received);
+        assert_eq!(slices.into_inner(), vec![vec![6, 7], vec![8, 9, 10]]);
+    }
+
+    #[test]
+    fn test_factory_slots_are_reused_in_place() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let counter = created.clone();
+        let (tx, rx) = spsc_with_factory(
+            4,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+            move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Vec::<u32>::with_capacity(16)
+            },
+        );
+
+        let sums = RefCell::new(Vec::new());
+        for round in 0..10 {
+            let mut slot = tx.claim_existing().unwrap();
+            assert_eq!(slot.capacity(), 16);
+            slot.clear();
+            slot.extend([round, round]);
+            slot.commit();
+            rx.recv_in_place(4, &|item: &mut Vec<u32>| {
+                sums.borrow_mut().push(item.iter().sum::<u32>())
+            });
+        }
+
+        assert_eq!(created.load(Ordering::Relaxed), 4);
+        assert_eq!(
+            sums.into_inner(),
+            (0..10).map(|round| round * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_fallible_handlers_follow_the_error_policy() {
+        let (tx, rx) = filled_spsc::<u32>(8, 1..7);
+        let received = RefCell::new(Vec::new());
+        let failures = Cell::new(0);
+        let handler = |item: &mut u32| match *item {
+            3 => Err("three"),
+            4 if failures.replace(failures.get() + 1) < 2 => Err("four"),
+            _ => {
+                received.borrow_mut().push(*item);
+                Ok(())
+            }
+        };
+
+        let failed = rx.recv_fallible(8, handler, ErrorPolicy::Skip).unwrap_err();
+        assert_eq!((failed.sequence, failed.error), (2, "three"));
+        let retry = ErrorPolicy::Retry { attempts: 2 };
+        assert_eq!(rx.recv_fallible(1, handler, retry), Ok(RecvState::Received));
+        assert_eq!(*received.borrow(), [1, 2, 4]);
+
+        tx.send(3).unwrap();
+        assert_eq!(
+            rx.recv_fallible(2, handler, ErrorPolicy::Halt),
+            Ok(RecvState::Received)
+        );
+        let failed = rx.recv_fallible(8, handler, ErrorPolicy::Halt).unwrap_err();
+        assert_eq!(failed.sequence, 6);
+        assert!(rx.is_poisoned());
+        let reason = tx.send(7).unwrap_err().reason().cloned().unwrap();
+        assert!(reason.is::<ChannelPoisoned>());
+        assert_eq!(
+            rx.recv_fallible(8, handler, ErrorPolicy::Skip),
+            Ok(RecvState::Disconnected)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Non-consuming reports of the state of a channel, for diagnosing stalls.
+//!
+//! With the `inspect` feature, [`Sender::debug_state`] and
+//! [`Receiver::debug_state`] report the sequences of the ring buffer without
+//! taking any item: how far producers claimed and published, where the
+//! consumers are, and which sequences producers wait for. A consumer stuck at
+//! sequence `N` while producers are at `M` shows up as a position of `N` and a
+//! published sequence of `M`, and a producer that claimed a slot and never
+//! published it shows up as an unpublished sequence that stops the published
+//! sequence from advancing.
+//!
+//! [`Receiver::debug_state_with_items`] also clones the items the receiver has
+//! yet to take.
+//!
+//! [`Sender::debug_state`]: crate::channels::Sender::debug_state
+//! [`Receiver::debug_state`]: crate::channels::Receiver::debug_state
+//! [`Receiver::debug_state_with_items`]: crate::channels::Receiver::debug_state_with_items
+
+use std::fmt;
+
+/// The sequences of a channel at one point in time, see the [module documentation](self).
+///
+/// Sequences start at `-1` and count every item ever published, wrapping
+/// around the ring buffer every [`capacity`](Self::capacity) items.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugState<T = ()> {
+    pub(crate) capacity: usize,
+    pub(crate) claimed: i64,
+    pub(crate) published: i64,
+    pub(crate) unpublished: usize,
+    pub(crate) gating: i64,
+    pub(crate) gating_minimum: i64,
+    pub(crate) registered: Vec<i64>,
+    pub(crate) position: Option<i64>,
+    pub(crate) items: Option<Vec<T>>,
+}
+
+impl<T> DebugState<T> {
+    /// Returns the number of slots of the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the highest sequence claimed by producers, published or not.
+    pub fn claimed(&self) -> i64 {
+        self.claimed
+    }
+
+    /// Returns the highest sequence up to which every item is published, the
+    /// last item consumers can take.
+    pub fn published(&self) -> i64 {
+        self.published
+    }
+
+    /// Returns the number of sequences after [`published`](Self::published)
+    /// that producers claimed and have not published yet.
+    ///
+    /// Sequences published after an unpublished one are not counted, since
+    /// consumers cannot take them before it.
+    pub fn unpublished(&self) -> usize {
+        self.unpublished
+    }
+
+    /// Returns the gating sequence of the consumers, the last sequence whose
+    /// slot they handed back to producers.
+    pub fn gating(&self) -> i64 {
+        self.gating
+    }
+
+    /// Returns the sequence producers wait for before reusing slots: the
+    /// gating sequence, lowered by the [`registered`](Self::registered) ones
+    /// and by the credits of credit-paced channels.
+    pub fn gating_minimum(&self) -> i64 {
+        self.gating_minimum
+    }
+
+    /// Returns the gating sequences registered at runtime.
+    pub fn registered(&self) -> &[i64] {
+        &self.registered
+    }
+
+    /// Returns the last sequence the reporting receiver took, or `None` if a
+    /// sender reported.
+    ///
+    /// Receivers that split the items between them share a position: the
+    /// last sequence any of them took.
+    pub fn position(&self) -> Option<i64> {
+        self.position
+    }
+
+    /// Returns the number of published items the consumers have yet to take,
+    /// from the [`position`](Self::position) of the reporting receiver if any.
+    pub fn backlog(&self) -> usize {
+        (self.published - self.position.unwrap_or(self.gating)).max(0) as usize
+    }
+
+    /// Returns clones of the items the reporting receiver has yet to take,
+    /// oldest first, if they were requested and could be cloned safely.
+    pub fn items(&self) -> Option<&[T]> {
+        self.items.as_deref()
+    }
+
+    /// Attach clones of the items ahead of the receiver.
+    pub(crate) fn with_items<U>(self, items: Option<Vec<U>>) -> DebugState<U> {
+        DebugState {
+            capacity: self.capacity,
+            claimed: self.claimed,
+            published: self.published,
+            unpublished: self.unpublished,
+            gating: self.gating,
+            gating_minimum: self.gating_minimum,
+            registered: self.registered,
+            position: self.position,
+            items,
+        }
+    }
+}
+
+impl<T> fmt::Display for DebugState<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "producers claimed {} and published {} ({} unpublished), ",
+            self.claimed, self.published, self.unpublished
+        )?;
+        if let Some(position) = self.position {
+            write!(f, "receiver at {position}, ")?;
+        }
+        write!(
+            f,
+            "consumers released {}, producers gated on {}",
+            self.gating, self.gating_minimum
+        )
+    }
+}
+
+#[cfg(all(test, feature = "mp", not(feature = "loom")))]
+mod tests {
+    use crate::channels::mpsc;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+
+    #[test]
+    fn test_debug_state_reports_a_stalled_claim_without_consuming() {
+        let (tx, mut rx) = mpsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        for value in 0..5 {
+            tx.send(value).unwrap();
+        }
+        assert_eq!(rx.try_iter().take(2).count(), 2);
+
+        let other = tx.clone();
+        let claimed = tx.claim().unwrap();
+        other.send(5).unwrap();
+
+        let state = rx.debug_state_with_items();
+        assert_eq!(state.claimed(), 6);
+        assert_eq!(state.published(), 4);
+        assert_eq!(state.unpublished(), 1);
+        assert_eq!(state.gating(), 1);
+        assert_eq!(state.position(), Some(1));
+        assert_eq!(state.backlog(), 3);
+        assert_eq!(state.items(), Some(&[2, 3, 4][..]));
+
+        let state = tx.debug_state();
+        assert_eq!(state.position(), None);
+        assert_eq!(state.items(), None);
+        assert_eq!(
+            state.to_string(),
+            "producers claimed 6 and published 4 (1 unpublished), \
+             consumers released 1, producers gated on 1"
+        );
+
+        drop(claimed);
+        let received: Vec<u32> = rx.try_iter().collect();
+        assert_eq!(received, [2, 3, 4, 0, 5]);
+    }
+}
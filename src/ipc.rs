@@ -0,0 +1,542 @@
+//! Single-producer single-consumer channels between processes.
+//!
+//! An [`IpcSender`] creates a file and maps it into memory, laying out a
+//! header with the cursor and gating sequences of the ring followed by its
+//! slots, and an [`IpcReceiver`] in another process maps the same file. Items
+//! are copied into and out of the shared slots, so they must be [`Pod`]; the
+//! sequences are published with the same release and acquire pairs as the
+//! in-process rings. A file under `/dev/shm` keeps the mapping in memory.
+//!
+//! The header records a layout version together with the size, alignment and
+//! capacity of the slots, and [`IpcReceiver::open`] refuses a mapping that
+//! does not match what it expects. Each side also records its process id, so
+//! that a peer that crashed is noticed: the receiver reports a disconnect once
+//! it drained what a dead producer published, and sends to a dead consumer
+//! fail. Neither side removes the file, which is up to the application.
+//!
+//! Processes have no common wait strategy, so both sides yield the thread
+//! while they wait. Mapping uses `mmap` on Linux, and creating or opening a
+//! channel fails with [`io::ErrorKind::Unsupported`] on other systems.
+
+use crate::channels::RecvState;
+use crate::errors::{SendError, TrySendError};
+use crate::utils::{CachePadded, Indexing, assert_buffer_size_is_not_zero};
+use std::cell::Cell;
+use std::fs::OpenOptions;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+
+/// Marks the start of a mapping laid out by this module.
+const MAGIC: u64 = u64::from_le_bytes(*b"CHRSIPC\0");
+
+/// Version of the layout of the header and slots, bumped on every change.
+const LAYOUT_VERSION: u32 = 1;
+
+/// The side of a channel has not attached yet.
+const ABSENT: u32 = 0;
+/// The side of a channel is attached.
+const ATTACHED: u32 = 1;
+/// The side of a channel detached and will not come back.
+const DETACHED: u32 = 2;
+
+/// Types whose values can be shared with another process as plain bytes.
+///
+/// # Safety
+/// Every bit pattern of the size of the type must be a valid value, and the
+/// type must not hold pointers or references, which mean nothing in another
+/// address space. Structs must be `#[repr(C)]` so that both processes agree on
+/// their layout.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+pod!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+pod!(f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A process attached to one side of a channel.
+#[repr(C)]
+struct Peer {
+    pid: AtomicU32,
+    state: AtomicU32,
+}
+
+impl Peer {
+    /// Returns `true` once the side detached.
+    fn is_detached(&self) -> bool {
+        self.state.load(Ordering::Acquire) == DETACHED
+    }
+
+    /// Returns `true` once the side is gone, either detached or crashed,
+    /// which takes a system call while it is attached.
+    fn is_gone(&self) -> bool {
+        match self.state.load(Ordering::Acquire) {
+            ATTACHED => !imp::is_alive(self.pid.load(Ordering::Relaxed)),
+            state => state == DETACHED,
+        }
+    }
+}
+
+/// The header at the start of a mapping, followed by the slots.
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    version: u32,
+    header_size: u32,
+    slot_size: u64,
+    slot_align: u64,
+    capacity: u64,
+    producer: Peer,
+    consumer: Peer,
+    cursor: CachePadded<AtomicI64>,
+    gating: CachePadded<AtomicI64>,
+}
+
+/// Returns the offset of the first slot of items of type `T`.
+fn slots_offset<T>() -> usize {
+    size_of::<Header>().next_multiple_of(align_of::<T>())
+}
+
+/// Returns the length of a mapping of `capacity` slots of type `T`.
+fn mapping_len<T>(capacity: usize) -> io::Result<usize> {
+    size_of::<T>()
+        .checked_mul(capacity)
+        .and_then(|slots| slots.checked_add(slots_offset::<T>()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "capacity is too large"))
+}
+
+/// A ring laid out in a shared mapping.
+struct Ring<T> {
+    mapping: imp::Mapping,
+    indexing: Indexing,
+    capacity: i64,
+    items: PhantomData<T>,
+}
+
+impl<T: Pod> Ring<T> {
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping starts with an initialized header, which is
+        // aligned since mappings are page aligned.
+        unsafe { &*self.mapping.ptr.cast::<Header>() }
+    }
+
+    /// Returns a pointer to the slot of `sequence`.
+    fn slot(&self, sequence: i64) -> *mut T {
+        let index = self.indexing.wrap(sequence, 0);
+        // SAFETY: the index is within the capacity the mapping was sized for.
+        unsafe {
+            self.mapping
+                .ptr
+                .add(slots_offset::<T>())
+                .cast::<T>()
+                .add(index)
+        }
+    }
+}
+
+/// The sending half of an inter-process channel, created by [`IpcSender::create`].
+///
+/// A channel has a single producer, so the sender can be moved to another
+/// thread but not shared.
+pub struct IpcSender<T: Pod> {
+    ring: Ring<T>,
+    next: Cell<i64>,
+    cached_gating: Cell<i64>,
+}
+
+impl<T: Pod> IpcSender<T> {
+    /// Create the file at `path`, replacing any file there, and lay out a
+    /// channel of `capacity` slots in it.
+    ///
+    /// # Errors
+    /// Returns the error of the file system or of the mapping, and
+    /// [`io::ErrorKind::Unsupported`] on systems without shared mappings.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn create(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+        assert_buffer_size_is_not_zero(capacity);
+        let len = mapping_len::<T>(capacity)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+        let ring = Ring {
+            mapping: imp::Mapping::new(&file, len)?,
+            indexing: Indexing::new(capacity),
+            capacity: capacity as i64,
+            items: PhantomData,
+        };
+
+        let header = ring.mapping.ptr.cast::<Header>();
+        // SAFETY: the mapping is large enough for the header, aligned, and
+        // nobody reads it before the magic number is published below.
+        unsafe {
+            header.write(Header {
+                magic: AtomicU64::new(0),
+                version: LAYOUT_VERSION,
+                header_size: size_of::<Header>() as u32,
+                slot_size: size_of::<T>() as u64,
+                slot_align: align_of::<T>() as u64,
+                capacity: capacity as u64,
+                producer: Peer {
+                    pid: AtomicU32::new(std::process::id()),
+                    state: AtomicU32::new(ATTACHED),
+                },
+                consumer: Peer {
+                    pid: AtomicU32::new(0),
+                    state: AtomicU32::new(ABSENT),
+                },
+                cursor: CachePadded(AtomicI64::new(-1)),
+                gating: CachePadded(AtomicI64::new(-1)),
+            });
+        }
+        ring.header().magic.store(MAGIC, Ordering::Release);
+
+        Ok(Self {
+            ring,
+            next: Cell::new(0),
+            cached_gating: Cell::new(-1),
+        })
+    }
+
+    /// Send a single value, yielding the thread while the ring is full.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the receiver detached
+    /// or its process died.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = value;
+        loop {
+            value = match self.try_send(value) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Closed(value, reason)) => {
+                    return Err(SendError::Closed(value, reason));
+                }
+                Err(error) => error.into_inner(),
+            };
+            std::thread::yield_now();
+        }
+    }
+
+    /// Try to send a single value without waiting for free space.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the ring has no free slot.
+    /// - [`TrySendError::Closed`] if the receiver detached or its process died.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let header = self.ring.header();
+        if header.consumer.is_detached() {
+            return Err(TrySendError::Closed(value, None));
+        }
+        let sequence = self.next.get();
+        let wrap_point = sequence - self.ring.capacity;
+        if wrap_point > self.cached_gating.get() {
+            self.cached_gating
+                .set(header.gating.load(Ordering::Acquire));
+            if wrap_point > self.cached_gating.get() {
+                // Only a full ring is worth checking for a crashed consumer.
+                return match header.consumer.is_gone() {
+                    true => Err(TrySendError::Closed(value, None)),
+                    false => Err(TrySendError::Full(value)),
+                };
+            }
+        }
+
+        // SAFETY: the consumer moved past the slot, so nobody else accesses it.
+        unsafe { self.ring.slot(sequence).write(value) };
+        header.cursor.store(sequence, Ordering::Release);
+        self.next.set(sequence + 1);
+        Ok(())
+    }
+
+    /// Returns `true` once the receiver detached or its process died.
+    pub fn is_closed(&self) -> bool {
+        self.ring.header().consumer.is_gone()
+    }
+}
+
+impl<T: Pod> Drop for IpcSender<T> {
+    fn drop(&mut self) {
+        let header = self.ring.header();
+        header.producer.state.store(DETACHED, Ordering::Release);
+    }
+}
+
+/// The receiving half of an inter-process channel, created by [`IpcReceiver::open`].
+///
+/// A channel has a single consumer, so the receiver can be moved to another
+/// thread but not shared.
+pub struct IpcReceiver<T: Pod> {
+    ring: Ring<T>,
+    next: Cell<i64>,
+}
+
+impl<T: Pod> IpcReceiver<T> {
+    /// Map the channel laid out in the file at `path` by [`IpcSender::create`]
+    /// and attach to it as its consumer.
+    ///
+    /// # Errors
+    /// - [`io::ErrorKind::InvalidData`] if the file holds no channel, a channel
+    ///   of another layout version, or slots of another size or alignment than
+    ///   those of `T`.
+    /// - [`io::ErrorKind::ResourceBusy`] if another consumer attached before.
+    /// - The error of the file system or of the mapping, and
+    ///   [`io::ErrorKind::Unsupported`] on systems without shared mappings.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len < size_of::<Header>() {
+            return Err(invalid("the file is too short to hold a channel"));
+        }
+        let mapping = imp::Mapping::new(&file, len)?;
+
+        // SAFETY: the mapping is large enough for a header and aligned; the
+        // fields are only trusted once the magic number is checked.
+        let header = unsafe { &*mapping.ptr.cast::<Header>() };
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(invalid("the file holds no channel"));
+        }
+        if header.version != LAYOUT_VERSION || header.header_size as usize != size_of::<Header>() {
+            return Err(invalid("the channel has another layout version"));
+        }
+        if header.slot_size != size_of::<T>() as u64 || header.slot_align != align_of::<T>() as u64
+        {
+            return Err(invalid("the channel holds items of another type"));
+        }
+        let capacity = header.capacity as usize;
+        if capacity == 0 || mapping_len::<T>(capacity)? != len {
+            return Err(invalid("the channel has a corrupt capacity"));
+        }
+
+        // A consumer that crashed can be replaced, one that is attached cannot.
+        let consumer = &header.consumer;
+        let state = consumer.state.load(Ordering::Acquire);
+        let replaceable = state == ABSENT || (state == ATTACHED && consumer.is_gone());
+        if !replaceable
+            || consumer
+                .state
+                .compare_exchange(state, ATTACHED, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                "the channel already has a consumer",
+            ));
+        }
+        consumer.pid.store(std::process::id(), Ordering::Relaxed);
+
+        let next = header.gating.load(Ordering::Acquire) + 1;
+        Ok(Self {
+            ring: Ring {
+                mapping,
+                indexing: Indexing::new(capacity),
+                capacity: capacity as i64,
+                items: PhantomData,
+            },
+            next: Cell::new(next),
+        })
+    }
+
+    /// Attempt to receive up to `batch_size` items.
+    ///
+    /// Invokes `handler` for each item. If no item is available, yields the
+    /// thread once, unless the producer detached or its process died, in
+    /// which case [`RecvState::Disconnected`] is returned.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        let header = self.ring.header();
+        let next = self.next.get();
+        let batch_size = batch_size.min(self.ring.capacity as usize) as i64;
+        let highest = || {
+            let cursor = header.cursor.load(Ordering::Acquire);
+            cursor.min(next + batch_size - 1)
+        };
+        let mut available = highest();
+        if available < next {
+            if !header.producer.is_gone() {
+                std::thread::yield_now();
+                return RecvState::Empty;
+            }
+            // Read again, since the producer may have published before it left.
+            available = highest();
+            if available < next {
+                return RecvState::Disconnected;
+            }
+        }
+
+        for sequence in next..=available {
+            // SAFETY: the slot was published and is not reused before the
+            // gating sequence moves past it.
+            handler(unsafe { self.ring.slot(sequence).read() });
+        }
+        header.gating.store(available, Ordering::Release);
+        self.next.set(available + 1);
+        RecvState::Received
+    }
+
+    /// Returns `true` once the producer detached or its process died.
+    pub fn is_disconnected(&self) -> bool {
+        self.ring.header().producer.is_gone()
+    }
+}
+
+impl<T: Pod> Drop for IpcReceiver<T> {
+    fn drop(&mut self) {
+        let header = self.ring.header();
+        header.consumer.state.store(DETACHED, Ordering::Release);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    const PROT_READ: i32 = 1;
+    const PROT_WRITE: i32 = 2;
+    const MAP_SHARED: i32 = 1;
+    const EPERM: i32 = 1;
+
+    unsafe extern "C" {
+        fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+        fn munmap(addr: *mut u8, len: usize) -> i32;
+        fn kill(pid: i32, signal: i32) -> i32;
+    }
+
+    /// A shared mapping of a file.
+    pub(super) struct Mapping {
+        pub(super) ptr: *mut u8,
+        len: usize,
+    }
+
+    // SAFETY: the mapping is plain memory that any thread may access.
+    unsafe impl Send for Mapping {}
+
+    impl Mapping {
+        pub(super) fn new(file: &File, len: usize) -> io::Result<Self> {
+            let prot = PROT_READ | PROT_WRITE;
+            // SAFETY: a fresh mapping of an open file, which the kernel keeps
+            // alive after the file is closed.
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    prot,
+                    MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            match ptr as isize {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(Self { ptr, len }),
+            }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            // SAFETY: the mapping is not used any more.
+            unsafe { munmap(self.ptr, self.len) };
+        }
+    }
+
+    /// Returns `true` if the process `pid` exists.
+    pub(super) fn is_alive(pid: u32) -> bool {
+        // SAFETY: signal zero only checks that the process can be signaled.
+        match unsafe { kill(pid as i32, 0) } {
+            0 => true,
+            _ => io::Error::last_os_error().raw_os_error() == Some(EPERM),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::fs::File;
+    use std::io;
+
+    pub(super) struct Mapping {
+        pub(super) ptr: *mut u8,
+    }
+
+    // SAFETY: a mapping is never created.
+    unsafe impl Send for Mapping {}
+
+    impl Mapping {
+        pub(super) fn new(_file: &File, _len: usize) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "shared mappings are not supported on this system",
+            ))
+        }
+    }
+
+    pub(super) fn is_alive(_pid: u32) -> bool {
+        true
+    }
+}
+
+#[cfg(all(test, target_os = "linux", not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::errors::TrySendError;
+    use crate::ipc::{IpcReceiver, IpcSender};
+    use std::cell::RefCell;
+    use std::io;
+
+    #[test]
+    fn test_items_cross_a_shared_mapping() {
+        let path = std::env::temp_dir().join(format!("channels-rs-ipc-{}", std::process::id()));
+        let tx = IpcSender::<[u64; 2]>::create(&path, 4).unwrap();
+        let error = IpcReceiver::<u64>::open(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // The receiver maps the file again, as another process would.
+        let rx = IpcReceiver::<[u64; 2]>::open(&path).unwrap();
+        let error = IpcReceiver::<[u64; 2]>::open(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::ResourceBusy);
+        for value in 0..4 {
+            tx.try_send([value, value * 10]).unwrap();
+        }
+        assert!(matches!(tx.try_send([4, 40]), Err(TrySendError::Full(_))));
+
+        let consumer = std::thread::spawn(move || {
+            let received = RefCell::new(Vec::new());
+            while rx.recv(3, &|[value, tenfold]| {
+                assert_eq!(tenfold, value * 10);
+                received.borrow_mut().push(value);
+            }) != RecvState::Disconnected
+            {}
+            received.into_inner()
+        });
+        for value in 4..100 {
+            tx.send([value, value * 10]).unwrap();
+        }
+        drop(tx);
+        assert!(consumer.join().unwrap().into_iter().eq(0..100));
+
+        let tx = IpcSender::<u32>::create(&path, 4).unwrap();
+        drop(IpcReceiver::<u32>::open(&path).unwrap());
+        assert!(tx.is_closed());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+//! End-to-end latency of the items of a channel.
+//!
+//! An [`Instrumented`] sender wraps every item in a [`Stamped`] envelope that
+//! records when it was sent, and an [`Instrumented`] receiver unwraps it and
+//! records how long the item spent in the channel into a [`LatencyHistogram`]
+//! shared by both halves. [`instrument`] wraps both halves of a channel of
+//! stamped items:
+//!
+//! ```
+//! use channels_rs::channels::spsc;
+//! use channels_rs::latency::{Stamped, instrument};
+//! use channels_rs::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+//!
+//! let (tx, rx) = instrument(spsc::<Stamped<u32>>(
+//!     64,
+//!     ProducerWaitStrategyKind::Yielding,
+//!     ConsumerWaitStrategyKind::Yielding,
+//! ));
+//! tx.send(7).unwrap();
+//! rx.recv(8, &|value| assert_eq!(value, 7));
+//! assert_eq!(rx.latency().count(), 1);
+//! ```
+//!
+//! The histogram keeps HDR-style buckets: exact below 32 nanoseconds, and 32
+//! buckets for every power of two above, so every recorded latency is off by
+//! less than 1/32 of its value. Recording is a few relaxed atomic additions,
+//! and [`LatencyHistogram::snapshot`] copies the counts for percentile queries.
+//! Timestamps are only taken through these wrappers, which need the `latency`
+//! feature, so plain channels never read the clock.
+
+use crate::channels::{PollOutcome, Receiver, RecvResult, Sender};
+use crate::errors::{SendError, TrySendError};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of bits of a latency every bucket resolves.
+const SUB_BUCKET_BITS: u32 = 5;
+
+/// Number of buckets per power of two.
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Number of buckets covering every `u64` of nanoseconds.
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Returns the bucket of a latency of `nanos` nanoseconds.
+fn bucket(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let shift = 63 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (nanos >> shift) as usize - SUB_BUCKETS;
+    (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+}
+
+/// Returns the highest latency in nanoseconds that falls into `bucket`.
+fn highest_in(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = (bucket / SUB_BUCKETS - 1) as u32;
+    let low = ((SUB_BUCKETS + bucket % SUB_BUCKETS) as u64) << shift;
+    low + ((1 << shift) - 1)
+}
+
+/// An item together with the instant it was sent, as carried by the channel
+/// of an [`Instrumented`] pair.
+#[derive(Clone, Debug)]
+pub struct Stamped<T> {
+    sent_at: Instant,
+    value: T,
+}
+
+/// A histogram of latencies that many threads record into concurrently.
+pub struct LatencyHistogram {
+    counts: Box<[AtomicU64]>,
+    total: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl LatencyHistogram {
+    /// Create an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a latency, saturating at `u64::MAX` nanoseconds.
+    pub fn record(&self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(nanos, Ordering::Relaxed);
+        self.min.fetch_min(nanos, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns a copy of the recorded latencies.
+    ///
+    /// Latencies recorded while the copy is taken may be missing from some of
+    /// its statistics.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let counts: Box<[u64]> = self
+            .counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        LatencySnapshot {
+            count: counts.iter().sum(),
+            counts,
+            total: self.total.load(Ordering::Relaxed),
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The latencies recorded by a [`LatencyHistogram`] up to a point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    counts: Box<[u64]>,
+    count: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl LatencySnapshot {
+    /// Returns the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the lowest recorded latency, or zero if none was recorded.
+    pub fn min(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            _ => Duration::from_nanos(self.min),
+        }
+    }
+
+    /// Returns the highest recorded latency.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Returns the average recorded latency, or zero if none was recorded.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos(self.total / count),
+        }
+    }
+
+    /// Returns the latency that `percentile` percent of the recorded
+    /// latencies do not exceed, up to the resolution of the buckets, or zero
+    /// if none was recorded.
+    ///
+    /// # Panics
+    /// Panics if `percentile` is not between `0.0` and `100.0`.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "percentile must be between 0 and 100"
+        );
+        let rank = ((percentile / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(highest_in(bucket).min(self.max));
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+/// A sender or receiver of [`Stamped`] items that records their latency.
+///
+/// See the [module documentation](self).
+pub struct Instrumented<C> {
+    inner: C,
+    histogram: Arc<LatencyHistogram>,
+}
+
+/// A sender that stamps the items it sends.
+pub type InstrumentedSender<T> = Instrumented<Sender<Stamped<T>>>;
+
+/// A receiver that records the latency of the items it receives.
+pub type InstrumentedReceiver<T> = Instrumented<Receiver<Stamped<T>>>;
+
+/// Wrap both halves of a channel of [`Stamped`] items, sharing a new histogram.
+pub fn instrument<T>(
+    (sender, receiver): (Sender<Stamped<T>>, Receiver<Stamped<T>>),
+) -> (InstrumentedSender<T>, InstrumentedReceiver<T>) {
+    let histogram = Arc::new(LatencyHistogram::new());
+    (
+        Instrumented::new(sender, histogram.clone()),
+        Instrumented::new(receiver, histogram),
+    )
+}
+
+impl<C> Instrumented<C> {
+    /// Wrap `inner`, recording latencies into `histogram`.
+    pub fn new(inner: C, histogram: Arc<LatencyHistogram>) -> Self {
+        Self { inner, histogram }
+    }
+
+    /// Returns the histogram the latencies are recorded into.
+    pub fn histogram(&self) -> &Arc<LatencyHistogram> {
+        &self.histogram
+    }
+
+    /// Returns a snapshot of the recorded latencies.
+    pub fn latency(&self) -> LatencySnapshot {
+        self.histogram.snapshot()
+    }
+
+    /// Returns the wrapped sender or receiver.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Unwrap the sender or receiver.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Clone> Clone for Instrumented<C> {
+    /// Clone the wrapped sender or receiver, recording into the same histogram.
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.histogram.clone())
+    }
+}
+
+impl<T> InstrumentedSender<T> {
+    /// Stamp `value` with the current instant and send it, see [`Sender::send`].
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let stamped = Stamped {
+            sent_at: Instant::now(),
+            value,
+        };
+        self.inner
+            .send(stamped)
+            .map_err(|SendError::Closed(stamped, reason)| SendError::Closed(stamped.value, reason))
+    }
+
+    /// Stamp `value` with the current instant and send it without waiting,
+    /// see [`Sender::try_send`].
+    ///
+    /// # Errors
+    /// Returns the value with the reason it could not be sent.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let stamped = Stamped {
+            sent_at: Instant::now(),
+            value,
+        };
+        self.inner.try_send(stamped).map_err(|error| match error {
+            TrySendError::Full(stamped) => TrySendError::Full(stamped.value),
+            TrySendError::WouldBlock(stamped) => TrySendError::WouldBlock(stamped.value),
+            TrySendError::Closed(stamped, reason) => TrySendError::Closed(stamped.value, reason),
+        })
+    }
+}
+
+impl<T> InstrumentedReceiver<T> {
+    /// Receive up to `batch_size` items like [`Receiver::recv`], recording the
+    /// latency of each one before it is handed to `handler`.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        self.inner
+            .recv(batch_size, &|stamped| handler(self.unstamp(stamped)))
+    }
+
+    /// Receive up to `batch_size` items without waiting like
+    /// [`Receiver::try_recv_batch`], recording the latency of each one.
+    pub fn try_recv_batch<H>(&self, batch_size: usize, handler: &H) -> RecvResult
+    where
+        H: Fn(T),
+    {
+        self.inner
+            .try_recv_batch(batch_size, &|stamped| handler(self.unstamp(stamped)))
+    }
+
+    /// Record the latency of `stamped` and return its value.
+    #[inline(always)]
+    fn unstamp(&self, stamped: Stamped<T>) -> T {
+        self.histogram.record(stamped.sent_at.elapsed());
+        stamped.value
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvResult, spsc};
+    use crate::latency::{LatencyHistogram, Stamped, bucket, highest_in, instrument};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    #[test]
+    fn test_latencies_are_recorded_into_percentiles() {
+        for nanos in [0, 31, 32, 33, 1_000, 123_456_789, u64::MAX] {
+            let bucket = bucket(nanos);
+            assert!(highest_in(bucket) >= nanos);
+            assert!(highest_in(bucket) - nanos <= nanos / 32);
+        }
+
+        let histogram = LatencyHistogram::new();
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count(), 100);
+        assert_eq!(snapshot.min(), Duration::from_micros(1));
+        assert_eq!(snapshot.max(), Duration::from_micros(100));
+        assert_eq!(snapshot.mean(), Duration::from_nanos(50_500));
+        for (percentile, micros) in [(50.0, 50), (99.0, 99), (100.0, 100)] {
+            let latency = snapshot.percentile(percentile).as_nanos() as f64;
+            let expected = (micros * 1_000) as f64;
+            assert!((expected..=expected * 1.04).contains(&latency));
+        }
+
+        let (tx, rx) = instrument(spsc::<Stamped<u32>>(
+            8,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        ));
+        tx.send(1).unwrap();
+        tx.try_send(2).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        let received = RefCell::new(Vec::new());
+        let handler = |value| received.borrow_mut().push(value);
+        assert_eq!(rx.try_recv_batch(8, &handler), RecvResult::Processed(2));
+        assert_eq!(*received.borrow(), [1, 2]);
+        let latency = rx.latency();
+        assert_eq!(latency.count(), 2);
+        assert!(latency.min() >= Duration::from_millis(2));
+        assert_eq!(tx.latency(), latency);
+    }
+}
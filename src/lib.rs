@@ -1,10 +1,69 @@
+pub mod adaptive;
+#[cfg(feature = "affinity")]
+pub mod affinity;
+pub mod audit;
+pub mod autotune;
+#[cfg(feature = "mp")]
 pub(crate) mod availability_buffer;
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub mod bus;
 pub mod channels;
+pub mod combinators;
+pub mod combining;
 pub(crate) mod constants;
 pub mod coordinator;
+pub mod decoding;
+pub mod errors;
+pub(crate) mod failover;
+pub mod fan_in;
+pub mod flow;
+pub mod framed;
+#[cfg(feature = "futures")]
+pub mod futures;
+#[cfg(feature = "mp")]
+pub mod growable;
+pub(crate) mod in_place;
+#[cfg(feature = "inspect")]
+pub mod inspect;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+#[cfg(feature = "latency")]
+pub mod latency;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod ordering;
+pub mod pipeline;
 pub mod poller;
+#[cfg(feature = "mc")]
+pub mod pool;
 pub mod prelude;
+pub mod primitives;
+#[cfg(feature = "mp")]
+pub mod priority;
+pub mod producers;
+#[cfg(feature = "raw")]
+pub mod raw;
+pub mod recycle;
+#[cfg(feature = "registry")]
+pub mod registry;
 pub(crate) mod ring_buffer;
+pub(crate) mod sched;
+pub(crate) mod scoped;
+pub mod select;
 pub(crate) mod sequence;
 pub(crate) mod sequencer;
+#[cfg(feature = "mp")]
+pub mod sharded;
+pub mod spill;
+pub mod static_channels;
+pub(crate) mod sync;
+#[cfg(all(test, not(feature = "loom")))]
+pub(crate) mod testing;
+pub mod timeouts;
+pub mod topology;
+pub mod transform;
 pub(crate) mod utils;
+pub(crate) mod windows;
+
+#[cfg(all(test, feature = "loom"))]
+mod loom_tests;
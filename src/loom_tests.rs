@@ -0,0 +1,289 @@
+//! Loom models of the sequencer and poller protocols and of blocking waits.
+//!
+//! Built with the `loom` feature, every atomic of the crate is a loom atomic,
+//! so these models explore each interleaving of the protocol and each value a
+//! load may return under the orderings it uses. The slots of the ring are
+//! modelled with loom cells, which fail the model on any access to a slot
+//! that is not ordered after the previous one, i.e. whenever the sequences
+//! let a producer and a consumer touch a slot at the same time.
+//!
+//! Run them with `cargo test --features loom --lib`.
+
+use crate::utils::Indexing;
+use loom::cell::UnsafeCell;
+
+/// Check `model` under loom.
+///
+/// Preemptions are bounded to two per execution unless `LOOM_MAX_PREEMPTIONS`
+/// says otherwise, which covers the bugs that need few context switches to
+/// show while keeping the models with three threads at seconds.
+fn model<F>(model: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound.get_or_insert(2);
+    builder.check(model);
+}
+
+/// The slots of a ring, written by producers and read by consumers.
+struct Slots {
+    cells: Box<[UnsafeCell<i64>]>,
+    indexing: Indexing,
+}
+
+// SAFETY: loom checks that every access to a cell is ordered with the others.
+unsafe impl Sync for Slots {}
+
+impl Slots {
+    fn new(buffer_size: usize) -> Self {
+        Self {
+            cells: (0..buffer_size).map(|_| UnsafeCell::new(-1)).collect(),
+            indexing: Indexing::new(buffer_size),
+        }
+    }
+
+    /// Write the slot of a claimed `sequence`.
+    fn write(&self, sequence: i64, value: i64) {
+        let cell = &self.cells[self.indexing.wrap(sequence, 0)];
+        // SAFETY: loom fails the model if the claim does not make this exclusive.
+        cell.with_mut(|slot| unsafe { *slot = value });
+    }
+
+    /// Read the slot of a published `sequence`.
+    fn read(&self, sequence: i64) -> i64 {
+        let cell = &self.cells[self.indexing.wrap(sequence, 0)];
+        // SAFETY: loom fails the model if the publish does not order this read.
+        cell.with(|slot| unsafe { *slot })
+    }
+}
+
+mod sequencers {
+    use super::{Slots, model};
+    use crate::sequencer::{ClaimError, Sequencer, SingleProducerSequencer};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    /// Claim the next sequence, yielding to the other threads while the buffer is full.
+    fn claim<S: Sequencer>(sequencer: &S) -> i64 {
+        loop {
+            match sequencer.try_next() {
+                Ok(sequence) => return sequence,
+                Err(ClaimError::Full | ClaimError::Contended) => thread::yield_now(),
+                Err(error) => panic!("unexpected claim error {error:?}"),
+            }
+        }
+    }
+
+    /// Consume `count` items as a single consumer, checking that every slot
+    /// holds the value written for its sequence.
+    fn consume<S: Sequencer>(sequencer: &S, slots: &Slots, count: i64) {
+        let mut next = 0;
+        while next < count {
+            let highest = sequencer.get_highest(next, sequencer.get_cursor_sequence_acquire());
+            if highest < next {
+                thread::yield_now();
+                continue;
+            }
+            for sequence in next..=highest {
+                assert_eq!(slots.read(sequence), sequence);
+            }
+            sequencer.publish_gating_sequence(highest);
+            next = highest + 1;
+        }
+    }
+
+    #[test]
+    fn loom_single_producer_waits_for_the_wrap_point() {
+        model(|| {
+            let sequencer = Arc::new(SingleProducerSequencer::new(1));
+            let slots = Arc::new(Slots::new(1));
+
+            let producer = {
+                let (sequencer, slots) = (sequencer.clone(), slots.clone());
+                thread::spawn(move || {
+                    for _ in 0..2 {
+                        let sequence = claim(&*sequencer);
+                        slots.write(sequence, sequence);
+                        sequencer.publish_cursor_sequence(sequence);
+                    }
+                })
+            };
+            consume(&*sequencer, &slots, 2);
+            producer.join().unwrap();
+        });
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn loom_multi_producer_publishes_through_the_availability_buffer() {
+        use crate::sequencer::MultiProducerSequencer;
+
+        model(|| {
+            let sequencer = Arc::new(MultiProducerSequencer::new(2));
+            let slots = Arc::new(Slots::new(2));
+
+            let producers: Vec<_> = (0..2)
+                .map(|_| {
+                    let (sequencer, slots) = (sequencer.clone(), slots.clone());
+                    thread::spawn(move || {
+                        let sequence = claim(&*sequencer);
+                        slots.write(sequence, sequence);
+                        sequencer.publish_cursor_sequence(sequence);
+                    })
+                })
+                .collect();
+            consume(&*sequencer, &slots, 2);
+            for producer in producers {
+                producer.join().unwrap();
+            }
+        });
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn loom_multi_producer_waits_for_the_wrap_point() {
+        use crate::sequencer::MultiProducerSequencer;
+
+        model(|| {
+            let sequencer = Arc::new(MultiProducerSequencer::new(1));
+            let slots = Arc::new(Slots::new(1));
+
+            let producer = {
+                let (sequencer, slots) = (sequencer.clone(), slots.clone());
+                thread::spawn(move || {
+                    for _ in 0..2 {
+                        let sequence = claim(&*sequencer);
+                        slots.write(sequence, sequence);
+                        sequencer.publish_cursor_sequence(sequence);
+                    }
+                })
+            };
+            consume(&*sequencer, &slots, 2);
+            producer.join().unwrap();
+        });
+    }
+}
+
+#[cfg(feature = "mc")]
+mod pollers {
+    use super::{Slots, model};
+    use crate::poller::{MultiConsumerPoller, Poller};
+    use crate::sequencer::{Sequencer, SingleProducerSequencer};
+    use crate::sync::{AtomicUsize, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    type ItemPoller = dyn Poller<i64, SingleProducerSequencer>;
+
+    #[test]
+    fn loom_multi_consumer_claims_each_sequence_once() {
+        model(|| {
+            let sequencer = Arc::new(SingleProducerSequencer::new(2));
+            let slots = Arc::new(Slots::new(2));
+            let poller = Arc::new(MultiConsumerPoller::new(2));
+            let claims = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+            for _ in 0..2 {
+                let sequence = sequencer.try_next().unwrap();
+                slots.write(sequence, sequence);
+                sequencer.publish_cursor_sequence(sequence);
+            }
+
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    let (sequencer, slots) = (sequencer.clone(), slots.clone());
+                    let (poller, claims) = (poller.clone(), claims.clone());
+                    thread::spawn(move || {
+                        let poller: &ItemPoller = &*poller;
+                        while let Some((low, high)) = poller.claim(&sequencer, 1) {
+                            for sequence in low..=high {
+                                assert_eq!(slots.read(sequence), sequence);
+                                claims[sequence as usize].fetch_add(1, Ordering::Relaxed);
+                            }
+                            poller.release(&sequencer, low, high);
+                        }
+                    })
+                })
+                .collect();
+            for consumer in consumers {
+                consumer.join().unwrap();
+            }
+
+            for claim in claims.iter() {
+                assert_eq!(claim.load(Ordering::Relaxed), 1);
+            }
+            assert_eq!(sequencer.get_gating_sequence_relaxed(), 1);
+        });
+    }
+}
+
+mod peeks {
+    use super::model;
+    use crate::ring_buffer::copy_published;
+    use crate::sequencer::{Sequencer, SingleProducerSequencer};
+    use crate::sync::{AtomicI64, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn loom_peek_discards_copies_of_an_overwritten_slot() {
+        model(|| {
+            // A single slot, read word by word through an atomic so that the
+            // model checks the validation rather than flagging the racy copy.
+            let sequencer = Arc::new(SingleProducerSequencer::new(1));
+            let slot = Arc::new(AtomicI64::new(-1));
+            let sequence = sequencer.try_next().unwrap();
+            slot.store(sequence, Ordering::Relaxed);
+            sequencer.publish_cursor_sequence(sequence);
+            sequencer.publish_gating_sequence(sequence);
+
+            let producer = {
+                let (sequencer, slot) = (sequencer.clone(), slot.clone());
+                thread::spawn(move || {
+                    let sequence = sequencer.try_next().unwrap();
+                    slot.store(sequence, Ordering::Relaxed);
+                    sequencer.publish_cursor_sequence(sequence);
+                })
+            };
+            let copy = copy_published(&*sequencer, 1, 0, || slot.load(Ordering::Relaxed));
+            assert!(matches!(copy, None | Some(0)), "peeked {copy:?}");
+            producer.join().unwrap();
+
+            let copy = copy_published(&*sequencer, 1, 1, || slot.load(Ordering::Relaxed));
+            assert_eq!(copy, Some(1));
+        });
+    }
+}
+
+mod wakeups {
+    use super::model;
+    use crate::coordinator::Wakeup;
+    use crate::sync::{AtomicBool, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn loom_signal_wakes_every_waiter() {
+        model(|| {
+            let wakeup = Arc::new(Wakeup::default());
+            let ready = Arc::new(AtomicBool::new(false));
+            let waiters: Vec<_> = (0..2)
+                .map(|_| {
+                    let (wakeup, ready) = (wakeup.clone(), ready.clone());
+                    thread::spawn(move || {
+                        while !ready.load(Ordering::Acquire) {
+                            wakeup.wait(None);
+                        }
+                    })
+                })
+                .collect();
+            // A single signal has to wake both waiters, whether they park
+            // before it or only wait after it.
+            ready.store(true, Ordering::Release);
+            wakeup.signal();
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        });
+    }
+}
@@ -0,0 +1,153 @@
+//! Channel metrics.
+//!
+//! With the `metrics` feature every channel counts the items published and
+//! consumed, how often producers waited for free slots and consumers waited
+//! for items, and how many batches consumers took. The counters are shared by
+//! every sender and receiver of the channel; [`Sender::metrics`] and
+//! [`Receiver::metrics`] return a snapshot of them.
+//!
+//! [`Sender::metrics`]: crate::channels::Sender::metrics
+//! [`Receiver::metrics`]: crate::channels::Receiver::metrics
+
+use crate::primitives::PaddedCounter;
+use std::iter::Sum;
+
+/// A snapshot of the counters of a channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    published: u64,
+    consumed: u64,
+    batches: u64,
+    producer_waits: u64,
+    consumer_idle_polls: u64,
+}
+
+impl MetricsSnapshot {
+    /// Returns the number of items producers published.
+    pub fn published(&self) -> u64 {
+        self.published
+    }
+
+    /// Returns the number of items consumers took from the ring buffer.
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Returns the number of batches consumers took from the ring buffer.
+    pub fn batches(&self) -> u64 {
+        self.batches
+    }
+
+    /// Returns the number of times producers waited for free slots, counting
+    /// every iteration of the producer wait strategy.
+    pub fn producer_waits(&self) -> u64 {
+        self.producer_waits
+    }
+
+    /// Returns the number of polls that found no item and waited according to
+    /// the consumer wait strategy.
+    pub fn consumer_idle_polls(&self) -> u64 {
+        self.consumer_idle_polls
+    }
+
+    /// Returns the average number of items per batch, or `0.0` before the first batch.
+    pub fn average_batch_size(&self) -> f64 {
+        match self.batches {
+            0 => 0.0,
+            batches => self.consumed as f64 / batches as f64,
+        }
+    }
+}
+
+impl Sum for MetricsSnapshot {
+    /// Add up the counters of several channels, such as the shards of a
+    /// [`sharded_mpsc`](crate::sharded::sharded_mpsc) channel.
+    fn sum<I: Iterator<Item = Self>>(snapshots: I) -> Self {
+        snapshots.fold(Self::default(), |total, snapshot| Self {
+            published: total.published + snapshot.published,
+            consumed: total.consumed + snapshot.consumed,
+            batches: total.batches + snapshot.batches,
+            producer_waits: total.producer_waits + snapshot.producer_waits,
+            consumer_idle_polls: total.consumer_idle_polls + snapshot.consumer_idle_polls,
+        })
+    }
+}
+
+/// The counters of a channel, updated by every sender and receiver.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    published: PaddedCounter,
+    consumed: PaddedCounter,
+    batches: PaddedCounter,
+    producer_waits: PaddedCounter,
+    consumer_idle_polls: PaddedCounter,
+}
+
+impl Metrics {
+    /// Count `n` published items.
+    #[inline(always)]
+    pub fn published(&self, n: usize) {
+        self.published.fetch_add(n as u64);
+    }
+
+    /// Count a batch of `n` consumed items.
+    #[inline(always)]
+    pub fn consumed(&self, n: usize) {
+        self.consumed.fetch_add(n as u64);
+        self.batches.fetch_add(1);
+    }
+
+    /// Count an iteration of the producer wait strategy.
+    #[inline(always)]
+    pub fn producer_wait(&self) {
+        self.producer_waits.fetch_add(1);
+    }
+
+    /// Count a poll that found no item.
+    #[inline(always)]
+    pub fn consumer_idle(&self) {
+        self.consumer_idle_polls.fetch_add(1);
+    }
+
+    /// Returns a snapshot of the counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            published: self.published.get(),
+            consumed: self.consumed.get(),
+            batches: self.batches.get(),
+            producer_waits: self.producer_waits.get(),
+            consumer_idle_polls: self.consumer_idle_polls.get(),
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvState, spsc};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+
+    #[test]
+    fn test_metrics_count_items_batches_and_waits() {
+        let (tx, rx) = spinning_spsc::<u32>(4);
+        tx.send_n(0..4).unwrap();
+        let sender = std::thread::spawn(move || {
+            tx.send(4).unwrap();
+            tx
+        });
+        while rx.metrics().producer_waits() == 0 {
+            std::thread::yield_now();
+        }
+
+        assert_eq!(rx.recv(2, &drop), RecvState::Received);
+        let tx = sender.join().unwrap();
+        assert_eq!(rx.recv(4, &drop), RecvState::Received);
+        assert_eq!(rx.recv(4, &drop), RecvState::Empty);
+
+        let metrics = tx.metrics();
+        assert_eq!(metrics.published(), 5);
+        assert_eq!(metrics.consumed(), 5);
+        assert_eq!(metrics.batches(), 2);
+        assert_eq!(metrics.average_batch_size(), 2.5);
+        assert_eq!(metrics.consumer_idle_polls(), 1);
+    }
+}
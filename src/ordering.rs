@@ -0,0 +1,390 @@
+//! Memory-ordering audit of the sequencing protocol.
+//!
+//! The safety of the ring buffer rests on two happens-before edges:
+//!
+//! - [`Edge::Publish`]: a producer writes a slot, then publishes its sequence
+//!   with a release store; a consumer that observes the sequence with an
+//!   acquire load may read the slot.
+//! - [`Edge::Gating`]: a consumer reads a slot, then releases its sequence to
+//!   producers; a producer that observes the gating sequence with an acquire
+//!   load may overwrite the slot on the next lap.
+//!
+//! Every atomic access the edges rely on is annotated with the `ordered!`
+//! macro, which names the edge, the side of it the access is on and the
+//! ordering it uses, so the annotations document the protocol next to the code.
+//!
+//! With the `ordering-audit` feature, debug builds also record every annotated
+//! access and every slot read and write, and check them as they happen: each
+//! access must use an ordering strong enough for its side, each slot read
+//! must follow the write of the same sequence, and each slot write must follow
+//! the read of the previous lap. `report` returns the annotated sites and
+//! any violation found, in a line-oriented format tools can check.
+
+/// A happens-before edge the sequencing protocol relies on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Edge {
+    /// Producer slot writes happen before consumer slot reads.
+    Publish,
+    /// Consumer slot reads happen before producers overwrite the slot.
+    Gating,
+}
+
+/// The side of an [`Edge`] an atomic access is on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Side {
+    /// The access makes the preceding slot accesses visible.
+    Release,
+    /// The access observes a release and orders the following slot accesses after it.
+    Acquire,
+}
+
+/// Annotate an atomic access as one side of an [`Edge`].
+///
+/// `ordered!(Publish, Release, Release, cursor.set_release(high))` evaluates
+/// the access, declaring it the release side of the publish edge made with
+/// release ordering. With the `ordering-audit` feature, debug builds record the
+/// access at its call site.
+macro_rules! ordered {
+    ($edge:ident, $side:ident, $ordering:ident, $access:expr) => {{
+        #[cfg(all(feature = "ordering-audit", debug_assertions))]
+        $crate::ordering::audit::site(
+            $crate::ordering::Edge::$edge,
+            $crate::ordering::Side::$side,
+            ::std::sync::atomic::Ordering::$ordering,
+            concat!(file!(), ":", line!()),
+        );
+        $access
+    }};
+}
+pub(crate) use ordered;
+
+/// Record that the slot of `$sequence` in `$buffer` was `written` or `read`.
+///
+/// Expands to nothing unless the `ordering-audit` feature is enabled in a debug build.
+macro_rules! slot_access {
+    ($kind:ident, $buffer:expr, $sequence:expr) => {
+        #[cfg(all(feature = "ordering-audit", debug_assertions))]
+        $crate::ordering::audit::$kind(
+            $buffer as *const _ as *const () as usize,
+            $buffer.buffer_size(),
+            $sequence,
+            concat!(file!(), ":", line!()),
+        );
+    };
+}
+pub(crate) use slot_access;
+
+#[cfg(feature = "ordering-audit")]
+pub use audit::{Report, SiteReport, Violation, report, reset};
+
+#[cfg(feature = "ordering-audit")]
+pub(crate) mod audit {
+    use crate::ordering::{Edge, Side};
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::atomic::Ordering;
+    use std::sync::{LazyLock, Mutex};
+
+    /// An annotated atomic access site and how often it was reached.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct SiteReport {
+        /// The edge the access belongs to.
+        pub edge: Edge,
+        /// The side of the edge the access is on.
+        pub side: Side,
+        /// The ordering the access uses.
+        pub ordering: Ordering,
+        /// The `file:line` of the annotation.
+        pub site: &'static str,
+        /// How many times the access was made.
+        pub accesses: u64,
+    }
+
+    /// A breach of the protocol found while recording.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Violation {
+        /// An annotated access uses an ordering too weak for its side of the edge.
+        WeakOrdering {
+            /// The `file:line` of the annotation.
+            site: &'static str,
+            /// The edge the access belongs to.
+            edge: Edge,
+            /// The side of the edge the access is on.
+            side: Side,
+            /// The ordering the access uses.
+            ordering: Ordering,
+        },
+        /// A slot was read before the write of its sequence was recorded.
+        UnpublishedRead {
+            /// The `file:line` of the read.
+            site: &'static str,
+            /// The sequence that was read.
+            sequence: i64,
+        },
+        /// A slot was written before the item of the previous lap was read.
+        UnreadOverwrite {
+            /// The `file:line` of the write.
+            site: &'static str,
+            /// The sequence that was written.
+            sequence: i64,
+        },
+    }
+
+    /// The annotated sites reached and the violations found since the last [`reset`].
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Report {
+        /// The sites, ordered by edge, side and location.
+        pub sites: Vec<SiteReport>,
+        /// The violations, in the order they were found.
+        pub violations: Vec<Violation>,
+    }
+
+    impl Report {
+        /// Returns `true` if no violation was found.
+        pub fn is_consistent(&self) -> bool {
+            self.violations.is_empty()
+        }
+    }
+
+    impl fmt::Display for Report {
+        /// One line per site, `site <edge> <side> <ordering> <accesses> <location>`,
+        /// then one line per violation starting with `violation`.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for site in &self.sites {
+                writeln!(
+                    f,
+                    "site {:?} {:?} {:?} {} {}",
+                    site.edge, site.side, site.ordering, site.accesses, site.site
+                )?;
+            }
+            for violation in &self.violations {
+                match violation {
+                    Violation::WeakOrdering {
+                        site,
+                        edge,
+                        side,
+                        ordering,
+                    } => writeln!(
+                        f,
+                        "violation weak-ordering {edge:?} {side:?} {ordering:?} {site}"
+                    )?,
+                    Violation::UnpublishedRead { site, sequence } => {
+                        writeln!(f, "violation unpublished-read {sequence} {site}")?
+                    }
+                    Violation::UnreadOverwrite { site, sequence } => {
+                        writeln!(f, "violation unread-overwrite {sequence} {site}")?
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// The last sequence written and read through every slot of a buffer.
+    struct Slots {
+        written: Vec<i64>,
+        read: Vec<i64>,
+    }
+
+    /// Checks recorded accesses as they are made.
+    #[derive(Default)]
+    pub(crate) struct Checker {
+        sites: HashMap<&'static str, SiteReport>,
+        buffers: HashMap<usize, Slots>,
+        violations: Vec<Violation>,
+    }
+
+    impl Checker {
+        fn site(&mut self, edge: Edge, side: Side, ordering: Ordering, site: &'static str) {
+            let entry = self.sites.entry(site).or_insert_with(|| SiteReport {
+                edge,
+                side,
+                ordering,
+                site,
+                accesses: 0,
+            });
+            if entry.accesses == 0 && !is_strong_enough(side, ordering) {
+                self.violations.push(Violation::WeakOrdering {
+                    site,
+                    edge,
+                    side,
+                    ordering,
+                });
+            }
+            entry.accesses += 1;
+        }
+
+        fn slots(&mut self, buffer: usize, buffer_size: usize) -> &mut Slots {
+            self.buffers.entry(buffer).or_insert_with(|| Slots {
+                written: vec![-1; buffer_size],
+                read: vec![-1; buffer_size],
+            })
+        }
+
+        pub(crate) fn written(
+            &mut self,
+            buffer: usize,
+            buffer_size: usize,
+            sequence: i64,
+            site: &'static str,
+        ) {
+            let slots = self.slots(buffer, buffer_size);
+            let index = sequence as usize % buffer_size;
+            let previous = sequence - buffer_size as i64;
+            let unread = previous >= 0 && slots.read[index] != previous;
+            slots.written[index] = sequence;
+            if unread {
+                self.violations
+                    .push(Violation::UnreadOverwrite { site, sequence });
+            }
+        }
+
+        pub(crate) fn read(
+            &mut self,
+            buffer: usize,
+            buffer_size: usize,
+            sequence: i64,
+            site: &'static str,
+        ) {
+            let slots = self.slots(buffer, buffer_size);
+            let index = sequence as usize % buffer_size;
+            let unpublished = slots.written[index] != sequence;
+            slots.read[index] = sequence;
+            if unpublished {
+                self.violations
+                    .push(Violation::UnpublishedRead { site, sequence });
+            }
+        }
+
+        pub(crate) fn forget(&mut self, buffer: usize) {
+            self.buffers.remove(&buffer);
+        }
+
+        pub(crate) fn report(&self) -> Report {
+            let mut sites: Vec<SiteReport> = self.sites.values().cloned().collect();
+            sites.sort_by_key(|site| (site.edge, site.side, site.site));
+            Report {
+                sites,
+                violations: self.violations.clone(),
+            }
+        }
+    }
+
+    /// Returns `true` if `ordering` is strong enough for the `side` of an edge.
+    fn is_strong_enough(side: Side, ordering: Ordering) -> bool {
+        match side {
+            Side::Release => matches!(
+                ordering,
+                Ordering::Release | Ordering::AcqRel | Ordering::SeqCst
+            ),
+            Side::Acquire => matches!(
+                ordering,
+                Ordering::Acquire | Ordering::AcqRel | Ordering::SeqCst
+            ),
+        }
+    }
+
+    static CHECKER: LazyLock<Mutex<Checker>> = LazyLock::new(Mutex::default);
+
+    fn with_checker<R>(f: impl FnOnce(&mut Checker) -> R) -> R {
+        f(&mut CHECKER.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub(crate) fn site(edge: Edge, side: Side, ordering: Ordering, site: &'static str) {
+        with_checker(|checker| checker.site(edge, side, ordering, site));
+    }
+
+    pub(crate) fn written(buffer: usize, buffer_size: usize, sequence: i64, site: &'static str) {
+        with_checker(|checker| checker.written(buffer, buffer_size, sequence, site));
+    }
+
+    pub(crate) fn read(buffer: usize, buffer_size: usize, sequence: i64, site: &'static str) {
+        with_checker(|checker| checker.read(buffer, buffer_size, sequence, site));
+    }
+
+    /// Forget the slots of a buffer that is dropped or rebased.
+    pub(crate) fn forget(buffer: usize) {
+        with_checker(|checker| checker.forget(buffer));
+    }
+
+    /// Returns the sites reached and the violations found since the last [`reset`].
+    ///
+    /// Accesses are only recorded in debug builds, so the report of a release
+    /// build is empty.
+    pub fn report() -> Report {
+        with_checker(|checker| checker.report())
+    }
+
+    /// Forget every recorded access and violation.
+    ///
+    /// Only call this while no channel is in use, since the slots of live
+    /// buffers are forgotten as well.
+    pub fn reset() {
+        with_checker(|checker| *checker = Checker::default());
+    }
+
+    #[cfg(all(test, not(feature = "loom")))]
+    mod tests {
+        use crate::channels::spsc;
+        use crate::ordering::audit::{self, Checker, Violation};
+        use crate::ordering::{Edge, Side};
+        use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+
+        #[test]
+        fn test_checker_flags_broken_happens_before_edges() {
+            let mut checker = Checker::default();
+            checker.written(1, 2, 0, "write");
+            checker.read(1, 2, 0, "read");
+            checker.written(1, 2, 1, "write");
+            checker.written(1, 2, 2, "write");
+            checker.written(1, 2, 3, "write");
+            checker.read(1, 2, 4, "read");
+            assert_eq!(
+                checker.report().violations,
+                vec![
+                    Violation::UnreadOverwrite {
+                        site: "write",
+                        sequence: 3
+                    },
+                    Violation::UnpublishedRead {
+                        site: "read",
+                        sequence: 4
+                    },
+                ]
+            );
+
+            let (tx, rx) = spsc::<u32>(
+                4,
+                ProducerWaitStrategyKind::Spinning,
+                ConsumerWaitStrategyKind::Spinning,
+            );
+            let consumer = std::thread::spawn(move || rx.iter().count());
+            for value in 0..64 {
+                tx.send(value).unwrap();
+            }
+            drop(tx);
+            assert_eq!(consumer.join().unwrap(), 64);
+
+            let report = audit::report();
+            assert!(
+                !report
+                    .violations
+                    .iter()
+                    .any(|violation| matches!(violation, Violation::WeakOrdering { .. }))
+            );
+            for (edge, side) in [
+                (Edge::Publish, Side::Release),
+                (Edge::Publish, Side::Acquire),
+                (Edge::Gating, Side::Release),
+                (Edge::Gating, Side::Acquire),
+            ] {
+                assert!(
+                    report
+                        .sites
+                        .iter()
+                        .any(|site| site.edge == edge && site.side == side && site.accesses > 0)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,428 @@
+//! Consumer pipelines over a single ring buffer.
+//!
+//! A [`Pipeline`] runs a chain of handler stages, Disruptor style: every stage
+//! runs on its own thread and works on the items in place, in the ring, and a
+//! stage only processes sequences the stage before it has already handled.
+//! The final stage moves the items out of the ring and frees their slots for
+//! producers, so items travel through every stage without being copied into a
+//! second channel.
+//!
+//! A stage can also be a work pool of several handlers, each on its own
+//! thread, that split the items of the stage between them: every item is
+//! handled by exactly one handler of the pool, and the next stage only sees an
+//! item once every item before it is handled as well.
+//!
+//! ```
+//! use channels_rs::pipeline::pipeline;
+//! use channels_rs::prelude::*;
+//!
+//! let (tx, rx) = spsc::<u64>(64, ProducerWaitStrategyKind::Yielding, ConsumerWaitStrategyKind::Yielding);
+//! let running = pipeline(rx)
+//!     .handle_with(|value| *value += 1)
+//!     .then_pool((0..3).map(|_| |value: &mut u64| *value *= 2))
+//!     .then(|value| assert!(*value % 2 == 0))
+//!     .start();
+//! for value in 0..100 {
+//!     tx.send(value).unwrap();
+//! }
+//! drop(tx);
+//! running.join().unwrap();
+//! ```
+
+use crate::channels::Receiver;
+use crate::coordinator::Coordinator;
+use crate::primitives::PaddedFlag;
+use crate::ring_buffer::RingBuffer;
+use crate::sequence::Sequence;
+use crate::sequencer::SequenceBarrier;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// A handler that works on items in place.
+type Handler<T> = Box<dyn FnMut(&mut T) + Send>;
+
+/// A stage of the pipeline.
+enum Stage<T> {
+    /// A single handler that handles every item.
+    Single(Handler<T>),
+    /// Handlers that split the items between them.
+    Pool(Vec<Handler<T>>),
+}
+
+/// The progress of a single stage, or of a single handler of a pool.
+struct Progress {
+    sequence: Arc<Sequence>,
+    done: PaddedFlag,
+}
+
+impl Progress {
+    fn new(start: i64) -> Arc<Self> {
+        Arc::new(Self {
+            sequence: Arc::new(Sequence::new(start)),
+            done: PaddedFlag::default(),
+        })
+    }
+}
+
+/// Marks a stage as done when its thread exits, and closes the channel if it
+/// exits by panicking, so neither downstream stages nor producers wait for it forever.
+struct Finish<'a> {
+    progress: &'a Progress,
+    coordinator: &'a Coordinator,
+}
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.coordinator.close(None);
+        }
+        self.progress.done.set();
+    }
+}
+
+/// The stage a handler works behind: the progress of every handler of the
+/// stage before it, or nothing for the first stage, which follows producers.
+struct Upstream {
+    progress: Vec<Arc<Progress>>,
+    barrier: SequenceBarrier,
+}
+
+impl Upstream {
+    fn new(progress: Vec<Arc<Progress>>) -> Self {
+        let sequences = progress.iter().map(|p| p.sequence.clone()).collect();
+        Self {
+            progress,
+            barrier: SequenceBarrier::new(sequences),
+        }
+    }
+
+    /// Returns `true` once nothing more can be released to this stage.
+    fn is_finished(&self, coordinator: &Coordinator) -> bool {
+        match self.progress.is_empty() {
+            true => coordinator.is_finished(),
+            false => self.progress.iter().all(|p| p.done.is_set()),
+        }
+    }
+
+    /// Wait for the stage before to release more sequences.
+    fn wait(&self, coordinator: &Coordinator) {
+        match self.progress.is_empty() {
+            true => coordinator.consumer_wait(),
+            false => thread::yield_now(),
+        }
+    }
+}
+
+/// Wires handler stages in order on top of the ring buffer of a receiver.
+pub struct PipelineBuilder<T> {
+    receiver: Receiver<T>,
+    stages: Vec<Stage<T>>,
+}
+
+/// Start a pipeline that consumes the items of `receiver`, see [`PipelineBuilder::new`].
+pub fn pipeline<T: Send + 'static>(receiver: Receiver<T>) -> PipelineBuilder<T> {
+    PipelineBuilder::new(receiver)
+}
+
+impl<T: Send + 'static> PipelineBuilder<T> {
+    /// Start a pipeline that consumes the items of `receiver`.
+    ///
+    /// The pipeline takes over consumption: other clones of the receiver must
+    /// not receive while it runs.
+    ///
+    /// # Panics
+    /// Panics if `receiver` belongs to a broadcast channel.
+    pub fn new(receiver: Receiver<T>) -> Self {
+        assert!(
+            !receiver.retains(),
+            "pipelines cannot consume broadcast channels"
+        );
+        Self {
+            receiver,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Append a stage that runs `handler` on every item, after every stage
+    /// added before it.
+    pub fn stage<H>(mut self, handler: H) -> Self
+    where
+        H: FnMut(&mut T) + Send + 'static,
+    {
+        self.stages.push(Stage::Single(Box::new(handler)));
+        self
+    }
+
+    /// Same as [`stage`](Self::stage), reading as the first stage of a chain.
+    pub fn handle_with<H>(self, handler: H) -> Self
+    where
+        H: FnMut(&mut T) + Send + 'static,
+    {
+        self.stage(handler)
+    }
+
+    /// Same as [`stage`](Self::stage), reading as a stage that follows another.
+    pub fn then<H>(self, handler: H) -> Self
+    where
+        H: FnMut(&mut T) + Send + 'static,
+    {
+        self.stage(handler)
+    }
+
+    /// Append a work pool stage, after every stage added before it, whose
+    /// `handlers` each run on a thread of their own and split the items
+    /// between them.
+    ///
+    /// # Panics
+    /// Panics if `handlers` is empty.
+    pub fn then_pool<I, H>(mut self, handlers: I) -> Self
+    where
+        I: IntoIterator<Item = H>,
+        H: FnMut(&mut T) + Send + 'static,
+    {
+        let handlers: Vec<Handler<T>> = handlers
+            .into_iter()
+            .map(|handler| Box::new(handler) as Handler<T>)
+            .collect();
+        assert!(!handlers.is_empty(), "a pool needs at least one handler");
+        self.stages.push(Stage::Pool(handlers));
+        self
+    }
+
+    /// Start the stages, dropping every item once all of them have handled it.
+    pub fn start(self) -> Pipeline {
+        self.build(drop)
+    }
+
+    /// Start the stages, with `sink` as the final stage that takes every item
+    /// once all other stages have handled it.
+    pub fn build<S>(self, mut sink: S) -> Pipeline
+    where
+        S: FnMut(T) + Send + 'static,
+    {
+        let receiver = Arc::new(self.receiver);
+        let start = receiver.parts().0.gating_sequence();
+        let mut upstream = Vec::new();
+        let mut threads = Vec::with_capacity(self.stages.len() + 1);
+
+        for stage in self.stages {
+            let dependencies = Upstream::new(upstream);
+            upstream = match stage {
+                Stage::Single(mut handler) => {
+                    let progress = Progress::new(start);
+                    let (receiver, own) = (receiver.clone(), progress.clone());
+                    threads.push(thread::spawn(move || {
+                        drive(&receiver, &own, &dependencies, |buffer, low, high| {
+                            for sequence in low..=high {
+                                // SAFETY: the barrier grants this stage exclusive access
+                                // to sequences the previous stage has released and the
+                                // next one has not reached yet.
+                                handler(unsafe { &mut *buffer.slot(sequence) });
+                            }
+                        })
+                    }));
+                    vec![progress]
+                }
+                Stage::Pool(handlers) => {
+                    let work = Arc::new(Sequence::new(start));
+                    let dependencies = Arc::new(dependencies);
+                    let pool: Vec<_> = handlers.iter().map(|_| Progress::new(start)).collect();
+                    for (handler, progress) in handlers.into_iter().zip(&pool) {
+                        let (receiver, own) = (receiver.clone(), progress.clone());
+                        let (work, dependencies) = (work.clone(), dependencies.clone());
+                        threads.push(thread::spawn(move || {
+                            work_on(&receiver, &own, &dependencies, &work, handler)
+                        }));
+                    }
+                    pool
+                }
+            };
+        }
+
+        let progress = Progress::new(start);
+        let dependencies = Upstream::new(upstream);
+        threads.push(thread::spawn(move || {
+            drive(&receiver, &progress, &dependencies, |buffer, low, high| {
+                for sequence in low..=high {
+                    sink(buffer.dequeue(sequence));
+                }
+                buffer.release(high);
+            })
+        }));
+
+        Pipeline { threads }
+    }
+}
+
+/// Process batches of the sequences `upstream` has released, until no more
+/// can arrive.
+fn drive<T, F>(receiver: &Receiver<T>, progress: &Progress, upstream: &Upstream, mut process: F)
+where
+    F: FnMut(&RingBuffer<T>, i64, i64),
+{
+    let (buffer, coordinator) = receiver.parts();
+    let _finish = Finish {
+        progress,
+        coordinator,
+    };
+    let batch_size = buffer.buffer_size() as i64;
+    let mut current = progress.sequence.get_relaxed();
+
+    loop {
+        // Read before looking for work, so that nothing released before the
+        // upstream finished is missed.
+        let finished = upstream.is_finished(coordinator);
+
+        let highest = buffer.get_highest(&upstream.barrier, current + 1, current + batch_size);
+        if highest > current {
+            process(buffer, current + 1, highest);
+            progress.sequence.set_release(highest);
+            coordinator.consumer_progress((highest - current) as usize);
+            current = highest;
+            continue;
+        }
+
+        if finished {
+            return;
+        }
+        upstream.wait(coordinator);
+    }
+}
+
+/// Handle single sequences of a pool stage, claimed from the shared `work`
+/// sequence, until no more can arrive.
+///
+/// The progress of a handler stays just below the sequence it claimed last,
+/// so the next stage, which follows the lowest progress of the pool, only
+/// passes sequences every handler is done with.
+fn work_on<T>(
+    receiver: &Receiver<T>,
+    progress: &Progress,
+    upstream: &Upstream,
+    work: &Sequence,
+    mut handler: Handler<T>,
+) {
+    let (buffer, coordinator) = receiver.parts();
+    let _finish = Finish {
+        progress,
+        coordinator,
+    };
+
+    loop {
+        let claimed = work.fetch_add_volatile(1) + 1;
+        progress.sequence.set_release(claimed - 1);
+        loop {
+            let finished = upstream.is_finished(coordinator);
+            if buffer.get_highest(&upstream.barrier, claimed, claimed) >= claimed {
+                // SAFETY: the sequence was claimed by this handler alone, and
+                // the next stage does not pass it before the handler moves on.
+                handler(unsafe { &mut *buffer.slot(claimed) });
+                break;
+            }
+            if finished {
+                return;
+            }
+            upstream.wait(coordinator);
+        }
+    }
+}
+
+/// The running stages of a pipeline, created by [`PipelineBuilder::build`].
+pub struct Pipeline {
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    /// Wait for every stage to finish.
+    ///
+    /// The stages finish once the channel is closed or every sender is gone,
+    /// and every item has passed through the pipeline. A stage that panics
+    /// closes the channel, which stops the stages after it.
+    ///
+    /// # Errors
+    /// Returns the panic payload of the first stage that panicked.
+    pub fn join(self) -> thread::Result<()> {
+        let mut result = Ok(());
+        for thread in self.threads {
+            let joined = thread.join();
+            if result.is_ok() {
+                result = joined;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(test, feature = "mp", not(feature = "loom")))]
+mod tests {
+    use crate::channels::mpsc;
+    use crate::pipeline::{PipelineBuilder, pipeline};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_stages_run_in_order_on_every_item() {
+        let (tx, rx) = mpsc::<u64>(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let pipeline = PipelineBuilder::new(rx)
+            .stage(|value| *value += 1)
+            .stage(|value| *value *= 2)
+            .build(move |value| sink.lock().unwrap().push(value));
+
+        for value in 0..1000 {
+            tx.send(value).unwrap();
+        }
+        drop(tx);
+        pipeline.join().unwrap();
+
+        let expected: Vec<u64> = (0..1000).map(|value| (value + 1) * 2).collect();
+        assert_eq!(*received.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_pools_split_items_between_workers() {
+        let (tx, rx) = mpsc::<u64>(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let handled = Arc::new([(); 3].map(|_| AtomicUsize::new(0)));
+        let workers: Vec<_> = (0..3)
+            .map(|index| {
+                let handled = handled.clone();
+                move |value: &mut u64| {
+                    assert_eq!(*value % 10, 1);
+                    *value += 1;
+                    handled[index].fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .collect();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let running = pipeline(rx)
+            .handle_with(|value| *value = *value * 10 + 1)
+            .then_pool(workers)
+            .then(|value| assert_eq!(*value % 10, 2))
+            .build(move |value| sink.lock().unwrap().push(value / 10));
+
+        for value in 0..1000 {
+            tx.send(value).unwrap();
+        }
+        drop(tx);
+        running.join().unwrap();
+
+        let expected: Vec<u64> = (0..1000).collect();
+        assert_eq!(*received.lock().unwrap(), expected);
+        let total: usize = handled
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum();
+        assert_eq!(total, 1000);
+    }
+}
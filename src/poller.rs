@@ -1,40 +1,225 @@
-use crate::ring_buffer::RingBuffer;
-use crate::sequence::Sequence;
+#[cfg(feature = "mc")]
+use crate::channels::ConsumerFairness;
+#[cfg(feature = "registry")]
+use crate::channels::Consumers;
+use crate::channels::Context;
+#[cfg(feature = "mc")]
+use crate::ordering::ordered;
+use crate::ring_buffer::{HeapSlots, RingBuffer, Slots};
+use crate::sequence::{INITIAL_VALUE, Sequence};
 use crate::sequencer::Sequencer;
+#[cfg(feature = "mc")]
+use crate::sync::{AtomicBool, AtomicI64, AtomicUsize, Ordering, fence, spin_loop};
+#[cfg(feature = "mc")]
+use crate::utils::{CachePadded, Indexing};
+use std::cell::Cell;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+#[cfg(feature = "mc")]
+use std::sync::Mutex;
+#[cfg(all(feature = "mp", feature = "mc"))]
+use std::sync::RwLock;
 
 /// Represents the current state of a consumer poll operation.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) enum State {
     /// No items were available to process.
     Idle,
-    /// One or more items were processed.
-    Processing,
+    /// The given number of items, at least one, were processed.
+    Processing(usize),
 }
 
 /// Trait defining a poller for a ring buffer.
 ///
 /// A poller is responsible for consuming items from a [`RingBuffer`]
 /// according to the rules of a sequencer. It allows both single and
-/// multi-consumer implementations.
-pub(crate) trait Poller<T>: Send + Sync {
+/// multi-consumer implementations. Every receiver polls through its own
+/// poller, which receivers that split the items between them share.
+///
+/// Pollers are generic over the sequencer type `S` of the buffer they poll, so
+/// a buffer with a concrete sequencer is polled without dynamic dispatch.
+pub(crate) trait Poller<T, S: Sequencer + ?Sized = dyn Sequencer, B: Slots<T> = HeapSlots<T>>:
+    Send + Sync
+{
+    /// Claim up to `batch_size` published items for this consumer.
+    ///
+    /// # Returns
+    /// The inclusive range of claimed sequences, or `None` if no items were available.
+    /// The claimed items must be dequeued and the range handed back with
+    /// [`release`](Self::release) or [`abandon`](Self::abandon).
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)>;
+
+    /// Take the item at a claimed `sequence` out of the buffer.
+    ///
+    /// Moves the item out by default.
+    fn read(&self, buffer: &RingBuffer<T, S, B>, sequence: i64) -> T {
+        buffer.dequeue(sequence)
+    }
+
+    /// Returns `true` if items are read by reference and left in the buffer,
+    /// which must then be [`retaining`](RingBuffer::retaining).
+    fn retains(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the receivers sharing this poller claim from the same
+    /// sequence, so that a range one of them never hands back holds back the
+    /// others' releases.
+    fn shares_claims(&self) -> bool {
+        false
+    }
+
+    /// Returns the highest sequence this consumer has claimed.
+    ///
+    /// Defaults to the gating sequence, which only this consumer moves.
+    fn position(&self, sequencer: &S) -> i64 {
+        sequencer.get_gating_sequence_relaxed()
+    }
+
+    /// Returns how many consumers may share the items, as reported by the registry.
+    #[cfg(feature = "registry")]
+    fn consumers(&self) -> Consumers {
+        Consumers::Single
+    }
+
+    /// Create the poller of a new receiver that consumes independently of this one.
+    ///
+    /// Returns `None` by default, which makes the new receiver share this poller.
+    fn subscribe(&self) -> Option<Box<dyn Poller<T, S, B>>> {
+        None
+    }
+
+    /// Like [`subscribe`](Self::subscribe), but the new receiver only sees
+    /// items published from now on, wherever this one is.
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    fn subscribe_latest(&self, _sequencer: &S) -> Option<Box<dyn Poller<T, S, B>>> {
+        self.subscribe()
+    }
+
+    /// Stop gating producers on a receiver created by [`subscribe`](Self::subscribe)
+    /// that is being dropped.
+    fn unsubscribe(&self, _sequencer: &S) {}
+
+    /// Move the consumer progress back to the initial sequence after the
+    /// sequencer was [`rebase`](Sequencer::rebase)d.
+    fn rebase(&self) {}
+
+    /// Release the fully consumed range `[low, highest]` to producers.
+    fn release(&self, sequencer: &S, _low: i64, highest: i64) {
+        sequencer.publish_gating_sequence(highest);
+    }
+
+    /// Give up the claimed range `[low, high]` of which only the sequences up
+    /// to `consumed` were dequeued.
+    ///
+    /// By default the remaining items are dequeued and dropped, since other
+    /// consumers have already moved past the range.
+    fn abandon(
+        &self,
+        sequencer: &S,
+        buffer: &RingBuffer<T, S, B>,
+        range: (i64, i64),
+        consumed: i64,
+    ) {
+        let (low, high) = range;
+        for sequence in consumed + 1..=high {
+            drop(buffer.dequeue(sequence));
+        }
+        self.release(sequencer, low, high);
+    }
+
+    /// Hand back the claimed range `[low, high]` of a handler that panicked
+    /// after the sequences up to `consumed` were dequeued, so that the
+    /// remaining items are claimed again.
+    ///
+    /// Defaults to [`abandon`](Self::abandon), which leaves the remaining
+    /// items in the buffer for pollers that claim from a sequence of their own.
+    fn requeue(
+        &self,
+        sequencer: &S,
+        buffer: &RingBuffer<T, S, B>,
+        range: (i64, i64),
+        consumed: i64,
+    ) {
+        self.abandon(sequencer, buffer, range, consumed);
+    }
+
     /// Poll up to `batch_size` items from the ring buffer.
     ///
     /// # Parameters
     /// - `sequencer`: Tracks available and consumed sequences.
     /// - `buffer`: The underlying ring buffer to consume from.
     /// - `batch_size`: Maximum number of items to consume in this poll.
-    /// - `handler`: Closure called for each consumed item.
+    /// - `handler`: Closure called with the context and value of each consumed item.
     ///
     /// # Returns
     /// - [`State::Idle`] if no items were available.
     /// - [`State::Processing`] if one or more items were consumed.
+    ///
+    /// If `handler` panics, the rest of the batch is [`requeue`](Self::requeue)d
+    /// while the panic unwinds.
     fn poll(
         &self,
-        sequencer: &dyn Sequencer,
-        buffer: &RingBuffer<T>,
+        sequencer: &S,
+        buffer: &RingBuffer<T, S, B>,
         batch_size: i64,
-        handler: &dyn Fn(T),
-    ) -> State;
+        handler: &dyn Fn(Context, T),
+    ) -> State {
+        let Some((next, highest)) = self.claim(sequencer, batch_size) else {
+            return State::Idle;
+        };
+
+        let consumed = Cell::new(next - 1);
+        let unwinding =
+            OnUnwind(|| self.requeue(sequencer, buffer, (next, highest), consumed.get()));
+        for sequence in next..=highest {
+            let end_of_batch = sequence == highest;
+            let context = Context {
+                sequence,
+                end_of_batch,
+            };
+            let item = self.read(buffer, sequence);
+            consumed.set(sequence);
+            handler(context, item);
+        }
+        std::mem::forget(unwinding);
+
+        self.release(sequencer, next, highest);
+        State::Processing((highest - next + 1) as usize)
+    }
+
+    /// Poll up to `out.len()` items from the ring buffer into `out`.
+    ///
+    /// # Returns
+    /// The number of items written to the front of `out`, zero if no items
+    /// were available.
+    fn poll_into(
+        &self,
+        sequencer: &S,
+        buffer: &RingBuffer<T, S, B>,
+        out: &mut [MaybeUninit<T>],
+    ) -> usize {
+        let Some((next, highest)) = self.claim(sequencer, out.len() as i64) else {
+            return 0;
+        };
+
+        for (slot, sequence) in out.iter_mut().zip(next..=highest) {
+            slot.write(self.read(buffer, sequence));
+        }
+
+        self.release(sequencer, next, highest);
+        (highest - next + 1) as usize
+    }
+}
+
+/// Runs its closure when dropped, unless forgotten once the guarded code
+/// completed.
+pub(crate) struct OnUnwind<F: FnMut()>(pub(crate) F);
+
+impl<F: FnMut()> Drop for OnUnwind<F> {
+    fn drop(&mut self) {
+        (self.0)();
+    }
 }
 
 /// Single-consumer poller.
@@ -49,14 +234,8 @@ impl SingleConsumerPoller {
     }
 }
 
-impl<T> Poller<T> for SingleConsumerPoller {
-    fn poll(
-        &self,
-        sequencer: &dyn Sequencer,
-        buffer: &RingBuffer<T>,
-        batch_size: i64,
-        handler: &dyn Fn(T),
-    ) -> State {
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> Poller<T, S, B> for SingleConsumerPoller {
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)> {
         let current = sequencer.get_gating_sequence_relaxed();
         let next: i64 = current + 1;
         let available: i64 = std::cmp::min(
@@ -65,16 +244,97 @@ impl<T> Poller<T> for SingleConsumerPoller {
         );
 
         if next > available {
-            return State::Idle;
+            return None;
         }
 
-        let highest: i64 = sequencer.get_highest(next, available);
-        for sequence in next..=highest {
-            handler(buffer.dequeue(sequence));
+        let highest = sequencer.get_highest(next, available);
+        if highest < next {
+            return None;
         }
+        Some((next, highest))
+    }
 
-        sequencer.publish_gating_sequence(highest);
-        State::Processing
+    /// The single consumer claims from its own gating sequence, so items that
+    /// were not dequeued stay in the buffer for the next claim.
+    fn abandon(&self, sequencer: &S, _: &RingBuffer<T, S, B>, _: (i64, i64), consumed: i64) {
+        sequencer.publish_gating_sequence(consumed);
+    }
+}
+
+/// Poller of a receiver that acknowledges the items it received.
+///
+/// Consumed and released sequences are kept apart: the poller claims from a
+/// [`Sequence`] of its own, the consumed one, while the gating sequence only
+/// moves when the receiver acknowledges items. Items are cloned out of the
+/// buffer, which is [`retaining`](RingBuffer::retaining), so the items
+/// between the two sequences stay in their slots and moving the consumed
+/// sequence back replays them.
+pub(crate) struct AckPoller<T> {
+    consumed: Arc<Sequence>,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> AckPoller<T> {
+    /// Create a poller that moves `consumed` past the items it releases.
+    pub fn new(consumed: Arc<Sequence>) -> Self {
+        Self {
+            consumed,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, S> Poller<T, S> for AckPoller<T>
+where
+    T: Clone + Send + Sync + 'static,
+    S: Sequencer + ?Sized,
+{
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)> {
+        let current = self.consumed.get_relaxed();
+        let next: i64 = current + 1;
+        let available: i64 = std::cmp::min(
+            sequencer.get_cursor_sequence_acquire(),
+            current + batch_size,
+        );
+
+        if next > available {
+            return None;
+        }
+
+        let highest = sequencer.get_highest(next, available);
+        if highest < next {
+            return None;
+        }
+        Some((next, highest))
+    }
+
+    fn retains(&self) -> bool {
+        true
+    }
+
+    fn position(&self, _sequencer: &S) -> i64 {
+        self.consumed.get_acquire()
+    }
+
+    fn read(&self, buffer: &RingBuffer<T, S>, sequence: i64) -> T {
+        // SAFETY: the sequence was claimed, so it is published, and producers
+        // cannot reuse its slot before the receiver acknowledges it.
+        unsafe { buffer.get(sequence) }.clone()
+    }
+
+    /// Released items stay in the buffer until they are acknowledged.
+    fn release(&self, _sequencer: &S, _: i64, highest: i64) {
+        self.consumed.set_release(highest);
+    }
+
+    /// Items are cloned rather than moved out, so the rest of the range stays
+    /// in the buffer for the next claim.
+    fn abandon(&self, sequencer: &S, _: &RingBuffer<T, S>, range: (i64, i64), consumed: i64) {
+        self.release(sequencer, range.0, consumed);
+    }
+
+    fn rebase(&self) {
+        self.consumed.set_release(INITIAL_VALUE);
     }
 }
 
@@ -82,27 +342,146 @@ impl<T> Poller<T> for SingleConsumerPoller {
 ///
 /// Supports multiple consumers consuming concurrently from a single buffer.
 /// Uses a local [`Sequence`] to claim ranges of items safely.
+///
+/// Consumers finish their ranges in any order, so a released range is only
+/// handed to producers once every range before it is released too. Each
+/// release records the end of its range in the slot of its first sequence,
+/// and whoever releases the range right after the gating sequence moves the
+/// gating sequence over every released range that follows on.
+///
+/// A range requeued by a consumer whose handler panicked is claimed again,
+/// before any new item, by the next consumer to claim.
+///
+/// With [`ConsumerFairness::Fair`], consumers take turns to claim, see [`Turns`].
+#[cfg(feature = "mc")]
 pub(crate) struct MultiConsumerPoller {
     sequence: Sequence,
+    released: Box<[AtomicI64]>,
+    indexing: Indexing,
+    turns: Option<Turns>,
+    requeued: Mutex<Vec<(i64, i64)>>,
+    has_requeued: AtomicBool,
+}
+
+/// Number of polls of the turn a waiting consumer spins for before yielding.
+#[cfg(feature = "mc")]
+const SPIN_POLLS: usize = 64;
+
+/// The claim turns of the consumers of a fair [`MultiConsumerPoller`].
+///
+/// A consumer draws a ticket and claims once the tickets before it are
+/// served, so a consumer coming back from a batch queues up behind the others
+/// waiting to claim instead of winning the race for the next batch again.
+/// Every claim takes at most an even share of the backlog among the
+/// consumers, so the first consumer to claim cannot take everything either.
+#[cfg(feature = "mc")]
+struct Turns {
+    next: CachePadded<AtomicUsize>,
+    serving: CachePadded<AtomicUsize>,
+    consumers: AtomicUsize,
 }
 
+#[cfg(feature = "mc")]
 impl MultiConsumerPoller {
-    /// Create a new multi-consumer poller.
-    pub fn new() -> Self {
+    /// Create a new multi-consumer poller for a buffer of `buffer_size` slots.
+    pub fn new(buffer_size: usize) -> Self {
+        Self::with_fairness(buffer_size, ConsumerFairness::Throughput)
+    }
+
+    /// Create a new multi-consumer poller for a buffer of `buffer_size` slots
+    /// that spreads items between consumers as `fairness` asks.
+    pub fn with_fairness(buffer_size: usize, fairness: ConsumerFairness) -> Self {
         Self {
             sequence: Sequence::default(),
+            released: (0..buffer_size)
+                .map(|_| AtomicI64::new(INITIAL_VALUE))
+                .collect(),
+            indexing: Indexing::new(buffer_size),
+            turns: match fairness {
+                ConsumerFairness::Throughput => None,
+                ConsumerFairness::Fair => Some(Turns {
+                    next: CachePadded(AtomicUsize::new(0)),
+                    serving: CachePadded(AtomicUsize::new(0)),
+                    consumers: AtomicUsize::new(1),
+                }),
+            },
+            requeued: Mutex::new(Vec::new()),
+            has_requeued: AtomicBool::new(false),
         }
     }
-}
 
-impl<T> Poller<T> for MultiConsumerPoller {
-    fn poll(
+    /// Claim up to `batch_size` items of a requeued range, if there is one.
+    #[cold]
+    fn claim_requeued(&self, batch_size: i64) -> Option<(i64, i64)> {
+        let mut requeued = self.requeued.lock().unwrap_or_else(|e| e.into_inner());
+        let (low, high) = requeued.pop()?;
+        let claimed = (low, high.min(low + batch_size - 1));
+        if claimed.1 < high {
+            requeued.push((claimed.1 + 1, high));
+        }
+        self.has_requeued
+            .store(!requeued.is_empty(), Ordering::Release);
+        Some(claimed)
+    }
+
+    /// Claim up to `batch_size` items in turn with the other consumers, see [`Turns`].
+    fn claim_in_turn<S: Sequencer + ?Sized>(
         &self,
-        sequencer: &dyn Sequencer,
-        buffer: &RingBuffer<T>,
+        turns: &Turns,
+        sequencer: &S,
         batch_size: i64,
-        handler: &dyn Fn(T),
-    ) -> State {
+    ) -> Option<(i64, i64)> {
+        let ticket = turns.next.fetch_add(1, Ordering::Relaxed);
+        let mut polls = 0;
+        while turns.serving.load(Ordering::Acquire) != ticket {
+            if polls < SPIN_POLLS {
+                polls += 1;
+                spin_loop();
+            } else {
+                std::thread::yield_now();
+            }
+        }
+
+        // Nobody else claims until the turn is passed on.
+        let current = self.sequence.get_acquire();
+        let cursor = sequencer.get_cursor_sequence_acquire();
+        let consumers = turns.consumers.load(Ordering::Relaxed).max(1) as i64;
+        let share = ((cursor - current) / consumers).max(1);
+        let available = cursor.min(current + batch_size.min(share));
+        let highest = sequencer.get_highest(current + 1, available);
+        let claimed = (highest > current).then(|| {
+            self.sequence.set_release(highest);
+            (current + 1, highest)
+        });
+        turns.serving.store(ticket + 1, Ordering::Release);
+        claimed
+    }
+
+    /// Returns the end of the released range starting at `low`, if the range
+    /// was released in the current lap.
+    #[inline(always)]
+    fn released_from(&self, low: i64) -> Option<i64> {
+        let slot = &self.released[self.indexing.wrap(low, 0)];
+        let high = ordered!(Gating, Acquire, Acquire, slot.load(Ordering::Acquire));
+        (low..low + self.released.len() as i64)
+            .contains(&high)
+            .then_some(high)
+    }
+}
+
+#[cfg(feature = "mc")]
+impl<T, S: Sequencer + ?Sized> Poller<T, S> for MultiConsumerPoller {
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)> {
+        if self.has_requeued.load(Ordering::Acquire) {
+            let requeued = self.claim_requeued(batch_size);
+            if requeued.is_some() {
+                return requeued;
+            }
+        }
+        if let Some(turns) = &self.turns {
+            return self.claim_in_turn(turns, sequencer, batch_size);
+        }
+
         let mut current: i64;
         let mut next: i64;
         let mut available: i64;
@@ -117,24 +496,410 @@ impl<T> Poller<T> for MultiConsumerPoller {
             );
 
             if next > available {
-                return State::Idle;
+                return None;
             }
 
             highest = sequencer.get_highest(next, available);
+            if highest < next {
+                return None;
+            }
             if self
                 .sequence
                 .compare_and_exchange_weak_volatile(current, highest)
             {
-                break;
+                return Some((next, highest));
             }
         }
+    }
 
-        for sequence in next..=highest {
-            handler(buffer.dequeue(sequence));
+    fn release(&self, sequencer: &S, low: i64, highest: i64) {
+        let slot = &self.released[self.indexing.wrap(low, 0)];
+        ordered!(
+            Gating,
+            Release,
+            Release,
+            slot.store(highest, Ordering::Release)
+        );
+
+        // Pairs with the fence of a consumer that just moved the gating
+        // sequence, so either it sees this range or this consumer sees its move.
+        fence(Ordering::SeqCst);
+        let mut gating = sequencer.get_gating_sequence_relaxed();
+        while let Some(high) = self.released_from(gating + 1) {
+            sequencer.publish_gating_sequence(high);
+            fence(Ordering::SeqCst);
+            gating = sequencer.get_gating_sequence_relaxed();
         }
+    }
 
-        sequencer.publish_gating_sequence(highest);
-        State::Processing
+    /// Releases the consumed part of the range and keeps the rest for the
+    /// next claim, since other consumers have moved past the range.
+    fn requeue(&self, sequencer: &S, _: &RingBuffer<T, S>, range: (i64, i64), consumed: i64) {
+        let (low, high) = range;
+        if consumed >= low {
+            Poller::<T, S>::release(self, sequencer, low, consumed);
+        }
+        if consumed < high {
+            let mut requeued = self.requeued.lock().unwrap_or_else(|e| e.into_inner());
+            requeued.push((consumed.max(low - 1) + 1, high));
+            self.has_requeued.store(true, Ordering::Release);
+        }
+    }
+
+    fn shares_claims(&self) -> bool {
+        true
+    }
+
+    /// Counts the consumers sharing the poller, which a fair poller splits
+    /// the backlog between.
+    fn subscribe(&self) -> Option<Box<dyn Poller<T, S>>> {
+        if let Some(turns) = &self.turns {
+            turns.consumers.fetch_add(1, Ordering::Relaxed);
+        }
+        None
+    }
+
+    fn unsubscribe(&self, _sequencer: &S) {
+        if let Some(turns) = &self.turns {
+            turns.consumers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the highest sequence claimed by any consumer sharing the poller.
+    fn position(&self, _sequencer: &S) -> i64 {
+        self.sequence.get_acquire()
+    }
+
+    #[cfg(feature = "registry")]
+    fn consumers(&self) -> Consumers {
+        Consumers::Multi
+    }
+
+    fn rebase(&self) {
+        self.sequence.set_release(INITIAL_VALUE);
+        self.requeued
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.has_requeued.store(false, Ordering::Release);
+        for slot in &self.released {
+            slot.store(INITIAL_VALUE, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The consumer sequences of every receiver of a broadcast channel.
+///
+/// Receivers are only added and removed when they are cloned or dropped, so the
+/// hot path takes the lock for reading only.
+#[cfg(all(feature = "mp", feature = "mc"))]
+struct BroadcastGroup {
+    consumers: RwLock<Vec<Arc<Sequence>>>,
+}
+
+#[cfg(all(feature = "mp", feature = "mc"))]
+impl BroadcastGroup {
+    /// Publish the lowest consumer sequence as the gating sequence of producers.
+    ///
+    /// Every consumer advances its own sequence before publishing, and the
+    /// gating sequence only ever moves forward, so concurrent publishers settle
+    /// on the minimum of all consumers.
+    fn publish_minimum<S: Sequencer + ?Sized>(&self, sequencer: &S) {
+        fence(Ordering::SeqCst);
+        let consumers = self.consumers.read().unwrap_or_else(|e| e.into_inner());
+        let sequences = consumers.iter();
+        let minimum = sequences.map(|c| ordered!(Gating, Acquire, Acquire, c.get_acquire()));
+        if let Some(minimum) = minimum.min() {
+            sequencer.publish_gating_sequence(minimum);
+        }
+    }
+}
+
+/// Broadcast poller.
+///
+/// Every receiver of a broadcast channel has its own poller with its own
+/// [`Sequence`], observes every published item, and gets a clone of it. The
+/// items stay in the buffer, which is [`retaining`](RingBuffer::retaining),
+/// until the slowest receiver has moved past them. A single receiver is
+/// polled like a single-consumer channel.
+#[cfg(all(feature = "mp", feature = "mc"))]
+pub(crate) struct BroadcastPoller<T> {
+    group: Arc<BroadcastGroup>,
+    sequence: Arc<Sequence>,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+#[cfg(all(feature = "mp", feature = "mc"))]
+impl<T> BroadcastPoller<T> {
+    /// Create the poller of the first receiver of a broadcast channel.
+    pub fn new() -> Self {
+        let sequence = Arc::new(Sequence::default());
+        Self {
+            group: Arc::new(BroadcastGroup {
+                consumers: RwLock::new(vec![sequence.clone()]),
+            }),
+            sequence,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Create the poller of another receiver of the group, starting after the
+    /// sequence returned by `start`.
+    ///
+    /// `start` is called with the group locked for writing, so no receiver
+    /// publishes a gating sequence computed without the new one meanwhile.
+    fn join<F>(&self, start: F) -> Self
+    where
+        F: FnOnce() -> i64,
+    {
+        let mut consumers = self
+            .group
+            .consumers
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        let sequence = Arc::new(Sequence::new(start()));
+        consumers.push(sequence.clone());
+        Self {
+            group: self.group.clone(),
+            sequence,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "mp", feature = "mc"))]
+impl<T, S> Poller<T, S> for BroadcastPoller<T>
+where
+    T: Clone + Send + Sync + 'static,
+    S: Sequencer + ?Sized,
+{
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)> {
+        let current = self.sequence.get_relaxed();
+        let next: i64 = current + 1;
+        let available: i64 = std::cmp::min(
+            sequencer.get_cursor_sequence_acquire(),
+            current + batch_size,
+        );
+
+        if next > available {
+            return None;
+        }
+
+        let highest = sequencer.get_highest(next, available);
+        if highest < next {
+            return None;
+        }
+        Some((next, highest))
+    }
+
+    fn retains(&self) -> bool {
+        true
+    }
+
+    fn position(&self, _sequencer: &S) -> i64 {
+        self.sequence.get_acquire()
+    }
+
+    #[cfg(feature = "registry")]
+    fn consumers(&self) -> Consumers {
+        Consumers::Multi
+    }
+
+    fn read(&self, buffer: &RingBuffer<T, S>, sequence: i64) -> T {
+        // SAFETY: the sequence was claimed, so it is published, and producers
+        // cannot reuse its slot before this receiver releases it.
+        unsafe { buffer.get(sequence) }.clone()
+    }
+
+    /// The new receiver starts where this one is, so it sees every item this
+    /// one has yet to receive.
+    fn subscribe(&self) -> Option<Box<dyn Poller<T, S>>> {
+        Some(Box::new(self.join(|| self.sequence.get_acquire())))
+    }
+
+    /// The new receiver starts after the highest published item. No receiver
+    /// is past it, so producers stay gated below every item it has yet to see.
+    fn subscribe_latest(&self, sequencer: &S) -> Option<Box<dyn Poller<T, S>>> {
+        Some(Box::new(self.join(|| {
+            let low = sequencer.get_gating_sequence_relaxed() + 1;
+            sequencer.get_highest(low, sequencer.get_cursor_sequence_acquire())
+        })))
+    }
+
+    fn release(&self, sequencer: &S, _: i64, highest: i64) {
+        ordered!(Gating, Release, Release, self.sequence.set_release(highest));
+        self.group.publish_minimum(sequencer);
+    }
+
+    /// Items are cloned rather than moved out, so the rest of the range stays
+    /// in the buffer for the next claim.
+    fn abandon(&self, sequencer: &S, _: &RingBuffer<T, S>, range: (i64, i64), consumed: i64) {
+        self.release(sequencer, range.0, consumed);
+    }
+
+    fn rebase(&self) {
+        self.sequence.set_release(INITIAL_VALUE);
+    }
+
+    fn unsubscribe(&self, sequencer: &S) {
+        let mut consumers = self
+            .group
+            .consumers
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        consumers.retain(|sequence| !Arc::ptr_eq(sequence, &self.sequence));
+        drop(consumers);
+        self.group.publish_minimum(sequencer);
+    }
+}
+
+/// The consumer sequences of the fixed groups of a tee channel.
+#[cfg(feature = "mc")]
+struct TeeGroups {
+    groups: Box<[TeeGroup]>,
+}
+
+/// A consumer group of a tee channel.
+#[cfg(feature = "mc")]
+struct TeeGroup {
+    sequence: Sequence,
+    /// The live receivers of the group, which stops gating producers once
+    /// they are all gone.
+    receivers: AtomicUsize,
+}
+
+#[cfg(feature = "mc")]
+impl TeeGroups {
+    /// Publish the lowest sequence of the groups with live receivers as the
+    /// gating sequence of producers.
+    ///
+    /// The gating sequence only ever moves forward, so concurrent publishers
+    /// settle on the minimum of all groups.
+    fn publish_minimum<S: Sequencer + ?Sized>(&self, sequencer: &S) {
+        fence(Ordering::SeqCst);
+        let live = self
+            .groups
+            .iter()
+            .filter(|group| group.receivers.load(Ordering::Acquire) > 0);
+        let minimum =
+            live.map(|group| ordered!(Gating, Acquire, Acquire, group.sequence.get_acquire()));
+        if let Some(minimum) = minimum.min() {
+            sequencer.publish_gating_sequence(minimum);
+        }
+    }
+}
+
+/// Tee poller.
+///
+/// Every group of a tee channel has its own poller with its own [`Sequence`],
+/// observes every published item, and gets a clone of it. Unlike with a
+/// broadcast poller, the groups are fixed when the channel is created, so
+/// the set of sequences gating producers needs no lock. Clones of a receiver
+/// stay in its group and share its poller.
+#[cfg(feature = "mc")]
+pub(crate) struct TeePoller<T> {
+    groups: Arc<TeeGroups>,
+    group: usize,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "mc")]
+impl<T> TeePoller<T> {
+    /// Create the pollers of `groups` groups, each with a single receiver.
+    pub fn groups(groups: usize) -> Vec<Self> {
+        let shared = Arc::new(TeeGroups {
+            groups: (0..groups)
+                .map(|_| TeeGroup {
+                    sequence: Sequence::default(),
+                    receivers: AtomicUsize::new(1),
+                })
+                .collect(),
+        });
+        (0..groups)
+            .map(|group| Self {
+                groups: shared.clone(),
+                group,
+                _marker: std::marker::PhantomData,
+            })
+            .collect()
+    }
+
+    /// Returns the group of this poller.
+    fn group(&self) -> &TeeGroup {
+        &self.groups.groups[self.group]
+    }
+}
+
+#[cfg(feature = "mc")]
+impl<T, S> Poller<T, S> for TeePoller<T>
+where
+    T: Clone + Send + Sync + 'static,
+    S: Sequencer + ?Sized,
+{
+    fn claim(&self, sequencer: &S, batch_size: i64) -> Option<(i64, i64)> {
+        let current = self.group().sequence.get_relaxed();
+        let next: i64 = current + 1;
+        let available: i64 = std::cmp::min(
+            sequencer.get_cursor_sequence_acquire(),
+            current + batch_size,
+        );
+
+        if next > available {
+            return None;
+        }
+
+        let highest = sequencer.get_highest(next, available);
+        if highest < next {
+            return None;
+        }
+        Some((next, highest))
+    }
+
+    fn retains(&self) -> bool {
+        true
+    }
+
+    fn position(&self, _sequencer: &S) -> i64 {
+        self.group().sequence.get_acquire()
+    }
+
+    #[cfg(feature = "registry")]
+    fn consumers(&self) -> Consumers {
+        Consumers::Multi
+    }
+
+    fn read(&self, buffer: &RingBuffer<T, S>, sequence: i64) -> T {
+        // SAFETY: the sequence was claimed, so it is published, and producers
+        // cannot reuse its slot before this group releases it.
+        unsafe { buffer.get(sequence) }.clone()
+    }
+
+    /// Counts the receivers of the group, which share this poller.
+    fn subscribe(&self) -> Option<Box<dyn Poller<T, S>>> {
+        self.group().receivers.fetch_add(1, Ordering::AcqRel);
+        None
+    }
+
+    fn unsubscribe(&self, sequencer: &S) {
+        if self.group().receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.groups.publish_minimum(sequencer);
+        }
+    }
+
+    fn release(&self, sequencer: &S, _: i64, highest: i64) {
+        let sequence = &self.group().sequence;
+        ordered!(Gating, Release, Release, sequence.set_release(highest));
+        self.groups.publish_minimum(sequencer);
+    }
+
+    /// Items are cloned rather than moved out, so the rest of the range stays
+    /// in the buffer for the next claim.
+    fn abandon(&self, sequencer: &S, _: &RingBuffer<T, S>, range: (i64, i64), consumed: i64) {
+        Poller::<T, S>::release(self, sequencer, range.0, consumed);
+    }
+
+    fn rebase(&self) {
+        self.group().sequence.set_release(INITIAL_VALUE);
     }
 }
 
@@ -143,6 +908,160 @@ unsafe impl Send for SingleConsumerPoller {}
 
 unsafe impl Sync for SingleConsumerPoller {}
 
+#[cfg(feature = "mc")]
 unsafe impl Send for MultiConsumerPoller {}
 
+#[cfg(feature = "mc")]
 unsafe impl Sync for MultiConsumerPoller {}
+
+#[cfg(all(test, feature = "mc", not(feature = "loom")))]
+mod tests {
+    #[cfg(feature = "mp")]
+    use crate::channels::broadcast;
+    use crate::channels::{
+        ChannelBuilder, ConsumerFairness, Consumers, RecvResult, spmc_with_fairness, tee,
+    };
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::errors::TrySendError;
+    use crate::poller::{MultiConsumerPoller, Poller};
+    use crate::sequencer::{Sequencer, SingleProducerSequencer};
+    use loom::sync::Arc;
+
+    /// A sequencer of `buffer_size` slots with every slot published.
+    fn published(buffer_size: usize) -> SingleProducerSequencer {
+        let sequencer = SingleProducerSequencer::new(buffer_size);
+        sequencer.publish_cursor_sequence_range(0, buffer_size as i64 - 1);
+        sequencer
+    }
+
+    fn claim(poller: &MultiConsumerPoller, sequencer: &SingleProducerSequencer) -> (i64, i64) {
+        Poller::<u32, _>::claim(poller, sequencer, 2).unwrap()
+    }
+
+    fn release(
+        poller: &MultiConsumerPoller,
+        sequencer: &SingleProducerSequencer,
+        range: (i64, i64),
+    ) {
+        Poller::<u32, _>::release(poller, sequencer, range.0, range.1);
+    }
+
+    #[test]
+    fn test_out_of_order_releases_never_move_gating_past_a_held_range() {
+        let sequencer = published(8);
+        let poller = MultiConsumerPoller::new(8);
+        let first = claim(&poller, &sequencer);
+        let second = claim(&poller, &sequencer);
+
+        release(&poller, &sequencer, second);
+        assert_eq!(sequencer.get_gating_sequence_relaxed(), -1);
+        release(&poller, &sequencer, first);
+        assert_eq!(sequencer.get_gating_sequence_relaxed(), 3);
+    }
+
+    #[test]
+    fn test_concurrent_releases_publish_every_contiguous_range() {
+        loom::model(|| {
+            let sequencer = Arc::new(published(4));
+            let poller = Arc::new(MultiConsumerPoller::new(4));
+
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    let (sequencer, poller) = (sequencer.clone(), poller.clone());
+                    loom::thread::spawn(move || {
+                        let range = claim(&poller, &sequencer);
+                        release(&poller, &sequencer, range);
+                    })
+                })
+                .collect();
+            for consumer in consumers {
+                consumer.join().unwrap();
+            }
+            assert_eq!(sequencer.get_gating_sequence_relaxed(), 3);
+        })
+    }
+
+    #[cfg(feature = "mc")]
+    #[test]
+    fn test_fair_consumers_claim_an_even_share() {
+        let (tx, rx) = spmc_with_fairness::<u32>(
+            16,
+            ConsumerFairness::Fair,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let others = [rx.clone(), rx.clone(), rx.clone()];
+        tx.send_n(0..12).unwrap();
+        assert_eq!(rx.try_recv_batch(16, &drop), RecvResult::Processed(3));
+        assert_eq!(
+            others[0].try_recv_batch(16, &drop),
+            RecvResult::Processed(2)
+        );
+        assert_eq!(others[1].try_recv_batch(1, &drop), RecvResult::Processed(1));
+
+        // The remaining consumers split what is left.
+        drop(others);
+        assert_eq!(rx.try_recv_batch(16, &drop), RecvResult::Processed(6));
+
+        let (tx, rx) = ChannelBuilder::<u32>::new()
+            .capacity(16)
+            .consumers(Consumers::Multi)
+            .build();
+        let _other = rx.clone();
+        tx.send_n(0..12).unwrap();
+        assert_eq!(rx.try_recv_batch(16, &drop), RecvResult::Processed(12));
+    }
+
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    #[test]
+    fn test_broadcast_delivers_every_item_to_every_receiver() {
+        let (tx, rx) = broadcast::<String>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let other = rx.clone();
+        let dropped = rx.clone();
+        drop(dropped);
+
+        let producer = std::thread::spawn(move || {
+            for value in 0..500 {
+                tx.send(value.to_string()).unwrap();
+            }
+        });
+        let consumer = std::thread::spawn(move || other.iter().collect::<Vec<String>>());
+        let received: Vec<String> = rx.iter().collect();
+        producer.join().unwrap();
+
+        let expected: Vec<String> = (0..500).map(|value| value.to_string()).collect();
+        assert_eq!(received, expected);
+        assert_eq!(consumer.join().unwrap(), expected);
+    }
+
+    #[cfg(feature = "mc")]
+    #[test]
+    fn test_tee_mirrors_every_item_to_every_group() {
+        let (tx, groups) = tee::<String>(
+            4,
+            3,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let [first, second, third] = <[_; 3]>::try_from(groups).ok().unwrap();
+        let member = first.clone();
+        tx.send_n((0..4).map(|value| value.to_string())).unwrap();
+        assert_eq!(first.drain_all(), ["0", "1", "2", "3"]);
+        assert_eq!(second.drain_all(), ["0", "1", "2", "3"]);
+        assert!(member.drain_all().is_empty());
+
+        // The third group gates the producer until it is gone.
+        assert!(matches!(
+            tx.try_send("4".into()),
+            Err(TrySendError::Full(_))
+        ));
+        drop(third);
+        tx.send("4".into()).unwrap();
+        assert_eq!(member.drain_all(), ["4"]);
+        assert_eq!(second.drain_all(), ["4"]);
+    }
+}
@@ -0,0 +1,290 @@
+//! A pool of consumer threads sharing the items of a channel.
+//!
+//! A [`ConsumerPool`] runs a number of workers, each a clone of one
+//! [`Receiver`] of a multi-consumer channel consuming on a thread of its own
+//! with a handler made by a factory, so every item is handled by exactly one
+//! worker. [`ConsumerPool::resize`] adds workers or stops the latest ones
+//! while the pool runs.
+//!
+//! A handler that panics loses the item it was handling. The worker catches
+//! the panic and, as its [`RestartPolicy`] allows, replaces the handler by a
+//! fresh one from the factory and carries on with the next item; once the
+//! policy is exhausted the panic ends the worker thread.
+
+use crate::channels::{ConsumerHandle, ConsumerOptions, Receiver};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Makes the handler of the worker with the given index.
+type HandlerFactory<T> = dyn Fn(usize) -> Box<dyn FnMut(T) + Send> + Send + Sync;
+
+/// How often a worker replaces a handler that panicked.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum RestartPolicy {
+    /// Let the first panic end the worker.
+    Never,
+    /// Replace the handler after every panic.
+    #[default]
+    Always,
+    /// Replace the handler after up to this many panics of the worker, and
+    /// let the next one end it.
+    Limited(usize),
+}
+
+impl RestartPolicy {
+    /// Returns `true` if a worker that restarted `restarts` times may restart again.
+    fn allows(self, restarts: usize) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::Limited(limit) => restarts < limit,
+        }
+    }
+}
+
+/// How a [`ConsumerPool`] runs its workers.
+#[derive(Clone, Debug)]
+pub struct PoolOptions {
+    name: String,
+    batch_size: usize,
+    restart: RestartPolicy,
+}
+
+impl PoolOptions {
+    /// Run threads named `name-<index>` that receive batches of up to
+    /// `batch_size` items and always restart their handlers.
+    pub fn new(name: impl Into<String>, batch_size: usize) -> Self {
+        Self {
+            name: name.into(),
+            batch_size,
+            restart: RestartPolicy::default(),
+        }
+    }
+
+    /// Restart handlers that panic according to `restart`.
+    pub fn restart(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self::new("consumer-pool", 64)
+    }
+}
+
+/// Consumer threads sharing the items of a channel.
+///
+/// See the [module documentation](self). Dropping the pool detaches its
+/// workers, which keep consuming until the channel disconnects.
+pub struct ConsumerPool<T> {
+    receiver: Receiver<T>,
+    factory: Arc<HandlerFactory<T>>,
+    options: PoolOptions,
+    restarts: Arc<AtomicUsize>,
+    /// The workers in the order they were spawned, their index being their
+    /// position.
+    workers: Vec<ConsumerHandle>,
+}
+
+impl<T: Send + 'static> ConsumerPool<T> {
+    /// Consume from `receiver` on `num_threads` threads, the worker with index
+    /// `i` handing the items to the handler returned by `handler_factory(i)`.
+    ///
+    /// # Panics
+    /// Panics if a thread cannot be spawned.
+    pub fn new<F, H>(receiver: Receiver<T>, num_threads: usize, handler_factory: F) -> Self
+    where
+        F: Fn(usize) -> H + Send + Sync + 'static,
+        H: FnMut(T) + Send + 'static,
+    {
+        Self::with_options(
+            receiver,
+            num_threads,
+            PoolOptions::default(),
+            handler_factory,
+        )
+        .expect("failed to spawn the consumer pool")
+    }
+
+    /// Like [`new`](Self::new), with the workers configured by `options`.
+    ///
+    /// # Errors
+    /// Returns the error of spawning a thread, in which case the workers
+    /// spawned before are stopped.
+    pub fn with_options<F, H>(
+        receiver: Receiver<T>,
+        num_threads: usize,
+        options: PoolOptions,
+        handler_factory: F,
+    ) -> io::Result<Self>
+    where
+        F: Fn(usize) -> H + Send + Sync + 'static,
+        H: FnMut(T) + Send + 'static,
+    {
+        let mut pool = Self {
+            receiver,
+            factory: Arc::new(move |index| Box::new(handler_factory(index))),
+            options,
+            restarts: Arc::default(),
+            workers: Vec::with_capacity(num_threads),
+        };
+        if let Err(error) = pool.resize(num_threads) {
+            pool.stop();
+            return Err(error);
+        }
+        Ok(pool)
+    }
+
+    /// Run `num_threads` workers from now on.
+    ///
+    /// Spawns the missing workers, or stops the latest ones and waits for
+    /// them to finish their current batch. Items the stopped workers have
+    /// not taken yet are left to the others.
+    ///
+    /// # Errors
+    /// Returns the error of spawning a thread, keeping the workers spawned before.
+    pub fn resize(&mut self, num_threads: usize) -> io::Result<()> {
+        while self.workers.len() < num_threads {
+            let worker = self.spawn(self.workers.len())?;
+            self.workers.push(worker);
+        }
+        let stopped = self.workers.split_off(num_threads);
+        for worker in &stopped {
+            worker.stop();
+        }
+        for worker in stopped {
+            // A worker that ended on a panic already reported it.
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+
+    /// Spawn the worker with the given index.
+    fn spawn(&self, index: usize) -> io::Result<ConsumerHandle> {
+        let factory = self.factory.clone();
+        let restarts = self.restarts.clone();
+        let policy = self.options.restart;
+        let mut handler = factory(index);
+        let mut restarted = 0;
+        let worker = move |item| {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| handler(item)));
+            if let Err(payload) = outcome {
+                if !policy.allows(restarted) {
+                    panic::resume_unwind(payload);
+                }
+                restarted += 1;
+                restarts.fetch_add(1, Ordering::Relaxed);
+                handler = factory(index);
+            }
+        };
+        let options = ConsumerOptions::new(
+            format!("{}-{index}", self.options.name),
+            self.options.batch_size,
+        );
+        self.receiver.clone().spawn_consumer_with(options, worker)
+    }
+
+    /// Returns the number of workers.
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns `true` if the pool has no workers.
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Returns the number of workers whose thread is still running.
+    pub fn running(&self) -> usize {
+        self.workers
+            .iter()
+            .filter(|worker| !worker.is_finished())
+            .count()
+    }
+
+    /// Returns how many handlers were replaced after a panic.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Ask every worker to stop once it has handled its current batch.
+    pub fn stop(&self) {
+        for worker in &self.workers {
+            worker.stop();
+        }
+    }
+
+    /// Wait for every worker to exit.
+    ///
+    /// Workers exit once they are [`stop`](Self::stop)ped, or once the
+    /// channel is closed or every sender is gone and the buffer is drained.
+    ///
+    /// # Errors
+    /// Returns the panic payload of the first worker that ended on a panic.
+    pub fn join(mut self) -> thread::Result<()> {
+        let mut result = Ok(());
+        for worker in self.workers.drain(..) {
+            let joined = worker.join();
+            if result.is_ok() {
+                result = joined;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::spmc;
+    use crate::pool::{ConsumerPool, PoolOptions, RestartPolicy};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn test_pool_resizes_and_restarts_panicking_handlers() {
+        let (tx, rx) = spmc::<u32>(
+            128,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handled = received.clone();
+        let options = PoolOptions::new("pool", 4).restart(RestartPolicy::Limited(1));
+        let mut pool = ConsumerPool::with_options(rx, 2, options, move |_| {
+            let handled = handled.clone();
+            move |value| {
+                assert_ne!(value, 13, "unlucky");
+                handled.lock().unwrap().push(value);
+            }
+        })
+        .unwrap();
+        assert_eq!(pool.len(), 2);
+
+        tx.send_n(0..100).unwrap();
+        pool.resize(4).unwrap();
+        while pool.restarts() == 0 {
+            thread::yield_now();
+        }
+        tx.send_n(100..200).unwrap();
+        pool.resize(1).unwrap();
+        assert_eq!((pool.len(), pool.running()), (1, 1));
+        tx.send_n(200..300).unwrap();
+        drop(tx);
+        assert!(pool.join().is_ok());
+
+        // The item the handler panicked on is lost, every other one handled once.
+        let mut received = received.lock().unwrap().clone();
+        received.sort_unstable();
+        assert!(
+            received
+                .into_iter()
+                .eq((0..300).filter(|&value| value != 13))
+        );
+    }
+}
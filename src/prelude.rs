@@ -1,2 +1,11 @@
 pub use crate::channels::*;
-pub use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+pub use crate::combinators::Receive;
+pub use crate::coordinator::{
+    ConsumerWaitStrategy, ConsumerWaitStrategyKind, NotifyPolicy, ProducerWaitStrategy,
+    ProducerWaitStrategyKind,
+};
+pub use crate::errors::*;
+pub use crate::flow::{FlowController, FlowHandle};
+pub use crate::static_channels::{
+    ConstReceiver, ConstSender, StaticReceiver, StaticSender, spsc_const, spsc_static,
+};
@@ -0,0 +1,309 @@
+//! Small cache-padded synchronization primitives.
+//!
+//! These are the building blocks the channels use internally (see the
+//! crate-private `Sequence`), exposed for telemetry that lives next to a
+//! channel: counters and flags written by one thread and read wait-free by
+//! others, and a seqlock-style cell for consistent multi-field snapshots.
+//!
+//! Every primitive is aligned to the cache line size of the target architecture,
+//! so that neighbouring instances never share a cache line.
+
+use crate::sync::{AtomicBool, AtomicU64, Ordering, fence, spin_loop};
+use crate::utils::CachePadded;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ptr;
+
+/// A cache-padded monotonic counter.
+///
+/// [`add`](Self::add) and [`increment`](Self::increment) are meant for a single
+/// writer: they use a plain load and a **Release** store instead of a locked
+/// read-modify-write, so neither the writer nor the readers ever wait.
+/// Use [`fetch_add`](Self::fetch_add) when several threads update the counter.
+#[derive(Default)]
+pub struct PaddedCounter {
+    value: CachePadded<AtomicU64>,
+}
+
+impl PaddedCounter {
+    /// Create a new counter initialized to `value`.
+    pub fn new(value: u64) -> Self {
+        Self {
+            value: CachePadded(AtomicU64::new(value)),
+        }
+    }
+
+    /// Get the current value with **Acquire** memory ordering.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Set the value with **Release** memory ordering.
+    pub fn set(&self, value: u64) {
+        self.value.store(value, Ordering::Release);
+    }
+
+    /// Add `n` to the counter from its single writer.
+    ///
+    /// Concurrent calls from several threads can lose updates.
+    pub fn add(&self, n: u64) {
+        let current = self.value.load(Ordering::Relaxed);
+        self.value.store(current.wrapping_add(n), Ordering::Release);
+    }
+
+    /// Add one to the counter from its single writer.
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Atomically add `n` using **AcqRel** ordering, safe for any number of writers.
+    ///
+    /// Returns the previous value.
+    pub fn fetch_add(&self, n: u64) -> u64 {
+        self.value.fetch_add(n, Ordering::AcqRel)
+    }
+
+    /// Atomically subtract `n` using **AcqRel** ordering, safe for any number of writers.
+    ///
+    /// Returns the previous value.
+    pub fn fetch_sub(&self, n: u64) -> u64 {
+        self.value.fetch_sub(n, Ordering::AcqRel)
+    }
+
+    /// Atomically raise the counter to `n` if it is lower, using **AcqRel**
+    /// ordering, safe for any number of writers.
+    ///
+    /// Returns the previous value.
+    pub fn fetch_max(&self, n: u64) -> u64 {
+        self.value.fetch_max(n, Ordering::AcqRel)
+    }
+}
+
+impl fmt::Debug for PaddedCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaddedCounter")
+            .field("value", &self.value.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// A cache-padded boolean flag.
+#[derive(Default)]
+pub struct PaddedFlag {
+    flag: CachePadded<AtomicBool>,
+}
+
+impl PaddedFlag {
+    /// Create a new flag with the given initial state.
+    pub fn new(value: bool) -> Self {
+        Self {
+            flag: CachePadded(AtomicBool::new(value)),
+        }
+    }
+
+    /// Returns `true` if the flag is set, with **Acquire** memory ordering.
+    pub fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Acquire)
+    }
+
+    /// Set the flag with **Release** memory ordering.
+    pub fn set(&self) {
+        self.flag.store(true, Ordering::Release);
+    }
+
+    /// Clear the flag with **Release** memory ordering.
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Release);
+    }
+
+    /// Set the flag and return its previous state, using **AcqRel** ordering.
+    pub fn swap(&self, value: bool) -> bool {
+        self.flag.swap(value, Ordering::AcqRel)
+    }
+}
+
+impl fmt::Debug for PaddedFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaddedFlag")
+            .field("flag", &self.flag.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// A seqlock-style cell holding a `T: Copy` snapshot.
+///
+/// Writers bump a version counter to an odd value, write the value and bump it
+/// back to even. Readers copy the value optimistically and retry if the version
+/// changed underneath them, so a read always returns a value that was written
+/// as a whole. Readers never block writers; concurrent writers serialize on the
+/// version counter.
+pub struct SeqLock<T: Copy> {
+    version: CachePadded<AtomicU64>,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the value is only written while the version is odd, which only one
+// writer can hold at a time, and readers discard copies taken during a write.
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// Create a new cell holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            version: CachePadded(AtomicU64::new(0)),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Replace the stored value.
+    pub fn write(&self, value: T) {
+        let mut version = self.version.load(Ordering::Relaxed);
+        loop {
+            if version & 1 == 0 {
+                match self.version.compare_exchange_weak(
+                    version,
+                    version + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(current) => version = current,
+                }
+            } else {
+                spin_loop();
+                version = self.version.load(Ordering::Relaxed);
+            }
+        }
+        fence(Ordering::Release);
+
+        // SAFETY: the odd version grants exclusive write access to the value.
+        unsafe { ptr::write_volatile(self.value.get(), value) };
+
+        self.version.store(version + 2, Ordering::Release);
+    }
+
+    /// Try to copy the stored value once.
+    ///
+    /// Returns `None` if a write was in progress or completed during the copy.
+    pub fn try_read(&self) -> Option<T> {
+        let before = self.version.load(Ordering::Acquire);
+        if before & 1 == 1 {
+            return None;
+        }
+
+        // SAFETY: a torn copy of a `Copy` value is discarded below, never returned.
+        let value = unsafe { ptr::read_volatile(self.value.get()) };
+        fence(Ordering::Acquire);
+
+        if self.version.load(Ordering::Relaxed) != before {
+            return None;
+        }
+        Some(value)
+    }
+
+    /// Copy the stored value, retrying until a consistent snapshot is taken.
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            spin_loop();
+        }
+    }
+
+    /// Returns the number of completed writes.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire) >> 1
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        SeqLock::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::CACHE_LINE_SIZE;
+    use crate::primitives::{PaddedCounter, PaddedFlag, SeqLock};
+    use loom::sync::Arc;
+
+    #[test]
+    fn test_padded_layout() {
+        assert_eq!(align_of::<PaddedCounter>(), CACHE_LINE_SIZE);
+        assert_eq!(align_of::<PaddedFlag>(), CACHE_LINE_SIZE);
+        assert_eq!(align_of::<SeqLock<u64>>(), CACHE_LINE_SIZE);
+    }
+
+    #[test]
+    fn test_counter_single_writer() {
+        loom::model(|| {
+            let counter = Arc::new(PaddedCounter::default());
+            let cloned = counter.clone();
+
+            loom::thread::spawn(move || {
+                cloned.increment();
+                cloned.add(2);
+            });
+
+            let value = counter.get();
+            assert!(value == 0 || value == 1 || value == 3);
+        })
+    }
+
+    #[test]
+    fn test_flag_set_and_clear() {
+        loom::model(|| {
+            let flag = Arc::new(PaddedFlag::default());
+            let cloned = flag.clone();
+
+            loom::thread::spawn(move || {
+                cloned.set();
+            });
+
+            let _ = flag.is_set();
+            flag.clear();
+            assert!(!flag.swap(true) || flag.is_set());
+        })
+    }
+
+    #[test]
+    fn test_seqlock_snapshot_is_never_torn() {
+        loom::model(|| {
+            let cell = Arc::new(SeqLock::new((0u64, 0u64)));
+            let cloned = cell.clone();
+
+            loom::thread::spawn(move || {
+                for i in 1..=3 {
+                    cloned.write((i, i));
+                }
+            });
+
+            for _ in 0..3 {
+                let (a, b) = cell.read();
+                assert_eq!(a, b);
+            }
+        })
+    }
+
+    #[test]
+    fn test_seqlock_concurrent_writers() {
+        loom::model(|| {
+            let cell = Arc::new(SeqLock::new((0u64, 0u64)));
+            let first = cell.clone();
+            let second = cell.clone();
+
+            let a = loom::thread::spawn(move || first.write((1, 1)));
+            let b = loom::thread::spawn(move || second.write((2, 2)));
+            a.join().unwrap();
+            b.join().unwrap();
+
+            let (x, y) = cell.read();
+            assert_eq!(x, y);
+            assert_eq!(cell.version(), 2);
+        })
+    }
+}
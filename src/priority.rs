@@ -0,0 +1,244 @@
+//! Priority channels with a ring buffer per priority lane.
+//!
+//! A [`priority_mpsc`] channel keeps one multi-producer ring per lane, so
+//! control-plane messages sent on a high priority lane never queue up behind
+//! the data-plane traffic of a lower one. The [`PriorityReceiver`] serves the
+//! lanes in rounds, highest priority first, and every lane delivers at most
+//! its weight of items per round: higher lanes get the larger share, while a
+//! busy high lane cannot starve the lanes below it.
+
+use crate::channels::{Receiver, RecvState, Sender, mpsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::{SendError, TrySendError};
+
+/// The lane of a priority channel, lane `0` being the highest priority.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(usize);
+
+impl Priority {
+    /// The first lane.
+    pub const HIGH: Priority = Priority(0);
+    /// The second lane.
+    pub const NORMAL: Priority = Priority(1);
+    /// The third lane.
+    pub const LOW: Priority = Priority(2);
+
+    /// Returns the priority of lane `index`.
+    pub const fn lane(index: usize) -> Self {
+        Priority(index)
+    }
+
+    /// Returns the index of the lane.
+    pub const fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The sending half of a priority channel, created by [`priority_mpsc`].
+pub struct PrioritySender<T> {
+    lanes: Vec<Sender<T>>,
+}
+
+impl<T> PrioritySender<T> {
+    /// Send a single value on the lane of `priority`.
+    ///
+    /// Waits according to the producer wait strategy while that lane is full,
+    /// whatever the other lanes hold.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed.
+    ///
+    /// # Panics
+    /// Panics if the channel has no lane of `priority`.
+    pub fn send_with_priority(&self, value: T, priority: Priority) -> Result<(), SendError<T>> {
+        self.lane(priority).send(value)
+    }
+
+    /// Try to send a single value on the lane of `priority` without waiting
+    /// for free space.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the lane has no free slot.
+    /// - [`TrySendError::Closed`] if the channel is closed.
+    ///
+    /// # Panics
+    /// Panics if the channel has no lane of `priority`.
+    pub fn try_send_with_priority(
+        &self,
+        value: T,
+        priority: Priority,
+    ) -> Result<(), TrySendError<T>> {
+        self.lane(priority).try_send(value)
+    }
+
+    /// Returns the number of lanes.
+    pub fn lanes(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.lanes.iter().any(Sender::is_closed)
+    }
+
+    fn lane(&self, priority: Priority) -> &Sender<T> {
+        self.lanes
+            .get(priority.0)
+            .expect("the channel has no lane of this priority")
+    }
+}
+
+impl<T> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            lanes: self.lanes.clone(),
+        }
+    }
+}
+
+/// A lane of a [`PriorityReceiver`].
+struct Lane<T> {
+    receiver: Receiver<T>,
+    weight: usize,
+}
+
+/// The receiving half of a priority channel, created by [`priority_mpsc`].
+///
+/// See the [module documentation](self).
+pub struct PriorityReceiver<T> {
+    lanes: Vec<Lane<T>>,
+}
+
+impl<T> PriorityReceiver<T> {
+    /// Run a single round over every lane, highest priority first, without waiting.
+    ///
+    /// Invokes `handler` with the priority and the item for every item
+    /// delivered. Returns [`RecvState::Disconnected`] once every sender is gone
+    /// or the channel is closed, and every lane has been drained.
+    pub fn recv<H>(&self, handler: &H) -> RecvState
+    where
+        H: Fn(Priority, T),
+    {
+        let mut received = false;
+        let mut disconnected = true;
+        for (index, lane) in self.lanes.iter().enumerate() {
+            let priority = Priority(index);
+            let (_, state) = lane
+                .receiver
+                .recv_up_to(lane.weight, &|item| handler(priority, item));
+            received |= state == RecvState::Received;
+            disconnected &= state == RecvState::Disconnected;
+        }
+
+        match (received, disconnected) {
+            (true, _) => RecvState::Received,
+            (false, true) => RecvState::Disconnected,
+            (false, false) => RecvState::Empty,
+        }
+    }
+
+    /// Run rounds until at least one item is delivered or the channel is disconnected.
+    ///
+    /// The lanes have no common wait strategy, so the thread yields between
+    /// empty rounds.
+    pub fn blocking_recv<H>(&self, handler: &H) -> RecvState
+    where
+        H: Fn(Priority, T),
+    {
+        loop {
+            match self.recv(handler) {
+                RecvState::Empty => std::thread::yield_now(),
+                state => return state,
+            }
+        }
+    }
+
+    /// Returns the number of items waiting in the lane of `priority`.
+    ///
+    /// # Panics
+    /// Panics if the channel has no lane of `priority`.
+    pub fn backlog(&self, priority: Priority) -> usize {
+        self.lanes[priority.0].receiver.len()
+    }
+
+    /// Close every lane, see [`Receiver::close`].
+    pub fn close(&self) {
+        for lane in &self.lanes {
+            lane.receiver.close();
+        }
+    }
+}
+
+/// Create a **multi-producer single-consumer (MPSC)** channel with a ring of
+/// `buffer_size` slots per priority lane.
+///
+/// `weights` holds the number of items each lane delivers per round of the
+/// receiver, highest priority first, so `&[8, 4, 1]` creates the
+/// [`HIGH`](Priority::HIGH), [`NORMAL`](Priority::NORMAL) and
+/// [`LOW`](Priority::LOW) lanes.
+///
+/// # Panics
+/// Panics if `weights` is empty or holds a zero weight, and if `buffer_size`
+/// is not a valid buffer size.
+pub fn priority_mpsc<T>(
+    buffer_size: usize,
+    weights: &[usize],
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    assert!(!weights.is_empty(), "a priority channel needs a lane");
+    assert!(
+        weights.iter().all(|&weight| weight > 0),
+        "weights must be greater than zero"
+    );
+    let (senders, lanes) = weights
+        .iter()
+        .map(|&weight| {
+            let (sender, receiver) = mpsc(buffer_size, pw, cw);
+            (sender, Lane { receiver, weight })
+        })
+        .unzip();
+    (
+        PrioritySender { lanes: senders },
+        PriorityReceiver { lanes },
+    )
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::priority::{Priority, priority_mpsc};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_lanes_are_served_by_priority_and_weight() {
+        let (tx, rx) = priority_mpsc::<u32>(
+            16,
+            &[3, 2, 1],
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        for value in 0..4 {
+            tx.send_with_priority(value, Priority::LOW).unwrap();
+            tx.send_with_priority(10 + value, Priority::NORMAL).unwrap();
+            tx.send_with_priority(20 + value, Priority::HIGH).unwrap();
+        }
+        assert_eq!(rx.backlog(Priority::HIGH), 4);
+
+        let received = RefCell::new(Vec::new());
+        let handler =
+            |priority: Priority, item| received.borrow_mut().push((priority.index(), item));
+        assert_eq!(rx.recv(&handler), RecvState::Received);
+        assert_eq!(
+            received.take(),
+            [(0, 20), (0, 21), (0, 22), (1, 10), (1, 11), (2, 0)]
+        );
+        assert_eq!(rx.recv(&handler), RecvState::Received);
+        assert_eq!(received.take(), [(0, 23), (1, 12), (1, 13), (2, 1)]);
+
+        drop(tx);
+        while rx.recv(&handler) != RecvState::Disconnected {}
+        assert_eq!(received.take(), [(2, 2), (2, 3)]);
+    }
+}
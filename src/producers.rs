@@ -0,0 +1,210 @@
+//! Per-producer slots for channels with a bounded number of senders.
+//!
+//! Channels created with [`mpsc_with_producers`](crate::channels::mpsc_with_producers)
+//! or [`mpmc_with_producers`](crate::channels::mpmc_with_producers) give every
+//! [`Sender`](crate::channels::Sender) its own slot, indexed by a producer id.
+//! Cloning a sender beyond the maximum fails fast instead of silently adding
+//! contention, and the slots tell which producer is stuck waiting for space.
+//! Every item is stamped with the id of the producer that sent it, see
+//! [`Receiver::recv_with_producer`](crate::channels::Receiver::recv_with_producer),
+//! and the slots count what each producer sent and how long its sends took,
+//! which shows who generates the load and who suffers from backpressure.
+//!
+//! The slots are for registration and diagnostics only: producers still claim
+//! sequences through the shared cursor of the multi-producer sequencer, which
+//! the producer ids do not make any cheaper.
+
+use crate::primitives::{PaddedCounter, PaddedFlag};
+use std::time::Duration;
+
+/// A snapshot of a registered producer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProducerStatus {
+    id: usize,
+    waiting: bool,
+    sent: u64,
+    send_time: Duration,
+    longest_send: Duration,
+}
+
+impl ProducerStatus {
+    /// Returns the producer id, which is the index of its slot.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns `true` if the producer is inside a send, such as one waiting
+    /// for free slots. A producer that stays in this state is stalled.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Returns the number of items the producer has sent since it registered.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Returns the total time the producer spent inside sends since it
+    /// registered, which grows with the time spent waiting for free slots.
+    pub fn send_time(&self) -> Duration {
+        self.send_time
+    }
+
+    /// Returns the time the producer spent inside its longest send since it registered.
+    pub fn longest_send(&self) -> Duration {
+        self.longest_send
+    }
+}
+
+/// The slot owned by a single producer.
+#[derive(Default)]
+struct ProducerSlot {
+    registered: PaddedFlag,
+    /// The number of sends in progress.
+    sending: PaddedCounter,
+    sent: PaddedCounter,
+    send_nanos: PaddedCounter,
+    longest_nanos: PaddedCounter,
+}
+
+/// A fixed set of producer slots.
+pub(crate) struct ProducerRegistry {
+    slots: Box<[ProducerSlot]>,
+}
+
+impl ProducerRegistry {
+    /// Create a registry with room for `max_producers` producers.
+    ///
+    /// # Panics
+    /// Panics if `max_producers` is zero.
+    pub fn new(max_producers: usize) -> Self {
+        assert!(max_producers > 0, "max_producers must be greater than zero");
+        Self {
+            slots: (0..max_producers)
+                .map(|_| ProducerSlot::default())
+                .collect(),
+        }
+    }
+
+    /// Returns the maximum number of producers.
+    pub fn max_producers(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Claim a free slot, returning its id, or `None` if every slot is taken.
+    pub fn register(&self) -> Option<usize> {
+        let id = self
+            .slots
+            .iter()
+            .position(|slot| !slot.registered.swap(true))?;
+        let slot = &self.slots[id];
+        slot.sent.set(0);
+        slot.send_nanos.set(0);
+        slot.longest_nanos.set(0);
+        Some(id)
+    }
+
+    /// Free the slot of producer `id`.
+    pub fn release(&self, id: usize) {
+        let slot = &self.slots[id];
+        slot.sending.set(0);
+        slot.registered.clear();
+    }
+
+    /// Mark producer `id` as inside a send.
+    #[inline(always)]
+    pub fn begin(&self, id: usize) {
+        self.slots[id].sending.fetch_add(1);
+    }
+
+    /// Mark producer `id` as done with a send that delivered `sent` items
+    /// and took `elapsed`.
+    #[inline(always)]
+    pub fn end(&self, id: usize, sent: usize, elapsed: Duration) {
+        let slot = &self.slots[id];
+        let nanos = elapsed.as_nanos() as u64;
+        // A sender shared by reference sends from several threads at once.
+        slot.sent.fetch_add(sent as u64);
+        slot.send_nanos.fetch_add(nanos);
+        slot.longest_nanos.fetch_max(nanos);
+        slot.sending.fetch_sub(1);
+    }
+
+    /// Take a snapshot of every registered producer.
+    pub fn statuses(&self) -> Vec<ProducerStatus> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.registered.is_set())
+            .map(|(id, slot)| ProducerStatus {
+                id,
+                waiting: slot.sending.get() > 0,
+                sent: slot.sent.get(),
+                send_time: Duration::from_nanos(slot.send_nanos.get()),
+                longest_send: Duration::from_nanos(slot.longest_nanos.get()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "mp", not(feature = "loom")))]
+mod tests {
+    use crate::channels::mpsc_with_producers;
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_producer_slots_track_waiting_and_concurrent_sends() {
+        let (tx, rx) = mpsc_with_producers::<u32>(
+            4,
+            1,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| tx.send_n([1, 2, 3]).unwrap());
+            }
+            // One of the sends cannot complete before items are drained, so
+            // the producer shows as waiting until then.
+            let (mut received, mut waited) = (0, false);
+            while received < 6 {
+                waited |= tx.producer_statuses()[0].is_waiting();
+                if waited {
+                    received += rx.drain_all().len();
+                }
+            }
+        });
+
+        let status = tx.producer_statuses()[0];
+        assert!(!status.is_waiting());
+        assert_eq!(status.sent(), 6);
+        assert!(status.send_time() >= status.longest_send());
+        assert!(status.longest_send() > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_registering_past_the_maximum_producers_fails_fast() {
+        let (first, _rx) = mpsc_with_producers::<u32>(
+            8,
+            2,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let second = first.try_clone().unwrap();
+        assert_eq!(
+            (first.producer_id(), second.producer_id()),
+            (Some(0), Some(1))
+        );
+        assert!(first.try_clone().is_none());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| second.clone()));
+        assert!(result.is_err());
+
+        drop(first);
+        let third = second.try_clone().unwrap();
+        assert_eq!(third.producer_id(), Some(0));
+        assert_eq!(second.producer_statuses().len(), 2);
+    }
+}
@@ -0,0 +1,499 @@
+//! The ring buffer behind the channels, for building custom topologies.
+//!
+//! The channels of this crate are a [`RingBuffer`] with a [`Sequencer`] that
+//! claims sequences for producers and a poller that hands published sequences
+//! to consumers. This module exposes those parts on their own, so a topology
+//! the channels do not offer, such as consumers gated on several others
+//! through a [`SequenceBarrier`] or a [`Poller`] with its own claiming rules,
+//! is built on the same ring without forking the crate.
+//!
+//! ```
+//! use channels_rs::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+//! use channels_rs::raw::RingBuffer;
+//!
+//! let buffer = RingBuffer::<u64>::new(
+//!     64,
+//!     ProducerWaitStrategyKind::Yielding,
+//!     ConsumerWaitStrategyKind::Yielding,
+//! );
+//! assert_eq!(buffer.publish(7), Ok(0));
+//! let consumer = buffer.single_consumer().unwrap();
+//! let mut sum = 0;
+//! assert_eq!(buffer.poll(&consumer, 16, |value| sum += value), 1);
+//! assert_eq!(sum, 7);
+//! ```
+//!
+//! # Invariants
+//!
+//! A slot holds its element from the moment its sequence is published until
+//! a consumer moves it out, and producers only write a slot again once its
+//! sequence of the previous lap is released. Safe code cannot break this:
+//! the [`Sequencer`] is sealed, publishing always writes before it publishes
+//! and checks that a single-producer buffer has one producer at a time, the
+//! consumers taken from a buffer are the only ones, and [`Poller`] is an
+//! `unsafe` trait whose implementations promise to claim every sequence at
+//! most once and to release only what was consumed. Reading slots directly
+//! is `unsafe`, with the same obligations.
+//!
+//! # Stability
+//!
+//! The raw API follows the internals of the channels and is exempt from the
+//! semver guarantees of the crate: it may change in any release.
+
+use crate::channels::assert_buffer_size;
+use crate::coordinator::{ConsumerWaitStrategyKind, Coordinator, ProducerWaitStrategyKind};
+#[cfg(feature = "mc")]
+use crate::poller::MultiConsumerPoller;
+use crate::poller::{self, OnUnwind, SingleConsumerPoller};
+use crate::ring_buffer;
+#[cfg(feature = "mp")]
+use crate::sequencer::MultiProducerSequencer;
+use crate::sequencer::{self, SingleProducerSequencer};
+use crate::topology::Topology;
+use std::cell::Cell;
+use std::iter;
+use std::marker::PhantomData;
+#[cfg(feature = "mc")]
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub use crate::sequence::{INITIAL_VALUE, Sequence};
+pub use crate::sequencer::SequenceBarrier;
+
+mod sealed {
+    /// Restricts [`Sequencer`](super::Sequencer) to the sequencers of the crate.
+    pub trait Sealed {
+        /// Whether producers claim through the multi-producer sequencer.
+        const MULTI_PRODUCER: bool;
+    }
+}
+
+/// How the producers of a [`RingBuffer`] claim sequences.
+///
+/// Sealed: it is implemented by [`SingleProducer`] and, with the `mp`
+/// feature, by [`MultiProducer`], which name the sequencers of the channels.
+pub trait Sequencer: sealed::Sealed {}
+
+/// The sequencer of a buffer that a single thread publishes to at a time.
+pub enum SingleProducer {}
+
+impl sealed::Sealed for SingleProducer {
+    const MULTI_PRODUCER: bool = false;
+}
+
+impl Sequencer for SingleProducer {}
+
+/// The sequencer of a buffer that any number of threads publish to.
+#[cfg(feature = "mp")]
+pub enum MultiProducer {}
+
+#[cfg(feature = "mp")]
+impl sealed::Sealed for MultiProducer {
+    const MULTI_PRODUCER: bool = true;
+}
+
+#[cfg(feature = "mp")]
+impl Sequencer for MultiProducer {}
+
+/// A ring buffer of `T` whose producers claim sequences through `S`.
+///
+/// Share it between threads in an [`Arc`](std::sync::Arc). Producers and
+/// consumers wait as the wait strategies it was created with tell them.
+pub struct RingBuffer<T, S: Sequencer = SingleProducer> {
+    buffer: ring_buffer::RingBuffer<T>,
+    coordinator: Coordinator,
+    /// Identifies the buffer to the consumers taken from it.
+    id: u64,
+    consumers_taken: AtomicBool,
+    /// Set while a thread publishes to a single-producer buffer.
+    publishing: AtomicBool,
+    _sequencer: PhantomData<S>,
+}
+
+/// The id of the next raw ring buffer.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl<T, S: Sequencer> RingBuffer<T, S> {
+    /// Create a ring buffer of `buffer_size` slots.
+    ///
+    /// # Panics
+    /// Panics if `buffer_size` is zero or greater than `i64::MAX`.
+    pub fn new(
+        buffer_size: usize,
+        pw: ProducerWaitStrategyKind,
+        cw: ConsumerWaitStrategyKind,
+    ) -> Self {
+        assert_buffer_size(buffer_size);
+        let topology = Topology::current();
+        let sequencer: Box<dyn sequencer::Sequencer> = match S::MULTI_PRODUCER {
+            #[cfg(feature = "mp")]
+            true => Box::new(MultiProducerSequencer::new(buffer_size)),
+            _ => Box::new(SingleProducerSequencer::new(buffer_size)),
+        };
+        Self {
+            buffer: ring_buffer::RingBuffer::new(buffer_size, topology.array_padding(), sequencer),
+            coordinator: Coordinator::new(pw, cw, topology.spin_budget(), None),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            consumers_taken: AtomicBool::new(false),
+            publishing: AtomicBool::new(false),
+            _sequencer: PhantomData,
+        }
+    }
+
+    /// Take the single consumer of the buffer.
+    ///
+    /// Returns `None` if a consumer was taken already.
+    pub fn single_consumer(&self) -> Option<SingleConsumer> {
+        self.take_consumers().then(|| SingleConsumer {
+            poller: SingleConsumerPoller::new(),
+            buffer: self.id,
+            _unsync: PhantomData,
+        })
+    }
+
+    /// Take the consumers of the buffer as a multi-consumer poller, which is
+    /// cloned for every consumer.
+    ///
+    /// Returns `None` if a consumer was taken already.
+    #[cfg(feature = "mc")]
+    pub fn multi_consumer(&self) -> Option<MultiConsumer> {
+        self.take_consumers().then(|| MultiConsumer {
+            poller: Arc::new(MultiConsumerPoller::new(self.buffer_size())),
+            buffer: self.id,
+        })
+    }
+
+    /// Returns `true` once, for the first consumer taken from the buffer.
+    fn take_consumers(&self) -> bool {
+        !self.consumers_taken.swap(true, Ordering::Relaxed)
+    }
+
+    /// Returns the number of slots.
+    pub fn buffer_size(&self) -> usize {
+        self.buffer.buffer_size()
+    }
+
+    /// Returns the highest sequence claimed by producers.
+    ///
+    /// Every sequence up to it is published on a single-producer buffer; on a
+    /// multi-producer one, see [`highest_published`](Self::highest_published).
+    pub fn cursor(&self) -> i64 {
+        self.buffer.sequencer().get_cursor_sequence_acquire()
+    }
+
+    /// Returns the highest sequence in `[low, high]` such that every sequence
+    /// from `low` up to it is published, or `low - 1` if `low` is not.
+    pub fn highest_published(&self, low: i64, high: i64) -> i64 {
+        self.buffer.sequencer().get_highest(low, high)
+    }
+
+    /// Returns the highest sequence released to producers.
+    pub fn gating_sequence(&self) -> i64 {
+        self.buffer.gating_sequence()
+    }
+
+    /// Publish `value`, waiting for a free slot, and return its sequence.
+    ///
+    /// # Errors
+    /// Hands `value` back if the buffer is closed, including while waiting.
+    ///
+    /// # Panics
+    /// Panics if another thread publishes to a single-producer buffer at the
+    /// same time.
+    pub fn publish(&self, value: T) -> Result<i64, T> {
+        self.exclusively(|| {
+            if self.coordinator.is_closed() {
+                return Err(value);
+            }
+            match self.buffer.reserve(1, &self.coordinator) {
+                Ok((sequence, _)) => Ok(self.publish_reserved(sequence, value)),
+                Err(_) => Err(value),
+            }
+        })
+    }
+
+    /// Publish `value` if a slot is free, and return its sequence.
+    ///
+    /// # Errors
+    /// Hands `value` back if the buffer is full or closed.
+    ///
+    /// # Panics
+    /// Panics if another thread publishes to a single-producer buffer at the
+    /// same time.
+    pub fn try_publish(&self, value: T) -> Result<i64, T> {
+        self.exclusively(|| {
+            if self.coordinator.is_closed() {
+                return Err(value);
+            }
+            match self.buffer.try_reserve(1) {
+                Ok((sequence, _)) => Ok(self.publish_reserved(sequence, value)),
+                Err(_) => Err(value),
+            }
+        })
+    }
+
+    /// Run `publish` as the only producer of a single-producer buffer.
+    ///
+    /// The flag also orders the claims of producers that take turns on
+    /// different threads, since a single-producer sequencer tracks its
+    /// claims with relaxed accesses.
+    #[inline(always)]
+    fn exclusively<R>(&self, publish: impl FnOnce() -> R) -> R {
+        if S::MULTI_PRODUCER {
+            return publish();
+        }
+        assert!(
+            !self.publishing.swap(true, Ordering::Acquire),
+            "a single-producer ring buffer is published to by one thread at a time"
+        );
+        let result = publish();
+        self.publishing.store(false, Ordering::Release);
+        result
+    }
+
+    /// Write `value` into the claimed `sequence`, publish it and wake consumers.
+    fn publish_reserved(&self, sequence: i64, value: T) -> i64 {
+        self.buffer
+            .publish_reserved(sequence, sequence, iter::once(value), None);
+        self.coordinator
+            .notify_consumer(1, || self.buffer.backlog());
+        sequence
+    }
+
+    /// Returns the highest sequence in `[low, high]` a consumer behind
+    /// `barrier` may process: published by producers and processed by every
+    /// dependency of the barrier. Returns a value below `low` if there is none.
+    pub fn highest_behind(&self, barrier: &SequenceBarrier, low: i64, high: i64) -> i64 {
+        self.buffer.get_highest(barrier, low, high)
+    }
+
+    /// Move up to `batch_size` elements out through `poller` and hand them
+    /// to `handler`, returning how many were handled.
+    ///
+    /// If `handler` panics, the rest of the batch is dropped and the whole
+    /// batch released while the panic unwinds.
+    ///
+    /// # Panics
+    /// Panics if `batch_size` is greater than the buffer size.
+    pub fn poll<P, H>(&self, poller: &P, batch_size: usize, mut handler: H) -> usize
+    where
+        P: Poller<T, S> + ?Sized,
+        H: FnMut(T),
+    {
+        assert!(
+            batch_size <= self.buffer_size(),
+            "size is greater than buffer size"
+        );
+        let Some((low, high)) = poller.claim(self, batch_size) else {
+            return 0;
+        };
+
+        let consumed = Cell::new(low - 1);
+        let unwinding = OnUnwind(|| {
+            for sequence in consumed.get() + 1..=high {
+                drop(self.buffer.dequeue(sequence));
+            }
+            poller.release(self, low, high);
+            self.coordinator.wakeup_producers();
+        });
+        for sequence in low..=high {
+            let element = self.buffer.dequeue(sequence);
+            consumed.set(sequence);
+            handler(element);
+        }
+        std::mem::forget(unwinding);
+
+        poller.release(self, low, high);
+        self.coordinator.wakeup_producers();
+        self.coordinator
+            .consumer_progress((high - low + 1) as usize);
+        (high - low + 1) as usize
+    }
+
+    /// Returns a reference to the element published at `sequence`.
+    ///
+    /// # Safety
+    /// The element at `sequence` must be published and not moved out, and
+    /// must stay so, without being written through any other reference,
+    /// for as long as the returned reference lives; for example because the
+    /// consumers that move it out and write it in place are behind a
+    /// [`SequenceBarrier`] on a [`Sequence`] the caller only advances later.
+    pub unsafe fn get(&self, sequence: i64) -> &T {
+        // SAFETY: guaranteed by the caller.
+        unsafe { &*self.buffer.slot(sequence) }
+    }
+
+    /// Move the element published at `sequence` out of the buffer.
+    ///
+    /// # Safety
+    /// The element at `sequence` must be published and not moved out, and
+    /// nobody may access it any more until its slot is released and written
+    /// again by a producer.
+    pub unsafe fn take(&self, sequence: i64) -> T {
+        self.buffer.dequeue(sequence)
+    }
+
+    /// Release every sequence up to `highest` to producers, who may then
+    /// write their slots again.
+    ///
+    /// # Safety
+    /// Every published element up to `highest` must have been moved out, or
+    /// be left to leak, and nobody may access them any more.
+    pub unsafe fn release(&self, highest: i64) {
+        self.buffer.release(highest);
+        self.coordinator.wakeup_producers();
+    }
+
+    /// Wait for producers to publish, as the consumer wait strategy tells.
+    pub fn wait(&self) {
+        self.coordinator.consumer_wait();
+    }
+
+    /// Close the buffer, so that producers stop publishing and stop waiting.
+    ///
+    /// Returns `false` if it was closed already.
+    pub fn close(&self) -> bool {
+        self.coordinator.close(None)
+    }
+
+    /// Returns `true` if the buffer is closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+}
+
+/// Hands the published sequences of a [`RingBuffer`] to its consumers.
+///
+/// # Safety
+/// [`RingBuffer::poll`] moves every element an implementation claims out of
+/// the buffer, so an implementation must:
+/// - only claim published sequences, and never a sequence claimed before,
+///   through this poller or any other one, including the consumers taken
+///   from the buffer;
+/// - release every claimed range exactly once, and only let the gating
+///   sequence of the buffer move over ranges that are released.
+pub unsafe trait Poller<T, S: Sequencer = SingleProducer> {
+    /// Claim up to `batch_size` published sequences, returning the inclusive
+    /// range claimed, or `None` if none is available.
+    fn claim(&self, buffer: &RingBuffer<T, S>, batch_size: usize) -> Option<(i64, i64)>;
+
+    /// Release the claimed range `[low, high]`, whose elements were moved out.
+    fn release(&self, buffer: &RingBuffer<T, S>, low: i64, high: i64);
+}
+
+/// The only consumer of a [`RingBuffer`], which it hands every published
+/// sequence to, like the receiver of an SPSC or MPSC channel.
+///
+/// Taken with [`RingBuffer::single_consumer`]. It may be sent to another
+/// thread, but not shared, so a single thread polls through it at a time.
+pub struct SingleConsumer {
+    poller: SingleConsumerPoller,
+    buffer: u64,
+    _unsync: PhantomData<Cell<()>>,
+}
+
+// SAFETY: the single consumer claims from the gating sequence, which only it
+// moves, and releases every range before it claims the next one. Only one
+// thread polls through it at a time, and nothing else claims from its buffer.
+unsafe impl<T, S: Sequencer> Poller<T, S> for SingleConsumer {
+    fn claim(&self, buffer: &RingBuffer<T, S>, batch_size: usize) -> Option<(i64, i64)> {
+        assert_eq!(self.buffer, buffer.id, "consumer of another ring buffer");
+        poller::Poller::<T>::claim(&self.poller, buffer.buffer.sequencer(), batch_size as i64)
+    }
+
+    fn release(&self, buffer: &RingBuffer<T, S>, low: i64, high: i64) {
+        poller::Poller::<T>::release(&self.poller, buffer.buffer.sequencer(), low, high);
+    }
+}
+
+/// The consumers of a [`RingBuffer`], which split the published sequences
+/// between them, like the receivers of an SPMC or MPMC channel.
+///
+/// Taken with [`RingBuffer::multi_consumer`], and cloned for every consumer.
+#[cfg(feature = "mc")]
+#[derive(Clone)]
+pub struct MultiConsumer {
+    poller: Arc<MultiConsumerPoller>,
+    buffer: u64,
+}
+
+// SAFETY: the consumers claim disjoint ranges by moving a shared sequence
+// with compare-and-swap, and the gating sequence only moves over ranges that
+// are released and follow on from it. Nothing else claims from its buffer.
+#[cfg(feature = "mc")]
+unsafe impl<T, S: Sequencer> Poller<T, S> for MultiConsumer {
+    fn claim(&self, buffer: &RingBuffer<T, S>, batch_size: usize) -> Option<(i64, i64)> {
+        assert_eq!(self.buffer, buffer.id, "consumer of another ring buffer");
+        poller::Poller::<T>::claim(&*self.poller, buffer.buffer.sequencer(), batch_size as i64)
+    }
+
+    fn release(&self, buffer: &RingBuffer<T, S>, low: i64, high: i64) {
+        poller::Poller::<T>::release(&*self.poller, buffer.buffer.sequencer(), low, high);
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::raw::{Poller, RingBuffer, Sequence, SequenceBarrier};
+    use std::sync::Arc;
+
+    /// Consumes behind a barrier, like the last stage of a pipeline.
+    struct Behind(SequenceBarrier);
+
+    // SAFETY: claims from the gating sequence, which only this poller moves,
+    // and the consumer of the buffer is taken only once this one is done.
+    unsafe impl Poller<u32> for Behind {
+        fn claim(&self, buffer: &RingBuffer<u32>, batch_size: usize) -> Option<(i64, i64)> {
+            let low = buffer.gating_sequence() + 1;
+            let high = buffer.highest_behind(&self.0, low, low + batch_size as i64 - 1);
+            (high >= low).then_some((low, high))
+        }
+
+        fn release(&self, buffer: &RingBuffer<u32>, _low: i64, high: i64) {
+            // SAFETY: every element of the range was moved out by the poll.
+            unsafe { buffer.release(high) };
+        }
+    }
+
+    #[test]
+    fn test_raw_ring_buffer_runs_custom_pollers_behind_barriers() {
+        let buffer = RingBuffer::<u32>::new(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        for value in 0..4 {
+            assert_eq!(buffer.publish(value), Ok(value as i64));
+        }
+        assert_eq!(buffer.try_publish(4), Err(4));
+        assert_eq!((buffer.cursor(), buffer.highest_published(0, 3)), (3, 3));
+
+        let stage = Arc::new(Sequence::default());
+        let last = Behind(SequenceBarrier::new(vec![stage.clone()]));
+        let mut received = Vec::new();
+        assert_eq!(buffer.poll(&last, 4, |value| received.push(value)), 0);
+
+        // The stage reads in place, and the last consumer follows it.
+        // SAFETY: the last consumer waits for the stage to move past them.
+        let seen: u32 = (0..2)
+            .map(|sequence| unsafe { *buffer.get(sequence) })
+            .sum();
+        assert_eq!(seen, 1);
+        stage.set_release(1);
+        assert_eq!(buffer.poll(&last, 4, |value| received.push(value)), 2);
+        assert_eq!(buffer.gating_sequence(), 1);
+        assert_eq!(buffer.try_publish(4), Ok(4));
+        stage.set_release(4);
+        assert_eq!(buffer.poll(&last, 4, |value| received.push(value)), 3);
+
+        buffer.publish(5).unwrap();
+        let consumer = buffer.single_consumer().unwrap();
+        assert!(buffer.single_consumer().is_none());
+        assert_eq!(buffer.poll(&consumer, 4, |value| received.push(value)), 1);
+        assert_eq!(received, [0, 1, 2, 3, 4, 5]);
+
+        assert!(buffer.close());
+        assert_eq!(buffer.publish(6), Err(6));
+    }
+}
@@ -0,0 +1,187 @@
+//! Recycling buffers through a return channel.
+//!
+//! A common way to avoid allocating a buffer for every item is to send filled
+//! buffers over one ring and hand the emptied buffers back over a second one.
+//! [`recycle_pair`] wires both rings: the return ring starts out holding every
+//! buffer, the producer takes a buffer from it with
+//! [`acquire_buf`](RecycleSender::acquire_buf), fills and sends it, and the
+//! consumer hands every buffer back once its handler is done with it.
+//!
+//! Both rings hold at least as many slots as there are buffers, so neither a
+//! send nor a return ever waits for a free slot: the producer only ever waits
+//! for a buffer to come back.
+
+use crate::channels::{PollOutcome, Receiver, RecvState, Sender, spsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::SendError;
+use std::cell::Cell;
+
+/// The producing half of a recycle pair, created by [`recycle_pair`].
+pub struct RecycleSender<T> {
+    data: Sender<T>,
+    returns: Receiver<T>,
+}
+
+impl<T> RecycleSender<T> {
+    /// Take a buffer handed back by the consumer, waiting for one according to
+    /// the consumer wait strategy if all of them are in flight.
+    ///
+    /// Buffers come back in the state the consumer's handler left them in.
+    /// Returns `None` once the [`RecycleReceiver`] is gone and every buffer it
+    /// handed back has been taken.
+    pub fn acquire_buf(&self) -> Option<T> {
+        let buf = Cell::new(None);
+        match self
+            .returns
+            .blocking_recv(1, &|item| buf.set(Some(item)))
+            .state()
+        {
+            RecvState::Received => buf.into_inner(),
+            _ => None,
+        }
+    }
+
+    /// Take a buffer handed back by the consumer without waiting, or return
+    /// `None` if all of them are in flight.
+    pub fn try_acquire_buf(&self) -> Option<T> {
+        self.returns.try_iter().next()
+    }
+
+    /// Send a filled buffer to the consumer.
+    ///
+    /// Never waits for a free slot, since the data ring has a slot for every buffer.
+    ///
+    /// # Errors
+    /// Returns the buffer if the [`RecycleReceiver`] is gone.
+    pub fn send(&self, buf: T) -> Result<(), SendError<T>> {
+        self.data.send(buf)
+    }
+}
+
+/// The consuming half of a recycle pair, created by [`recycle_pair`].
+pub struct RecycleReceiver<T> {
+    data: Receiver<T>,
+    returns: Sender<T>,
+}
+
+impl<T> RecycleReceiver<T> {
+    /// Attempt to receive up to `batch_size` buffers, handing every buffer back
+    /// to the producer once `handler` returns.
+    ///
+    /// See [`Receiver::recv`]. Buffers the producer can no longer take, because
+    /// the [`RecycleSender`] is gone, are dropped instead.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(&mut T),
+    {
+        self.data.recv(batch_size, &|mut buf| {
+            handler(&mut buf);
+            let _ = self.returns.send(buf);
+        })
+    }
+
+    /// Continuously attempt to receive buffers until at least one batch is processed.
+    ///
+    /// See [`Receiver::blocking_recv`]. Once the [`RecycleSender`] is gone, the
+    /// buffers it already sent are still handled before
+    /// [`RecvState::Disconnected`] is returned.
+    pub fn blocking_recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(&mut T),
+    {
+        loop {
+            let outcome = self.recv(batch_size, handler);
+            if outcome != RecvState::Empty {
+                return outcome;
+            }
+        }
+    }
+}
+
+/// Create a recycle pair circulating `capacity` buffers created with [`Default`].
+///
+/// See [`recycle_pair_with`].
+pub fn recycle_pair<T: Default>(
+    capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (RecycleSender<T>, RecycleReceiver<T>) {
+    recycle_pair_with(capacity, pw, cw, T::default)
+}
+
+/// Create a recycle pair circulating `capacity` buffers created by `factory`.
+///
+/// Both rings get `capacity` rounded up to a power of two slots, and the
+/// return ring starts out holding every buffer. The wait strategies apply to
+/// both rings: `cw` to the consumer waiting for filled buffers and to the
+/// producer waiting in [`acquire_buf`](RecycleSender::acquire_buf), `pw` to
+/// sends, which never actually wait.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+pub fn recycle_pair_with<T, F>(
+    capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+    factory: F,
+) -> (RecycleSender<T>, RecycleReceiver<T>)
+where
+    F: FnMut() -> T,
+{
+    assert!(capacity > 0, "a recycle pair needs at least one buffer");
+    let buffer_size = capacity.next_power_of_two();
+    let (data_tx, data_rx) = spsc(buffer_size, pw, cw);
+    let (returns_tx, returns_rx) = spsc(buffer_size, pw, cw);
+    returns_tx
+        .send_n(std::iter::repeat_with(factory).take(capacity))
+        .expect("the return ring is open until the pair is returned");
+
+    let sender = RecycleSender {
+        data: data_tx,
+        returns: returns_rx,
+    };
+    let receiver = RecycleReceiver {
+        data: data_rx,
+        returns: returns_tx,
+    };
+    (sender, receiver)
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::recycle::recycle_pair;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_buffers_circulate_and_drain_on_shutdown() {
+        let (tx, rx) = recycle_pair::<Vec<u8>>(
+            3,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let mut first = tx.acquire_buf().unwrap();
+        first.extend_from_slice(b"abc");
+        let allocation = first.as_ptr();
+        tx.send(first).unwrap();
+        tx.send(tx.acquire_buf().unwrap()).unwrap();
+        tx.send(tx.acquire_buf().unwrap()).unwrap();
+        assert_eq!(tx.try_acquire_buf(), None);
+
+        let lengths = RefCell::new(Vec::new());
+        let handler = |buf: &mut Vec<u8>| {
+            lengths.borrow_mut().push(buf.len());
+            buf.clear();
+        };
+        assert_eq!(rx.recv(1, &handler), RecvState::Received);
+        let recycled = tx.acquire_buf().unwrap();
+        assert!(recycled.is_empty());
+        assert_eq!(recycled.as_ptr(), allocation);
+
+        drop(rx);
+        assert!(tx.send(recycled).is_err());
+        assert_eq!(tx.acquire_buf(), None);
+        assert_eq!(*lengths.borrow(), vec![3]);
+    }
+}
@@ -0,0 +1,285 @@
+//! A registry of the live channels of the process.
+//!
+//! With the `registry` feature every channel created by the functions of the
+//! [`channels`](crate::channels) module and by [`ChannelBuilder`] is
+//! registered under an id that is unique for the lifetime of the process,
+//! together with the name given to [`ChannelBuilder::name`]. [`list`] returns
+//! a [`ChannelInfo`] for every channel that is still alive, combining its
+//! configuration with its occupancy, the number of its senders and receivers
+//! and, with the `metrics` feature, its counters, which lets a dashboard cover
+//! every internal queue of a service.
+//!
+//! Registering takes a global lock once per channel and listing takes it for
+//! as long as it collects the live channels; sends and receives never touch
+//! the registry. A channel leaves the registry once every sender and receiver
+//! is gone.
+//!
+//! [`ChannelBuilder`]: crate::channels::ChannelBuilder
+//! [`ChannelBuilder::name`]: crate::channels::ChannelBuilder::name
+
+use crate::channels::{Consumers, Producers};
+use crate::coordinator::{ConsumerWaitStrategyKind, Coordinator, ProducerWaitStrategyKind};
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSnapshot;
+use crate::sequencer::Sequencer;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+/// The id of the next channel to register.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The coordinators of the registered channels, including the ones that are
+/// gone but were not pruned yet.
+static CHANNELS: Mutex<Vec<Weak<Coordinator>>> = Mutex::new(Vec::new());
+
+/// The entry of a channel in the registry, held by its coordinator.
+pub(crate) struct Registration {
+    id: u64,
+    name: OnceLock<String>,
+    capacity: usize,
+    consumers: Consumers,
+    broadcast: bool,
+    /// The sequencer of the ring buffer, replaced when a channel is upgraded
+    /// to multiple producers.
+    sequencer: Mutex<Weak<dyn Sequencer>>,
+}
+
+impl Registration {
+    /// Create the entry of a new channel with a fresh id.
+    pub fn new(
+        capacity: usize,
+        consumers: Consumers,
+        broadcast: bool,
+        sequencer: Weak<dyn Sequencer>,
+    ) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: OnceLock::new(),
+            capacity,
+            consumers,
+            broadcast,
+            sequencer: Mutex::new(sequencer),
+        }
+    }
+
+    /// Returns the id of the channel.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Name the channel, unless it already has a name.
+    pub fn set_name(&self, name: String) {
+        let _ = self.name.set(name);
+    }
+
+    /// Read the sequences from `sequencer` from now on.
+    #[cfg(feature = "mp")]
+    pub fn rebind(&self, sequencer: Weak<dyn Sequencer>) {
+        *self.sequencer.lock().unwrap() = sequencer;
+    }
+
+    /// Returns the configuration and statistics of the channel, or `None` if
+    /// its ring buffer is gone.
+    fn info(&self, coordinator: &Coordinator) -> Option<ChannelInfo> {
+        let sequencer = self.sequencer.lock().unwrap().upgrade()?;
+        let cursor = sequencer.get_cursor_sequence_acquire();
+        let backlog = (cursor - sequencer.get_gating_sequence_relaxed()).max(0) as usize;
+        let producers = match () {
+            #[cfg(feature = "mp")]
+            () if sequencer.is_multi_producer() => Producers::Multi,
+            () => Producers::Single,
+        };
+        let waits = coordinator.waits();
+        Some(ChannelInfo {
+            id: self.id,
+            name: self.name.get().cloned(),
+            capacity: self.capacity,
+            producers,
+            consumers: self.consumers,
+            broadcast: self.broadcast,
+            producer_wait: waits.map(|(pw, _)| pw),
+            consumer_wait: waits.map(|(_, cw)| cw),
+            len: backlog.min(self.capacity),
+            senders: coordinator.sender_count(),
+            receivers: coordinator.receiver_count(),
+            closed: coordinator.is_closed(),
+            #[cfg(feature = "metrics")]
+            metrics: coordinator.metrics(),
+        })
+    }
+}
+
+/// Add the channel of `coordinator`, which holds its [`Registration`], to the registry.
+pub(crate) fn register(coordinator: &Arc<Coordinator>) {
+    let mut channels = CHANNELS.lock().unwrap();
+    channels.retain(|channel| channel.strong_count() > 0);
+    channels.push(Arc::downgrade(coordinator));
+}
+
+/// Returns the configuration and statistics of every live channel, in the
+/// order the channels were created.
+pub fn list() -> Vec<ChannelInfo> {
+    let channels: Vec<Arc<Coordinator>> = {
+        let mut channels = CHANNELS.lock().unwrap();
+        channels.retain(|channel| channel.strong_count() > 0);
+        channels.iter().filter_map(Weak::upgrade).collect()
+    };
+    channels
+        .iter()
+        .filter_map(|coordinator| coordinator.registration()?.info(coordinator))
+        .collect()
+}
+
+/// The configuration and statistics of a channel, returned by [`list`].
+///
+/// The statistics are a snapshot of the sequences and counts of the channel,
+/// which may be stale by the time they are used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelInfo {
+    id: u64,
+    name: Option<String>,
+    capacity: usize,
+    producers: Producers,
+    consumers: Consumers,
+    broadcast: bool,
+    producer_wait: Option<ProducerWaitStrategyKind>,
+    consumer_wait: Option<ConsumerWaitStrategyKind>,
+    len: usize,
+    senders: usize,
+    receivers: usize,
+    closed: bool,
+    #[cfg(feature = "metrics")]
+    metrics: MetricsSnapshot,
+}
+
+impl ChannelInfo {
+    /// Returns the id of the channel, see [`Sender::channel_id`].
+    ///
+    /// [`Sender::channel_id`]: crate::channels::Sender::channel_id
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the name given to [`ChannelBuilder::name`], if any.
+    ///
+    /// [`ChannelBuilder::name`]: crate::channels::ChannelBuilder::name
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns how many producers the channel currently supports, which
+    /// changes once a single-producer channel is upgraded.
+    pub fn producers(&self) -> Producers {
+        self.producers
+    }
+
+    /// Returns how many consumers may share the items of the channel.
+    pub fn consumers(&self) -> Consumers {
+        self.consumers
+    }
+
+    /// Returns `true` if every receiver gets every item.
+    pub fn is_broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Returns the producer wait strategy, or `None` if the channel was
+    /// created with a custom one.
+    pub fn producer_wait(&self) -> Option<ProducerWaitStrategyKind> {
+        self.producer_wait
+    }
+
+    /// Returns the consumer wait strategy, or `None` if the channel was
+    /// created with a custom one.
+    pub fn consumer_wait(&self) -> Option<ConsumerWaitStrategyKind> {
+        self.consumer_wait
+    }
+
+    /// Returns the number of items waiting in the ring buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no item was waiting in the ring buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the share of occupied slots, between `0.0` and `1.0`.
+    pub fn occupancy(&self) -> f64 {
+        self.len as f64 / self.capacity as f64
+    }
+
+    /// Returns the number of live senders.
+    pub fn senders(&self) -> usize {
+        self.senders
+    }
+
+    /// Returns the number of live receivers.
+    pub fn receivers(&self) -> usize {
+        self.receivers
+    }
+
+    /// Returns `true` if the channel was closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the counters of the channel.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{ChannelBuilder, Consumers, Producers};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::registry::{ChannelInfo, list};
+
+    fn find(id: u64) -> Option<ChannelInfo> {
+        list().into_iter().find(|info| info.id() == id)
+    }
+
+    #[test]
+    fn test_live_channels_are_listed_with_their_configuration() {
+        let (tx, rx) = ChannelBuilder::<u32>::new()
+            .capacity(8)
+            .producer_wait(ProducerWaitStrategyKind::Yielding)
+            .name("orders")
+            .build();
+        let (other, _other_rx) = ChannelBuilder::<u32>::new().build();
+        assert_eq!(tx.channel_id(), rx.channel_id());
+        assert_ne!(tx.channel_id(), other.channel_id());
+
+        tx.send_n(0..6).unwrap();
+        let clone = tx.clone();
+        let info = find(tx.channel_id()).unwrap();
+        assert_eq!(info.name(), Some("orders"));
+        assert_eq!(info.capacity(), 8);
+        assert_eq!(info.producers(), Producers::Single);
+        assert_eq!(info.consumers(), Consumers::Single);
+        assert!(!info.is_broadcast());
+        assert_eq!(
+            info.producer_wait(),
+            Some(ProducerWaitStrategyKind::Yielding)
+        );
+        assert_eq!(
+            info.consumer_wait(),
+            Some(ConsumerWaitStrategyKind::default())
+        );
+        assert_eq!((info.len(), info.occupancy()), (6, 0.75));
+        assert_eq!((info.senders(), info.receivers()), (2, 1));
+        assert_eq!(find(other.channel_id()).unwrap().name(), None);
+
+        let id = tx.channel_id();
+        drop((tx, clone, rx));
+        assert_eq!(find(id), None);
+    }
+}
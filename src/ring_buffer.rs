@@ -1,58 +1,292 @@
+use crate::channels::Context;
+use crate::constants;
 use crate::coordinator::Coordinator;
+#[cfg(feature = "inspect")]
+use crate::inspect::DebugState;
+use crate::ordering::slot_access;
 use crate::poller::{Poller, State};
-use crate::sequencer::Sequencer;
-use crate::{constants, utils};
+use crate::sequencer::{ClaimError, GatingSequences, SequenceBarrier, Sequencer};
+use crate::sync::{AtomicI64, Ordering, fence};
+use crate::utils::Indexing;
 use std::cell::UnsafeCell;
 use std::mem::MaybeUninit;
 use std::ptr;
+#[cfg(feature = "registry")]
+use std::sync::Weak;
+use std::sync::{Arc, Mutex};
 
 /// A high-performance ring buffer for concurrent producers and consumers.
 ///
 /// `RingBuffer<T>` stores elements in a pre-allocated, fixed-size array with
-/// cache-line padding to reduce false sharing. It supports both **single**
-/// and **multi-consumer** pollers via a [`Poller<T>`] trait and coordinates
-/// access through a [`Sequencer`] and [`Coordinator`].
+/// cache-line padding to reduce false sharing. Consumers poll it through a
+/// [`Poller<T>`] of their own, which lets **single**, **multi-consumer** and
+/// broadcast receivers share the same buffer, and access is coordinated
+/// through a [`Sequencer`] and [`Coordinator`].
+///
+/// The sequencer is type-erased by default. Naming a concrete sequencer type
+/// `S` instead lets the compiler inline the sequencer on the publish and poll
+/// hot paths, at the cost of fixing the producer configuration at compile time.
+///
+/// The slots are heap allocated and sized at runtime by default. Naming
+/// [`InlineSlots`] instead stores a compile-time number of them inline.
 ///
 /// # Safety
 /// Internally uses [`UnsafeCell`] and [`MaybeUninit`] to perform lock-free reads and writes.
-pub(crate) struct RingBuffer<T> {
-    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
-    sequencer: Box<dyn Sequencer>,
-    poller: Box<dyn Poller<T>>,
-    mask: i64,
+pub(crate) struct RingBuffer<T, S: Sequencer + ?Sized = dyn Sequencer, B: Slots<T> = HeapSlots<T>> {
+    sequencer: Arc<S>,
     buffer_size: usize,
+    retains: bool,
+    contiguous: bool,
+    factory: Option<Box<dyn Fn() -> T + Send + Sync>>,
+    stamps: Option<Box<[UnsafeCell<usize>]>>,
+    /// The epochs of the laps before the last rebase, which reused their sequences.
+    epochs: AtomicI64,
+    slots: B,
 }
 
-impl<T> RingBuffer<T> {
-    /// Create a new ring buffer with the specified size, sequencer, and poller.
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The slots of a channel's ring buffer, and how sequences map onto them.
+///
+/// Implemented by [`HeapSlots`], sized at runtime, and [`InlineSlots`],
+/// sized at compile time.
+pub trait Slots<T>: sealed::Sealed {
+    /// Returns the index of the slot `sequence` maps onto, below the capacity.
+    fn index(&self, sequence: i64) -> usize;
+
+    /// Returns the slot `sequence` maps onto.
+    fn cell(&self, sequence: i64) -> &UnsafeCell<MaybeUninit<T>>;
+
+    /// Returns the lap of the buffer that `sequence` belongs to.
+    fn lap(&self, sequence: i64) -> i64;
+
+    /// Returns the slot `sequence` maps onto, for exclusive access.
+    fn cell_mut(&mut self, sequence: i64) -> &mut MaybeUninit<T> {
+        // SAFETY: borrowing the slots mutably excludes every other access.
+        unsafe { &mut *self.cell(sequence).get() }
+    }
+}
+
+/// Slots in an allocation of their own, of any size chosen at runtime, with
+/// cache-line padding on both sides.
+pub struct HeapSlots<T> {
+    cells: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    indexing: Indexing,
+    padding: usize,
+}
+
+impl<T> HeapSlots<T> {
+    /// Allocate `buffer_size` slots with `padding` slots on each side.
+    fn new(buffer_size: usize, padding: usize) -> Self {
+        Self {
+            cells: (0..buffer_size + (padding << 1))
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            indexing: Indexing::new(buffer_size),
+            padding,
+        }
+    }
+}
+
+impl<T> sealed::Sealed for HeapSlots<T> {}
+
+impl<T> Slots<T> for HeapSlots<T> {
+    #[inline(always)]
+    fn index(&self, sequence: i64) -> usize {
+        self.indexing.wrap(sequence, 0)
+    }
+
+    #[inline(always)]
+    fn cell(&self, sequence: i64) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.cells[self.indexing.wrap(sequence, self.padding)]
+    }
+
+    #[inline(always)]
+    fn lap(&self, sequence: i64) -> i64 {
+        self.indexing.lap(sequence)
+    }
+}
+
+/// `N` slots stored inline in the ring buffer, `N` being a power of two, so
+/// that mapping a sequence onto a slot folds into a constant mask and shift.
+pub struct InlineSlots<T, const N: usize> {
+    cells: [UnsafeCell<MaybeUninit<T>>; N],
+}
+
+impl<T, const N: usize> InlineSlots<T, N> {
+    /// Mask wrapping a sequence into a slot index.
+    const MASK: i64 = N as i64 - 1;
+    /// Shift dividing a sequence by `N`.
+    const SHIFT: u32 = N.trailing_zeros();
+}
+
+impl<T, const N: usize> sealed::Sealed for InlineSlots<T, N> {}
+
+impl<T, const N: usize> Slots<T> for InlineSlots<T, N> {
+    #[inline(always)]
+    fn index(&self, sequence: i64) -> usize {
+        (sequence & Self::MASK) as usize
+    }
+
+    #[inline(always)]
+    fn cell(&self, sequence: i64) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.cells[self.index(sequence)]
+    }
+
+    #[inline(always)]
+    fn lap(&self, sequence: i64) -> i64 {
+        sequence >> Self::SHIFT
+    }
+}
+
+impl<T, S: Sequencer + ?Sized> RingBuffer<T, S> {
+    /// Create a new ring buffer with the specified size and sequencer.
     ///
     /// # Parameters
-    /// - `buffer_size`: number of elements in the buffer (must be power of two for mask).
+    /// - `buffer_size`: number of elements in the buffer (power-of-two sizes use the fast mask path).
+    /// - `padding`: number of padding slots on each side of the buffer, one cache line wide.
     /// - `sequencer`: manages sequences for producer/consumer coordination.
-    /// - `poller`: manages of polling of items from this buffer.
     ///
     /// # Returns
     /// A new `RingBuffer<T>` instance ready for push and poll operations.
-    pub fn new(
-        buffer_size: usize,
-        sequencer: Box<dyn Sequencer>,
-        poller: Box<dyn Poller<T>>,
-    ) -> RingBuffer<T> {
+    pub fn new(buffer_size: usize, padding: usize, sequencer: Box<S>) -> Self {
         RingBuffer {
-            buffer: Self::create_buffer(buffer_size),
-            sequencer,
-            poller,
-            mask: (buffer_size - 1) as i64,
+            sequencer: Arc::from(sequencer),
             buffer_size,
+            retains: false,
+            contiguous: false,
+            factory: None,
+            stamps: None,
+            epochs: AtomicI64::new(0),
+            slots: HeapSlots::new(buffer_size, padding),
         }
     }
+}
 
-    /// Allocate the underlying buffer with cache-line padding.
-    fn create_buffer(buffer_size: usize) -> Box<[UnsafeCell<MaybeUninit<T>>]> {
-        (0..buffer_size + (constants::ARRAY_PADDING << 1))
-            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
-            .collect::<Vec<_>>()
-            .into_boxed_slice()
+impl<T, S: Sequencer, const N: usize> RingBuffer<T, S, InlineSlots<T, N>> {
+    /// Create a buffer of `N` inline slots directly in its shared allocation,
+    /// so that the slots are never moved.
+    pub fn new_inline(sequencer: Box<S>) -> Arc<Self> {
+        const {
+            assert!(N.is_power_of_two(), "capacity must be a power of two");
+            assert!(
+                N <= i64::MAX as usize,
+                "capacity must be less than i64::MAX"
+            );
+        }
+        // Naming every field without `..` fails to compile once a field is
+        // added, until it is initialized below as well.
+        let _ = |buffer: Self| {
+            let Self {
+                sequencer: _,
+                buffer_size: _,
+                retains: _,
+                contiguous: _,
+                factory: _,
+                stamps: _,
+                epochs: _,
+                slots: _,
+            } = buffer;
+        };
+        let mut buffer = Arc::<Self>::new_uninit();
+        let uninit = Arc::get_mut(&mut buffer)
+            .expect("new allocation")
+            .as_mut_ptr();
+        // SAFETY: every field but the slots is initialized below, and slots of
+        // `MaybeUninit` need no initialization.
+        unsafe {
+            ptr::addr_of_mut!((*uninit).sequencer).write(Arc::from(sequencer));
+            ptr::addr_of_mut!((*uninit).buffer_size).write(N);
+            ptr::addr_of_mut!((*uninit).retains).write(false);
+            ptr::addr_of_mut!((*uninit).contiguous).write(false);
+            ptr::addr_of_mut!((*uninit).factory).write(None);
+            ptr::addr_of_mut!((*uninit).stamps).write(None);
+            ptr::addr_of_mut!((*uninit).epochs).write(AtomicI64::new(0));
+            buffer.assume_init()
+        }
+    }
+}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> RingBuffer<T, S, B> {
+    /// Keep consumed elements in their slots until producers reuse them.
+    ///
+    /// Pollers of such a buffer read elements by reference instead of moving
+    /// them out, and a producer drops the element of the previous lap before
+    /// writing a slot again.
+    pub fn retaining(mut self) -> Self {
+        self.retains = true;
+        self
+    }
+
+    /// Publish every batch at once instead of in chunks of
+    /// [`PUBLISH_CHUNK_SIZE`](constants::PUBLISH_CHUNK_SIZE), so that
+    /// consumers see either all of a batch or none of it.
+    pub fn contiguous(mut self) -> Self {
+        self.contiguous = true;
+        self
+    }
+
+    /// Fill every slot with an element created by `factory`, and keep the
+    /// slots initialized for the lifetime of the buffer.
+    ///
+    /// Producers of such a buffer can [`reuse_reserved`](Self::reuse_reserved)
+    /// a claimed slot and mutate the element left in it by the previous lap, and
+    /// consumers can process elements in place, so the allocations owned by
+    /// the elements are reused. An element moved out of its slot is replaced
+    /// by a new one from `factory`.
+    pub fn prefilled<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        for sequence in 0..self.buffer_size as i64 {
+            self.slots.cell_mut(sequence).write(factory());
+        }
+        self.factory = Some(Box::new(factory));
+        self
+    }
+
+    /// Record the id of the producer that wrote each element.
+    ///
+    /// Producers pass their id to every write, and consumers read it back with
+    /// [`stamp_of`](Self::stamp_of) until they release the element.
+    pub fn stamped(mut self) -> Self {
+        self.stamps = Some((0..self.buffer_size).map(|_| UnsafeCell::new(0)).collect());
+        self
+    }
+
+    /// Returns the id of the producer that wrote the element at `sequence`,
+    /// or `None` if the buffer is not [`stamped`](Self::stamped).
+    ///
+    /// Only valid while `sequence` is claimed by the caller.
+    pub fn stamp_of(&self, sequence: i64) -> Option<usize> {
+        let stamps = self.stamps.as_ref()?;
+        // SAFETY: the stamp was written before the element was published, and
+        // is not written again before the claiming consumer releases it.
+        Some(unsafe { *stamps[self.slots.index(sequence)].get() })
+    }
+
+    /// Record `producer` as the writer of the element at a claimed `sequence`.
+    #[inline(always)]
+    fn stamp(&self, sequence: i64, producer: Option<usize>) {
+        if let (Some(stamps), Some(producer)) = (&self.stamps, producer) {
+            // SAFETY: the sequence is claimed and not yet published, so no
+            // consumer reads the stamp.
+            unsafe { *stamps[self.slots.index(sequence)].get() = producer };
+        }
+    }
+
+    /// Returns `true` if every slot always holds an element, see [`prefilled`](Self::prefilled).
+    #[inline(always)]
+    pub fn is_prefilled(&self) -> bool {
+        self.factory.is_some()
+    }
+
+    /// Returns the number of slots in the buffer.
+    #[inline(always)]
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
     }
 
     /// Check that a requested batch size does not exceed the buffer capacity.
@@ -70,20 +304,51 @@ impl<T> RingBuffer<T> {
     /// the element at `sequence` has been properly initialized via `push` before calling.
     /// This method is only called by `Poller`. If the buffer has no available data to consume, the 'Poller' will wait for it.
     pub(crate) fn dequeue(&self, sequence: i64) -> T {
-        let index: usize = utils::wrap_index(sequence, self.mask, constants::ARRAY_PADDING);
-        let cell = &self.buffer[index];
+        let cell = self.slots.cell(sequence);
 
         // SAFETY:
         // An item is only moved once, and it is managed and guaranteed by the sequencer.
-        unsafe { ptr::read((*cell.get()).as_ptr()) }
+        // The slot of a prefilled buffer is refilled, so it stays initialized.
+        let item = unsafe {
+            match &self.factory {
+                Some(factory) => std::mem::replace((*cell.get()).assume_init_mut(), factory()),
+                None => ptr::read((*cell.get()).as_ptr()),
+            }
+        };
+        slot_access!(read, self, sequence);
+        item
+    }
+
+    /// Returns a reference to the element published at `sequence`.
+    ///
+    /// # Safety
+    /// The element at `sequence` must have been published, and the buffer must
+    /// be [`retaining`](Self::retaining) so it is not moved out or overwritten
+    /// while the caller's gating sequence is below `sequence`.
+    pub(crate) unsafe fn get(&self, sequence: i64) -> &T {
+        let cell = self.slots.cell(sequence);
+
+        slot_access!(read, self, sequence);
+        // SAFETY: guaranteed by the caller.
+        unsafe { (*cell.get()).assume_init_ref() }
+    }
+
+    /// Returns a pointer to the element published at `sequence`, for in-place access.
+    ///
+    /// # Safety
+    /// The element at `sequence` must have been published and not moved out,
+    /// and the caller must have exclusive access to it, as granted to a
+    /// pipeline stage by its [`SequenceBarrier`].
+    pub(crate) unsafe fn slot(&self, sequence: i64) -> *mut T {
+        // SAFETY: guaranteed by the caller.
+        unsafe { (*self.slots.cell(sequence).get()).as_mut_ptr() }
     }
 
     /// Writes an element into the buffer at the position derived from the given `sequence`.
     ///
-    /// The sequence number is first transformed into an array index using
-    /// [`utils::wrap_index`], taking into account the ring buffer's mask and
-    /// padding. The resulting index is then used to locate the corresponding
-    /// buffer cell, and the provided element is written directly into it.
+    /// The sequence number is first mapped onto its slot using [`Slots::cell`],
+    /// taking into account the ring buffer's indexing mode and padding, and the
+    /// provided element is written directly into it.
     ///
     /// # Safety
     ///
@@ -98,17 +363,24 @@ impl<T> RingBuffer<T> {
     /// - `sequence`: The monotonically increasing sequence number identifying
     ///   the logical slot in the ring buffer.
     /// - `element`: The element to be stored in the buffer at that slot.
+    /// - `producer`: The id of the producer writing the element, if it has one.
     ///
     #[inline(always)]
-    fn write(&self, sequence: i64, element: T) {
-        let index = utils::wrap_index(sequence, self.mask, constants::ARRAY_PADDING);
-        let cell = &self.buffer[index];
+    fn write(&self, sequence: i64, element: T, producer: Option<usize>) {
+        self.stamp(sequence, producer);
+        let cell = self.slots.cell(sequence);
 
         // SAFETY:
         // The item may not be overwritten if it was not consumed and it is managed and guaranteed by the sequencer.
+        // A retained item of the previous lap has been read by every consumer once the slot is claimed again,
+        // and the slots of a prefilled buffer are always initialized.
         unsafe {
+            if self.is_prefilled() || (self.retains && sequence >= self.buffer_size as i64) {
+                (*cell.get()).assume_init_drop();
+            }
             (*cell.get()).write(element);
         }
+        slot_access!(written, self, sequence);
     }
 
     /// Poll up to `batch_size` elements and process them with the provided handler.
@@ -118,10 +390,296 @@ impl<T> RingBuffer<T> {
     ///
     /// # Panics
     // If the batch size is greater than buffer size it will panic
-    pub fn poll<H: Fn(T)>(&self, batch_size: usize, handler: &H) -> State {
+    pub fn poll<P, H>(&self, poller: &P, batch_size: usize, handler: &H) -> State
+    where
+        P: Poller<T, S, B> + ?Sized,
+        H: Fn(T),
+    {
+        self.poll_sequenced(poller, batch_size, &|_, item| handler(item))
+    }
+
+    /// Poll up to `batch_size` elements, passing each one to the handler together
+    /// with its [`Context`].
+    ///
+    /// # Panics
+    // If the batch size is greater than buffer size it will panic
+    pub fn poll_sequenced<P, H>(&self, poller: &P, batch_size: usize, handler: &H) -> State
+    where
+        P: Poller<T, S, B> + ?Sized,
+        H: Fn(Context, T),
+    {
         self.check_size(batch_size);
-        self.poller
-            .poll(&*self.sequencer, self, batch_size as i64, &handler)
+        poller.poll(&*self.sequencer, self, batch_size as i64, handler)
+    }
+
+    /// Poll up to `out.len()` elements, capped at the buffer size, into `out`.
+    ///
+    /// Returns the number of elements written to the front of `out`.
+    pub fn poll_into<P>(&self, poller: &P, out: &mut [MaybeUninit<T>]) -> usize
+    where
+        P: Poller<T, S, B> + ?Sized,
+    {
+        let len = out.len().min(self.buffer_size);
+        match len {
+            0 => 0,
+            _ => poller.poll_into(&*self.sequencer, self, &mut out[..len]),
+        }
+    }
+
+    /// Claim up to `batch_size` published elements, to be moved out one at a time.
+    ///
+    /// Returns `None` if no elements are available. Dropping the claim releases
+    /// the slots and wakes the producers of `coordinator` waiting for them.
+    /// The claim is recorded in `claims` until then, if given.
+    ///
+    /// # Panics
+    // If the batch size is greater than buffer size it will panic
+    pub fn claim<'a>(
+        &'a self,
+        poller: &'a dyn Poller<T, S, B>,
+        batch_size: usize,
+        coordinator: &'a Coordinator,
+        claims: Option<&'a ActiveClaims>,
+    ) -> Option<Claimed<'a, T, S, B>> {
+        self.check_size(batch_size);
+        let (next, high) = poller.claim(&*self.sequencer, batch_size as i64)?;
+        if let Some(claims) = claims {
+            claims.insert(next, high);
+        }
+        Some(Claimed {
+            buffer: self,
+            poller,
+            coordinator,
+            claims,
+            low: next,
+            next,
+            high,
+        })
+    }
+
+    /// Determine the highest sequence in `[low, high]` a consumer behind `barrier` may process.
+    pub fn get_highest(&self, barrier: &SequenceBarrier, low: i64, high: i64) -> i64 {
+        barrier.get_highest(&*self.sequencer, low, high)
+    }
+
+    /// Returns the sequencer of the buffer.
+    #[cfg(feature = "raw")]
+    pub fn sequencer(&self) -> &S {
+        &self.sequencer
+    }
+
+    /// Returns the highest sequence released to producers.
+    pub fn gating_sequence(&self) -> i64 {
+        self.sequencer.get_gating_sequence_relaxed()
+    }
+
+    /// Release every sequence up to `highest` to producers.
+    pub fn release(&self, highest: i64) {
+        self.sequencer.publish_gating_sequence(highest);
+    }
+
+    /// Move every sequence back to its initial value, once every published
+    /// element has been consumed through `poller` and every registered gating
+    /// sequence has caught up.
+    ///
+    /// Returns `false` and changes nothing if elements are still waiting.
+    /// Elements retained for broadcast receivers are dropped. Only valid while
+    /// no producer or consumer is active.
+    pub fn rebase(&self, poller: &dyn Poller<T, S, B>) -> bool {
+        let cursor = self.sequencer.get_claimed_sequence_acquire();
+        if cursor != self.sequencer.get_gating_sequence_relaxed()
+            || self.sequencer.gating_sequences().minimum(|| cursor) < cursor
+        {
+            return false;
+        }
+
+        if self.retains {
+            for sequence in (cursor - self.buffer_size as i64 + 1).max(0)..=cursor {
+                // SAFETY: retained elements stay initialized until producers
+                // reuse their slot, which no producer is about to do.
+                unsafe { self.slot(sequence).drop_in_place() };
+            }
+        }
+        // Rebasing needs the only receiver, so no resolve reads this meanwhile.
+        let laps = self.slots.lap(cursor.max(0)) + 1;
+        self.epochs.fetch_add(laps, Ordering::Relaxed);
+        self.sequencer.rebase();
+        poller.rebase();
+        #[cfg(all(feature = "ordering-audit", debug_assertions))]
+        crate::ordering::audit::forget(self as *const Self as usize);
+        true
+    }
+
+    /// Returns `true` if several producers may publish to the buffer concurrently.
+    #[cfg(feature = "mp")]
+    pub fn is_multi_producer(&self) -> bool {
+        self.sequencer.is_multi_producer()
+    }
+
+    /// Create the poller of a new receiver that only sees the elements
+    /// published from now on, see [`Poller::subscribe_latest`].
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    pub fn subscribe_latest(
+        &self,
+        poller: &dyn Poller<T, S, B>,
+    ) -> Option<Box<dyn Poller<T, S, B>>> {
+        poller.subscribe_latest(&self.sequencer)
+    }
+
+    /// Register a gating sequence that producers wait for, starting at the
+    /// highest claimed sequence.
+    ///
+    /// Returns the slot of the sequence in [`gating_sequences`](Self::gating_sequences),
+    /// or `None` if no slot is free.
+    pub fn add_gating_sequence(&self) -> Option<usize> {
+        self.sequencer
+            .gating_sequences()
+            .add(|| self.sequencer.get_claimed_sequence_acquire())
+    }
+
+    /// Returns a handle on the sequencer that does not keep it alive, for
+    /// reading the sequences of the buffer from the registry.
+    #[cfg(feature = "registry")]
+    pub fn downgrade_sequencer(&self) -> Weak<S> {
+        Arc::downgrade(&self.sequencer)
+    }
+
+    /// Returns the gating sequences registered at runtime.
+    pub fn gating_sequences(&self) -> &Arc<GatingSequences> {
+        self.sequencer.gating_sequences()
+    }
+
+    /// Detach the independent receiver polling through `poller` from producers.
+    pub fn unsubscribe(&self, poller: &dyn Poller<T, S, B>) {
+        poller.unsubscribe(&*self.sequencer);
+    }
+
+    /// Release the ranges left in `claims` by claims that were forgotten
+    /// instead of dropped, returning how many were released.
+    ///
+    /// The elements of such a claim that were not moved out are leaked, since
+    /// there is no telling which ones were.
+    pub fn reclaim(&self, poller: &dyn Poller<T, S, B>, claims: &ActiveClaims) -> usize {
+        let ranges = claims.take();
+        for &(low, high) in &ranges {
+            poller.release(&*self.sequencer, low, high);
+        }
+        ranges.len()
+    }
+
+    /// Grant producers `n` more credits on a credit-paced buffer.
+    pub fn grant(&self, n: usize) {
+        self.sequencer.grant(n);
+    }
+
+    /// Returns the number of published elements consumers have not released yet.
+    pub fn backlog(&self) -> usize {
+        let cursor = self.sequencer.get_cursor_sequence_acquire();
+        (cursor - self.sequencer.get_gating_sequence_relaxed()).max(0) as usize
+    }
+
+    /// Returns the epoch of the lap of the ring buffer that `sequence` belongs to.
+    ///
+    /// The epoch increments every time the sequences wrap around the buffer,
+    /// and keeps counting across a [`rebase`](Self::rebase), so `(sequence,
+    /// epoch)` pairs stay distinct even though slots and sequences are reused.
+    #[inline(always)]
+    pub fn epoch_of(&self, sequence: i64) -> i64 {
+        self.epochs.load(Ordering::Relaxed) + self.slots.lap(sequence)
+    }
+
+    /// Returns the highest sequence claimed by producers, published or not.
+    pub fn cursor(&self) -> i64 {
+        self.sequencer.get_claimed_sequence_acquire()
+    }
+
+    /// Returns the highest sequence up to which every element is published.
+    pub fn published(&self) -> i64 {
+        let gating = self.sequencer.get_gating_sequence_relaxed();
+        let cursor = self.sequencer.get_cursor_sequence_acquire();
+        match cursor > gating {
+            true => self.sequencer.get_highest(gating + 1, cursor),
+            false => cursor,
+        }
+    }
+
+    /// Returns the highest sequence claimed by the consumers of `poller`.
+    pub fn position(&self, poller: &dyn Poller<T, S, B>) -> i64 {
+        poller.position(&self.sequencer)
+    }
+
+    /// Returns the epoch of the most recently published sequence.
+    pub fn epoch(&self) -> i64 {
+        self.epoch_of(self.sequencer.get_cursor_sequence_acquire().max(0))
+    }
+
+    /// Copy the element published at `sequence` without consuming it.
+    ///
+    /// Returns `None` if the sequence has not been published yet or if its slot
+    /// has already been reused by a later lap, see [`copy_published`].
+    ///
+    /// The slot is copied with a volatile read while a producer may be writing
+    /// it, which the Rust memory model counts as a data race even though a
+    /// torn copy is never handed out. Seqlocks rely on the same read, and the
+    /// `T: Copy` bound keeps the torn copy from being dropped or used.
+    pub fn peek(&self, sequence: i64) -> Option<T>
+    where
+        T: Copy,
+    {
+        let cell = self.slots.cell(sequence);
+        // SAFETY: the slot is in bounds. A producer that claimed it again can
+        // tear the copy, which is why it stays a `MaybeUninit` until validated.
+        let copy = || unsafe { ptr::read_volatile(cell.get()) };
+        let value = copy_published(&*self.sequencer, self.buffer_size, sequence, copy)?;
+        // SAFETY: no producer claimed the slot again while it was copied, so
+        // the copy is the published element.
+        Some(unsafe { value.assume_init() })
+    }
+
+    /// Report the sequences of the buffer, and the position of the consumer
+    /// polling through `poller`, if any.
+    ///
+    /// Every sequence is read on its own, so the report may mix values read
+    /// a few publishes apart on a live channel.
+    #[cfg(feature = "inspect")]
+    pub fn snapshot(&self, poller: Option<&dyn Poller<T, S, B>>) -> DebugState {
+        let sequencer = &*self.sequencer;
+        let gating = sequencer.get_gating_sequence_relaxed();
+        let cursor = sequencer.get_cursor_sequence_acquire();
+        let claimed = sequencer.get_claimed_sequence_acquire();
+        let published = self.published();
+        let unpublished = (published + 1..=claimed)
+            .filter(|&sequence| {
+                sequence > cursor || sequencer.get_highest(sequence, sequence) != sequence
+            })
+            .count();
+        DebugState {
+            capacity: self.buffer_size,
+            claimed,
+            published,
+            unpublished,
+            gating,
+            gating_minimum: sequencer.get_gating_minimum_acquire(),
+            registered: sequencer.gating_sequences().values(),
+            position: poller.map(|poller| poller.position(sequencer)),
+            items: None,
+        }
+    }
+
+    /// Clone the elements published at `low..=high`, oldest first.
+    ///
+    /// # Safety
+    /// The elements must have been published, and no consumer may move them
+    /// out nor any producer reuse their slots until this returns.
+    #[cfg(feature = "inspect")]
+    pub unsafe fn clone_range(&self, low: i64, high: i64) -> Vec<T>
+    where
+        T: Clone,
+    {
+        // SAFETY: guaranteed by the caller.
+        (low..=high)
+            .map(|sequence| unsafe { self.get(sequence) }.clone())
+            .collect()
     }
 
     /// Push a single element into the ring buffer.
@@ -130,10 +688,224 @@ impl<T> RingBuffer<T> {
     ///
     /// # Safety
     /// If there is no available space the producer will wait for it until it became available
-    pub fn push(&self, element: T, coordinator: &Coordinator) {
-        let sequence = self.sequencer.next(coordinator);
-        self.write(sequence, element);
+    ///
+    /// # Errors
+    /// Hands the element back if the channel is closed while waiting for space.
+    pub fn push(
+        &self,
+        element: T,
+        coordinator: &Coordinator,
+        producer: Option<usize>,
+    ) -> Result<(), T> {
+        let Ok(sequence) = self.sequencer.next(coordinator) else {
+            return Err(element);
+        };
+        self.write(sequence, element, producer);
         self.sequencer.publish_cursor_sequence(sequence);
+        Ok(())
+    }
+
+    /// Try to push a single element without waiting for free space.
+    ///
+    /// A claim that loses the race against other producers is retried at most
+    /// `max_retries` times. On failure the element is handed back with the reason.
+    pub fn try_push_bounded(
+        &self,
+        element: T,
+        max_retries: usize,
+        producer: Option<usize>,
+    ) -> Result<(), (ClaimError, T)> {
+        let claim = self.sequencer.try_next_n_bounded(1, max_retries);
+        self.publish_claimed(claim, element, producer)
+    }
+
+    /// Try to push a single element without waiting for free space.
+    ///
+    /// On failure the element is handed back with the reason.
+    pub fn try_push(&self, element: T, producer: Option<usize>) -> Result<(), (ClaimError, T)> {
+        self.publish_claimed(self.sequencer.try_next(), element, producer)
+    }
+
+    /// Write `element` into a claimed sequence and publish it, or hand it back
+    /// if the claim failed.
+    #[inline(always)]
+    fn publish_claimed(
+        &self,
+        claim: Result<i64, ClaimError>,
+        element: T,
+        producer: Option<usize>,
+    ) -> Result<(), (ClaimError, T)> {
+        match claim {
+            Ok(sequence) => {
+                self.write(sequence, element, producer);
+                self.sequencer.publish_cursor_sequence(sequence);
+                Ok(())
+            }
+            Err(error) => Err((error, element)),
+        }
+    }
+
+    /// Initialize the slot of a sequence claimed with [`reserve`](Self::reserve)
+    /// with `element`, without publishing it yet.
+    ///
+    /// The slot stays invisible to consumers until it is [`publish`](Self::publish)ed.
+    pub fn init_reserved(&self, sequence: i64, element: T, producer: Option<usize>) {
+        self.write(sequence, element, producer);
+    }
+
+    /// Take over the slot of a sequence claimed with [`reserve`](Self::reserve)
+    /// in a [`prefilled`](Self::prefilled) buffer, keeping the element it
+    /// already holds, without publishing it yet.
+    pub fn reuse_reserved(&self, sequence: i64, producer: Option<usize>) {
+        self.stamp(sequence, producer);
+        slot_access!(written, self, sequence);
+    }
+
+    /// Publish a sequence claimed with [`reserve`](Self::reserve) whose slot
+    /// was filled with [`init_reserved`](Self::init_reserved) or
+    /// [`reuse_reserved`](Self::reuse_reserved).
+    pub fn publish(&self, sequence: i64) {
+        self.sequencer.publish_cursor_sequence(sequence);
+    }
+
+    /// Claim `n` consecutive sequences without writing to them yet.
+    ///
+    /// Returns the inclusive `(low, high)` range. The slots stay invisible to
+    /// consumers until they are written and published by [`publish_reserved`](Self::publish_reserved).
+    ///
+    /// # Panics
+    /// If `n` is zero or greater than buffer size it will panic
+    pub fn reserve(&self, n: usize, coordinator: &Coordinator) -> Result<(i64, i64), ClaimError> {
+        assert!(n > 0, "cannot reserve an empty range");
+        self.check_size(n);
+        let high = self.sequencer.next_n(n, coordinator)?;
+        Ok((high - (n - 1) as i64, high))
+    }
+
+    /// Claim `n` consecutive sequences like [`reserve`](Self::reserve), without
+    /// waiting for free space.
+    pub fn try_reserve(&self, n: usize) -> Result<(i64, i64), ClaimError> {
+        assert!(n > 0, "cannot reserve an empty range");
+        self.check_size(n);
+        let high = self.sequencer.try_next_n(n)?;
+        Ok((high - (n - 1) as i64, high))
+    }
+
+    /// Hand the unpublished range `[low, high]` claimed with [`reserve`](Self::reserve)
+    /// back to producers, see [`Sequencer::try_unclaim`].
+    pub fn unreserve(&self, low: i64, high: i64) -> bool {
+        self.sequencer.try_unclaim(low, high)
+    }
+
+    /// Write `items` into a range previously claimed with [`reserve`](Self::reserve)
+    /// and publish it.
+    ///
+    /// # Panics
+    /// If the number of items does not match the size of the range it will panic
+    pub fn publish_reserved<I>(&self, low: i64, high: i64, items: I, producer: Option<usize>)
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iterator = items.into_iter();
+        assert_eq!(
+            iterator.len() as i64,
+            high - low + 1,
+            "number of items must match the reserved range"
+        );
+
+        self.write_range(low, high, iterator, producer);
+    }
+
+    /// Fill a range previously claimed with [`reserve`](Self::reserve) in place
+    /// and publish it.
+    ///
+    /// Every slot starts out as `T::default()`, or keeps the element of the
+    /// previous lap if the buffer is [`prefilled`](Self::prefilled), and is then
+    /// handed to `translate` with its sequence and the next of `args`. The
+    /// range is published even if `translate` panics, so consumers never stall
+    /// on it.
+    ///
+    /// # Panics
+    /// If the number of args does not match the size of the range it will panic
+    pub fn translate_reserved<I, F>(
+        &self,
+        low: i64,
+        high: i64,
+        args: I,
+        mut translate: F,
+        producer: Option<usize>,
+    ) where
+        T: Default,
+        I: ExactSizeIterator,
+        F: FnMut(&mut T, i64, I::Item),
+    {
+        assert_eq!(
+            args.len() as i64,
+            high - low + 1,
+            "number of args must match the reserved range"
+        );
+
+        /// Publishes the range once every slot holds an element.
+        struct Publish<'a, T, S: Sequencer + ?Sized, B: Slots<T>>(
+            &'a RingBuffer<T, S, B>,
+            i64,
+            i64,
+        );
+
+        impl<T, S: Sequencer + ?Sized, B: Slots<T>> Drop for Publish<'_, T, S, B> {
+            fn drop(&mut self) {
+                self.0
+                    .sequencer
+                    .publish_cursor_sequence_range(self.1, self.2);
+            }
+        }
+
+        for sequence in low..=high {
+            if self.is_prefilled() {
+                self.stamp(sequence, producer);
+                slot_access!(written, self, sequence);
+            } else {
+                self.write(sequence, T::default(), producer);
+            }
+        }
+        let _publish = Publish(self, low, high);
+        for (sequence, arg) in (low..=high).zip(args) {
+            // SAFETY: the range is claimed and initialized, and consumers
+            // cannot read it before it is published.
+            translate(unsafe { &mut *self.slot(sequence) }, sequence, arg);
+        }
+    }
+
+    /// Write `items` into the claimed range `[low, high]` and publish it.
+    ///
+    /// Large ranges are published every [`PUBLISH_CHUNK_SIZE`](constants::PUBLISH_CHUNK_SIZE)
+    /// slots, so consumers start draining the head of a batch while the tail is
+    /// still being written. Ranges up to that size, and every range of a
+    /// [`contiguous`](Self::contiguous) buffer, are published at once.
+    #[inline(always)]
+    fn write_range<I>(&self, low: i64, high: i64, items: I, producer: Option<usize>)
+    where
+        I: Iterator<Item = T>,
+    {
+        let chunk_size = match self.contiguous {
+            true => i64::MAX,
+            false => constants::PUBLISH_CHUNK_SIZE as i64,
+        };
+        let mut chunk_low = low;
+
+        for (index, item) in items.enumerate() {
+            let sequence = index as i64 + low;
+            self.write(sequence, item, producer);
+            if sequence - chunk_low + 1 == chunk_size && sequence < high {
+                self.sequencer
+                    .publish_cursor_sequence_range(chunk_low, sequence);
+                chunk_low = sequence + 1;
+            }
+        }
+
+        self.sequencer
+            .publish_cursor_sequence_range(chunk_low, high);
     }
 
     /// Push multiple elements into the ring buffer in a batch.
@@ -141,35 +913,428 @@ impl<T> RingBuffer<T> {
     /// More efficient than calling `push` repeatedly, reducing sequencer overhead.
     ///
     /// # Parameters
-    /// - `items`: iterable of elements to push (must implement `ExactSizeIterator`).
+    /// - `iterator`: elements to push.
     /// - `coordinator`: coordinates waiting if buffer space is not available.
+    /// - `producer`: the id of the producer writing the elements, if it has one.
     ///
     ///# Safety
     /// If there is no available space the producer will wait for it until it became available
     ///
+    /// # Errors
+    /// Hands the iterator back untouched if the channel is closed while waiting for space.
+    ///
     /// # Panics
     /// If items size is greater than buffer size it will panic
-    pub fn push_n<I>(&self, items: I, coordinator: &Coordinator)
+    pub fn push_n<I>(
+        &self,
+        iterator: I,
+        coordinator: &Coordinator,
+        producer: Option<usize>,
+    ) -> Result<(), I>
     where
-        I: IntoIterator<Item = T>,
-        I::IntoIter: ExactSizeIterator,
+        I: ExactSizeIterator<Item = T>,
     {
-        let iterator = items.into_iter();
         let length = iterator.len();
         self.check_size(length);
-        let high = self.sequencer.next_n(length, coordinator);
+        let Ok(high) = self.sequencer.next_n(length, coordinator) else {
+            return Err(iterator);
+        };
         let low = high - (length - 1) as i64;
 
-        for (index, item) in iterator.enumerate() {
-            self.write(index as i64 + low, item);
+        self.write_range(low, high, iterator, producer);
+        Ok(())
+    }
+}
+
+/// Copy the element published at `sequence` with `copy`, without consuming it.
+///
+/// Returns `None` if the sequence has not been published yet or if its slot
+/// has already been reused by a later lap. The claim sequence of producers is
+/// the version of a seqlock: it moves past the slot before any producer writes
+/// it again, so the slot is copied while the claim sequence leaves it alone,
+/// and the copy is only trusted if it still does afterwards.
+#[inline(always)]
+pub(crate) fn copy_published<S, V, F>(
+    sequencer: &S,
+    buffer_size: usize,
+    sequence: i64,
+    copy: F,
+) -> Option<V>
+where
+    S: Sequencer + ?Sized,
+    F: FnOnce() -> V,
+{
+    let overwritten = || sequencer.get_claimed_sequence_acquire() - sequence >= buffer_size as i64;
+    if sequence < 0
+        || sequence > sequencer.get_cursor_sequence_acquire()
+        || sequencer.get_highest(sequence, sequence) != sequence
+        || overwritten()
+    {
+        return None;
+    }
+
+    let value = copy();
+    // Orders the copy before the claim sequence is read again, as the fence
+    // after a claim orders it before the slot is written.
+    fence(Ordering::Acquire);
+
+    match overwritten() {
+        true => None,
+        false => Some(value),
+    }
+}
+
+impl<T> RingBuffer<T> {
+    /// Switch to a multi-producer sequencer once every published element has
+    /// been consumed through `poller`, moving every sequence back to its
+    /// initial value as [`rebase`](Self::rebase) does.
+    ///
+    /// Returns `false` and changes nothing if elements are still waiting.
+    #[cfg(feature = "mp")]
+    pub fn upgrade(&mut self, poller: &dyn Poller<T>) -> bool {
+        if !self.rebase(poller) {
+            return false;
         }
+        if let Some(sequencer) = self.sequencer.to_multi_producer() {
+            self.sequencer = Arc::from(sequencer);
+        }
+        true
+    }
+}
+
+/// A range of elements claimed from a [`RingBuffer`] by a consumer.
+///
+/// Iterating moves the elements out in sequence order. Dropping the claim hands
+/// the range back to producers; elements that were not moved out are left in
+/// the buffer when the poller allows it, and dropped otherwise. A claim dropped
+/// while the thread panics [`requeue`](Poller::requeue)s them instead.
+pub(crate) struct Claimed<'a, T, S: Sequencer + ?Sized = dyn Sequencer, B: Slots<T> = HeapSlots<T>>
+{
+    buffer: &'a RingBuffer<T, S, B>,
+    poller: &'a dyn Poller<T, S, B>,
+    coordinator: &'a Coordinator,
+    claims: Option<&'a ActiveClaims>,
+    low: i64,
+    next: i64,
+    high: i64,
+}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> Claimed<'_, T, S, B> {
+    /// Returns the next element in place, without moving it out.
+    ///
+    /// Only valid on buffers that are not [`retaining`](RingBuffer::retaining),
+    /// where a claimed range belongs to a single consumer.
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        if self.next > self.high {
+            return None;
+        }
+        // SAFETY: the sequence is claimed and published, and no other consumer
+        // reads or moves it out before this claim is dropped.
+        Some(unsafe { &mut *self.buffer.slot(self.next) })
+    }
+
+    /// Returns the sequence of the next element.
+    pub fn sequence(&self) -> i64 {
+        self.next
+    }
+
+    /// Returns the remaining elements as at most two contiguous slices of the
+    /// ring, split where the range wraps around its end.
+    pub fn as_slices(&self) -> (&[T], &[T])
+    where
+        T: Copy,
+    {
+        if self.next > self.high {
+            return (&[], &[]);
+        }
+        let len = (self.high - self.next + 1) as usize;
+        let offset = self.buffer.slots.index(self.next);
+        let first = len.min(self.buffer.buffer_size - offset);
+
+        // SAFETY: the remaining sequences are claimed and published, and slots
+        // of consecutive sequences are adjacent up to the end of the ring.
+        // `UnsafeCell<MaybeUninit<T>>` has the same layout as `T`.
+        unsafe {
+            (
+                std::slice::from_raw_parts(self.buffer.slot(self.next), first),
+                std::slice::from_raw_parts(self.buffer.slot(self.next + first as i64), len - first),
+            )
+        }
+    }
+
+    /// Mark the next element as consumed after it was processed in place.
+    ///
+    /// The element stays in its slot on [`prefilled`](RingBuffer::prefilled)
+    /// buffers, and is moved out and dropped otherwise.
+    pub fn advance(&mut self) {
+        if self.buffer.is_prefilled() {
+            slot_access!(read, self.buffer, self.next);
+            self.next += 1;
+        } else {
+            drop(self.next());
+        }
+    }
+
+    /// Mark every remaining element as consumed without moving it out.
+    pub fn consume(&mut self)
+    where
+        T: Copy,
+    {
+        for _sequence in self.next..=self.high {
+            slot_access!(read, self.buffer, _sequence);
+        }
+        self.next = self.high + 1;
+    }
+}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> Iterator for Claimed<'_, T, S, B> {
+    type Item = T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<T> {
+        if self.next > self.high {
+            return None;
+        }
+        let sequence = self.next;
+        self.next += 1;
+        Some(self.poller.read(self.buffer, sequence))
+    }
 
-        self.sequencer.publish_cursor_sequence_range(low, high);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.high - self.next + 1) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> ExactSizeIterator for Claimed<'_, T, S, B> {}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> Drop for Claimed<'_, T, S, B> {
+    fn drop(&mut self) {
+        let buffer = self.buffer;
+        let range = (self.low, self.high);
+        if self.next > self.high {
+            self.poller.release(&*buffer.sequencer, self.low, self.high);
+        } else if std::thread::panicking() {
+            self.poller
+                .requeue(&*buffer.sequencer, buffer, range, self.next - 1);
+        } else {
+            self.poller
+                .abandon(&*buffer.sequencer, buffer, range, self.next - 1);
+        }
+        if let Some(claims) = self.claims {
+            claims.remove(self.low);
+        }
+        self.coordinator.wakeup_producers();
+    }
+}
+
+/// The ranges claimed through one receiver that were not handed back yet.
+///
+/// A [`Claimed`] that is forgotten instead of dropped never releases its
+/// range. On a poller shared between receivers, the others then move past
+/// it while producers stay gated on it, until the whole ring is taken and
+/// every producer waits for good. A receiver sharing its poller records its
+/// claims here, and [`reclaim`](RingBuffer::reclaim)s the ranges left over
+/// when it is dropped, since no claim can outlive it.
+#[derive(Default)]
+pub(crate) struct ActiveClaims {
+    ranges: Mutex<Vec<(i64, i64)>>,
+}
+
+impl ActiveClaims {
+    /// Record the claimed range `[low, high]`.
+    fn insert(&self, low: i64, high: i64) {
+        self.ranges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((low, high));
+    }
+
+    /// Forget the range starting at `low`, which was handed back.
+    fn remove(&self, low: i64) {
+        let mut ranges = self.ranges.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = ranges.iter().position(|&(start, _)| start == low) {
+            ranges.swap_remove(index);
+        }
+    }
+
+    /// Take every recorded range.
+    fn take(&self) -> Vec<(i64, i64)> {
+        std::mem::take(&mut *self.ranges.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<T, S: Sequencer + ?Sized, B: Slots<T>> Drop for RingBuffer<T, S, B> {
+    /// Drop the elements still held by the buffer.
+    ///
+    /// These are the published elements consumers have not released, and on
+    /// retaining buffers the consumed elements of the last lap as well. Slots
+    /// claimed by producers but never published hold no element, and neither
+    /// do the published slots after them on multi-producer buffers, whose
+    /// elements are leaked rather than risking a read of uninitialized memory.
+    fn drop(&mut self) {
+        #[cfg(all(feature = "ordering-audit", debug_assertions))]
+        crate::ordering::audit::forget(self as *const Self as usize);
+        let buffer_size = self.buffer_size as i64;
+        let (first, last) = match self.is_prefilled() {
+            true => (0, buffer_size - 1),
+            false => {
+                let cursor = self.sequencer.get_cursor_sequence_acquire();
+                let first = match self.retains {
+                    true => (cursor - buffer_size + 1).max(0),
+                    false => self.sequencer.get_gating_sequence_relaxed() + 1,
+                };
+                (first, self.sequencer.get_highest(first, cursor))
+            }
+        };
+
+        for sequence in first..=last {
+            // SAFETY: the slots of a prefilled buffer are always initialized, and
+            // otherwise the range only covers published elements that were not
+            // moved out. No producer or consumer is left to access them.
+            unsafe { self.slots.cell_mut(sequence).assume_init_drop() };
+        }
     }
 }
 
 // SAFETY: `RingBuffer` is safe to share between threads because all internal mutability
 // is handled with `UnsafeCell` and sequencer coordination ensures proper synchronization.
-unsafe impl<T> Sync for RingBuffer<T> {}
+// Elements written by one thread are moved out or dropped by another, so they must be
+// `Send`; receivers that read them by reference from several threads require `Sync` too.
+unsafe impl<T: Send, S: Sequencer + ?Sized, B: Slots<T>> Sync for RingBuffer<T, S, B> {}
+
+unsafe impl<T: Send, S: Sequencer + ?Sized, B: Slots<T>> Send for RingBuffer<T, S, B> {}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    use crate::channels::mpmc;
+    #[cfg(feature = "mp")]
+    use crate::channels::{BatchAtomicity, ChannelBuilder, Producers, RecvResult};
+    #[cfg(feature = "mc")]
+    use crate::channels::{ConsumerFairness, spmc_with_fairness};
+    #[cfg(feature = "mc")]
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    #[cfg(feature = "mc")]
+    use crate::errors::TrySendError;
+    use crate::testing::yielding_spsc;
+    use std::cell::RefCell;
+    #[cfg(feature = "mp")]
+    use std::time::Duration;
+
+    #[cfg(all(feature = "mp", feature = "mc"))]
+    #[test]
+    fn test_non_power_of_two_capacity_wraps_in_order() {
+        let (tx, rx) = mpmc::<u32>(
+            10,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let producer = std::thread::spawn(move || {
+            for value in 0..1000 {
+                tx.send(value).unwrap();
+            }
+        });
+        let received: Vec<u32> = rx.iter().collect();
+        producer.join().unwrap();
+        assert_eq!(received, (0..1000).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_large_batches_are_visible_before_they_complete() {
+        use crate::constants::PUBLISH_CHUNK_SIZE;
+
+        let (tx, rx) = yielding_spsc::<usize>(1024);
+        // Drain what is visible while the producer writes the last item.
+        let head = RefCell::new(Vec::new());
+        let items = (0..PUBLISH_CHUNK_SIZE * 2 + 1).inspect(|&i| {
+            if i == PUBLISH_CHUNK_SIZE * 2 {
+                rx.try_recv_batch(1024, &|item| head.borrow_mut().push(item));
+            }
+        });
+        tx.send_n(items).unwrap();
+
+        assert!(head.into_inner().into_iter().eq(0..PUBLISH_CHUNK_SIZE * 2));
+        assert_eq!(rx.drain_all(), [PUBLISH_CHUNK_SIZE * 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "mp")]
+    fn test_contiguous_batches_become_visible_at_once() {
+        let (tx, rx) = ChannelBuilder::<(usize, usize)>::new()
+            .capacity(1024)
+            .producers(Producers::Multi)
+            .batch_atomicity(BatchAtomicity::Contiguous)
+            .build();
+        // Batches larger than a publish chunk, from producers racing each
+        // other and pausing while they write the tail of a batch.
+        const BATCH: usize = 300;
+        let item = |producer, i| {
+            if i == BATCH - 1 {
+                std::thread::sleep(Duration::from_micros(200));
+            }
+            (producer, i)
+        };
+        let producers: Vec<_> = (0..2)
+            .map(|producer| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        tx.send_n((0..BATCH).map(|i| item(producer, i))).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = 0;
+        let mut items = Vec::new();
+        loop {
+            items.clear();
+            match rx.drain_into(&mut items, 1024) {
+                RecvResult::Processed(_) => {}
+                RecvResult::Empty => std::thread::yield_now(),
+                RecvResult::Disconnected => break,
+            }
+            // Every drain ends on a batch boundary, with each batch whole.
+            assert_eq!(items.len() % BATCH, 0);
+            for batch in items.chunks(BATCH) {
+                assert!(batch.iter().map(|&(_, i)| i).eq(0..BATCH));
+                assert!(batch.iter().all(|&(producer, _)| producer == batch[0].0));
+            }
+            received += items.len();
+        }
+        assert_eq!(received, 2 * 20 * BATCH);
+        for producer in producers {
+            producer.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "mc")]
+    #[test]
+    fn test_dropped_receivers_release_forgotten_claims() {
+        let (tx, rx) = spmc_with_fairness::<u32>(
+            8,
+            ConsumerFairness::Throughput,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let other = rx.clone();
+        tx.send_n(0..4).unwrap();
+        let mut iter = rx.try_iter();
+        assert_eq!(iter.next(), Some(0));
+        std::mem::forget(iter);
+
+        // The other receiver moves on, but producers stay gated on the claim.
+        tx.send_n(4..8).unwrap();
+        assert_eq!(other.try_iter().collect::<Vec<_>>(), [4, 5, 6, 7]);
+        assert!(matches!(tx.try_send(8), Err(TrySendError::Full(8))));
 
-unsafe impl<T> Send for RingBuffer<T> {}
+        drop(rx);
+        tx.send_n(8..16).unwrap();
+        assert_eq!(
+            other.try_iter().collect::<Vec<_>>(),
+            (8..16).collect::<Vec<_>>()
+        );
+    }
+}
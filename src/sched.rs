@@ -0,0 +1,144 @@
+//! Deterministic scheduling hooks for tests.
+//!
+//! The sequencers call into this module at the key steps of the protocol:
+//! before producers try to claim sequences, after they claim them, before they
+//! publish them, and before consumers publish their gating sequence. In test builds a thread can install
+//! a [`SchedHook`] that runs at those steps, for example to stall a producer
+//! between its claim and its publish while other threads carry on, which forces
+//! a specific interleaving without loom. Outside of tests the calls compile to
+//! nothing.
+
+/// Callbacks run on the current thread at the key steps of the protocol.
+///
+/// Every callback does nothing by default.
+#[cfg(test)]
+pub(crate) trait SchedHook {
+    /// Called before a producer tries to claim the sequences up to `high`
+    /// without waiting, once per attempt.
+    fn before_try_claim(&self, _high: i64) {}
+
+    /// Called after a producer claimed the sequences up to `high`.
+    fn after_claim(&self, _high: i64) {}
+
+    /// Called before a producer publishes the sequences `[low, high]`.
+    fn before_publish(&self, _low: i64, _high: i64) {}
+
+    /// Called before a consumer publishes `sequence` as its gating sequence.
+    fn before_gating_publish(&self, _sequence: i64) {}
+}
+
+#[cfg(test)]
+thread_local! {
+    static HOOK: std::cell::RefCell<Option<std::rc::Rc<dyn SchedHook>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Removes the hook installed by [`install`] when dropped.
+#[cfg(test)]
+#[cfg_attr(any(not(feature = "mp"), feature = "loom"), allow(dead_code))]
+pub(crate) struct HookGuard(());
+
+#[cfg(test)]
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        HOOK.with(|hook| hook.borrow_mut().take());
+    }
+}
+
+/// Run `hook` at the protocol steps of the current thread until the guard is dropped.
+#[cfg(test)]
+#[cfg_attr(any(not(feature = "mp"), feature = "loom"), allow(dead_code))]
+pub(crate) fn install<H: SchedHook + 'static>(hook: H) -> HookGuard {
+    HOOK.with(|slot| *slot.borrow_mut() = Some(std::rc::Rc::new(hook)));
+    HookGuard(())
+}
+
+/// Run `step` against the hook of the current thread, if one is installed.
+#[cfg(test)]
+fn with_hook(step: impl FnOnce(&dyn SchedHook)) {
+    if let Some(hook) = HOOK.with(|hook| hook.borrow().clone()) {
+        step(&*hook);
+    }
+}
+
+/// Called before a producer tries to claim the sequences up to `high` without waiting.
+#[inline(always)]
+#[cfg_attr(not(feature = "mp"), allow(dead_code))]
+pub(crate) fn before_try_claim(_high: i64) {
+    #[cfg(test)]
+    with_hook(|hook| hook.before_try_claim(_high));
+}
+
+/// Called after a producer claimed the sequences up to `high`.
+#[inline(always)]
+pub(crate) fn after_claim(_high: i64) {
+    #[cfg(test)]
+    with_hook(|hook| hook.after_claim(_high));
+}
+
+/// Called before a producer publishes the sequences `[low, high]`.
+#[inline(always)]
+pub(crate) fn before_publish(_low: i64, _high: i64) {
+    #[cfg(test)]
+    with_hook(|hook| hook.before_publish(_low, _high));
+}
+
+/// Called before a consumer publishes `sequence` as its gating sequence.
+#[inline(always)]
+pub(crate) fn before_gating_publish(_sequence: i64) {
+    #[cfg(test)]
+    with_hook(|hook| hook.before_gating_publish(_sequence));
+}
+
+#[cfg(all(test, feature = "mp", not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvResult, mpsc};
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::sched::{self, SchedHook};
+    use std::cell::RefCell;
+    use std::sync::{Arc, Barrier};
+
+    /// Stalls the producer between its claim and its publish.
+    struct StallAfterClaim {
+        stalled: Arc<Barrier>,
+        resume: Arc<Barrier>,
+    }
+
+    impl SchedHook for StallAfterClaim {
+        fn after_claim(&self, _: i64) {
+            self.stalled.wait();
+            self.resume.wait();
+        }
+    }
+
+    #[test]
+    fn test_consumer_does_not_skip_a_claimed_but_unpublished_sequence() {
+        let (tx, rx) = mpsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+        );
+        let stalled = Arc::new(Barrier::new(2));
+        let resume = Arc::new(Barrier::new(2));
+        let slow = tx.clone();
+        let hook = StallAfterClaim {
+            stalled: stalled.clone(),
+            resume: resume.clone(),
+        };
+        let producer = std::thread::spawn(move || {
+            let _guard = sched::install(hook);
+            slow.send(1).unwrap();
+        });
+
+        stalled.wait();
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv_batch(8, &|_| {}), RecvResult::Empty);
+
+        resume.wait();
+        producer.join().unwrap();
+        let received = RefCell::new(Vec::new());
+        let handler = |value| received.borrow_mut().push(value);
+        assert_eq!(rx.try_recv_batch(8, &handler), RecvResult::Processed(2));
+        assert_eq!(received.into_inner(), vec![1, 2]);
+    }
+}
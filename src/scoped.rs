@@ -0,0 +1,116 @@
+//! Channels tied to a [`std::thread::Scope`].
+
+use crate::channels::{Receiver, Sender};
+use std::thread::{Scope, ScopedJoinHandle};
+
+/// A channel whose lifetime is tied to a [`std::thread::Scope`], created by [`scoped`].
+///
+/// Consumers spawned with [`spawn_consumer`](Self::spawn_consumer) run on
+/// threads of the scope. When the `ScopedChannel` is dropped, which happens at
+/// the latest when the scope body returns, the channel is closed, so consumers
+/// drain what is left and stop before the scope joins them instead of waiting
+/// for items that will never arrive.
+pub struct ScopedChannel<'scope, 'env, T> {
+    scope: &'scope Scope<'scope, 'env>,
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+}
+
+impl<'scope, 'env, T: Send + 'scope> ScopedChannel<'scope, 'env, T> {
+    /// Returns the sending half; clone it to hand it to producers.
+    pub fn sender(&self) -> &Sender<T> {
+        &self.sender
+    }
+
+    /// Returns the receiving half; clone it to consume on the current thread.
+    pub fn receiver(&self) -> &Receiver<T> {
+        &self.receiver
+    }
+
+    /// Spawn a consumer on a thread of the scope, handing it its own receiver.
+    ///
+    /// The consumer should stop once its receiver reports that the channel is
+    /// closed, for example by looping over [`Receiver::iter`] or until
+    /// [`RecvState::Disconnected`](crate::channels::RecvState::Disconnected).
+    pub fn spawn_consumer<F, R>(&self, consumer: F) -> ScopedJoinHandle<'scope, R>
+    where
+        F: FnOnce(Receiver<T>) -> R + Send + 'scope,
+        R: Send + 'scope,
+    {
+        let receiver = self.receiver.clone();
+        self.scope.spawn(move || consumer(receiver))
+    }
+}
+
+impl<T> Drop for ScopedChannel<'_, '_, T> {
+    fn drop(&mut self) {
+        self.sender.close();
+    }
+}
+
+/// Tie a channel to `scope`, closing it when the returned handle is dropped.
+///
+/// Works with every channel flavour:
+///
+/// ```
+/// use channels_rs::channels::scoped;
+/// use channels_rs::prelude::*;
+///
+/// std::thread::scope(|scope| {
+///     let channel = scoped(
+///         scope,
+///         spsc::<u32>(64, ProducerWaitStrategyKind::Spinning, ConsumerWaitStrategyKind::Blocking),
+///     );
+///     let consumer = channel.spawn_consumer(|rx| rx.iter().sum::<u32>());
+///     for value in 1..=10 {
+///         channel.sender().send(value).unwrap();
+///     }
+///     drop(channel);
+///     assert_eq!(consumer.join().unwrap(), 55);
+/// });
+/// ```
+pub fn scoped<'scope, 'env, T>(
+    scope: &'scope Scope<'scope, 'env>,
+    (sender, receiver): (Sender<T>, Receiver<T>),
+) -> ScopedChannel<'scope, 'env, T> {
+    ScopedChannel {
+        scope,
+        sender,
+        receiver,
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{scoped, spsc};
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::errors::SendError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_scoped_channel_closes_when_its_scope_ends() {
+        let (tx, rx) = spsc::<usize>(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        // A sender outside the scope would keep the consumer waiting for items
+        // and the scope waiting for the consumer, if ending it did not close
+        // the channel.
+        let outlives_scope = tx.clone();
+        let total = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let channel = scoped(scope, (tx, rx));
+            channel.spawn_consumer(|rx| total.store(rx.iter().sum(), Ordering::Relaxed));
+            for value in 1..=10 {
+                channel.sender().send(value).unwrap();
+            }
+        });
+        assert_eq!(total.into_inner(), 55);
+        assert!(matches!(
+            outlives_scope.send(11),
+            Err(SendError::Closed(11, None))
+        ));
+    }
+}
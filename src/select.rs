@@ -0,0 +1,242 @@
+//! Waiting on several receivers at once.
+//!
+//! A [`Selector`] watches receivers of any item type and blocks until one of
+//! them is ready, so a single event loop can consume several channels without
+//! a spinning thread per channel. Every watched channel signals the selector
+//! whenever it would wake its own consumer, see
+//! [`NotifyPolicy`](crate::coordinator::NotifyPolicy).
+
+use crate::channels::Receiver;
+use crate::constants;
+use crate::coordinator::Coordinator;
+use std::cell::Cell;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A wakeup shared by the channels a [`Selector`] watches.
+///
+/// Every signal bumps a generation, so a selector that read the generation
+/// before checking its receivers cannot miss a signal raised in between.
+pub(crate) struct Signal {
+    generation: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn new() -> Self {
+        Self {
+            generation: Mutex::new(0),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Wake the selector waiting on this signal.
+    pub fn notify(&self) {
+        let mut generation = self.generation.lock().unwrap();
+        *generation = generation.wrapping_add(1);
+        self.condvar.notify_all();
+    }
+
+    fn generation(&self) -> u64 {
+        *self.generation.lock().unwrap()
+    }
+
+    /// Wait until the generation moves past `seen`, or until `deadline`.
+    fn wait(&self, seen: u64, deadline: Instant) {
+        let mut generation = self.generation.lock().unwrap();
+        while *generation == seen {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            generation = self.condvar.wait_timeout(generation, remaining).unwrap().0;
+        }
+    }
+}
+
+/// A receiver as watched by a [`Selector`], independently of its item type.
+trait Watched {
+    /// Returns `true` if a receive would not wait.
+    fn is_ready(&self) -> bool;
+
+    /// Returns the coordinator the selector's signal is registered with.
+    fn coordinator(&self) -> &Coordinator;
+}
+
+impl<T> Watched for Receiver<T> {
+    fn is_ready(&self) -> bool {
+        let (buffer, coordinator) = self.parts();
+        buffer.backlog() > 0 || coordinator.is_finished()
+    }
+
+    fn coordinator(&self) -> &Coordinator {
+        self.parts().1
+    }
+}
+
+/// Waits until any of several receivers is ready.
+///
+/// A receiver is ready once items wait in its buffer, or once no more items
+/// can arrive, so a receive on it would not wait. Readiness is a hint:
+/// another consumer may take the items first, and on multi-producer channels
+/// an item may still be in the middle of being published, so the ready
+/// receiver should be polled with a non-waiting receive such as
+/// [`Receiver::try_recv_batch`].
+///
+/// ```
+/// use channels_rs::prelude::*;
+/// use channels_rs::select::Selector;
+///
+/// let (numbers, number_rx) = spsc::<u32>(8, ProducerWaitStrategyKind::Spinning, ConsumerWaitStrategyKind::Blocking);
+/// let (_words, word_rx) = spsc::<String>(8, ProducerWaitStrategyKind::Spinning, ConsumerWaitStrategyKind::Blocking);
+///
+/// let mut selector = Selector::new();
+/// let number_index = selector.register(&number_rx);
+/// selector.register(&word_rx);
+///
+/// numbers.send(7).unwrap();
+/// assert_eq!(selector.select(), number_index);
+/// ```
+pub struct Selector<'a> {
+    receivers: Vec<Option<&'a dyn Watched>>,
+    signal: Arc<Signal>,
+    next: Cell<usize>,
+}
+
+impl<'a> Selector<'a> {
+    /// Create a selector that watches no receiver yet.
+    pub fn new() -> Self {
+        Self {
+            receivers: Vec::new(),
+            signal: Arc::new(Signal::new()),
+            next: Cell::new(0),
+        }
+    }
+
+    /// Watch `receiver`, returning the index [`select`](Self::select) reports it by.
+    pub fn register<T>(&mut self, receiver: &'a Receiver<T>) -> usize {
+        receiver.coordinator().watch(self.signal.clone());
+        self.receivers.push(Some(receiver));
+        self.receivers.len() - 1
+    }
+
+    /// Stop watching the receiver registered at `index`, for example once it
+    /// is disconnected and would otherwise stay ready forever.
+    ///
+    /// Indices of the other receivers do not change.
+    pub fn remove(&mut self, index: usize) {
+        if let Some(receiver) = self.receivers.get_mut(index).and_then(Option::take) {
+            receiver.coordinator().unwatch(&self.signal);
+        }
+    }
+
+    /// Returns the index of a ready receiver, without waiting.
+    ///
+    /// Receivers are checked starting after the one returned last, so a busy
+    /// receiver does not hide the others.
+    pub fn ready(&self) -> Option<usize> {
+        let len = self.receivers.len();
+        let start = self.next.get();
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&index| self.receivers[index].is_some_and(|receiver| receiver.is_ready()))
+            .inspect(|index| self.next.set(index + 1))
+    }
+
+    /// Wait until a receiver is ready and return its index.
+    ///
+    /// Waits forever if no receiver is registered.
+    pub fn select(&self) -> usize {
+        loop {
+            if let Some(index) = self.select_until(Instant::now() + constants::SELECT_RECHECK) {
+                return index;
+            }
+        }
+    }
+
+    /// Wait at most `timeout` for a receiver to be ready and return its index.
+    pub fn select_timeout(&self, timeout: Duration) -> Option<usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let recheck = Instant::now() + constants::SELECT_RECHECK;
+            if let Some(index) = self.select_until(recheck.min(deadline)) {
+                return Some(index);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+        }
+    }
+
+    /// Wait until a receiver is ready or until `deadline`.
+    ///
+    /// A producer that published just as a receiver was registered may not
+    /// have seen the selector's signal yet, which is why callers re-check
+    /// their receivers at least every [`SELECT_RECHECK`](constants::SELECT_RECHECK).
+    fn select_until(&self, deadline: Instant) -> Option<usize> {
+        let seen = self.signal.generation();
+        if let Some(index) = self.ready() {
+            return Some(index);
+        }
+        self.signal.wait(seen, deadline);
+        self.ready()
+    }
+}
+
+impl Default for Selector<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Selector<'_> {
+    fn drop(&mut self) {
+        for receiver in self.receivers.iter().flatten() {
+            receiver.coordinator().unwatch(&self.signal);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::spsc;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::select::Selector;
+    use std::time::Duration;
+
+    #[test]
+    fn test_selector_wakes_for_any_receiver() {
+        let (numbers, number_rx) = spsc::<u32>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let (words, word_rx) = spsc::<String>(
+            8,
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        let mut selector = Selector::new();
+        let number_index = selector.register(&number_rx);
+        let word_index = selector.register(&word_rx);
+        assert_eq!(selector.select_timeout(Duration::from_millis(20)), None);
+
+        let producer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            words.send("ready".to_string()).unwrap();
+            words
+        });
+        assert_eq!(selector.select(), word_index);
+        let words = producer.join().unwrap();
+        word_rx.try_recv_batch(8, &|word| assert_eq!(word, "ready"));
+
+        numbers.send(1).unwrap();
+        assert_eq!(selector.select(), number_index);
+        number_rx.try_recv_batch(8, &|_| {});
+
+        drop(words);
+        assert_eq!(selector.select(), word_index);
+        selector.remove(word_index);
+        assert_eq!(selector.select_timeout(Duration::from_millis(20)), None);
+    }
+}
@@ -1,4 +1,5 @@
-use std::sync::atomic::{AtomicI64, Ordering};
+use crate::sync::{AtomicI64, Ordering};
+use crate::utils::CachePadded;
 
 /// Initial value for a [`Sequence`] when uninitialized.
 pub const INITIAL_VALUE: i64 = -1;
@@ -9,10 +10,9 @@ pub const INITIAL_VALUE: i64 = -1;
 /// configurable memory ordering. It is used to track **cursor positions**,
 /// **gating sequences**.
 ///
-/// The struct is aligned to 64 bytes to avoid false sharing between threads.
-#[repr(align(64))]
+/// The counter is padded to a cache line to avoid false sharing between threads.
 pub struct Sequence {
-    sequence: AtomicI64,
+    sequence: CachePadded<AtomicI64>,
 }
 
 // SAFETY: Sequence is thread-safe due to internal atomic operations.
@@ -24,7 +24,7 @@ impl Sequence {
     /// Create a new sequence initialized to `value`.
     pub fn new(value: i64) -> Self {
         Sequence {
-            sequence: AtomicI64::new(value),
+            sequence: CachePadded(AtomicI64::new(value)),
         }
     }
 
@@ -83,6 +83,7 @@ mod tests {
     use loom::sync::Arc;
 
     #[test]
+    #[cfg(not(feature = "loom"))]
     pub fn test_default_sequence_value() {
         let sequence = Sequence::default();
         assert_eq!(sequence.get_relaxed(), -1);
@@ -129,7 +130,7 @@ mod tests {
             });
 
             let value = sequence.get_acquire();
-            assert!(value == -1 || value == 1);
+            assert!(value == -1 || value == 0);
         })
     }
 
@@ -1,6 +1,27 @@
+#[cfg(feature = "mp")]
 use crate::availability_buffer::AvailabilityBuffer;
+use crate::constants;
 use crate::coordinator::Coordinator;
-use crate::sequence::Sequence;
+use crate::errors::SequencesExhausted;
+use crate::ordering::ordered;
+use crate::sched;
+use crate::sequence::{INITIAL_VALUE, Sequence};
+use crate::sync::{AtomicU32, AtomicU64, Mutex, Ordering, fence};
+use std::sync::Arc;
+
+/// Reason a non-blocking claim could not be satisfied.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ClaimError {
+    /// There are not enough free slots in the ring buffer.
+    Full,
+    /// The claim lost the race against other producers too many times.
+    #[cfg_attr(not(feature = "mp"), allow(dead_code))]
+    Contended,
+    /// The channel was closed while the producer was waiting for free slots.
+    Closed,
+    /// The claim would go past [`MAX_SEQUENCE`](constants::MAX_SEQUENCE).
+    Exhausted,
+}
 
 /// Trait defining a sequencer for coordinating producers and consumers in a ring buffer.
 ///
@@ -9,12 +30,39 @@ use crate::sequence::Sequence;
 /// for claiming sequences, publishing cursor progress, and waiting for consumers.
 pub trait Sequencer: Sync + Send {
     /// Claim the next sequence for a producer.
-    fn next(&self, strategy: &Coordinator) -> i64 {
+    fn next(&self, strategy: &Coordinator) -> Result<i64, ClaimError> {
         self.next_n(1, strategy)
     }
 
     /// Claim the next `n` sequences for batch production.
-    fn next_n(&self, n: usize, strategy: &Coordinator) -> i64;
+    ///
+    /// Waits for free slots according to the producer wait strategy, and fails
+    /// with [`ClaimError::Closed`] if the channel is closed while waiting. A
+    /// claim past [`MAX_SEQUENCE`](constants::MAX_SEQUENCE) closes the channel
+    /// and fails the same way.
+    fn next_n(&self, n: usize, strategy: &Coordinator) -> Result<i64, ClaimError>;
+
+    /// Try to claim the next `n` sequences without waiting for consumers.
+    ///
+    /// Fails with [`ClaimError::Full`] if the consumers have not freed enough slots.
+    /// Multi-producer sequencers retry a lost race for the cursor at most `max_retries`
+    /// times before failing with [`ClaimError::Contended`]. A claim past
+    /// [`MAX_SEQUENCE`](constants::MAX_SEQUENCE) fails with [`ClaimError::Exhausted`].
+    fn try_next_n_bounded(&self, n: usize, max_retries: usize) -> Result<i64, ClaimError>;
+
+    /// Try to claim the next sequence without waiting for consumers.
+    fn try_next(&self) -> Result<i64, ClaimError> {
+        self.try_next_n(1)
+    }
+
+    /// Try to claim the next `n` sequences without waiting for consumers.
+    ///
+    /// Fails with [`ClaimError::Full`] if the consumers have not freed enough slots.
+    /// A claim that loses the race against other producers is retried, which only
+    /// happens when another producer made progress, so this never spins on a full buffer.
+    fn try_next_n(&self, n: usize) -> Result<i64, ClaimError> {
+        self.try_next_n_bounded(n, usize::MAX)
+    }
 
     /// Publish a sequence to indicate it is ready for consumption.
     fn publish_cursor_sequence(&self, sequence: i64);
@@ -49,7 +97,12 @@ pub trait Sequencer: Sync + Send {
         let mut current: i64 = gating_sequence.get_relaxed();
         loop {
             if current > sequence
-                || gating_sequence.compare_and_exchange_weak_volatile(current, sequence)
+                || ordered!(
+                    Gating,
+                    Release,
+                    AcqRel,
+                    gating_sequence.compare_and_exchange_weak_volatile(current, sequence)
+                )
             {
                 break;
             }
@@ -63,26 +116,304 @@ pub trait Sequencer: Sync + Send {
     /// Get the current cursor sequence with Acquire ordering.
     fn get_cursor_sequence_acquire(&self) -> i64;
 
+    /// Get the highest sequence claimed by producers with Acquire ordering.
+    ///
+    /// Claims are fenced before the claimed slots are written, so a reader that
+    /// observes a slot write is guaranteed to observe the claim that preceded it.
+    fn get_claimed_sequence_acquire(&self) -> i64;
+
     /// Get the current gating sequence with Relaxed ordering.
     fn get_gating_sequence_relaxed(&self) -> i64;
 
+    /// Get the lowest sequence producers are gated on with Acquire ordering.
+    ///
+    /// This is the minimum of the consumer's gating sequence and the
+    /// [registered](Self::gating_sequences) ones, further limited by the credit
+    /// watermark on channels created with credits.
+    fn get_gating_minimum_acquire(&self) -> i64;
+
+    /// Get the gating sequences registered on the sequencer at runtime.
+    ///
+    /// Producers are gated on them in addition to the consumer's gating sequence.
+    fn gating_sequences(&self) -> &Arc<GatingSequences>;
+
+    /// Grant producers `n` more sequences to claim on channels created with credits.
+    ///
+    /// Has no effect on sequencers without a credit watermark.
+    fn grant(&self, n: usize);
+
+    /// Move every sequence back to its initial value, as if nothing had been published.
+    ///
+    /// Credits that were granted but not used yet are kept. Only valid while no
+    /// producer or consumer is active and every published sequence has been consumed.
+    fn rebase(&self);
+
+    /// Hand the claimed but unpublished sequences `[low, high]` back to producers.
+    ///
+    /// Only succeeds if `high` is still the highest claimed sequence, since the
+    /// claim cannot be taken back once later sequences were claimed after it.
+    fn try_unclaim(&self, low: i64, high: i64) -> bool;
+
+    /// Returns `true` if several producers may claim sequences concurrently.
+    #[cfg(feature = "mp")]
+    fn is_multi_producer(&self) -> bool {
+        false
+    }
+
+    /// Create a multi-producer sequencer for the same buffer with the same
+    /// credits and registered gating sequences, or return `None` if this one
+    /// already is one.
+    ///
+    /// Only valid right after a [`rebase`](Self::rebase), since the new
+    /// sequencer starts from the initial sequence.
+    #[cfg(feature = "mp")]
+    fn to_multi_producer(&self) -> Option<Box<dyn Sequencer>> {
+        None
+    }
+
     /// Wait until the consumer has processed sequences below `wrap_point`.
     ///
-    /// Uses the provided `Coordinator` to apply the producer wait strategy.
+    /// Uses the provided `Coordinator` to apply the producer wait strategy, and
+    /// gives up with [`ClaimError::Closed`] once the channel is closed.
     #[inline(always)]
-    fn wait(&self, gating_sequence: &Sequence, wrap_point: i64, coordinator: &Coordinator) -> i64 {
+    fn wait(&self, wrap_point: i64, coordinator: &Coordinator) -> Result<i64, ClaimError> {
         let mut gating: i64;
+        let mut waited = false;
         loop {
-            gating = gating_sequence.get_acquire();
+            gating = self.get_gating_minimum_acquire();
             if wrap_point > gating {
+                if coordinator.is_closed() {
+                    return Err(ClaimError::Closed);
+                }
                 coordinator.producer_wait();
+                waited = true;
                 continue;
             }
-            return gating;
+            if waited {
+                coordinator.producer_progress();
+            }
+            return Ok(gating);
+        }
+    }
+}
+
+/// The sequences a dependent consumer may process.
+///
+/// A consumer behind a barrier only sees sequences that are published by
+/// producers and already handled by every consumer it depends on, which is how
+/// the stages of a pipeline are chained on a single ring buffer.
+pub struct SequenceBarrier {
+    dependencies: Vec<Arc<Sequence>>,
+}
+
+impl SequenceBarrier {
+    /// Create a barrier over the progress of `dependencies`.
+    ///
+    /// A barrier without dependencies only waits for producers.
+    pub fn new(dependencies: Vec<Arc<Sequence>>) -> Self {
+        Self { dependencies }
+    }
+
+    /// Determine the highest sequence in `[low, high]` that may be processed.
+    ///
+    /// Returns a value below `low` if none may be processed yet.
+    pub(crate) fn get_highest<S>(&self, sequencer: &S, low: i64, high: i64) -> i64
+    where
+        S: Sequencer + ?Sized,
+    {
+        let high: i64 = self
+            .dependencies
+            .iter()
+            .map(|dependency| dependency.get_acquire())
+            .fold(high.min(sequencer.get_cursor_sequence_acquire()), i64::min);
+
+        if high < low {
+            return high;
+        }
+        sequencer.get_highest(low, high)
+    }
+}
+
+/// The most gating sequences that can be registered on a sequencer at once.
+pub(crate) const MAX_GATING_SEQUENCES: usize = 16;
+
+/// Gating sequences registered on a live sequencer, for consumers that join
+/// after the channel was created.
+///
+/// The sequences live in a fixed array of slots and a bitmask tells which of
+/// them are registered, so producers read the set without locks and without
+/// following pointers that could be freed under them. A version counter
+/// bumped on every registration lets producers detect a registration that
+/// raced with their read of the gating minimum and read it again.
+pub(crate) struct GatingSequences {
+    slots: Box<[Sequence]>,
+    /// The slots owned by a registration, including one still being set up.
+    claimed: AtomicU32,
+    /// The slots producers are gated on.
+    occupied: AtomicU32,
+    version: AtomicU64,
+}
+
+impl GatingSequences {
+    pub fn new() -> Self {
+        Self {
+            slots: (0..MAX_GATING_SEQUENCES)
+                .map(|_| Sequence::default())
+                .collect(),
+            claimed: AtomicU32::new(0),
+            occupied: AtomicU32::new(0),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the minimum of `gating` and every registered sequence.
+    ///
+    /// `gating` is read inside the same validated window as the registered
+    /// sequences, so the minimum never lets producers past a sequence that a
+    /// concurrent [`add`](Self::add) starts from.
+    #[inline(always)]
+    pub fn minimum<F>(&self, gating: F) -> i64
+    where
+        F: Fn() -> i64,
+    {
+        loop {
+            let version: u64 = self.version.load(Ordering::SeqCst);
+            let mut occupied: u32 = self.occupied.load(Ordering::SeqCst);
+            let mut minimum: i64 = gating();
+            while occupied != 0 {
+                let slot = occupied.trailing_zeros() as usize;
+                minimum = minimum.min(self.slots[slot].get_acquire());
+                occupied &= occupied - 1;
+            }
+            fence(Ordering::SeqCst);
+            if self.version.load(Ordering::Relaxed) == version {
+                return minimum;
+            }
+        }
+    }
+
+    /// Register a gating sequence that starts at the value returned by
+    /// `claimed`, the highest sequence claimed by producers.
+    ///
+    /// Returns the slot of the sequence, or `None` if
+    /// [`MAX_GATING_SEQUENCES`] are registered already.
+    pub fn add<F>(&self, claimed: F) -> Option<usize>
+    where
+        F: Fn() -> i64,
+    {
+        let mut current: u32 = self.claimed.load(Ordering::Relaxed);
+        let slot = loop {
+            let slot = current.trailing_ones() as usize;
+            if slot >= MAX_GATING_SEQUENCES {
+                return None;
+            }
+            match self.claimed.compare_exchange_weak(
+                current,
+                current | 1 << slot,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break slot,
+                Err(actual) => current = actual,
+            }
+        };
+
+        // Producers may read the sequence as soon as its bit is set, and they
+        // may claim past it until they see the bit. Starting from the claimed
+        // sequence read after the version bump covers both: a producer that
+        // read the set before the bit finished reading before the bump, or it
+        // reads the set again.
+        self.slots[slot].set_release(claimed());
+        self.occupied.fetch_or(1 << slot, Ordering::SeqCst);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        self.slots[slot].set_release(claimed());
+        Some(slot)
+    }
+
+    /// Stop gating producers on the sequence registered at `slot`.
+    pub fn remove(&self, slot: usize) {
+        self.occupied.fetch_and(!(1 << slot), Ordering::SeqCst);
+        self.claimed.fetch_and(!(1 << slot), Ordering::Release);
+    }
+
+    /// Returns the sequence registered at `slot`.
+    pub fn get(&self, slot: usize) -> &Sequence {
+        &self.slots[slot]
+    }
+
+    /// Returns the values of the registered sequences, in slot order.
+    #[cfg(feature = "inspect")]
+    pub fn values(&self) -> Vec<i64> {
+        let mut occupied: u32 = self.occupied.load(Ordering::Acquire);
+        let mut values = Vec::with_capacity(occupied.count_ones() as usize);
+        while occupied != 0 {
+            values.push(self.slots[occupied.trailing_zeros() as usize].get_acquire());
+            occupied &= occupied - 1;
+        }
+        values
+    }
+
+    /// Move every registered sequence back to the initial value.
+    fn rebase(&self) {
+        for slot in &self.slots[..] {
+            slot.set_relaxed(INITIAL_VALUE);
         }
     }
 }
 
+/// Combine a gating sequence with an optional credit watermark.
+///
+/// A watermark of `w` allows producers to claim up to sequence `w`, which is
+/// the same limit as a gating sequence of `w - buffer_size`.
+#[inline(always)]
+fn credited_minimum(gating: i64, credits: &Option<Sequence>, buffer_size: i64) -> i64 {
+    match credits {
+        Some(watermark) => gating.min(watermark.get_acquire() - buffer_size),
+        None => gating,
+    }
+}
+
+/// Shift a credit watermark by the `consumed` sequences a rebase removes,
+/// returning the new cached gating minimum.
+fn rebase_credits(credits: &Option<Sequence>, consumed: i64, buffer_size: i64) -> i64 {
+    match credits {
+        Some(watermark) => {
+            let watermark_value: i64 = watermark.get_relaxed() - consumed;
+            watermark.set_release(watermark_value);
+            INITIAL_VALUE.min(watermark_value - buffer_size)
+        }
+        None => INITIAL_VALUE,
+    }
+}
+
+/// The credited gating minimum before any consumer progress or grant.
+///
+/// Seeds the producers' cached gating value, which would otherwise start at
+/// the initial gating sequence and let claims skip the credit check.
+fn credited_minimum_initial(watermark: i64, buffer_size: usize) -> i64 {
+    INITIAL_VALUE.min(watermark - buffer_size as i64)
+}
+
+/// Check that a claim up to `next` stays within [`MAX_SEQUENCE`](constants::MAX_SEQUENCE).
+///
+/// The branch is never taken in practice, so it costs a predictable compare per claim.
+#[inline(always)]
+fn check_exhausted(next: i64) -> Result<(), ClaimError> {
+    match next > constants::MAX_SEQUENCE {
+        true => Err(ClaimError::Exhausted),
+        false => Ok(()),
+    }
+}
+
+/// Close the channel of `coordinator` once its sequences are used up, and
+/// report the claim as failing on a closed channel.
+#[cold]
+fn close_exhausted(coordinator: &Coordinator) -> ClaimError {
+    coordinator.close(Some(Arc::new(SequencesExhausted)));
+    ClaimError::Closed
+}
+
 /// Sequencer for a **single producer** scenario.
 ///
 /// Uses a local cursor and gating sequences to coordinate with consumers.
@@ -92,6 +423,10 @@ pub struct SingleProducerSequencer {
     buffer_size: i64,
     cursor_sequence: Sequence,
     gating_sequence: Sequence,
+    gating_sequences: Arc<GatingSequences>,
+    credits: Option<Sequence>,
+    /// Ranges published ahead of an earlier claim, see [`publish`](Self::publish).
+    held: Mutex<Vec<(i64, i64)>>,
 }
 
 impl SingleProducerSequencer {
@@ -103,33 +438,117 @@ impl SingleProducerSequencer {
             buffer_size: buffer_size as i64,
             cursor_sequence: Sequence::default(),
             gating_sequence: Sequence::default(),
+            gating_sequences: Arc::new(GatingSequences::new()),
+            credits: None,
+            held: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a new single-producer sequencer whose producer may only claim
+    /// `initial_credits` sequences until consumers [`grant`](Sequencer::grant) more.
+    pub fn with_credits(buffer_size: usize, initial_credits: usize) -> Self {
+        let watermark: i64 = initial_credits as i64 - 1;
+        Self {
+            cached: Sequence::new(credited_minimum_initial(watermark, buffer_size)),
+            credits: Some(Sequence::new(watermark)),
+            ..Self::new(buffer_size)
+        }
+    }
+}
+
+impl SingleProducerSequencer {
+    /// Publish `[low, high]`, or hold it back until every sequence below it
+    /// is published.
+    ///
+    /// A single-producer cursor publishes every sequence below it at once, so
+    /// moving it over a range while an earlier claimed range is still unwritten
+    /// would expose uninitialized slots to consumers. Sends publish their claim
+    /// right away, in order, but reserved slots and claimed guards are
+    /// published whenever their owner is done with them, so a range that does
+    /// not directly follow the cursor is parked in `held` instead, and the
+    /// cursor moves over it once the ranges before it are published.
+    #[inline(always)]
+    fn publish(&self, low: i64, high: i64) {
+        sched::before_publish(low, high);
+        if low == self.cursor_sequence.get_relaxed() + 1 && high == self.sequence.get_relaxed() {
+            ordered!(
+                Publish,
+                Release,
+                Release,
+                self.cursor_sequence.set_release(high)
+            );
+            return;
+        }
+        self.publish_held(low, high);
+    }
+
+    /// Slow path of [`publish`](Self::publish), taken while claims are
+    /// outstanding below or above `[low, high]`.
+    #[cold]
+    fn publish_held(&self, low: i64, high: i64) {
+        let mut held = self.held.lock().unwrap();
+        if low != self.cursor_sequence.get_relaxed() + 1 {
+            held.push((low, high));
+            return;
         }
+        let mut high: i64 = high;
+        while let Some(index) = held.iter().position(|&(next, _)| next == high + 1) {
+            high = held.swap_remove(index).1;
+        }
+        ordered!(
+            Publish,
+            Release,
+            Release,
+            self.cursor_sequence.set_release(high)
+        );
     }
 }
 
 impl Sequencer for SingleProducerSequencer {
-    fn next_n(&self, n: usize, coordinator: &Coordinator) -> i64 {
+    fn next_n(&self, n: usize, coordinator: &Coordinator) -> Result<i64, ClaimError> {
         let next: i64 = self.sequence.get_relaxed() + n as i64;
+        check_exhausted(next).map_err(|_| close_exhausted(coordinator))?;
         let wrap_point: i64 = next - self.buffer_size;
 
         if wrap_point > self.cached.get_relaxed() {
-            self.cached
-                .set_relaxed(self.wait(&self.gating_sequence, wrap_point, coordinator));
+            self.cached.set_relaxed(self.wait(wrap_point, coordinator)?);
+        }
+
+        self.sequence.set_relaxed(next);
+        fence(Ordering::Release);
+        sched::after_claim(next);
+        Ok(next)
+    }
+
+    fn try_next_n_bounded(&self, n: usize, _: usize) -> Result<i64, ClaimError> {
+        let next: i64 = self.sequence.get_relaxed() + n as i64;
+        check_exhausted(next)?;
+        let wrap_point: i64 = next - self.buffer_size;
+
+        if wrap_point > self.cached.get_relaxed() {
+            let gating: i64 = self.get_gating_minimum_acquire();
+            self.cached.set_relaxed(gating);
+            if wrap_point > gating {
+                return Err(ClaimError::Full);
+            }
         }
 
         self.sequence.set_relaxed(next);
-        next
+        fence(Ordering::Release);
+        sched::after_claim(next);
+        Ok(next)
     }
 
     fn publish_cursor_sequence(&self, sequence: i64) {
-        self.cursor_sequence.set_release(sequence);
+        self.publish(sequence, sequence);
     }
 
-    fn publish_cursor_sequence_range(&self, _: i64, high: i64) {
-        self.cursor_sequence.set_release(high)
+    fn publish_cursor_sequence_range(&self, low: i64, high: i64) {
+        self.publish(low, high);
     }
 
     fn publish_gating_sequence(&self, sequence: i64) {
+        sched::before_gating_publish(sequence);
         self.advance_gating_sequence(&self.gating_sequence, sequence);
     }
 
@@ -138,26 +557,91 @@ impl Sequencer for SingleProducerSequencer {
     }
 
     fn get_cursor_sequence_acquire(&self) -> i64 {
-        self.cursor_sequence.get_acquire()
+        ordered!(
+            Publish,
+            Acquire,
+            Acquire,
+            self.cursor_sequence.get_acquire()
+        )
+    }
+
+    fn get_claimed_sequence_acquire(&self) -> i64 {
+        self.sequence.get_acquire()
     }
 
     fn get_gating_sequence_relaxed(&self) -> i64 {
         self.gating_sequence.get_relaxed()
     }
+
+    fn get_gating_minimum_acquire(&self) -> i64 {
+        let gating: i64 = self
+            .gating_sequences
+            .minimum(|| ordered!(Gating, Acquire, Acquire, self.gating_sequence.get_acquire()));
+        credited_minimum(gating, &self.credits, self.buffer_size)
+    }
+
+    fn gating_sequences(&self) -> &Arc<GatingSequences> {
+        &self.gating_sequences
+    }
+
+    fn grant(&self, n: usize) {
+        if let Some(watermark) = &self.credits {
+            watermark.fetch_add_volatile(n as i64);
+        }
+    }
+
+    fn rebase(&self) {
+        let consumed: i64 = self.cursor_sequence.get_relaxed() - INITIAL_VALUE;
+        self.cached
+            .set_relaxed(rebase_credits(&self.credits, consumed, self.buffer_size));
+        self.sequence.set_relaxed(INITIAL_VALUE);
+        self.gating_sequence.set_relaxed(INITIAL_VALUE);
+        self.gating_sequences.rebase();
+        self.held.lock().unwrap().clear();
+        self.cursor_sequence.set_release(INITIAL_VALUE);
+    }
+
+    fn try_unclaim(&self, low: i64, high: i64) -> bool {
+        if self.sequence.get_relaxed() != high {
+            return false;
+        }
+        self.sequence.set_relaxed(low - 1);
+        true
+    }
+
+    #[cfg(feature = "mp")]
+    fn to_multi_producer(&self) -> Option<Box<dyn Sequencer>> {
+        let buffer_size = self.buffer_size as usize;
+        let sequencer = match &self.credits {
+            Some(watermark) => {
+                let credits = watermark.get_relaxed() - INITIAL_VALUE;
+                MultiProducerSequencer::with_credits(buffer_size, credits as usize)
+            }
+            None => MultiProducerSequencer::new(buffer_size),
+        };
+        Some(Box::new(MultiProducerSequencer {
+            gating_sequences: self.gating_sequences.clone(),
+            ..sequencer
+        }))
+    }
 }
 
 /// Sequencer for **multiple producers** scenario.
 ///
 /// Coordinates multiple producers using an availability buffer to safely
 /// publish sequences without overwriting each other's data.
+#[cfg(feature = "mp")]
 pub struct MultiProducerSequencer {
     buffer_size: i64,
     cached: Sequence,
     cursor_sequence: Sequence,
     gating_sequence: Sequence,
+    gating_sequences: Arc<GatingSequences>,
+    credits: Option<Sequence>,
     availability_buffer: AvailabilityBuffer,
 }
 
+#[cfg(feature = "mp")]
 impl MultiProducerSequencer {
     /// Create a new multi-producer sequencer with the specified buffer size.
     pub fn new(buffer_size: usize) -> Self {
@@ -166,34 +650,82 @@ impl MultiProducerSequencer {
             cached: Sequence::default(),
             cursor_sequence: Sequence::default(),
             gating_sequence: Sequence::default(),
+            gating_sequences: Arc::new(GatingSequences::new()),
+            credits: None,
             availability_buffer: AvailabilityBuffer::new(buffer_size),
         }
     }
+
+    /// Create a new multi-producer sequencer whose producers may only claim
+    /// `initial_credits` sequences until consumers [`grant`](Sequencer::grant) more.
+    pub fn with_credits(buffer_size: usize, initial_credits: usize) -> Self {
+        let watermark: i64 = initial_credits as i64 - 1;
+        Self {
+            cached: Sequence::new(credited_minimum_initial(watermark, buffer_size)),
+            credits: Some(Sequence::new(watermark)),
+            ..Self::new(buffer_size)
+        }
+    }
 }
 
+#[cfg(feature = "mp")]
 impl Sequencer for MultiProducerSequencer {
-    fn next_n(&self, n: usize, coordinator: &Coordinator) -> i64 {
+    fn next_n(&self, n: usize, coordinator: &Coordinator) -> Result<i64, ClaimError> {
+        // Sequences are only claimed once their slots are free, so a producer
+        // that gives up waiting on a closed channel leaves no claimed sequence
+        // behind that consumers would stall on.
+        loop {
+            match self.try_next_n(n) {
+                Err(ClaimError::Full) => {}
+                Err(ClaimError::Exhausted) => return Err(close_exhausted(coordinator)),
+                claimed => return claimed,
+            }
+            let wrap_point: i64 = self.cursor_sequence.get_acquire() + n as i64 - self.buffer_size;
+            self.cached.set_relaxed(self.wait(wrap_point, coordinator)?);
+        }
+    }
+
+    fn try_next_n_bounded(&self, n: usize, max_retries: usize) -> Result<i64, ClaimError> {
         let n: i64 = n as i64;
-        let next: i64 = self.cursor_sequence.fetch_add_volatile(n) + n;
-        let wrap_point: i64 = next - self.buffer_size;
+        for _ in 0..=max_retries {
+            let current: i64 = self.cursor_sequence.get_acquire();
+            let next: i64 = current + n;
+            check_exhausted(next)?;
+            let wrap_point: i64 = next - self.buffer_size;
+
+            if wrap_point > self.cached.get_relaxed() {
+                let gating: i64 = self.get_gating_minimum_acquire();
+                self.cached.set_relaxed(gating);
+                if wrap_point > gating {
+                    return Err(ClaimError::Full);
+                }
+            }
 
-        if wrap_point > self.cached.get_relaxed() {
-            self.cached
-                .set_relaxed(self.wait(&self.gating_sequence, wrap_point, coordinator));
+            sched::before_try_claim(next);
+            if self
+                .cursor_sequence
+                .compare_and_exchange_weak_volatile(current, next)
+            {
+                fence(Ordering::Release);
+                sched::after_claim(next);
+                return Ok(next);
+            }
         }
-
-        next
+        Err(ClaimError::Contended)
     }
 
     fn publish_cursor_sequence(&self, sequence: i64) {
+        sched::before_publish(sequence, sequence);
         self.availability_buffer.set(sequence);
     }
 
     fn publish_cursor_sequence_range(&self, low: i64, high: i64) {
+        sched::before_publish(low, high);
         self.availability_buffer.set_range(low, high);
     }
 
     fn publish_gating_sequence(&self, sequence: i64) {
+        sched::before_gating_publish(sequence);
         self.advance_gating_sequence(&self.gating_sequence, sequence);
     }
 
@@ -205,9 +737,56 @@ impl Sequencer for MultiProducerSequencer {
         self.cursor_sequence.get_acquire()
     }
 
+    fn get_claimed_sequence_acquire(&self) -> i64 {
+        self.cursor_sequence.get_acquire()
+    }
+
     fn get_gating_sequence_relaxed(&self) -> i64 {
         self.gating_sequence.get_relaxed()
     }
+
+    fn get_gating_minimum_acquire(&self) -> i64 {
+        let gating: i64 = self
+            .gating_sequences
+            .minimum(|| ordered!(Gating, Acquire, Acquire, self.gating_sequence.get_acquire()));
+        credited_minimum(gating, &self.credits, self.buffer_size)
+    }
+
+    fn gating_sequences(&self) -> &Arc<GatingSequences> {
+        &self.gating_sequences
+    }
+
+    fn grant(&self, n: usize) {
+        if let Some(watermark) = &self.credits {
+            watermark.fetch_add_volatile(n as i64);
+        }
+    }
+
+    fn rebase(&self) {
+        let consumed: i64 = self.cursor_sequence.get_relaxed() - INITIAL_VALUE;
+        self.cached
+            .set_relaxed(rebase_credits(&self.credits, consumed, self.buffer_size));
+        self.availability_buffer.reset();
+        self.gating_sequence.set_relaxed(INITIAL_VALUE);
+        self.gating_sequences.rebase();
+        self.cursor_sequence.set_release(INITIAL_VALUE);
+    }
+
+    fn try_unclaim(&self, low: i64, high: i64) -> bool {
+        while self.cursor_sequence.get_acquire() == high {
+            if self
+                .cursor_sequence
+                .compare_and_exchange_weak_volatile(high, low - 1)
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_multi_producer(&self) -> bool {
+        true
+    }
 }
 
 // SAFETY: Sequencers are thread-safe because all internal state modifications
@@ -216,6 +795,144 @@ unsafe impl Send for SingleProducerSequencer {}
 
 unsafe impl Sync for SingleProducerSequencer {}
 
+#[cfg(feature = "mp")]
 unsafe impl Send for MultiProducerSequencer {}
 
+#[cfg(feature = "mp")]
 unsafe impl Sync for MultiProducerSequencer {}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvResult, spsc_with_credits};
+    use crate::constants::MAX_SEQUENCE;
+    use crate::coordinator::{ConsumerWaitStrategyKind, Coordinator, ProducerWaitStrategyKind};
+    use crate::errors::{SequencesExhausted, TrySendError};
+    use crate::sequencer::{ClaimError, Sequencer, SingleProducerSequencer};
+    use crate::testing::{filled_spsc, yielding_spsc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_claims_past_the_sequence_cap_close_the_channel() {
+        let sequencer = SingleProducerSequencer::new(4);
+        for sequence in [
+            &sequencer.sequence,
+            &sequencer.cached,
+            &sequencer.cursor_sequence,
+            &sequencer.gating_sequence,
+        ] {
+            sequence.set_relaxed(MAX_SEQUENCE - 2);
+        }
+        let coordinator = Coordinator::new(
+            ProducerWaitStrategyKind::Spinning,
+            ConsumerWaitStrategyKind::Spinning,
+            0,
+            None,
+        );
+
+        assert_eq!(sequencer.try_next_n(3), Err(ClaimError::Exhausted));
+        assert_eq!(sequencer.next_n(2, &coordinator), Ok(MAX_SEQUENCE));
+        assert!(!coordinator.is_closed());
+        assert_eq!(sequencer.next(&coordinator), Err(ClaimError::Closed));
+        let reason = coordinator.close_reason().unwrap();
+        assert!(reason.downcast_ref::<SequencesExhausted>().is_some());
+    }
+
+    #[cfg(feature = "mp")]
+    #[test]
+    fn test_producers_giving_up_on_a_closed_channel_leave_no_claim_behind() {
+        use crate::sequencer::MultiProducerSequencer;
+
+        let sequencer = MultiProducerSequencer::new(4);
+        let coordinator = Coordinator::new(
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+            0,
+            None,
+        );
+        assert_eq!(sequencer.try_next_n(4), Ok(3));
+        sequencer.publish_cursor_sequence_range(0, 3);
+
+        thread::scope(|scope| {
+            let blocked = scope.spawn(|| sequencer.next_n(3, &coordinator));
+            thread::sleep(Duration::from_millis(20));
+            // A later claim that fits is not held up by the blocked one.
+            sequencer.publish_gating_sequence(0);
+            assert_eq!(sequencer.try_next(), Ok(4));
+            sequencer.publish_cursor_sequence(4);
+            coordinator.close(None);
+            assert_eq!(blocked.join().unwrap(), Err(ClaimError::Closed));
+        });
+        assert_eq!(sequencer.get_claimed_sequence_acquire(), 4);
+        assert_eq!(sequencer.get_highest(1, 4), 4);
+    }
+
+    #[test]
+    fn test_single_producer_holds_ranges_published_ahead_of_earlier_claims() {
+        let sequencer = SingleProducerSequencer::new(8);
+        assert_eq!(sequencer.try_next_n(2), Ok(1));
+        assert_eq!(sequencer.try_next_n(2), Ok(3));
+        assert_eq!(sequencer.try_next(), Ok(4));
+
+        sequencer.publish_cursor_sequence(4);
+        sequencer.publish_cursor_sequence_range(2, 3);
+        assert_eq!(sequencer.get_cursor_sequence_acquire(), -1);
+        sequencer.publish_cursor_sequence_range(0, 1);
+        assert_eq!(sequencer.get_cursor_sequence_acquire(), 4);
+
+        assert_eq!(sequencer.try_next(), Ok(5));
+        sequencer.publish_cursor_sequence(5);
+        assert_eq!(sequencer.get_cursor_sequence_acquire(), 5);
+    }
+
+    #[test]
+    fn test_registered_gating_sequences_gate_producers() {
+        let (tx, rx) = filled_spsc::<u32>(4, 0..2);
+        let late = rx.add_gating_sequence().unwrap();
+        assert_eq!(late.get(), 1);
+        assert_eq!(rx.try_recv_batch(4, &drop), RecvResult::Processed(2));
+
+        tx.send_n(2..6).unwrap();
+        assert_eq!(rx.try_recv_batch(4, &drop), RecvResult::Processed(4));
+        assert!(matches!(tx.try_send(6), Err(TrySendError::Full(6))));
+        let seen: Vec<_> = (late.get() + 1..=5)
+            .map(|sequence| rx.peek(sequence))
+            .collect();
+        assert_eq!(seen, [2, 3, 4, 5].map(Some));
+
+        late.set(3);
+        tx.send_n(6..8).unwrap();
+        assert!(matches!(tx.try_send(8), Err(TrySendError::Full(8))));
+
+        let (_other_tx, other_rx) = yielding_spsc::<u32>(4);
+        let foreign = other_rx.add_gating_sequence().unwrap();
+        assert!(!rx.remove_gating_sequence(foreign));
+        assert!(rx.remove_gating_sequence(late));
+        assert_eq!(rx.try_recv_batch(4, &drop), RecvResult::Processed(2));
+        tx.send_n(8..12).unwrap();
+    }
+
+    #[test]
+    fn test_exhausted_credits_block_producers_until_granted() {
+        let (tx, rx) = spsc_with_credits::<u32>(
+            8,
+            2,
+            ProducerWaitStrategyKind::Blocking,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send_n(0..2).unwrap();
+        assert!(matches!(tx.try_send(2), Err(TrySendError::Full(2))));
+        let producer = thread::spawn(move || tx.send_n(2..5).unwrap());
+
+        // Free slots alone do not admit the producer.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx.drain_all(), [0, 1]);
+        thread::sleep(Duration::from_millis(50));
+        assert!(!producer.is_finished());
+        assert!(rx.is_empty());
+
+        rx.grant(3);
+        producer.join().unwrap();
+        assert_eq!(rx.drain_all(), [2, 3, 4]);
+    }
+}
@@ -0,0 +1,467 @@
+//! Keyed channels with a ring buffer per shard.
+//!
+//! A [`sharded_mpsc`] channel spreads items over several multi-producer rings
+//! by key: the [`ShardedSender`] hashes the key of every item to a shard, so
+//! all items of a key travel through the same ring and reach the consumer of
+//! that shard in the order they were sent, while items of different keys are
+//! processed in parallel by the consumers of the other shards.
+//!
+//! Keys are mapped to shards with jump consistent hashing, which moves as few
+//! keys as possible to other shards when the number of shards changes between
+//! deployments, and maps a key to the same shard in every process.
+//!
+//! A [`ConsumerGroup`] scales the consumers of the shards independently of
+//! their number: every member that [joins](ConsumerGroup::join) the group is
+//! assigned a share of the shards, and the shards are reassigned whenever a
+//! member joins or leaves. A shard is polled by one member at a time, so the
+//! items of a key are still handled one after the other and in order, even
+//! while their shard moves from one member to another.
+
+use crate::channels::{Receiver, RecvState, Sender, mpsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use crate::errors::{SendError, TrySendError};
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSnapshot;
+use std::cell::RefCell;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Map the hash of a key to one of `shards` shards, see "A Fast, Minimal
+/// Memory, Consistent Hash Algorithm" by Lamping and Veach.
+fn jump_hash(mut hash: u64, shards: usize) -> usize {
+    let (mut shard, mut next) = (-1i64, 0i64);
+    while next < shards as i64 {
+        shard = next;
+        hash = hash.wrapping_mul(2862933555777941757).wrapping_add(1);
+        next = ((shard + 1) as f64 * ((1u64 << 31) as f64 / ((hash >> 33) + 1) as f64)) as i64;
+    }
+    shard as usize
+}
+
+/// The sending half of a sharded channel, created by [`sharded_mpsc`].
+///
+/// Routes items with keys of type `K` to the shard of their key.
+pub struct ShardedSender<K: ?Sized, T> {
+    shards: Vec<Sender<T>>,
+    key: PhantomData<fn(&K)>,
+}
+
+impl<K: Hash + ?Sized, T> ShardedSender<K, T> {
+    /// Send a single value to the shard of `key`.
+    ///
+    /// Waits according to the producer wait strategy while that shard is
+    /// full, whatever the other shards hold.
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the shard is closed.
+    pub fn send_keyed(&self, key: &K, value: T) -> Result<(), SendError<T>> {
+        self.shards[self.shard_of(key)].send(value)
+    }
+
+    /// Try to send a single value to the shard of `key` without waiting for
+    /// free space.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the shard has no free slot.
+    /// - [`TrySendError::Closed`] if the shard is closed.
+    pub fn try_send_keyed(&self, key: &K, value: T) -> Result<(), TrySendError<T>> {
+        self.shards[self.shard_of(key)].try_send(value)
+    }
+
+    /// Returns the index of the shard, and of its receiver, that the items of
+    /// `key` are sent to.
+    pub fn shard_of(&self, key: &K) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        jump_hash(hasher.finish(), self.shards.len())
+    }
+}
+
+impl<K: ?Sized, T> ShardedSender<K, T> {
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of items waiting in every shard together.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(Sender::len).sum()
+    }
+
+    /// Returns `true` if no shard holds an item.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(Sender::is_empty)
+    }
+
+    /// Close every shard, starting a shutdown of the whole channel.
+    ///
+    /// Every consumer still receives the items published to its shard before,
+    /// and then reports [`RecvState::Disconnected`].
+    /// Returns `false` if every shard was already closed.
+    pub fn close(&self) -> bool {
+        self.shards
+            .iter()
+            .fold(false, |closed, shard| shard.close() | closed)
+    }
+
+    /// Returns `true` if any shard has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.shards.iter().any(Sender::is_closed)
+    }
+
+    /// Returns the counters of every shard added up.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.shards.iter().map(Sender::metrics).sum()
+    }
+}
+
+impl<K: ?Sized, T> Clone for ShardedSender<K, T> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+            key: PhantomData,
+        }
+    }
+}
+
+/// Create a **multi-producer** channel of `shards` rings with `capacity`
+/// slots each, whose items are routed by key.
+///
+/// Returns the sender and one receiver per shard, in shard order, each of
+/// which is meant for a consumer of its own or for a [`ConsumerGroup`], see
+/// the [module documentation](self).
+///
+/// # Panics
+/// Panics if `shards` is zero, and if `capacity` is not a valid buffer size.
+pub fn sharded_mpsc<K: Hash + ?Sized, T>(
+    shards: usize,
+    capacity: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (ShardedSender<K, T>, Vec<Receiver<T>>) {
+    assert!(shards > 0, "a sharded channel needs a shard");
+    let (senders, receivers) = (0..shards).map(|_| mpsc(capacity, pw, cw)).unzip();
+    let sender = ShardedSender {
+        shards: senders,
+        key: PhantomData,
+    };
+    (sender, receivers)
+}
+
+/// A shard consumed by a [`ConsumerGroup`].
+struct Partition<T> {
+    receiver: Receiver<T>,
+    /// Held by the member polling the shard, so that a member the shard was
+    /// just assigned to waits for the batch of the previous one to finish.
+    polling: Mutex<()>,
+    drained: AtomicBool,
+}
+
+/// The members of a [`ConsumerGroup`], in the order they joined.
+struct Membership {
+    members: Vec<u64>,
+    next_id: u64,
+}
+
+/// The state shared by a [`ConsumerGroup`] and its members.
+struct Group<T> {
+    partitions: Vec<Partition<T>>,
+    membership: Mutex<Membership>,
+    /// Incremented on every rebalance, so that members notice it without
+    /// taking the lock.
+    generation: AtomicU64,
+}
+
+impl<T> Group<T> {
+    /// Returns the shards of the member with the given id, together with the
+    /// generation of the assignment.
+    fn assignment(&self, id: u64) -> (u64, Vec<usize>) {
+        let membership = self.membership.lock().unwrap();
+        let count = membership.members.len();
+        let assigned = match membership.members.iter().position(|&member| member == id) {
+            Some(index) => (index..self.partitions.len()).step_by(count).collect(),
+            None => Vec::new(),
+        };
+        (self.generation.load(Ordering::Acquire), assigned)
+    }
+}
+
+/// Consumers sharing the shards of a sharded channel, see the
+/// [module documentation](self).
+///
+/// Clones refer to the same group.
+pub struct ConsumerGroup<T> {
+    group: Arc<Group<T>>,
+}
+
+impl<T> ConsumerGroup<T> {
+    /// Create a group consuming the shards of `receivers`, as returned by
+    /// [`sharded_mpsc`].
+    ///
+    /// # Panics
+    /// Panics if `receivers` is empty.
+    pub fn new(receivers: Vec<Receiver<T>>) -> Self {
+        assert!(!receivers.is_empty(), "a consumer group needs a shard");
+        let partitions = receivers
+            .into_iter()
+            .map(|receiver| Partition {
+                receiver,
+                polling: Mutex::new(()),
+                drained: AtomicBool::new(false),
+            })
+            .collect();
+        let group = Group {
+            partitions,
+            membership: Mutex::new(Membership {
+                members: Vec::new(),
+                next_id: 0,
+            }),
+            generation: AtomicU64::new(0),
+        };
+        Self {
+            group: Arc::new(group),
+        }
+    }
+
+    /// Add a member to the group, rebalancing the shards over every member.
+    ///
+    /// The member leaves the group, and its shards are given to the others,
+    /// when it is dropped.
+    pub fn join(&self) -> GroupMember<T> {
+        let mut membership = self.group.membership.lock().unwrap();
+        let id = membership.next_id;
+        membership.next_id += 1;
+        membership.members.push(id);
+        self.group.generation.fetch_add(1, Ordering::Release);
+        GroupMember {
+            group: self.group.clone(),
+            id,
+            assignment: RefCell::new((u64::MAX, Vec::new())),
+        }
+    }
+
+    /// Returns the number of members.
+    pub fn members(&self) -> usize {
+        self.group.membership.lock().unwrap().members.len()
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.group.partitions.len()
+    }
+}
+
+impl<T> Clone for ConsumerGroup<T> {
+    fn clone(&self) -> Self {
+        Self {
+            group: self.group.clone(),
+        }
+    }
+}
+
+/// A member of a [`ConsumerGroup`], consuming the shards currently assigned
+/// to it.
+pub struct GroupMember<T> {
+    group: Arc<Group<T>>,
+    id: u64,
+    /// The generation the assigned shards were computed for.
+    assignment: RefCell<(u64, Vec<usize>)>,
+}
+
+impl<T> GroupMember<T> {
+    /// Run a single round over the shards assigned to the member, without
+    /// waiting.
+    ///
+    /// Receives up to `batch_size` items from every shard and invokes
+    /// `handler` with the shard index and the item. Shards still being polled
+    /// by the member they were assigned to before a rebalance are skipped
+    /// until that member is done. Returns [`RecvState::Disconnected`] once
+    /// every shard of the group has been drained, so also members without
+    /// shards find out when to stop.
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(usize, T),
+    {
+        self.refresh();
+        let mut received = false;
+        for &shard in &self.assignment.borrow().1 {
+            let partition = &self.group.partitions[shard];
+            if partition.drained.load(Ordering::Acquire) {
+                continue;
+            }
+            let Ok(_polling) = partition.polling.try_lock() else {
+                continue;
+            };
+            let (_, state) = partition
+                .receiver
+                .recv_up_to(batch_size, &|item| handler(shard, item));
+            match state {
+                RecvState::Received => received = true,
+                RecvState::Disconnected => partition.drained.store(true, Ordering::Release),
+                _ => {}
+            }
+        }
+
+        let drained = || {
+            self.group
+                .partitions
+                .iter()
+                .all(|partition| partition.drained.load(Ordering::Acquire))
+        };
+        match received {
+            true => RecvState::Received,
+            false if drained() => RecvState::Disconnected,
+            false => RecvState::Empty,
+        }
+    }
+
+    /// Returns the indices of the shards currently assigned to the member.
+    pub fn shards(&self) -> Vec<usize> {
+        self.refresh();
+        self.assignment.borrow().1.clone()
+    }
+
+    /// Recompute the assigned shards if the group was rebalanced.
+    fn refresh(&self) {
+        let generation = self.group.generation.load(Ordering::Acquire);
+        if self.assignment.borrow().0 != generation {
+            *self.assignment.borrow_mut() = self.group.assignment(self.id);
+        }
+    }
+}
+
+impl<T> Drop for GroupMember<T> {
+    fn drop(&mut self) {
+        let mut membership = self.group.membership.lock().unwrap();
+        membership.members.retain(|&member| member != self.id);
+        self.group.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::RecvState;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::sharded::{ConsumerGroup, jump_hash, sharded_mpsc};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn test_items_of_a_key_stay_in_order_on_one_shard() {
+        let (tx, rxs) = sharded_mpsc::<str, (String, u32)>(
+            4,
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let keys: Vec<String> = (0..12).map(|key| format!("key-{key}")).collect();
+        let consumers: Vec<_> = rxs
+            .into_iter()
+            .enumerate()
+            .map(|(shard, rx)| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    let received = RefCell::new(HashMap::<String, Vec<u32>>::new());
+                    while rx.recv(8, &|(key, value)| {
+                        assert_eq!(tx.shard_of(&key), shard);
+                        received.borrow_mut().entry(key).or_default().push(value);
+                    }) != RecvState::Disconnected
+                    {}
+                    received.into_inner()
+                })
+            })
+            .collect();
+
+        for value in 0..50 {
+            for key in &keys {
+                tx.send_keyed(key, (key.clone(), value)).unwrap();
+            }
+        }
+        assert!(tx.close());
+        assert!(tx.send_keyed("key-0", (String::new(), 0)).is_err());
+
+        let mut seen = 0;
+        for consumer in consumers {
+            for values in consumer.join().unwrap().into_values() {
+                assert!(values.iter().copied().eq(0..50));
+                seen += 1;
+            }
+        }
+        assert_eq!(seen, keys.len());
+
+        // Growing from 4 to 5 shards only moves keys to the new shard.
+        for hash in 0..1000u64 {
+            let hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            let (before, after) = (jump_hash(hash, 4), jump_hash(hash, 5));
+            assert!(after == before || after == 4);
+        }
+    }
+
+    #[test]
+    fn test_consumer_groups_rebalance_shards_and_keep_key_order() {
+        let (tx, rxs) = sharded_mpsc::<u32, (u32, u32)>(
+            6,
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        let group = ConsumerGroup::new(rxs);
+        let received = Arc::new(Mutex::new(HashMap::<u32, Vec<u32>>::new()));
+        let spawn = |stop: Arc<AtomicBool>| {
+            let member = group.join();
+            let received = received.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Acquire)
+                    && member.recv(4, &|_, (key, value)| {
+                        received.lock().unwrap().entry(key).or_default().push(value);
+                    }) != RecvState::Disconnected
+                {
+                    thread::yield_now();
+                }
+            })
+        };
+
+        let never = Arc::new(AtomicBool::new(false));
+        let leaving = Arc::new(AtomicBool::new(false));
+        let first = spawn(never.clone());
+        let second = spawn(leaving.clone());
+        let send = |values: std::ops::Range<u32>| {
+            for value in values {
+                for key in 0..10 {
+                    tx.send_keyed(&key, (key, value)).unwrap();
+                }
+            }
+        };
+
+        send(0..100);
+        let third = spawn(never.clone());
+        assert_eq!(group.members(), 3);
+        send(100..200);
+        leaving.store(true, Ordering::Release);
+        second.join().unwrap();
+        assert_eq!(group.members(), 2);
+        send(200..300);
+
+        // An idle member is given shards and sees the group disconnect.
+        let idle = group.join();
+        assert_eq!(idle.shards(), vec![2, 5]);
+        drop(idle);
+        assert!(tx.close());
+        first.join().unwrap();
+        third.join().unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 10);
+        assert!(
+            received
+                .values()
+                .all(|values| values.iter().copied().eq(0..300))
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Slots sized for the small values of a message type.
+//!
+//! A ring buffer slot is as large as the largest value of its item type, so a
+//! message enum with a few huge variants makes every slot, and the cache lines
+//! the ring spans, as large as those. A spilling channel stores the values of
+//! a [`Spill`] type in their compact [`Inline`](Spill::Inline) form inside the
+//! slot, and moves the values that have no compact form to a box, taken from a
+//! pool of boxes the consumer hands back, so the large values neither grow the
+//! slots nor allocate once the pool is warm.
+//!
+//! Whether a value is stored inline is decided by [`Spill::into_inline`] on
+//! every send. [`spilling`] wraps the two halves of any channel of [`Slot`]s.
+
+use crate::channels::{PollOutcome, Receiver, RecvResult, Sender};
+use crate::errors::{SendError, TrySendError};
+use std::sync::{Arc, Mutex};
+
+/// A type with a compact form for its small values.
+pub trait Spill: Sized {
+    /// The form small values are stored in inside the slot.
+    type Inline;
+
+    /// Convert into the compact form, or hand the value back if it is too
+    /// large to be stored inline.
+    ///
+    /// # Errors
+    /// Returns the value itself if it is to be moved to a box.
+    fn into_inline(self) -> Result<Self::Inline, Self>;
+
+    /// Convert back from the compact form.
+    fn from_inline(inline: Self::Inline) -> Self;
+}
+
+/// A value in a slot of a spilling channel.
+enum Packed<T: Spill> {
+    Inline(T::Inline),
+    Spilled(Box<Option<T>>),
+}
+
+/// The item type of a spilling channel, holding a value either inline or
+/// in a box.
+pub struct Slot<T: Spill>(Packed<T>);
+
+/// The boxes of spilled values handed back by the consumers.
+struct SpillPool<T> {
+    boxes: Mutex<Vec<Box<Option<T>>>>,
+    limit: usize,
+}
+
+impl<T: Spill> SpillPool<T> {
+    fn pack(&self, value: T) -> Slot<T> {
+        match value.into_inline() {
+            Ok(inline) => Slot(Packed::Inline(inline)),
+            Err(value) => {
+                let pooled = self.boxes.lock().unwrap().pop();
+                let mut spilled = pooled.unwrap_or_else(|| Box::new(None));
+                *spilled = Some(value);
+                Slot(Packed::Spilled(spilled))
+            }
+        }
+    }
+
+    fn unpack(&self, slot: Slot<T>) -> T {
+        match slot.0 {
+            Packed::Inline(inline) => T::from_inline(inline),
+            Packed::Spilled(mut spilled) => {
+                let value = spilled.take().expect("a spilled slot holds its value");
+                let mut boxes = self.boxes.lock().unwrap();
+                if boxes.len() < self.limit {
+                    boxes.push(spilled);
+                }
+                value
+            }
+        }
+    }
+}
+
+/// The sending half of a spilling channel, created by [`spilling`].
+pub struct SpillSender<T: Spill> {
+    sender: Sender<Slot<T>>,
+    pool: Arc<SpillPool<T>>,
+}
+
+impl<T: Spill> SpillSender<T> {
+    /// Send a single value, inline or in a box of the pool.
+    ///
+    /// See [`Sender::send`].
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender
+            .send(self.pool.pack(value))
+            .map_err(|SendError::Closed(slot, reason)| {
+                SendError::Closed(self.pool.unpack(slot), reason)
+            })
+    }
+
+    /// Try to send a single value without waiting for free space.
+    ///
+    /// See [`Sender::try_send`].
+    ///
+    /// # Errors
+    /// Returns the value with the reason the send failed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.sender
+            .try_send(self.pool.pack(value))
+            .map_err(|error| match error {
+                TrySendError::Full(slot) => TrySendError::Full(self.pool.unpack(slot)),
+                TrySendError::WouldBlock(slot) => TrySendError::WouldBlock(self.pool.unpack(slot)),
+                TrySendError::Closed(slot, reason) => {
+                    TrySendError::Closed(self.pool.unpack(slot), reason)
+                }
+            })
+    }
+
+    /// Returns the number of boxes waiting in the pool to hold a large value.
+    pub fn pooled(&self) -> usize {
+        self.pool.boxes.lock().unwrap().len()
+    }
+
+    /// Close the channel, see [`Sender::close`].
+    pub fn close(&self) -> bool {
+        self.sender.close()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl<T: Spill> Clone for SpillSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+/// The receiving half of a spilling channel, created by [`spilling`].
+pub struct SpillReceiver<T: Spill> {
+    receiver: Receiver<Slot<T>>,
+    pool: Arc<SpillPool<T>>,
+}
+
+impl<T: Spill> SpillReceiver<T> {
+    /// Attempt to receive up to `batch_size` values, handing the boxes of
+    /// large values back to the pool.
+    ///
+    /// See [`Receiver::recv`].
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> PollOutcome
+    where
+        H: Fn(T),
+    {
+        self.receiver
+            .recv(batch_size, &|slot| handler(self.pool.unpack(slot)))
+    }
+
+    /// Receive up to `batch_size` values without ever waiting.
+    ///
+    /// See [`Receiver::try_recv_batch`].
+    pub fn try_recv_batch<H>(&self, batch_size: usize, handler: &H) -> RecvResult
+    where
+        H: Fn(T),
+    {
+        self.receiver
+            .try_recv_batch(batch_size, &|slot| handler(self.pool.unpack(slot)))
+    }
+
+    /// Close the channel, see [`Receiver::close`].
+    pub fn close(&self) -> bool {
+        self.receiver.close()
+    }
+
+    /// Close the channel because the consumer cannot continue, see
+    /// [`Receiver::close_with_error`].
+    pub fn close_with_error<E>(&self, reason: E) -> bool
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.receiver.close_with_error(reason)
+    }
+}
+
+impl<T: Spill> Clone for SpillReceiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            receiver: self.receiver.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+/// Turn the halves of a channel of [`Slot`]s into a spilling channel, keeping
+/// up to `pooled` boxes for large values around for reuse.
+///
+/// Works with channels of any topology, e.g. `spilling(mpsc(capacity, pw, cw), 16)`.
+pub fn spilling<T: Spill>(
+    (sender, receiver): (Sender<Slot<T>>, Receiver<Slot<T>>),
+    pooled: usize,
+) -> (SpillSender<T>, SpillReceiver<T>) {
+    let pool = Arc::new(SpillPool {
+        boxes: Mutex::new(Vec::with_capacity(pooled)),
+        limit: pooled,
+    });
+    let sender = SpillSender {
+        sender,
+        pool: pool.clone(),
+    };
+    (sender, SpillReceiver { receiver, pool })
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvState, spsc};
+    use crate::errors::TrySendError;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::spill::{Slot, Spill, spilling};
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::mem::size_of;
+
+    // The size difference is what a spilling channel is for.
+    #[allow(clippy::large_enum_variant)]
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Tick(u64),
+        Snapshot([u64; 64]),
+    }
+
+    impl Spill for Message {
+        type Inline = u64;
+
+        fn into_inline(self) -> Result<u64, Self> {
+            match self {
+                Message::Tick(tick) => Ok(tick),
+                snapshot => Err(snapshot),
+            }
+        }
+
+        fn from_inline(tick: u64) -> Self {
+            Message::Tick(tick)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Shutdown;
+
+    impl fmt::Display for Shutdown {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("shutdown")
+        }
+    }
+
+    impl std::error::Error for Shutdown {}
+
+    #[test]
+    fn test_large_values_spill_to_pooled_boxes() {
+        assert!(size_of::<Slot<Message>>() <= 16);
+        assert!(size_of::<Message>() > 512);
+
+        let (tx, rx) = spilling::<Message>(
+            spsc(
+                8,
+                ProducerWaitStrategyKind::Yielding,
+                ConsumerWaitStrategyKind::Yielding,
+            ),
+            2,
+        );
+        let message = |value: u64| match value % 10 {
+            0 => Message::Snapshot([value; 64]),
+            _ => Message::Tick(value),
+        };
+
+        let received = RefCell::new(Vec::new());
+        for round in 0..10 {
+            for value in round * 5..round * 5 + 5 {
+                tx.send(message(value)).unwrap();
+            }
+            while rx.recv(8, &|message| received.borrow_mut().push(message)) == RecvState::Received
+            {
+            }
+        }
+        assert!(received.into_inner().into_iter().eq((0..50).map(message)));
+        // A single box held every snapshot in turn.
+        assert_eq!(tx.pooled(), 1);
+
+        assert!(rx.close_with_error(Shutdown));
+        let rejected = tx.send(message(0)).unwrap_err().into_inner();
+        assert_eq!(rejected, message(0));
+        assert_eq!(tx.pooled(), 1);
+    }
+
+    #[test]
+    fn test_try_send_hands_back_spilled_values() {
+        let (tx, rx) = spilling::<Message>(
+            spsc(
+                2,
+                ProducerWaitStrategyKind::Yielding,
+                ConsumerWaitStrategyKind::Yielding,
+            ),
+            2,
+        );
+        tx.try_send(Message::Tick(1)).unwrap();
+        tx.try_send(Message::Snapshot([2; 64])).unwrap();
+        let Err(TrySendError::Full(rejected)) = tx.try_send(Message::Snapshot([3; 64])) else {
+            panic!("the buffer is full");
+        };
+        assert_eq!(rejected, Message::Snapshot([3; 64]));
+        // The box of the rejected value went back to the pool.
+        assert_eq!(tx.pooled(), 1);
+
+        let received = RefCell::new(Vec::new());
+        rx.try_recv_batch(2, &|message| received.borrow_mut().push(message));
+        assert_eq!(
+            received.into_inner(),
+            [Message::Tick(1), Message::Snapshot([2; 64])]
+        );
+        assert!(rx.close_with_error(Shutdown));
+        let Err(TrySendError::Closed(rejected, Some(reason))) = tx.try_send(Message::Tick(4))
+        else {
+            panic!("the channel is closed");
+        };
+        assert_eq!(
+            (rejected, reason.to_string()),
+            (Message::Tick(4), "shutdown".into())
+        );
+    }
+}
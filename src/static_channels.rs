@@ -0,0 +1,406 @@
+//! Statically typed channel variants.
+//!
+//! The channels of [`channels`](crate::channels) reach their sequencer and
+//! poller through trait objects, so one [`Sender`](crate::channels::Sender)
+//! type serves every configuration, at the cost of a virtual call per send
+//! and poll. The channels created here name their sequencer and poller types
+//! instead, which lets the compiler inline the whole publish and poll path.
+//!
+//! [`spsc_const`] goes further and fixes the capacity at compile time, so
+//! the slots are stored inline and the index arithmetic is constant-folded.
+//! Both run the same ring buffer, differing only in their [`Slots`].
+//!
+//! They cover the hot core of a channel only: sending, batched sending and
+//! receiving. Use the type-erased channels for anything else, such as
+//! cloning, flow control or claiming slots.
+
+use crate::channels::{RecvResult, RecvState};
+use crate::combinators::Receive;
+use crate::coordinator::{ConsumerWaitStrategyKind, Coordinator, ProducerWaitStrategyKind};
+use crate::errors::{SendError, SequencesExhausted, TrySendError};
+use crate::poller::SingleConsumerPoller;
+use crate::poller::State::{self, Idle};
+use crate::ring_buffer::RingBuffer;
+pub use crate::ring_buffer::{HeapSlots, InlineSlots, Slots};
+use crate::sequencer::{ClaimError, SingleProducerSequencer};
+use crate::topology::Topology;
+use crate::utils;
+use std::cell::Cell;
+use std::sync::Arc;
+
+/// The ring buffer of a statically typed SPSC channel.
+type SpscBuffer<T, B> = RingBuffer<T, SingleProducerSequencer, B>;
+
+/// The sending half of a statically typed SPSC channel, created by [`spsc_static`].
+///
+/// Behaves like [`Sender`](crate::channels::Sender) for the operations it
+/// offers. It cannot be cloned, since the channel has a single producer.
+pub struct StaticSender<T, B: Slots<T> = HeapSlots<T>> {
+    buffer: Arc<SpscBuffer<T, B>>,
+    coordinator: Arc<Coordinator>,
+}
+
+/// The receiving half of a statically typed SPSC channel, created by [`spsc_static`].
+///
+/// Behaves like [`Receiver`](crate::channels::Receiver) for the operations it
+/// offers. It cannot be cloned, since the channel has a single consumer.
+pub struct StaticReceiver<T, B: Slots<T> = HeapSlots<T>> {
+    buffer: Arc<SpscBuffer<T, B>>,
+    poller: SingleConsumerPoller,
+    coordinator: Arc<Coordinator>,
+}
+
+impl<T, B: Slots<T>> Drop for StaticSender<T, B> {
+    fn drop(&mut self) {
+        self.coordinator.remove_sender();
+    }
+}
+
+impl<T, B: Slots<T>> Drop for StaticReceiver<T, B> {
+    fn drop(&mut self) {
+        self.coordinator.remove_receiver();
+    }
+}
+
+impl<T, B: Slots<T>> StaticSender<T, B> {
+    /// Send a single value into the buffer.
+    ///
+    /// See [`Sender::send`](crate::channels::Sender::send).
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the value if the channel is closed,
+    /// including while this call waits for free space.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.coordinator.is_closed() {
+            return Err(self.closed(value));
+        }
+        self.buffer
+            .push(value, &self.coordinator, None)
+            .map_err(|value| self.closed(value))?;
+        self.notify(1);
+        Ok(())
+    }
+
+    /// Try to send a single value without waiting for free space.
+    ///
+    /// # Errors
+    /// - [`TrySendError::Full`] if the buffer has no free slot.
+    /// - [`TrySendError::Closed`] if the channel is closed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        if self.coordinator.is_closed() {
+            return Err(TrySendError::Closed(value, self.coordinator.close_reason()));
+        }
+        match self.buffer.try_push(value, None) {
+            Ok(()) => {
+                self.notify(1);
+                Ok(())
+            }
+            Err((ClaimError::Full, value)) => Err(TrySendError::Full(value)),
+            Err((ClaimError::Contended, value)) => Err(TrySendError::WouldBlock(value)),
+            Err((ClaimError::Closed, value)) => {
+                Err(TrySendError::Closed(value, self.coordinator.close_reason()))
+            }
+            Err((ClaimError::Exhausted, value)) => {
+                self.coordinator.close(Some(Arc::new(SequencesExhausted)));
+                Err(TrySendError::Closed(value, self.coordinator.close_reason()))
+            }
+        }
+    }
+
+    /// Send multiple values into the buffer in a batch.
+    ///
+    /// See [`Sender::send_n`](crate::channels::Sender::send_n).
+    ///
+    /// # Errors
+    /// Returns [`SendError::Closed`] with the untouched iterator if the channel
+    /// is closed, including while this call waits for free space.
+    pub fn send_n<I>(&self, items: I) -> Result<(), SendError<I::IntoIter>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let items = items.into_iter();
+        let len = items.len();
+        if self.coordinator.is_closed() {
+            return Err(self.closed(items));
+        }
+        self.buffer
+            .push_n(items, &self.coordinator, None)
+            .map_err(|items| self.closed(items))?;
+        self.notify(len);
+        Ok(())
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.buffer_size()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+
+    /// Wake the consumer after `published` items were published, as the notify policy allows.
+    #[inline(always)]
+    fn notify(&self, published: usize) {
+        self.coordinator
+            .notify_consumer(published, || self.buffer.backlog());
+    }
+
+    /// Build the error returned for a send on a closed channel.
+    fn closed<V>(&self, value: V) -> SendError<V> {
+        SendError::Closed(value, self.coordinator.close_reason())
+    }
+}
+
+impl<T, B: Slots<T>> StaticReceiver<T, B> {
+    /// Attempt to receive up to `batch_size` items.
+    ///
+    /// See [`Receiver::recv`](crate::channels::Receiver::recv).
+    pub fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        // Read before polling, so that everything the sender published is
+        // seen by the poll if it reports a disconnect.
+        let finished = self.coordinator.is_finished();
+        if self.poll(batch_size, handler) != Idle {
+            return RecvState::Received;
+        }
+        if finished {
+            return RecvState::Disconnected;
+        }
+        self.coordinator.consumer_wait();
+        RecvState::Empty
+    }
+
+    /// Receive up to `batch_size` items without ever waiting.
+    ///
+    /// See [`Receiver::try_recv_batch`](crate::channels::Receiver::try_recv_batch).
+    pub fn try_recv_batch<H>(&self, batch_size: usize, handler: &H) -> RecvResult
+    where
+        H: Fn(T),
+    {
+        let finished = self.coordinator.is_finished();
+        let processed = Cell::new(0);
+        self.poll(batch_size, &|item| {
+            processed.set(processed.get() + 1);
+            handler(item);
+        });
+
+        match processed.get() {
+            0 if finished => RecvResult::Disconnected,
+            0 => RecvResult::Empty,
+            n => RecvResult::Processed(n),
+        }
+    }
+
+    /// Continuously attempt to receive items until at least one batch is processed.
+    ///
+    /// See [`Receiver::blocking_recv`](crate::channels::Receiver::blocking_recv).
+    pub fn blocking_recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        loop {
+            match self.recv(batch_size, handler) {
+                RecvState::Empty => continue,
+                state => return state,
+            }
+        }
+    }
+
+    /// Returns the number of slots in the ring buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.buffer_size()
+    }
+
+    /// Returns `true` if the channel has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.coordinator.is_closed()
+    }
+
+    /// Poll up to `batch_size` items, noting progress to the consumer wait strategy.
+    #[inline(always)]
+    fn poll<H>(&self, batch_size: usize, handler: &H) -> State
+    where
+        H: Fn(T),
+    {
+        let state = self.buffer.poll(&self.poller, batch_size, handler);
+        if let State::Processing(consumed) = state {
+            self.coordinator.consumer_progress(consumed);
+        }
+        state
+    }
+}
+
+impl<T, B: Slots<T>> Receive for StaticReceiver<T, B> {
+    type Item = T;
+
+    fn recv<H>(&self, batch_size: usize, handler: &H) -> RecvState
+    where
+        H: Fn(T),
+    {
+        StaticReceiver::recv(self, batch_size, handler)
+    }
+}
+
+/// Create a statically typed **single-producer single-consumer (SPSC)** channel.
+///
+/// Works like [`spsc`](crate::channels::spsc), but the sequencer and poller
+/// of the returned halves are concrete types, so sends and receives are
+/// compiled without dynamic dispatch.
+///
+/// # Parameters
+/// - `buffer_size`: capacity of the underlying ring buffer.
+/// - `pw`: producer wait strategy.
+/// - `cw`: consumer wait strategy.
+pub fn spsc_static<T>(
+    buffer_size: usize,
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (StaticSender<T>, StaticReceiver<T>) {
+    utils::assert_buffer_size_is_equal_or_less_than_i64(buffer_size);
+    utils::assert_buffer_size_is_not_zero(buffer_size);
+    let topology = Topology::current();
+    let coordinator = Coordinator::new(pw, cw, topology.spin_budget(), None);
+    let coordinator = Arc::new(coordinator);
+    let sequencer = Box::new(SingleProducerSequencer::new(buffer_size));
+    let buffer = Arc::new(RingBuffer::new(
+        buffer_size,
+        topology.array_padding(),
+        sequencer,
+    ));
+
+    let sender = StaticSender {
+        buffer: buffer.clone(),
+        coordinator: coordinator.clone(),
+    };
+    let receiver = StaticReceiver {
+        buffer,
+        poller: SingleConsumerPoller::new(),
+        coordinator,
+    };
+    (sender, receiver)
+}
+
+/// The sending half of an SPSC channel of compile-time capacity `N`,
+/// created by [`spsc_const`].
+pub type ConstSender<T, const N: usize> = StaticSender<T, InlineSlots<T, N>>;
+
+/// The receiving half of an SPSC channel of compile-time capacity `N`,
+/// created by [`spsc_const`].
+pub type ConstReceiver<T, const N: usize> = StaticReceiver<T, InlineSlots<T, N>>;
+
+/// Create a **single-producer single-consumer (SPSC)** channel whose
+/// capacity `N` is a compile-time constant.
+///
+/// Works like [`spsc_static`], but the slots live inside the ring buffer
+/// instead of behind a pointer of their own, and every index and lap is
+/// computed from the constant `N`. Sizes known only at runtime keep using
+/// [`spsc_static`] or [`spsc`](crate::channels::spsc).
+///
+/// ```
+/// use channels_rs::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+/// use channels_rs::static_channels::spsc_const;
+///
+/// let (tx, rx) = spsc_const::<u64, 8192>(
+///     ProducerWaitStrategyKind::Spinning,
+///     ConsumerWaitStrategyKind::Spinning,
+/// );
+/// tx.send(1).unwrap();
+/// rx.recv(64, &|value| assert_eq!(value, 1));
+/// ```
+///
+/// Using a capacity `N` that is not a power of two fails to compile.
+pub fn spsc_const<T, const N: usize>(
+    pw: ProducerWaitStrategyKind,
+    cw: ConsumerWaitStrategyKind,
+) -> (ConstSender<T, N>, ConstReceiver<T, N>) {
+    let spin_budget = Topology::current().spin_budget();
+    let coordinator = Arc::new(Coordinator::new(pw, cw, spin_budget, None));
+    let sequencer = Box::new(SingleProducerSequencer::new(N));
+    let buffer = RingBuffer::new_inline(sequencer);
+    let sender = StaticSender {
+        buffer: buffer.clone(),
+        coordinator: coordinator.clone(),
+    };
+    let receiver = StaticReceiver {
+        buffer,
+        poller: SingleConsumerPoller::new(),
+        coordinator,
+    };
+    (sender, receiver)
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{RecvResult, RecvState};
+    use crate::errors::TrySendError;
+    use crate::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use crate::static_channels::{spsc_const, spsc_static};
+    use std::cell::RefCell;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_static_spsc_sends_receives_and_disconnects() {
+        let (tx, rx) = spsc_static::<u32>(
+            4,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        tx.send_n(0..3).unwrap();
+        tx.send(3).unwrap();
+        assert!(matches!(tx.try_send(4), Err(TrySendError::Full(4))));
+
+        let received = RefCell::new(Vec::new());
+        let handler = |item| received.borrow_mut().push(item);
+        assert_eq!(rx.try_recv_batch(2, &handler), RecvResult::Processed(2));
+        let sender = std::thread::spawn(move || tx.send_n(4..8).unwrap());
+        while received.borrow().len() < 8 {
+            rx.blocking_recv(4, &handler);
+        }
+        sender.join().unwrap();
+
+        assert_eq!(rx.recv(4, &handler), RecvState::Disconnected);
+        assert_eq!(*received.borrow(), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_const_spsc_wraps_and_drops_unreceived_items() {
+        let (tx, rx) = spsc_const::<Arc<u32>, 4>(
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Yielding,
+        );
+        assert_eq!((tx.capacity(), rx.capacity()), (4, 4));
+        let values: Vec<_> = (0..12).map(Arc::new).collect();
+        for value in &values[..4] {
+            tx.send(value.clone()).unwrap();
+        }
+        assert!(matches!(
+            tx.try_send(values[4].clone()),
+            Err(TrySendError::Full(_))
+        ));
+
+        let received = RefCell::new(Vec::new());
+        let handler = |item: Arc<u32>| received.borrow_mut().push(*item);
+        assert_eq!(rx.try_recv_batch(3, &handler), RecvResult::Processed(3));
+        let sent = values[4..10].to_vec();
+        let sender = std::thread::spawn(move || {
+            for value in sent {
+                tx.send(value).unwrap();
+            }
+        });
+        while received.borrow().len() < 7 {
+            rx.blocking_recv(1, &handler);
+        }
+        sender.join().unwrap();
+
+        // Three items are left in the buffer and dropped with it.
+        assert_eq!(*received.borrow(), (0..7).collect::<Vec<_>>());
+        assert_eq!(Arc::strong_count(&values[9]), 2);
+        drop(rx);
+        assert!(values.iter().all(|value| Arc::strong_count(value) == 1));
+    }
+}
@@ -0,0 +1,47 @@
+//! The atomics, spin loop hint, thread parking and thread locals the crate
+//! is built on.
+//!
+//! With the `loom` feature enabled they are replaced by the atomics of
+//! [loom](https://docs.rs/loom), which explores every interleaving and every
+//! weak memory outcome the orderings permit in the models of the concurrency
+//! test suite, and the spin loop hint lets loom run the other threads of a
+//! model while one spins. The mutex, parking and thread local used by the
+//! blocking wait strategies are replaced too, so loom reports a waiter that
+//! is never woken as a deadlock. Loom atomics only work inside a loom model,
+//! so that build is only good for running the suite, with
+//! `cargo test --features loom --lib`.
+
+#[cfg(not(feature = "loom"))]
+pub(crate) use std::hint::spin_loop;
+#[cfg(not(feature = "loom"))]
+pub(crate) use std::sync::Mutex;
+#[cfg(not(feature = "loom"))]
+#[cfg_attr(not(feature = "mp"), allow(unused_imports))]
+pub(crate) use std::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicI64, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering, fence,
+};
+#[cfg(not(feature = "loom"))]
+pub(crate) use std::thread::{Thread, current, park, park_timeout};
+#[cfg(not(feature = "loom"))]
+pub(crate) use std::thread_local;
+
+#[cfg(feature = "loom")]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(feature = "loom")]
+pub(crate) use loom::sync::Mutex;
+#[cfg(feature = "loom")]
+#[cfg_attr(not(feature = "mp"), allow(unused_imports))]
+pub(crate) use loom::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicI64, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering, fence,
+};
+#[cfg(feature = "loom")]
+pub(crate) use loom::thread::{Thread, current, park};
+#[cfg(feature = "loom")]
+pub(crate) use loom::thread_local;
+
+/// Loom has no clock, so a timed park returns right away, as a timed park
+/// is always allowed to.
+#[cfg(feature = "loom")]
+pub(crate) fn park_timeout(_: std::time::Duration) {
+    loom::thread::yield_now();
+}
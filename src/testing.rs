@@ -0,0 +1,47 @@
+//! Fixtures shared by the unit tests.
+
+use crate::channels::{Receiver, RecvState, Sender, spsc};
+use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use std::cell::RefCell;
+
+/// Create an SPSC channel of `capacity` slots whose producer and consumer spin.
+pub(crate) fn spinning_spsc<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    spsc(
+        capacity,
+        ProducerWaitStrategyKind::Spinning,
+        ConsumerWaitStrategyKind::Spinning,
+    )
+}
+
+/// Create an SPSC channel of `capacity` slots whose producer and consumer yield.
+pub(crate) fn yielding_spsc<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    spsc(
+        capacity,
+        ProducerWaitStrategyKind::Yielding,
+        ConsumerWaitStrategyKind::Yielding,
+    )
+}
+
+/// Create a [`yielding_spsc`] channel with `items` already sent.
+///
+/// # Panics
+/// Panics if `items` do not fit in the buffer.
+pub(crate) fn filled_spsc<T>(
+    capacity: usize,
+    items: impl IntoIterator<Item = T, IntoIter: ExactSizeIterator>,
+) -> (Sender<T>, Receiver<T>) {
+    let items = items.into_iter();
+    assert!(items.len() <= capacity, "items do not fit in the buffer");
+    let (tx, rx) = yielding_spsc(capacity);
+    tx.send_n(items).unwrap();
+    (tx, rx)
+}
+
+/// Receive up to `batch_size` items at a time until the channel disconnects,
+/// returning every item received.
+pub(crate) fn recv_all<T>(rx: &Receiver<T>, batch_size: usize) -> Vec<T> {
+    let received = RefCell::new(Vec::new());
+    while rx.recv(batch_size, &|item| received.borrow_mut().push(item)) != RecvState::Disconnected {
+    }
+    received.into_inner()
+}
@@ -0,0 +1,187 @@
+//! Deadlines for published events that await a response.
+//!
+//! Request/response and at-least-once delivery layers publish an event, wait
+//! for a matching completion, and act once it does not arrive in time. A
+//! [`TimeoutRegistry`] tracks the deadlines of such events by sequence: it
+//! holds one entry per sequence in a lap of the ring, like a timing wheel with
+//! a slot per ring position, so tracking and completing an event are a couple
+//! of atomic operations on an entry no other sequence of the lap shares.
+//! [`expire`](TimeoutRegistry::expire) scans the wheel and reports every event
+//! whose deadline passed without a completion.
+
+use crate::sync::{AtomicI64, AtomicU64, Ordering};
+use crate::utils::{CachePadded, Indexing};
+use std::time::{Duration, Instant};
+
+/// The sequence of an entry that tracks nothing.
+const FREE: i64 = -1;
+/// The sequence of an entry whose deadline is being written.
+const CLAIMED: i64 = -2;
+
+/// The deadline of a single tracked sequence.
+struct Entry {
+    sequence: AtomicI64,
+    /// Nanoseconds since the origin of the registry.
+    deadline: AtomicU64,
+}
+
+/// Tracks deadlines of published sequences until they are completed.
+///
+/// See the [module documentation](self).
+pub struct TimeoutRegistry {
+    entries: Box<[CachePadded<Entry>]>,
+    indexing: Indexing,
+    origin: Instant,
+    /// A lower bound of every pending deadline, which lets scans that would
+    /// find nothing to expire return early.
+    earliest: AtomicU64,
+}
+
+impl TimeoutRegistry {
+    /// Create a registry with one entry per sequence of a lap of a ring with
+    /// `capacity` slots, usually the capacity of the channel.
+    ///
+    /// # Panics
+    /// If `capacity` is zero it will panic
+    pub fn new(capacity: usize) -> Self {
+        let indexing = Indexing::new(capacity);
+        let entries = (0..capacity)
+            .map(|_| {
+                CachePadded(Entry {
+                    sequence: AtomicI64::new(FREE),
+                    deadline: AtomicU64::new(0),
+                })
+            })
+            .collect();
+        Self {
+            entries,
+            indexing,
+            origin: Instant::now(),
+            earliest: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Expect a completion of `sequence` within `timeout` from now.
+    ///
+    /// Returns `false` and tracks nothing if the entry of the sequence still
+    /// tracks the sequence of an earlier lap, i.e. more than a ring's worth of
+    /// events are awaiting a response.
+    pub fn track(&self, sequence: i64, timeout: Duration) -> bool {
+        let entry = self.entry(sequence);
+        if entry
+            .sequence
+            .compare_exchange(FREE, CLAIMED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        let deadline = self.nanos(Instant::now() + timeout);
+        entry.deadline.store(deadline, Ordering::Relaxed);
+        entry.sequence.store(sequence, Ordering::SeqCst);
+        self.earliest.fetch_min(deadline, Ordering::SeqCst);
+        true
+    }
+
+    /// Note that the response to `sequence` arrived.
+    ///
+    /// Returns `false` if the sequence is not tracked, for example because
+    /// its deadline already expired.
+    pub fn complete(&self, sequence: i64) -> bool {
+        self.entry(sequence)
+            .sequence
+            .compare_exchange(sequence, FREE, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Call `on_timeout` with every tracked sequence whose deadline is not
+    /// after `now`, and stop tracking it.
+    ///
+    /// Returns the number of expired sequences. A completion racing with the
+    /// expiry of its sequence either wins, or sees `complete` return `false`;
+    /// a sequence is never both completed and expired.
+    pub fn expire<F>(&self, now: Instant, mut on_timeout: F) -> usize
+    where
+        F: FnMut(i64),
+    {
+        let now = self.nanos(now);
+        if now < self.earliest.load(Ordering::SeqCst) {
+            return 0;
+        }
+
+        // Deadlines tracked while scanning lower the bound again, whether the
+        // scan sees their entry or not.
+        self.earliest.store(u64::MAX, Ordering::SeqCst);
+        let mut expired = 0;
+        let mut earliest = u64::MAX;
+        for entry in &self.entries[..] {
+            let sequence = entry.sequence.load(Ordering::SeqCst);
+            if sequence < 0 {
+                continue;
+            }
+            let deadline = entry.deadline.load(Ordering::Relaxed);
+            if deadline > now {
+                earliest = earliest.min(deadline);
+            } else if entry
+                .sequence
+                .compare_exchange(sequence, FREE, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                on_timeout(sequence);
+                expired += 1;
+            }
+        }
+        self.earliest.fetch_min(earliest, Ordering::SeqCst);
+        expired
+    }
+
+    /// Returns the number of tracked sequences.
+    pub fn pending(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.sequence.load(Ordering::Relaxed) != FREE)
+            .count()
+    }
+
+    /// Returns the entry `sequence` is tracked in.
+    fn entry(&self, sequence: i64) -> &Entry {
+        &self.entries[self.indexing.wrap(sequence, 0)]
+    }
+
+    /// Returns `instant` in nanoseconds since the origin of the registry.
+    fn nanos(&self, instant: Instant) -> u64 {
+        instant.saturating_duration_since(self.origin).as_nanos() as u64
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::timeouts::TimeoutRegistry;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_uncompleted_sequences_expire_once() {
+        let registry = TimeoutRegistry::new(4);
+        let start = Instant::now();
+        let short = Duration::from_millis(1);
+        let long = Duration::from_secs(60);
+        assert!(registry.track(0, short));
+        assert!(registry.track(1, short));
+        assert!(registry.track(2, long));
+        assert!(!registry.track(4, short));
+        assert!(registry.complete(1));
+        assert!(!registry.complete(1));
+        assert_eq!(registry.pending(), 2);
+
+        assert_eq!(registry.expire(start, |_| unreachable!()), 0);
+        let mut expired = Vec::new();
+        let later = Instant::now() + Duration::from_millis(10);
+        assert_eq!(registry.expire(later, |sequence| expired.push(sequence)), 1);
+        assert_eq!(expired, [0]);
+        assert!(!registry.complete(0));
+        assert_eq!(registry.expire(later, |_| unreachable!()), 0);
+
+        assert!(registry.track(4, short));
+        assert!(registry.complete(2));
+        assert_eq!(registry.pending(), 1);
+    }
+}
@@ -0,0 +1,204 @@
+//! Cache topology used to pick padding and spin budgets at channel construction.
+//!
+//! Channels pad their ring buffers by one cache line on each side and spin a
+//! number of pause hints per wait. Both depend on the machine: 64-byte lines are
+//! common, but several platforms ship 128-byte lines, and spinning next to an
+//! SMT sibling steals cycles from it.
+//!
+//! With the `topology` feature enabled, [`Topology::detected`] reads the cache
+//! line size and the number of SMT siblings from sysfs on Linux, falling back to
+//! `cpuid` on x86_64. Without the feature, or when detection fails, the
+//! cache line size of the target architecture and no SMT are assumed.
+//! [`Topology::set_override`] replaces the topology seen by channels created
+//! afterwards.
+
+use crate::constants;
+use std::sync::{OnceLock, RwLock};
+
+/// Cache line size and SMT layout of the machine, plus the spin budget derived from them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    cache_line_size: usize,
+    smt_siblings: usize,
+    spin_budget: usize,
+}
+
+/// Topology that replaces the detected one, if set.
+static OVERRIDE: RwLock<Option<Topology>> = RwLock::new(None);
+
+/// Topology detected on first use.
+static DETECTED: OnceLock<Topology> = OnceLock::new();
+
+impl Topology {
+    /// Create a topology with the given cache line size and number of hardware
+    /// threads per core.
+    ///
+    /// The spin budget defaults to one pause hint per hardware thread sharing the core.
+    ///
+    /// # Panics
+    /// Panics if `cache_line_size` is not a power of two or is smaller than a pointer,
+    /// or if `smt_siblings` is zero.
+    pub fn new(cache_line_size: usize, smt_siblings: usize) -> Self {
+        assert!(
+            cache_line_size.is_power_of_two() && cache_line_size >= constants::POINTER_SIZE,
+            "cache_line_size must be a power of two no smaller than a pointer"
+        );
+        assert!(smt_siblings > 0, "smt_siblings must be greater than zero");
+        Self {
+            cache_line_size,
+            smt_siblings,
+            spin_budget: smt_siblings,
+        }
+    }
+
+    /// Replace the number of pause hints a spinning wait strategy issues per wait.
+    ///
+    /// # Panics
+    /// Panics if `spin_budget` is zero.
+    pub fn with_spin_budget(mut self, spin_budget: usize) -> Self {
+        assert!(spin_budget > 0, "spin_budget must be greater than zero");
+        self.spin_budget = spin_budget;
+        self
+    }
+
+    /// Returns the topology detected for this machine.
+    ///
+    /// Detection runs once; later calls return the cached result.
+    pub fn detected() -> Topology {
+        *DETECTED.get_or_init(detect)
+    }
+
+    /// Returns the topology channels are constructed with: the override if one
+    /// is set, otherwise the detected topology.
+    pub fn current() -> Topology {
+        OVERRIDE.read().unwrap().unwrap_or_else(Self::detected)
+    }
+
+    /// Use `topology` for every channel created from now on.
+    pub fn set_override(topology: Topology) {
+        *OVERRIDE.write().unwrap() = Some(topology);
+    }
+
+    /// Go back to the detected topology for channels created from now on.
+    pub fn clear_override() {
+        *OVERRIDE.write().unwrap() = None;
+    }
+
+    /// Returns the cache line size in bytes.
+    pub fn cache_line_size(&self) -> usize {
+        self.cache_line_size
+    }
+
+    /// Returns the number of hardware threads sharing a core.
+    pub fn smt_siblings(&self) -> usize {
+        self.smt_siblings
+    }
+
+    /// Returns the number of pause hints a spinning wait strategy issues per wait.
+    pub fn spin_budget(&self) -> usize {
+        self.spin_budget
+    }
+
+    /// Returns the number of padding slots placed on each side of a ring buffer.
+    pub(crate) fn array_padding(&self) -> usize {
+        self.cache_line_size / constants::POINTER_SIZE
+    }
+}
+
+impl Default for Topology {
+    /// The topology assumed when nothing is detected: the cache line size of
+    /// the target architecture and no SMT.
+    fn default() -> Self {
+        Self::new(constants::CACHE_LINE_SIZE, 1)
+    }
+}
+
+/// Detect the topology, keeping the defaults for anything that cannot be read.
+#[cfg(feature = "topology")]
+fn detect() -> Topology {
+    let fallback = Topology::default();
+    let cache_line_size = detect::cache_line_size()
+        .filter(|size| size.is_power_of_two() && *size >= constants::POINTER_SIZE)
+        .unwrap_or(fallback.cache_line_size);
+    let smt_siblings = detect::smt_siblings()
+        .filter(|siblings| *siblings > 0)
+        .unwrap_or(fallback.smt_siblings);
+    Topology::new(cache_line_size, smt_siblings)
+}
+
+/// Without the `topology` feature nothing is detected.
+#[cfg(not(feature = "topology"))]
+fn detect() -> Topology {
+    Topology::default()
+}
+
+#[cfg(feature = "topology")]
+mod detect {
+    use std::fs;
+
+    const CPU0: &str = "/sys/devices/system/cpu/cpu0";
+
+    /// Read the coherency line size of the first data cache.
+    pub(super) fn cache_line_size() -> Option<usize> {
+        fs::read_to_string(format!("{CPU0}/cache/index0/coherency_line_size"))
+            .ok()
+            .and_then(|size| size.trim().parse().ok())
+            .or_else(fallback_line_size)
+    }
+
+    /// Count the hardware threads listed as siblings of the first CPU.
+    pub(super) fn smt_siblings() -> Option<usize> {
+        let list = fs::read_to_string(format!("{CPU0}/topology/thread_siblings_list")).ok()?;
+        count_cpu_list(list.trim())
+    }
+
+    /// Count the CPUs in a sysfs list such as `0,6` or `0-3`.
+    fn count_cpu_list(list: &str) -> Option<usize> {
+        list.split(',')
+            .try_fold(0, |count, part| match part.split_once('-') {
+                Some((low, high)) => {
+                    let low: usize = low.parse().ok()?;
+                    let high: usize = high.parse().ok()?;
+                    Some(count + high.checked_sub(low)? + 1)
+                }
+                None => part.parse::<usize>().ok().map(|_| count + 1),
+            })
+    }
+
+    /// Read the `CLFLUSH` line size reported by `cpuid` leaf 1.
+    #[cfg(target_arch = "x86_64")]
+    fn fallback_line_size() -> Option<usize> {
+        let leaf = std::arch::x86_64::__cpuid(1);
+        match (leaf.ebx >> 8) & 0xff {
+            0 => None,
+            lines => Some(lines as usize * 8),
+        }
+    }
+
+    /// Apple silicon uses 128-byte lines and exposes no sysfs.
+    #[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+    fn fallback_line_size() -> Option<usize> {
+        Some(128)
+    }
+
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        all(target_arch = "aarch64", target_os = "macos")
+    )))]
+    fn fallback_line_size() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "topology"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detected_topology_is_usable() {
+        let topology = Topology::detected();
+        assert!(topology.cache_line_size().is_power_of_two());
+        assert!(topology.smt_siblings() > 0);
+        assert_eq!(topology.spin_budget(), topology.smt_siblings());
+    }
+}
@@ -0,0 +1,42 @@
+//! Bounded scratch memory for in-place transform stages.
+//!
+//! Decompression or decryption stages often need a temporary buffer per
+//! event. [`Receiver::map_in_place`](crate::channels::Receiver::map_in_place)
+//! hands its transform a [`Scratch`] that is allocated once by the consumer and
+//! reused for every event, so a transform never allocates on the hot path and
+//! its memory use stays bounded.
+
+use crate::errors::ScratchExhausted;
+
+/// A fixed-capacity scratch buffer reused across events.
+pub struct Scratch {
+    buffer: Box<[u8]>,
+}
+
+impl Scratch {
+    /// Allocate `capacity` bytes of scratch memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: vec![0; capacity].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the number of bytes available.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Borrow the first `len` bytes of scratch memory.
+    ///
+    /// The bytes keep whatever the previous event left in them.
+    ///
+    /// # Errors
+    /// Returns [`ScratchExhausted`] if `len` exceeds the capacity.
+    pub fn take(&mut self, len: usize) -> Result<&mut [u8], ScratchExhausted> {
+        let capacity = self.capacity();
+        self.buffer.get_mut(..len).ok_or(ScratchExhausted {
+            requested: len,
+            capacity,
+        })
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::ops::Deref;
+
 /// Wrap a sequence index to the actual buffer index, taking mask and padding into account.
 ///
 /// This is used in ring buffers to convert a monotonically increasing sequence number
@@ -15,24 +18,63 @@ pub fn wrap_index(sequence: i64, mask: i64, padding: usize) -> usize {
     (sequence & mask) as usize + padding
 }
 
-/// Assert that a buffer size is a power of two.
-///
-/// Many ring buffer implementations rely on power-of-two sizes to efficiently
-/// compute indices using a mask instead of modulus operations.
-///
-/// # Parameters
-/// - `buffer_size`: The size of the buffer to check.
+/// Maps sequences onto the slots of a buffer of a given size.
 ///
-/// # Returns
-/// The same buffer size if the assertion passes.
+/// Power-of-two sizes use the fast path: a bit mask for the slot and a shift
+/// for the lap. Any other size falls back to a modulo and a division, so
+/// capacities such as `10_000` do not have to be rounded up to `16_384`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Indexing {
+    /// Power-of-two size.
+    Mask { mask: i64, shift: u32 },
+    /// Arbitrary size.
+    Modulo { size: i64 },
+}
+
+impl Indexing {
+    /// Select the indexing mode for `buffer_size`.
+    ///
+    /// # Panics
+    /// Panics if `buffer_size` is zero.
+    pub fn new(buffer_size: usize) -> Self {
+        assert_buffer_size_is_not_zero(buffer_size);
+        if buffer_size.is_power_of_two() {
+            Indexing::Mask {
+                mask: (buffer_size - 1) as i64,
+                shift: buffer_size.ilog2(),
+            }
+        } else {
+            Indexing::Modulo {
+                size: buffer_size as i64,
+            }
+        }
+    }
+
+    /// Returns the index of the slot for `sequence`, offset by `padding`.
+    #[inline(always)]
+    pub fn wrap(&self, sequence: i64, padding: usize) -> usize {
+        match *self {
+            Indexing::Mask { mask, .. } => wrap_index(sequence, mask, padding),
+            Indexing::Modulo { size } => (sequence % size) as usize + padding,
+        }
+    }
+
+    /// Returns the lap of the buffer that `sequence` belongs to.
+    #[inline(always)]
+    pub fn lap(&self, sequence: i64) -> i64 {
+        match *self {
+            Indexing::Mask { shift, .. } => sequence >> shift,
+            Indexing::Modulo { size } => sequence / size,
+        }
+    }
+}
+
+/// Assert that a buffer size is not zero.
 ///
 /// # Panics
-/// Panics if `buffer_size` is not a power of two.
-pub fn assert_buffer_size_pow_of_2(buffer_size: usize) {
-    assert!(
-        buffer_size.is_power_of_two(),
-        "buffer_size must be a power of two"
-    );
+/// Panics if `buffer_size` is zero.
+pub fn assert_buffer_size_is_not_zero(buffer_size: usize) {
+    assert!(buffer_size > 0, "buffer_size must be greater than zero");
 }
 
 /// Asserts that a given buffer size fits within the range of an `i64`.
@@ -56,3 +98,41 @@ pub fn assert_buffer_size_is_equal_or_less_than_i64(buffer_size: usize) {
         "buffer_size must be less than i64::MAX"
     );
 }
+
+/// Pads and aligns a value to [`CACHE_LINE_SIZE`](crate::constants::CACHE_LINE_SIZE)
+/// bytes, so that neighbouring values never share a cache line.
+///
+/// `repr(align)` only takes a literal, so the alignment of each architecture
+/// is spelled out here and must match the constant.
+#[cfg_attr(
+    any(target_arch = "aarch64", target_arch = "powerpc64"),
+    repr(align(128))
+)]
+#[cfg_attr(target_arch = "s390x", repr(align(256)))]
+#[cfg_attr(
+    not(any(
+        target_arch = "aarch64",
+        target_arch = "powerpc64",
+        target_arch = "s390x"
+    )),
+    repr(align(64))
+)]
+#[derive(Default)]
+pub(crate) struct CachePadded<T>(pub T);
+
+const _: () = assert!(align_of::<CachePadded<u8>>() == crate::constants::CACHE_LINE_SIZE);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,224 @@
+//! Receive paths that gather items over a window of items or time.
+//!
+//! [`Receiver::recv_batch_timeout`] collects a micro-batch whose latency is
+//! bounded, and [`Receiver::fold_window`] aggregates tumbling windows inside
+//! the poll loop.
+
+use crate::channels::Receiver;
+use crate::poller::State::Idle;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+/// Why [`Receiver::recv_batch_timeout`] returned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BatchReason {
+    /// At least the requested minimum number of items arrived.
+    MinReached,
+    /// The maximum number of items was collected.
+    MaxReached,
+    /// The timeout expired before the minimum was reached.
+    Timeout,
+    /// The channel is closed or every sender is gone, and the buffer was drained
+    /// before the minimum was reached.
+    Disconnected,
+}
+
+/// The extent of a tumbling window used by [`Receiver::fold_window`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Window {
+    /// Each window holds a fixed number of items.
+    Count(usize),
+    /// Each window spans a fixed interval of wall-clock time.
+    Duration(Duration),
+}
+
+impl From<usize> for Window {
+    fn from(size: usize) -> Self {
+        Window::Count(size)
+    }
+}
+
+impl From<Duration> for Window {
+    fn from(duration: Duration) -> Self {
+        Window::Duration(duration)
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Collect a micro-batch of items, bounding how long the caller waits for it.
+    ///
+    /// Returns as soon as at least `min` items have been collected, once `max`
+    /// items have been collected, or when `timeout` expires, whichever comes first.
+    /// The collected items are returned together with the [`BatchReason`]; on
+    /// timeout the batch may hold fewer than `min` items, or none at all. The same
+    /// holds when the channel is closed or every sender is gone and the buffer runs dry.
+    ///
+    /// This is the contract of downstream writers that amortize syscalls over a
+    /// batch but must still bound the latency of every item.
+    ///
+    /// # Panics
+    /// Panics if `min` is greater than `max`.
+    pub fn recv_batch_timeout(
+        &self,
+        min: usize,
+        max: usize,
+        timeout: Duration,
+    ) -> (Vec<T>, BatchReason) {
+        assert!(min <= max, "min must not be greater than max");
+
+        let deadline = Instant::now() + timeout;
+        let items = RefCell::new(Vec::with_capacity(max));
+        let handler = |item: T| items.borrow_mut().push(item);
+
+        loop {
+            let finished = self.coordinator().is_finished();
+            let want = (max - items.borrow().len()).min(self.capacity());
+            let state = self.poll_permitted(want, &handler);
+
+            let collected = items.borrow().len();
+            if collected >= max {
+                return (items.into_inner(), BatchReason::MaxReached);
+            }
+            if collected >= min {
+                return (items.into_inner(), BatchReason::MinReached);
+            }
+            if state == Idle && finished {
+                return (items.into_inner(), BatchReason::Disconnected);
+            }
+            if Instant::now() >= deadline {
+                return (items.into_inner(), BatchReason::Timeout);
+            }
+            if state == Idle {
+                self.coordinator().consumer_wait_until(deadline);
+            }
+        }
+    }
+
+    /// Aggregate items over tumbling windows inside the poll loop.
+    ///
+    /// Every item is folded into the current window's aggregate with `fold`,
+    /// starting from a fresh `init()` value, and the aggregate is passed to `emit`
+    /// once the window is complete. `window` is either an item count or a
+    /// [`Duration`]; time windows follow each other back to back, and windows
+    /// that received no items are not emitted. To feed a downstream channel,
+    /// forward the aggregate to a [`Sender`](crate::channels::Sender) from `emit`.
+    ///
+    /// Runs until the channel is closed or every sender is gone, and the buffer
+    /// is drained, emitting the last partial window before returning.
+    ///
+    /// # Panics
+    /// Panics if the window is empty, i.e. a count or duration of zero.
+    pub fn fold_window<W, A, I, F, E>(&self, window: W, init: I, fold: F, mut emit: E)
+    where
+        W: Into<Window>,
+        I: Fn() -> A,
+        F: FnMut(A, T) -> A,
+        E: FnMut(A),
+    {
+        let window = window.into();
+        let mut deadline = Instant::now();
+        match window {
+            Window::Count(size) => assert!(size > 0, "window size must be greater than zero"),
+            Window::Duration(duration) => {
+                assert!(
+                    !duration.is_zero(),
+                    "window duration must be greater than zero"
+                );
+                deadline += duration;
+            }
+        }
+
+        let fold = RefCell::new(fold);
+        let aggregate: RefCell<Option<A>> = RefCell::new(None);
+        let folded = Cell::new(0usize);
+        let handler = |item: T| {
+            let mut aggregate = aggregate.borrow_mut();
+            let current = aggregate.take().unwrap_or_else(&init);
+            *aggregate = Some((fold.borrow_mut())(current, item));
+            folded.set(folded.get() + 1);
+        };
+
+        loop {
+            let finished = self.coordinator().is_finished();
+            let want = match window {
+                Window::Count(size) => size - folded.get(),
+                Window::Duration(_) => self.capacity(),
+            };
+            let state = self.poll_permitted(want.min(self.capacity()), &handler);
+
+            let complete = match window {
+                Window::Count(size) => folded.get() >= size,
+                Window::Duration(duration) => {
+                    let now = Instant::now();
+                    let elapsed = now >= deadline;
+                    while deadline <= now {
+                        deadline += duration;
+                    }
+                    elapsed
+                }
+            };
+            if complete {
+                if let Some(aggregate) = aggregate.take() {
+                    emit(aggregate);
+                }
+                folded.set(0);
+            }
+
+            if state == Idle {
+                if finished {
+                    if let Some(aggregate) = aggregate.take() {
+                        emit(aggregate);
+                    }
+                    return;
+                }
+                match window {
+                    Window::Count(_) => self.coordinator().consumer_wait(),
+                    Window::Duration(_) => self.coordinator().consumer_wait_until(deadline),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use crate::channels::{BatchReason, spsc};
+    use crate::coordinator::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_recv_batch_timeout_returns_on_min_max_or_timeout() {
+        let (tx, rx) = spsc::<u32>(
+            16,
+            ProducerWaitStrategyKind::Yielding,
+            ConsumerWaitStrategyKind::Blocking,
+        );
+        tx.send_n(0..6).unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            rx.recv_batch_timeout(2, 4, timeout),
+            (vec![0, 1, 2, 3], BatchReason::MaxReached)
+        );
+        tx.send(6).unwrap();
+        assert_eq!(
+            rx.recv_batch_timeout(2, 4, timeout),
+            (vec![4, 5, 6], BatchReason::MinReached)
+        );
+
+        // A partial batch is returned once the timeout expires.
+        tx.send(7).unwrap();
+        let started = Instant::now();
+        let timeout = Duration::from_millis(50);
+        assert_eq!(
+            rx.recv_batch_timeout(2, 4, timeout),
+            (vec![7], BatchReason::Timeout)
+        );
+        assert!(started.elapsed() >= timeout);
+
+        drop(tx);
+        assert_eq!(
+            rx.recv_batch_timeout(2, 4, timeout),
+            (vec![], BatchReason::Disconnected)
+        );
+    }
+}
@@ -0,0 +1,8 @@
+//! Checks that channels reject payloads that cannot cross threads safely.
+
+#[cfg(all(feature = "mp", feature = "mc", not(feature = "loom")))]
+#[test]
+fn test_thread_unsafe_payloads_are_rejected() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
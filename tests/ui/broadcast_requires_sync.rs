@@ -0,0 +1,11 @@
+use channels_rs::channels::broadcast;
+use channels_rs::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use std::cell::Cell;
+
+fn main() {
+    let _ = broadcast::<Cell<u32>>(
+        8,
+        ProducerWaitStrategyKind::Yielding,
+        ConsumerWaitStrategyKind::Yielding,
+    );
+}
@@ -0,0 +1,8 @@
+use channels_rs::channels::Receiver;
+use std::rc::Rc;
+
+fn assert_send<T: Send>() {}
+
+fn main() {
+    assert_send::<Receiver<Rc<u32>>>();
+}
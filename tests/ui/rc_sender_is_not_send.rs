@@ -0,0 +1,12 @@
+use channels_rs::channels::spsc;
+use channels_rs::prelude::{ConsumerWaitStrategyKind, ProducerWaitStrategyKind};
+use std::rc::Rc;
+
+fn main() {
+    let (tx, _rx) = spsc::<Rc<u32>>(
+        8,
+        ProducerWaitStrategyKind::Yielding,
+        ConsumerWaitStrategyKind::Yielding,
+    );
+    std::thread::spawn(move || tx.send(Rc::new(1)).is_ok());
+}